@@ -0,0 +1,109 @@
+//! Compose files from arbitrary records and parse them back.
+//!
+//! The records are placed following the file structure, so the parser need
+//! to accept all the files composed. The parser normalize some records, eg:
+//! the checksums are returned as `Simple`, so the parsed records are
+//! compared by composing them again, resulting in the same file.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use gcd_rs::composer::compose_all;
+use gcd_rs::parser::parse_all;
+use gcd_rs::record::checksum::ChecksumRecord;
+use gcd_rs::record::descriptor::descriptor_data::DescriptorDecoded;
+use gcd_rs::record::descriptor::DescriptorRecord;
+use gcd_rs::record::filler::FillerRecord;
+use gcd_rs::record::firmware::FirmwareRecord;
+use gcd_rs::record::main::MainRecord;
+use gcd_rs::record::text::TextRecord;
+use gcd_rs::{Record, RecordId};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Debug, Arbitrary)]
+struct File {
+    texts: Vec<TextRecord>,
+    main: MainRecord,
+    main_texts: Vec<TextRecord>,
+    //a file have at least one firmware block
+    first: Block,
+    blocks: Vec<Block>,
+}
+
+#[derive(Debug, Arbitrary)]
+struct Block {
+    id: RecordId,
+    texts: Vec<TextRecord>,
+    records: Vec<BlockRecord>,
+}
+
+#[derive(Debug, Arbitrary)]
+enum BlockRecord {
+    Chunk(Vec<u8>),
+    Checksum(ChecksumRecord),
+    Filler(FillerRecord),
+    Text(TextRecord),
+}
+
+// the firmware ids can't be the id of other records
+const RECORD_IDS: [RecordId; 7] = [
+    RecordId::CHECKSUM,
+    RecordId::FILLER,
+    RecordId::MAIN_HEADER,
+    RecordId::TEXT,
+    RecordId::DESCRIPTOR_TYPE,
+    RecordId::DESCRIPTOR_DATA,
+    RecordId::END,
+];
+
+impl Block {
+    fn records(self, records: &mut Vec<Record>) {
+        let len: usize = self
+            .records
+            .iter()
+            .map(|record| match record {
+                BlockRecord::Chunk(data) => data.len(),
+                _ => 0,
+            })
+            .sum();
+        records.push(Record::Descriptor(DescriptorRecord::Simple(vec![
+            DescriptorDecoded::FirmwareId(self.id.get()).encode(),
+            DescriptorDecoded::FirmwareLen(len as u32).encode(),
+            DescriptorDecoded::End.encode(),
+        ])));
+        records.extend(self.texts.into_iter().map(Record::Text));
+        for record in self.records {
+            records.push(match record {
+                BlockRecord::Chunk(data) => {
+                    Record::FirmwareData(FirmwareRecord::new(data, self.id))
+                }
+                BlockRecord::Checksum(checksum) => Record::Checksum(checksum),
+                BlockRecord::Filler(filler) => Record::Filler(filler),
+                BlockRecord::Text(text) => Record::Text(text),
+            });
+        }
+    }
+}
+
+fuzz_target!(|file: File| {
+    let blocks = std::iter::once(file.first).chain(file.blocks);
+    let mut records = vec![];
+    records.extend(file.texts.into_iter().map(Record::Text));
+    records.push(Record::MainHeader(file.main));
+    records.extend(file.main_texts.into_iter().map(Record::Text));
+    for block in blocks {
+        if RECORD_IDS.contains(&block.id) {
+            return;
+        }
+        block.records(&mut records);
+    }
+    records.push(Record::End);
+
+    //only fail if the records are too big
+    let data = match compose_all(&records) {
+        Ok(data) => data,
+        Err(_) => return,
+    };
+    let parsed = parse_all(&data).unwrap();
+    assert_eq!(compose_all(&parsed).unwrap(), data);
+});
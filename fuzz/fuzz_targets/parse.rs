@@ -0,0 +1,34 @@
+//! Parse arbitrary data, the parser should fail without panicking.
+
+#![no_main]
+
+use gcd_rs::config::ParserConfig;
+use gcd_rs::parser::Parser;
+use gcd_rs::Record;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let strict = ParserConfig::default();
+    let recover = ParserConfig {
+        strict: false,
+        recover: true,
+        ..ParserConfig::default()
+    };
+    for config in [strict, recover].iter() {
+        let mut parser: Parser<&[u8]> =
+            match Parser::with_config(data, config.clone()) {
+                Ok(parser) => parser,
+                Err(_) => continue,
+            };
+        loop {
+            match parser.read_record() {
+                Ok(Record::End) => {
+                    let _ = parser.finish();
+                    break;
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+    }
+});
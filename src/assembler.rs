@@ -0,0 +1,483 @@
+//! Reassemble the firmware blocks into a memory image.
+//!
+//! Each firmware block with a FirmwareAddr descriptor is placed at the
+//! declared address, forming a sparse [`MemoryImage`]. The image report the
+//! gaps and overlaps between the regions, and can be flattened into a single
+//! flashable image if no region overlap, or exported to the Intel HEX
+//! ([`MemoryImage::to_ihex`]) and Motorola S-record
+//! ([`MemoryImage::to_srec`]) formats, used by flashing tools and
+//! disassemblers.
+//!
+//! ```
+//! use gcd_rs::assembler::{MemoryImage, Region};
+//!
+//! let mut image = MemoryImage::new();
+//! image.insert(Region::new(0, 0x0505, 0x1000, vec![1; 0x10]));
+//! image.insert(Region::new(1, 0x0506, 0x1020, vec![2; 0x10]));
+//! assert_eq!(image.gaps(), vec![0x1010..0x1020]);
+//! assert!(image.overlaps().is_empty());
+//! let flat = image.flatten(0xff).unwrap();
+//! assert_eq!(flat.len(), 0x30);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::io::Write;
+use std::ops::Range;
+
+use crate::error::{GcdError, Result};
+use crate::Record;
+
+/// Max data bytes of each Intel HEX and S-record line.
+pub const LINE_LEN: usize = 16;
+
+/// Data of a firmware block, placed at the block address.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Region {
+    /// Position of the block in the file, starting at 0
+    pub block: usize,
+    /// Firmware id, declared on the Descriptor
+    pub id: u16,
+    /// Address of the first byte, from the FirmwareAddr descriptor
+    pub addr: u64,
+    /// Firmware data, decoded
+    pub data: Vec<u8>,
+}
+
+impl Region {
+    pub fn new(block: usize, id: u16, addr: u64, data: Vec<u8>) -> Self {
+        Region {
+            block,
+            id,
+            addr,
+            data,
+        }
+    }
+    /// Address after the last byte.
+    pub fn end(&self) -> u64 {
+        self.addr + self.data.len() as u64
+    }
+    pub fn range(&self) -> Range<u64> {
+        self.addr..self.end()
+    }
+}
+
+/// Two regions that share addresses, see [`MemoryImage::overlaps`].
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Overlap {
+    /// Block of the first region
+    pub first: usize,
+    /// Block of the second region
+    pub second: usize,
+    /// Addresses written by both regions
+    pub range: Range<u64>,
+}
+
+/// Sparse memory image, composed of regions sorted by address.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct MemoryImage {
+    regions: Vec<Region>,
+    unplaced: Vec<usize>,
+}
+
+impl MemoryImage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Place the firmware blocks of the records, the blocks without a
+    /// FirmwareAddr are not placed, see [`MemoryImage::unplaced`].
+    ///
+    /// The firmware chunks need to be decoded, eg: the default
+    /// [`Parser`](crate::parser::Parser) config.
+    pub fn from_records<'a, I>(records: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Record>,
+    {
+        let mut image = MemoryImage::new();
+        //the current block, None if it have no address
+        let mut current: Option<Region> = None;
+        let mut block = 0;
+        for record in records {
+            match record {
+                Record::Descriptor(descriptor) => {
+                    image.insert_some(current.take());
+                    match descriptor.firmware_addr() {
+                        Some(addr) => {
+                            let id = descriptor.firmware_id().unwrap_or(0);
+                            let region =
+                                Region::new(block, id, addr.into(), vec![]);
+                            current = Some(region);
+                        }
+                        None => image.unplaced.push(block),
+                    }
+                    block += 1;
+                }
+                Record::FirmwareData(firmware) => {
+                    if let Some(region) = current.as_mut() {
+                        region.data.extend_from_slice(firmware.data());
+                    }
+                }
+                _ => {}
+            }
+        }
+        image.insert_some(current);
+        image
+    }
+
+    fn insert_some(&mut self, region: Option<Region>) {
+        if let Some(region) = region {
+            self.insert(region);
+        }
+    }
+
+    /// Add the region, after the regions with the same address.
+    pub fn insert(&mut self, region: Region) {
+        let pos = self.regions.partition_point(|x| x.addr <= region.addr);
+        self.regions.insert(pos, region);
+    }
+
+    /// The regions, sorted by address.
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// Blocks without a FirmwareAddr, not included in the image.
+    pub fn unplaced(&self) -> &[usize] {
+        &self.unplaced
+    }
+
+    /// Addresses from the first to the last byte of the image.
+    pub fn range(&self) -> Option<Range<u64>> {
+        let start = self.regions.first()?.addr;
+        let end = self.regions.iter().map(Region::end).max()?;
+        Some(start..end)
+    }
+
+    /// Addresses not covered by any region, between the start and end of
+    /// the image.
+    pub fn gaps(&self) -> Vec<Range<u64>> {
+        let mut gaps = vec![];
+        let mut end = match self.regions.first() {
+            Some(region) => region.addr,
+            None => return gaps,
+        };
+        for region in self.regions.iter() {
+            if region.addr > end {
+                gaps.push(end..region.addr);
+            }
+            end = end.max(region.end());
+        }
+        gaps
+    }
+
+    /// Pairs of regions that share addresses, empty regions never overlap.
+    pub fn overlaps(&self) -> Vec<Overlap> {
+        let mut overlaps = vec![];
+        for (i, first) in self.regions.iter().enumerate() {
+            //sorted by address, only the next regions can overlap
+            for second in self.regions[i + 1..].iter() {
+                if second.addr >= first.end() {
+                    break;
+                }
+                let end = first.end().min(second.end());
+                if second.addr < end {
+                    overlaps.push(Overlap {
+                        first: first.block,
+                        second: second.block,
+                        range: second.addr..end,
+                    });
+                }
+            }
+        }
+        overlaps
+    }
+
+    fn check_overlaps(&self) -> Result<()> {
+        match self.overlaps().first() {
+            Some(overlap) => Err(GcdError::invalid_data(format!(
+                "Block {} overlap block {} at {:#x}",
+                overlap.second, overlap.first, overlap.range.start
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    //the data of the regions in lines of up to LINE_LEN bytes, a line never
+    //cross a 64KiB boundary
+    fn lines(&self) -> impl Iterator<Item = (u64, &[u8])> {
+        self.regions.iter().flat_map(|region| {
+            let mut addr = region.addr;
+            let mut data = &region.data[..];
+            std::iter::from_fn(move || {
+                if data.is_empty() {
+                    return None;
+                }
+                let boundary = (0x10000 - (addr & 0xffff)) as usize;
+                let len = data.len().min(LINE_LEN).min(boundary);
+                let (line, rest) = data.split_at(len);
+                let line_addr = addr;
+                addr += len as u64;
+                data = rest;
+                Some((line_addr, line))
+            })
+        })
+    }
+
+    /// Flat image, from the start to the end of the image, the gaps are
+    /// filled with `fill`. Fail if any region overlap.
+    pub fn flatten(&self, fill: u8) -> Result<Vec<u8>> {
+        self.check_overlaps()?;
+        let range = match self.range() {
+            Some(range) => range,
+            None => return Ok(vec![]),
+        };
+        let len = usize::try_from(range.end - range.start)
+            .map_err(|_| GcdError::invalid_data("Image too big"))?;
+        let mut image = vec![fill; len];
+        for region in self.regions.iter() {
+            let start = (region.addr - range.start) as usize;
+            image[start..start + region.data.len()]
+                .copy_from_slice(&region.data);
+        }
+        Ok(image)
+    }
+
+    /// Write the image in the Intel HEX format, using extended linear
+    /// address records. Fail if any region overlap or the image don't fit
+    /// 32 bits of address.
+    pub fn to_ihex<W: Write>(&self, mut writer: W) -> Result<()> {
+        self.check_overlaps()?;
+        if self.range().is_some_and(|x| x.end > 1 << 32) {
+            return Err(GcdError::invalid_input(
+                "Image bigger than 32 bits of address",
+            ));
+        }
+        let mut upper = 0;
+        for (addr, data) in self.lines() {
+            let high = (addr >> 16) as u16;
+            if high != upper {
+                ihex_record(&mut writer, 0, 0x04, &high.to_be_bytes())?;
+                upper = high;
+            }
+            ihex_record(&mut writer, addr as u16, 0x00, data)?;
+        }
+        ihex_record(&mut writer, 0, 0x01, &[])
+    }
+
+    /// Write the image in the Motorola S-record format, the address size
+    /// (S1, S2 or S3) is the smallest that fit the image. Fail if any region
+    /// overlap or the image don't fit 32 bits of address.
+    pub fn to_srec<W: Write>(&self, mut writer: W) -> Result<()> {
+        self.check_overlaps()?;
+        let range = self.range().unwrap_or(0..0);
+        let (data_kind, end_kind, addr_len) = match range.end {
+            0..=0x1_0000 => (1, 9, 2),
+            0x1_0001..=0x100_0000 => (2, 8, 3),
+            0x100_0001..=0x1_0000_0000 => (3, 7, 4),
+            _ => {
+                return Err(GcdError::invalid_input(
+                    "Image bigger than 32 bits of address",
+                ))
+            }
+        };
+        srec_record(&mut writer, 0, 0, 2, &[])?;
+        let mut count = 0u64;
+        for (addr, data) in self.lines() {
+            srec_record(&mut writer, data_kind, addr, addr_len, data)?;
+            count += 1;
+        }
+        match count {
+            0..=0xffff => srec_record(&mut writer, 5, count, 2, &[])?,
+            0x1_0000..=0xff_ffff => srec_record(&mut writer, 6, count, 3, &[])?,
+            //the count record is optional
+            _ => {}
+        }
+        srec_record(&mut writer, end_kind, range.start, addr_len, &[])
+    }
+}
+
+// write a Intel HEX line, the checksum is the two's complement of the sum
+fn ihex_record<W: Write>(
+    writer: &mut W,
+    addr: u16,
+    kind: u8,
+    data: &[u8],
+) -> Result<()> {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend(addr.to_be_bytes());
+    bytes.push(kind);
+    bytes.extend(data);
+    let sum = bytes.iter().fold(0u8, |acc, x| acc.wrapping_add(*x));
+    bytes.push(sum.wrapping_neg());
+    write!(writer, ":")?;
+    write_hex(writer, &bytes)
+}
+
+// write a S-record line, the checksum is the one's complement of the sum
+fn srec_record<W: Write>(
+    writer: &mut W,
+    kind: u8,
+    addr: u64,
+    addr_len: usize,
+    data: &[u8],
+) -> Result<()> {
+    let mut bytes = vec![(addr_len + data.len() + 1) as u8];
+    bytes.extend(&addr.to_be_bytes()[8 - addr_len..]);
+    bytes.extend(data);
+    let sum = bytes.iter().fold(0u8, |acc, x| acc.wrapping_add(*x));
+    bytes.push(!sum);
+    write!(writer, "S{}", kind)?;
+    write_hex(writer, &bytes)
+}
+
+fn write_hex<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<()> {
+    for byte in bytes {
+        write!(writer, "{:02X}", byte)?;
+    }
+    writeln!(writer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryImage, Overlap, Region};
+    use crate::parser::parse_all;
+    use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+    use crate::record::descriptor::DescriptorRecord;
+    use crate::record::firmware::FirmwareRecord;
+    use crate::testing::vectors;
+    use crate::{Record, RecordId};
+
+    fn block(id: u16, addr: Option<u32>, data: &[u8]) -> Vec<Record> {
+        let mut descs = vec![DescriptorDecoded::FirmwareId(id)];
+        descs.extend(addr.map(DescriptorDecoded::FirmwareAddr));
+        descs.push(DescriptorDecoded::FirmwareLen(data.len() as u32));
+        descs.push(DescriptorDecoded::End);
+        let id = RecordId::new(id);
+        let mut records = vec![Record::Descriptor(DescriptorRecord::Simple(
+            descs.into_iter().map(|x| x.encode()).collect(),
+        ))];
+        records.extend(data.chunks(3).map(|x| {
+            Record::FirmwareData(FirmwareRecord::new(x.to_vec(), id))
+        }));
+        records
+    }
+
+    #[test]
+    fn memory_image() {
+        let mut records = vec![];
+        records.extend(block(0x0505, Some(0x1010), &[2; 8]));
+        records.extend(block(0x0506, None, &[0; 4]));
+        records.extend(block(0x0507, Some(0x1000), &[1; 10]));
+        records.push(Record::End);
+        let image = MemoryImage::from_records(&records);
+        let blocks: Vec<_> = image.regions().iter().map(|x| x.block).collect();
+        assert_eq!(blocks, vec![2, 0]);
+        assert_eq!(image.regions()[1].data, vec![2; 8]);
+        assert_eq!(image.unplaced(), &[1]);
+        assert_eq!(image.range(), Some(0x1000..0x1018));
+        assert_eq!(image.gaps(), vec![0x100a..0x1010]);
+        assert!(image.overlaps().is_empty());
+
+        let mut expected = vec![1; 10];
+        expected.extend([0xff; 6]);
+        expected.extend([2; 8]);
+        assert_eq!(image.flatten(0xff).unwrap(), expected);
+
+        //a region inside other, and one crossing the end
+        let mut image = image;
+        image.insert(Region::new(3, 0x0508, 0x1002, vec![3; 2]));
+        image.insert(Region::new(4, 0x0509, 0x1016, vec![4; 4]));
+        assert_eq!(
+            image.overlaps(),
+            vec![
+                Overlap {
+                    first: 2,
+                    second: 3,
+                    range: 0x1002..0x1004
+                },
+                Overlap {
+                    first: 0,
+                    second: 4,
+                    range: 0x1016..0x1018
+                },
+            ]
+        );
+        assert_eq!(image.gaps(), vec![0x100a..0x1010]);
+        assert!(image.flatten(0).is_err());
+
+        //no address, nothing to place
+        let records = parse_all(vectors::SINGLE_BLOCK).unwrap();
+        let image = MemoryImage::from_records(&records);
+        assert!(image.regions().is_empty());
+        assert_eq!(image.unplaced(), &[0]);
+        assert_eq!(image.gaps(), vec![]);
+        assert_eq!(image.flatten(0).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn hex_export() {
+        let export = |image: &MemoryImage| {
+            let (mut ihex, mut srec) = (vec![], vec![]);
+            image.to_ihex(&mut ihex).unwrap();
+            image.to_srec(&mut srec).unwrap();
+            let lines = |x: Vec<u8>| {
+                let x = String::from_utf8(x).unwrap();
+                x.lines().map(str::to_string).collect::<Vec<_>>()
+            };
+            (lines(ihex), lines(srec))
+        };
+        //split at the 64KiB boundary and in lines of 16 bytes
+        let mut image = MemoryImage::new();
+        image.insert(Region::new(0, 0x0505, 0x1fffe, vec![1, 2, 3, 4]));
+        image.insert(Region::new(1, 0x0506, 0x20002, (0..18).collect()));
+        let (ihex, srec) = export(&image);
+        assert_eq!(
+            ihex,
+            [
+                ":020000040001F9",
+                ":02FFFE000102FE",
+                ":020000040002F8",
+                ":020000000304F7",
+                ":10000200000102030405060708090A0B0C0D0E0F76",
+                ":020012001011CB",
+                ":00000001FF",
+            ]
+        );
+        assert_eq!(
+            srec,
+            [
+                "S0030000FC",
+                "S20601FFFE0102F8",
+                "S2060200000304F0",
+                "S214020002000102030405060708090A0B0C0D0E0F6F",
+                "S2060200121011C4",
+                "S5030004F8",
+                "S80401FFFEFD",
+            ]
+        );
+
+        //16 bits of address
+        let mut image = MemoryImage::new();
+        image.insert(Region::new(0, 0x0505, 0x1000, vec![0xaa, 0xbb]));
+        let (ihex, srec) = export(&image);
+        assert_eq!(ihex, [":02100000AABB89", ":00000001FF"]);
+        assert_eq!(
+            srec,
+            ["S0030000FC", "S1051000AABB85", "S5030001FB", "S9031000EC"]
+        );
+        let (ihex, srec) = export(&MemoryImage::new());
+        assert_eq!(ihex, [":00000001FF"]);
+        assert_eq!(srec, ["S0030000FC", "S5030000FC", "S9030000FC"]);
+
+        //the overlaps and big addresses are refused
+        image.insert(Region::new(1, 0x0506, 0x1001, vec![0]));
+        assert!(image.to_ihex(vec![]).is_err());
+        assert!(image.to_srec(vec![]).is_err());
+        let mut image = MemoryImage::new();
+        image.insert(Region::new(0, 0x0505, u32::MAX.into(), vec![0, 0]));
+        assert!(image.to_ihex(vec![]).is_err());
+        assert!(image.to_srec(vec![]).is_err());
+    }
+}
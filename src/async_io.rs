@@ -0,0 +1,317 @@
+//! Parse and compose GCD files from tokio async readers and writers, with
+//! the feature `async`.
+//!
+//! The records are read whole, one at a time, and parsed in memory by a
+//! [`Parser`], the data composed by each call is written by a [`Composer`] in
+//! memory, so the async versions behave exactly like the sync ones.
+
+use byteorder::ByteOrder;
+use std::io::{Cursor, ErrorKind};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::codec::SIGNATURE_LEN;
+use crate::composer::Composer;
+use crate::config::{ComposerConfig, ParserConfig};
+use crate::error::{GcdError, Result};
+use crate::lint::Warning;
+use crate::parser::{ParsePhase, ParseWarning, Parser};
+use crate::record::checksum::Checksums;
+use crate::{GcdDefaultEndian, Record, RECORD_HEADER_LEN};
+
+/// Parse a GCD file from an [`AsyncRead`], see [`Parser`].
+///
+/// The recovery of [`ParserConfig::recover`] is not supported, it need to
+/// scan the data ahead of the record.
+pub struct AsyncParser<R, B = GcdDefaultEndian>
+where
+    R: AsyncRead + Unpin,
+    B: ByteOrder,
+{
+    reader: R,
+    // the data of the next record is placed in the cursor, before parsing
+    parser: Parser<Cursor<Vec<u8>>, B>,
+}
+
+impl<R, B> AsyncParser<R, B>
+where
+    R: AsyncRead + Unpin,
+    B: ByteOrder,
+{
+    pub async fn new(reader: R) -> Result<Self> {
+        Self::with_config(reader, ParserConfig::default()).await
+    }
+
+    /// Create the parser with the options, see [`ParserConfig`].
+    pub async fn with_config(
+        mut reader: R,
+        config: ParserConfig,
+    ) -> Result<Self> {
+        if config.recover {
+            return Err(GcdError::invalid_input(
+                "The async parser can't recover from errors",
+            ));
+        }
+        let mut signature = vec![0; SIGNATURE_LEN];
+        reader.read_exact(&mut signature).await?;
+        let parser = Parser::with_config(Cursor::new(signature), config)?;
+        Ok(Self { reader, parser })
+    }
+
+    /// Read the next available record, see [`Parser::read_record`].
+    pub async fn read_record(&mut self) -> Result<Record> {
+        loop {
+            //after the End the parser return the error
+            if self.parser.state() != ParsePhase::End {
+                self.read_next().await?;
+            }
+            match self.parser.read_record() {
+                //the parser need the next record, eg: after the
+                //DescriptorType, the DescriptorData is returned with it
+                Err(GcdError::Io(error))
+                    if error.kind() == ErrorKind::UnexpectedEof
+                        && self.is_consumed() => {}
+                record => return record,
+            }
+        }
+    }
+
+    /// Read the data after the End record, until the end of the reader, see
+    /// [`Parser::finish`].
+    pub async fn finish(&mut self) -> Result<Vec<u8>> {
+        if self.parser.state() == ParsePhase::End {
+            let buf = self.parser.file_mut();
+            buf.get_mut().clear();
+            self.reader.read_to_end(buf.get_mut()).await?;
+            buf.set_position(0);
+        }
+        self.parser.finish()
+    }
+
+    /// Phase of the file structure the parser is in.
+    pub fn state(&self) -> ParsePhase {
+        self.parser.state()
+    }
+
+    /// Number of bytes read from the reader, including the signature.
+    pub fn position(&self) -> u64 {
+        self.parser.position()
+    }
+
+    /// Checksums of all the data read so far, see [`Parser::checksums`].
+    pub fn checksums(&self) -> Checksums {
+        self.parser.checksums()
+    }
+
+    /// Problems found while parsing, see [`Parser::warnings`].
+    pub fn warnings(&self) -> &[ParseWarning] {
+        self.parser.warnings()
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    // place the next record from the reader in the parser file
+    async fn read_next(&mut self) -> Result<()> {
+        let buf = self.parser.file_mut();
+        let data = buf.get_mut();
+        data.clear();
+        data.resize(RECORD_HEADER_LEN, 0);
+        self.reader.read_exact(data).await?;
+        let len = usize::from(B::read_u16(&data[2..]));
+        data.resize(RECORD_HEADER_LEN + len, 0);
+        self.reader.read_exact(&mut data[RECORD_HEADER_LEN..]).await?;
+        buf.set_position(0);
+        Ok(())
+    }
+
+    // all the data placed in the parser file was parsed
+    fn is_consumed(&mut self) -> bool {
+        let buf = self.parser.file_mut();
+        buf.position() == buf.get_ref().len() as u64
+    }
+}
+
+/// Compose a GCD file into an [`AsyncWrite`], see [`Composer`].
+pub struct AsyncComposer<W, B = GcdDefaultEndian>
+where
+    W: AsyncWrite + Unpin,
+    B: ByteOrder,
+{
+    writer: W,
+    // the composed data, written to the writer after each record
+    composer: Composer<Vec<u8>, B>,
+}
+
+impl<W, B> AsyncComposer<W, B>
+where
+    W: AsyncWrite + Unpin,
+    B: ByteOrder,
+{
+    pub async fn new(writer: W) -> Result<Self> {
+        Self::with_config(writer, &ComposerConfig::default()).await
+    }
+
+    /// Create the composer with the options, see [`ComposerConfig`].
+    pub async fn with_config(
+        writer: W,
+        config: &ComposerConfig,
+    ) -> Result<Self> {
+        let composer = Composer::with_config(vec![], config)?;
+        let mut composer = Self { writer, composer };
+        composer.write_composed().await?;
+        Ok(composer)
+    }
+
+    /// Write a record, see [`Composer::write_record`].
+    pub async fn write_record(&mut self, record: &Record) -> Result<()> {
+        self.composer.write_record(record)?;
+        self.write_composed().await
+    }
+
+    /// Write the firmware data split in chunks, see
+    /// [`Composer::write_firmware_data`].
+    pub async fn write_firmware_data(
+        &mut self,
+        id: u16,
+        data: &[u8],
+    ) -> Result<()> {
+        self.composer.write_firmware_data(id, data)?;
+        self.write_composed().await
+    }
+
+    /// Flush the underlying writer.
+    pub async fn flush(&mut self) -> Result<()> {
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    /// Bytes written, including the file signature.
+    pub fn position(&self) -> u64 {
+        self.composer.position()
+    }
+
+    /// Checksums of all the data written so far, see
+    /// [`Composer::checksums`].
+    pub fn checksums(&self) -> Checksums {
+        self.composer.checksums()
+    }
+
+    /// Problems found by the linter, see [`Composer::warnings`].
+    pub fn warnings(&self) -> &[Warning] {
+        self.composer.warnings()
+    }
+
+    /// Return the underlying writer, the data is not flushed.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    // write the data composed so far
+    async fn write_composed(&mut self) -> Result<()> {
+        let data = self.composer.file_mut();
+        self.writer.write_all(data).await?;
+        data.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AsyncComposer, AsyncParser};
+    use crate::composer::compose_all;
+    use crate::config::ParserConfig;
+    use crate::error::{GcdError, Result};
+    use crate::parser::parse_all;
+    use crate::record::firmware::FirmwareRecord;
+    use crate::testing::vectors;
+    use crate::{Record, RecordId};
+    use byteorder::LE;
+    use std::future::Future;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    async fn read_all(data: &[u8]) -> Result<(Vec<Record>, Vec<u8>)> {
+        let mut parser: AsyncParser<_, LE> = AsyncParser::new(data).await?;
+        let mut records = vec![];
+        loop {
+            let record = parser.read_record().await?;
+            let end = matches!(record, Record::End);
+            records.push(record);
+            if end {
+                break;
+            }
+        }
+        Ok((records, parser.finish().await?))
+    }
+
+    #[test]
+    fn parse() {
+        for (name, data) in vectors::ALL {
+            let (records, trailing) = block_on(read_all(data)).unwrap();
+            assert_eq!(records, parse_all(data).unwrap(), "{}", name);
+            assert!(trailing.is_empty(), "{}", name);
+        }
+
+        let mut data = vectors::MULTI_BLOCK.to_vec();
+        data.extend_from_slice(b"signature");
+        let (records, trailing) = block_on(read_all(&data)).unwrap();
+        assert_eq!(records, parse_all(vectors::MULTI_BLOCK).unwrap());
+        assert_eq!(trailing, b"signature");
+
+        //truncated in the middle of a record
+        let data = &vectors::MULTI_BLOCK[..vectors::MULTI_BLOCK.len() - 6];
+        assert!(block_on(read_all(data)).is_err());
+        assert!(block_on(read_all(b"GARMIN")).is_err());
+
+        let config = ParserConfig {
+            recover: true,
+            ..ParserConfig::default()
+        };
+        let parser = AsyncParser::<_, LE>::with_config(&b""[..], config);
+        assert!(block_on(parser).is_err());
+    }
+
+    #[test]
+    fn compose() {
+        for (name, data) in vectors::ALL {
+            let records = parse_all(data).unwrap();
+            let file = block_on(async {
+                let mut composer: AsyncComposer<_, LE> =
+                    AsyncComposer::new(vec![]).await?;
+                for record in &records {
+                    composer.write_record(record).await?;
+                }
+                composer.flush().await?;
+                Ok::<_, GcdError>(composer.into_inner())
+            })
+            .unwrap();
+            assert_eq!(file, compose_all(&records).unwrap(), "{}", name);
+        }
+
+        let data: Vec<u8> = (0..0x2800u32).map(|x| x as u8).collect();
+        let file = block_on(async {
+            let mut composer: AsyncComposer<_, LE> =
+                AsyncComposer::new(vec![]).await?;
+            composer.write_firmware_data(1, &data).await?;
+            assert_eq!(composer.position(), 8 + 3 * 4 + data.len() as u64);
+            Ok::<_, GcdError>(composer.into_inner())
+        })
+        .unwrap();
+        let chunks: Vec<Record> = data
+            .chunks(0x1000)
+            .map(|chunk| {
+                let id = RecordId::new(1);
+                Record::FirmwareData(FirmwareRecord::new(chunk.to_vec(), id))
+            })
+            .collect();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(file, compose_all(&chunks).unwrap());
+    }
+}
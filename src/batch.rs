@@ -0,0 +1,228 @@
+//! Extract the identification of many GCD files at once.
+//!
+//! The result is a [`GcdSummary`] for each file, or the error that stopped
+//! the parsing. With the feature `rayon` the files are parsed in parallel,
+//! otherwise one after the other.
+//!
+//! ```no_run
+//! use gcd_rs::batch::{parse_dir, BatchOptions};
+//!
+//! let options = BatchOptions {
+//!     recursive: true,
+//!     ..Default::default()
+//! };
+//! for (path, summary) in parse_dir("firmwares", &options).unwrap() {
+//!     match summary {
+//!         Ok(summary) => println!("{}: {:?}", path.display(), summary.hwid),
+//!         Err(error) => println!("{}: {}", path.display(), error),
+//!     }
+//! }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::{DeviceInfo, FirmwareInfo, PartNumber};
+
+/// How the directory is scanned and parsed, see [`parse_dir`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BatchOptions {
+    /// Number of worker threads, by default the rayon global thread pool is
+    /// used. Only used with the feature `rayon`.
+    pub threads: Option<usize>,
+    /// Also parse the files in the subdirectories.
+    pub recursive: bool,
+    /// Only parse the files with one of this extensions, case insensitive.
+    /// If empty, all the files are parsed.
+    pub extensions: Vec<String>,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions {
+            threads: None,
+            recursive: false,
+            extensions: vec!["gcd".to_string()],
+        }
+    }
+}
+
+/// The metadata that identify a GCD file.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GcdSummary {
+    /// Size of the file in bytes
+    pub len: u64,
+    /// HWID from the MainHeader record
+    pub hwid: Option<u16>,
+    /// PartNumber from the MainHeader record
+    pub part_number: Option<PartNumber>,
+    /// Id, HWID and versions of each firmware block, in order
+    pub firmwares: Vec<FirmwareInfo>,
+}
+
+impl GcdSummary {
+    /// Parse the whole file, verifying the checksums.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let info = DeviceInfo::read(BufReader::new(file))?;
+        Ok(GcdSummary {
+            len,
+            hwid: info.hwid,
+            part_number: info.part_number,
+            firmwares: info.firmwares,
+        })
+    }
+
+    /// Firmware id of each block, in order.
+    pub fn firmware_ids(&self) -> impl Iterator<Item = u16> + '_ {
+        self.firmwares.iter().filter_map(|x| x.id)
+    }
+}
+
+/// List the files that [`parse_dir`] would parse, sorted by path.
+pub fn list_dir<P: AsRef<Path>>(
+    dir: P,
+    options: &BatchOptions,
+) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut dirs = vec![dir.as_ref().to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                if options.recursive {
+                    dirs.push(entry.path());
+                }
+            } else if matches_extension(&entry.path(), &options.extensions) {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn matches_extension(path: &Path, extensions: &[String]) -> bool {
+    if extensions.is_empty() {
+        return true;
+    }
+    let ext = path.extension().and_then(|x| x.to_str()).unwrap_or("");
+    extensions.iter().any(|x| x.eq_ignore_ascii_case(ext))
+}
+
+/// Parse all the files in the directory, see [`BatchOptions`].
+///
+/// Only an error listing the directory is returned, the error from each
+/// file is returned with its path. The result is sorted by path.
+pub fn parse_dir<P: AsRef<Path>>(
+    dir: P,
+    options: &BatchOptions,
+) -> Result<Vec<(PathBuf, Result<GcdSummary>)>> {
+    let files = list_dir(dir, options)?;
+    Ok(parse_files(files, options.threads))
+}
+
+/// Parse the files, in parallel with the feature `rayon`, the result is in
+/// the same order.
+#[cfg(feature = "rayon")]
+pub fn parse_files(
+    files: Vec<PathBuf>,
+    threads: Option<usize>,
+) -> Vec<(PathBuf, Result<GcdSummary>)> {
+    use rayon::prelude::*;
+    let parse = || {
+        files
+            .into_par_iter()
+            .map(|file| {
+                let summary = GcdSummary::read(&file);
+                (file, summary)
+            })
+            .collect()
+    };
+    //a dedicated pool, only if the number of threads is set
+    let pool = threads.and_then(|threads| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .ok()
+    });
+    match pool {
+        Some(pool) => pool.install(parse),
+        None => parse(),
+    }
+}
+
+/// Parse the files, in parallel with the feature `rayon`, the result is in
+/// the same order.
+#[cfg(not(feature = "rayon"))]
+pub fn parse_files(
+    files: Vec<PathBuf>,
+    _threads: Option<usize>,
+) -> Vec<(PathBuf, Result<GcdSummary>)> {
+    files
+        .into_iter()
+        .map(|file| {
+            let summary = GcdSummary::read(&file);
+            (file, summary)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{list_dir, parse_dir, BatchOptions};
+    use crate::testing::vectors;
+    use crate::DeviceInfo;
+
+    #[test]
+    fn batch_parse_dir() {
+        let dir = std::env::temp_dir()
+            .join(format!("gcd-rs-batch-{}", std::process::id()));
+        let sub = dir.join("sub");
+        std::fs::create_dir_all(&sub).unwrap();
+        for (i, (_, data)) in vectors::ALL.iter().enumerate() {
+            std::fs::write(dir.join(format!("{}.gcd", i)), data).unwrap();
+        }
+        std::fs::write(sub.join("nested.GCD"), vectors::SINGLE_BLOCK).unwrap();
+        std::fs::write(dir.join("damaged.gcd"), &vectors::SINGLE_BLOCK[..20])
+            .unwrap();
+        std::fs::write(dir.join("readme.txt"), "not a gcd").unwrap();
+
+        let options = BatchOptions {
+            threads: Some(3),
+            ..Default::default()
+        };
+        let result = parse_dir(&dir, &options).unwrap();
+        assert_eq!(result.len(), vectors::ALL.len() + 1);
+        for (i, (_, data)) in vectors::ALL.iter().enumerate() {
+            let (path, summary) = &result[i];
+            assert_eq!(path, &dir.join(format!("{}.gcd", i)));
+            let summary = summary.as_ref().unwrap();
+            let info = DeviceInfo::read(*data).unwrap();
+            assert_eq!(summary.len, data.len() as u64);
+            assert_eq!(summary.hwid, info.hwid);
+            assert_eq!(summary.firmwares, info.firmwares);
+            assert!(summary.firmware_ids().count() > 0);
+        }
+        let (path, summary) = result.last().unwrap();
+        assert_eq!(path, &dir.join("damaged.gcd"));
+        assert!(summary.is_err());
+
+        let options = BatchOptions {
+            recursive: true,
+            extensions: vec![],
+            ..Default::default()
+        };
+        let files = list_dir(&dir, &options).unwrap();
+        assert_eq!(files.len(), vectors::ALL.len() + 3);
+        assert!(files.contains(&sub.join("nested.GCD")));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,112 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use gcd_rs::block::FirmwareBlockBuilder;
+use gcd_rs::composer::Composer;
+use gcd_rs::parser::Parser;
+use gcd_rs::record::checksum::{Checksum, Sum8};
+#[cfg(feature = "crc")]
+use gcd_rs::record::checksum::{Checksums, Crc16, Crc32};
+use gcd_rs::record::firmware::transform::TransformTable;
+use gcd_rs::record::main::MainRecord;
+use gcd_rs::Record;
+use std::io::sink;
+
+const LEN: usize = 1 << 20;
+// the firmware data is processed one record at a time
+const CHUNK: usize = 0x1000;
+
+fn data() -> Vec<u8> {
+    (0..LEN).map(|x| (x * 7) as u8).collect()
+}
+
+// file with a single xored firmware of LEN bytes
+fn file() -> Vec<u8> {
+    let mut file = vec![];
+    let mut composer: Composer<_> = Composer::new(&mut file).unwrap();
+    composer
+        .write_record(&Record::MainHeader(MainRecord::DefaultHWID))
+        .unwrap();
+    FirmwareBlockBuilder::new(0x0505, data())
+        .xor_key(0x55)
+        .write(&mut composer)
+        .unwrap();
+    composer.write_record(&Record::End).unwrap();
+    drop(composer);
+    file
+}
+
+fn checksum(c: &mut Criterion) {
+    let data = data();
+    let mut group = c.benchmark_group("checksum");
+    group.throughput(Throughput::Bytes(LEN as u64));
+    group.bench_function("sum8", |b| {
+        b.iter(|| {
+            let mut sum = Sum8::default();
+            data.chunks(CHUNK).for_each(|x| sum.update(x));
+            sum.value()
+        })
+    });
+    #[cfg(feature = "crc")]
+    group.bench_function("crc16", |b| {
+        b.iter(|| {
+            let mut crc = Crc16::default();
+            data.chunks(CHUNK).for_each(|x| crc.update(x));
+            crc.value()
+        })
+    });
+    #[cfg(feature = "crc")]
+    group.bench_function("crc32", |b| {
+        b.iter(|| {
+            let mut crc = Crc32::default();
+            data.chunks(CHUNK).for_each(|x| crc.update(x));
+            crc.value()
+        })
+    });
+    #[cfg(feature = "crc")]
+    group.bench_function("all", |b| {
+        b.iter(|| {
+            let mut checksums = Checksums::with_crc();
+            data.chunks(CHUNK).for_each(|x| checksums.update(x));
+            checksums
+        })
+    });
+    group.finish();
+}
+
+fn xor(c: &mut Criterion) {
+    let mut data = data();
+    let table = TransformTable::empty();
+    let mut group = c.benchmark_group("xor");
+    group.throughput(Throughput::Bytes(LEN as u64));
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            data.chunks_mut(CHUNK)
+                .for_each(|x| table.decode(0x0505, 0x55, x))
+        })
+    });
+    group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+    let file = file();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(file.len() as u64));
+    group.bench_function("read_firmware_into", |b| {
+        b.iter(|| {
+            let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+            let mut len = 0;
+            loop {
+                match parser.read_record().unwrap() {
+                    Record::Descriptor(_) => {
+                        len += parser.read_firmware_into(sink()).unwrap()
+                    }
+                    Record::End => break len,
+                    _ => {}
+                }
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, checksum, xor, parse);
+criterion_main!(benches);
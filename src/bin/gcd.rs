@@ -0,0 +1,222 @@
+use gcd_rs::block;
+use gcd_rs::composer::Composer;
+use gcd_rs::display::Tree;
+use gcd_rs::hexdump;
+use gcd_rs::manifest::{self, ManifestRecord};
+use gcd_rs::parser::Parser;
+use gcd_rs::roundtrip;
+use gcd_rs::{DeviceInfo, GcdDefaultEndian, Record};
+
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::exit;
+
+const USAGE: &str = "Usage:
+    gcd info <file>
+        Print the device information and the firmware blocks.
+    gcd tree <file>
+        Print all the records, grouped by firmware block.
+    gcd extract [-o <dir>] <file>
+        Write the firmware blocks and a manifest.yaml into dir.
+    gcd create [-o <out>] <manifest.yaml>
+        Compose a file from the manifest and the firmware files.
+    gcd verify <file>
+        Check if the file is identical after a parse/compose round trip.
+    gcd hexdump [--limit <bytes>] <file>
+        Hexdump the file, annotated with the records structure.";
+
+const MANIFEST: &str = "manifest.yaml";
+
+type CmdResult = Result<(), Box<dyn Error>>;
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    exit(2)
+}
+
+//parse "[-o <output>] <input>", output is optional
+fn input_output(args: &[String]) -> (&str, Option<&str>) {
+    let mut input = None;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                output = Some(value.as_str());
+            }
+            _ if input.is_none() => input = Some(arg.as_str()),
+            _ => usage(),
+        }
+    }
+    (input.unwrap_or_else(|| usage()), output)
+}
+
+fn single_file(args: &[String]) -> &str {
+    match args {
+        [filename] => filename,
+        _ => usage(),
+    }
+}
+
+fn info(args: &[String]) -> CmdResult {
+    let parser: Parser<_> = Parser::open(single_file(args))?;
+    let records = parser.collect::<Result<Vec<Record>, _>>()?;
+    let info = DeviceInfo::from_records(&records);
+
+    if let Some(hwid) = info.hwid {
+        println!("HWID: {:#06x}", hwid);
+    }
+    if let Some(part_number) = &info.part_number {
+        println!("PartNumber: {}", part_number);
+    }
+    for text in info.texts.iter() {
+        println!("Text: {}", text);
+    }
+    for (block, firmware) in block::list_blocks(&records)
+        .iter()
+        .zip(info.firmwares.iter())
+    {
+        print!("Block {}:", block.index);
+        if let Some(id) = block.id {
+            print!(" id {:#06x}", id);
+        }
+        if let Some(len) = block.len {
+            print!(", len {}", len);
+        }
+        print!(", {} chunks", block.chunks);
+        if let Some(version) = &firmware.version_sw {
+            print!(", version {}", version);
+        }
+        if let Some(label) = &block.label {
+            print!(", {}", label);
+        }
+        println!();
+    }
+    Ok(())
+}
+
+fn tree(args: &[String]) -> CmdResult {
+    let parser: Parser<_> = Parser::open(single_file(args))?;
+    let records = parser.collect::<Result<Vec<Record>, _>>()?;
+    print!("{}", Tree::new(&records));
+    Ok(())
+}
+
+fn extract(args: &[String]) -> CmdResult {
+    let (filename, dir) = input_output(args);
+    let dir = Path::new(dir.unwrap_or("."));
+    fs::create_dir_all(dir)?;
+    let mut parser: Parser<_> = Parser::open(filename)?;
+    let records = manifest::extract(&mut parser, dir)?;
+    let manifest = File::create(dir.join(MANIFEST))?;
+    serde_yaml::to_writer(manifest, &records)?;
+    Ok(())
+}
+
+fn create(args: &[String]) -> CmdResult {
+    let (filename, output) = input_output(args);
+    let output = output.unwrap_or("out.gcd");
+    //firmware files are relative to the manifest
+    let dir = Path::new(filename)
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+    let records: Vec<ManifestRecord> =
+        serde_yaml::from_reader(File::open(filename)?)?;
+    let file = io::BufWriter::new(File::create(output)?);
+    let mut composer: Composer<_> = Composer::new(file)?;
+    manifest::create(&records, dir, &mut composer)?;
+    Ok(())
+}
+
+fn verify(args: &[String]) -> CmdResult {
+    let file = File::open(single_file(args))?;
+    let report = roundtrip::verify(file)?;
+    println!("{}", report);
+    if !report.is_identical() {
+        exit(1);
+    }
+    Ok(())
+}
+
+fn hexdump(args: &[String]) -> CmdResult {
+    let mut limit = None;
+    let mut filename = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-l" | "--limit" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                limit = Some(value.parse().unwrap_or_else(|_| usage()));
+            }
+            _ if filename.is_none() => filename = Some(arg),
+            _ => usage(),
+        }
+    }
+    let data = fs::read(filename.unwrap_or_else(|| usage()))?;
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    hexdump::annotate::<GcdDefaultEndian, _>(&data, limit, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<String>>();
+    let result = match args.get(1).map(String::as_str) {
+        Some("info") => info(&args[2..]),
+        Some("tree") => tree(&args[2..]),
+        Some("extract") => extract(&args[2..]),
+        Some("create") => create(&args[2..]),
+        Some("verify") => verify(&args[2..]),
+        Some("hexdump") => hexdump(&args[2..]),
+        _ => usage(),
+    };
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create, extract, info, tree, verify, MANIFEST};
+    use gcd_rs::testing::vectors;
+    use std::fs;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn extract_create() {
+        let dir = std::env::temp_dir()
+            .join(format!("gcd-cli-{}", std::process::id()));
+        let extracted = dir.join("extracted");
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.gcd");
+        let output = dir.join("out.gcd");
+        fs::write(&input, vectors::MULTI_BLOCK).unwrap();
+        let (input, output, extracted) = (
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            extracted.to_str().unwrap(),
+        );
+
+        extract(&args(&["-o", extracted, input])).unwrap();
+        let manifest = format!("{}/{}", extracted, MANIFEST);
+        create(&args(&["-o", output, &manifest])).unwrap();
+        assert_eq!(fs::read(output).unwrap(), vectors::MULTI_BLOCK);
+
+        //the other commands only print
+        verify(&args(&[output])).unwrap();
+        info(&args(&[output])).unwrap();
+        tree(&args(&[output])).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(info(&args(&[input])).is_err());
+    }
+}
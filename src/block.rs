@@ -0,0 +1,442 @@
+//! Summary and construction of the firmware blocks of a file.
+//!
+//! A firmware block is composed of a Descriptor followed by the firmware
+//! chunks and text records, until the next Descriptor or the End record.
+
+use byteorder::ByteOrder;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+use crate::composer::Composer;
+use crate::error::{GcdError, Result};
+use crate::record::checksum::ChecksumRecord;
+use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+use crate::record::descriptor::DescriptorRecord;
+use crate::record::firmware::FirmwareRecord;
+use crate::record::text::{RegionLabel, TextRecord};
+use crate::region;
+use crate::{Record, RecordId, Version};
+
+/// Max chunk size used by [`FirmwareBlockBuilder`], if not configured.
+pub const DEFAULT_MAX_CHUNK_SIZE: u16 = 0x1000;
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct BlockInfo {
+    /// Position of the block in the file, starting at 0
+    pub index: usize,
+    /// Firmware id, declared on the Descriptor
+    pub id: Option<u16>,
+    /// Firmware len, declared on the Descriptor
+    pub len: Option<u32>,
+    /// Number of firmware chunks
+    pub chunks: usize,
+    /// Sum of the firmware chunks len
+    pub data_len: u64,
+    /// Region from a text record, eg: "RGN 14 fw_all"
+    pub label: Option<RegionLabel>,
+}
+
+impl BlockInfo {
+    fn new(index: usize) -> Self {
+        BlockInfo {
+            index,
+            ..Default::default()
+        }
+    }
+
+    /// Region number of the block, from the region label, if any, or the
+    /// firmware id, see [`region::number`].
+    pub fn region(&self) -> Option<u16> {
+        match &self.label {
+            Some(label) => Some(label.index),
+            None => self.id.map(region::number),
+        }
+    }
+}
+
+/// List the firmware blocks of a file.
+///
+/// Region text records are attached to the block they are in, a region text
+/// before the first block is attached to the first block.
+pub fn list_blocks<'a, I>(records: I) -> Vec<BlockInfo>
+where
+    I: IntoIterator<Item = &'a Record>,
+{
+    let mut blocks: Vec<BlockInfo> = vec![];
+    let mut label = None;
+    for record in records {
+        match record {
+            Record::Descriptor(descriptor) => {
+                let mut block = BlockInfo::new(blocks.len());
+                block.label = label.take();
+                block.id = descriptor.firmware_id();
+                block.len = descriptor.firmware_len();
+                blocks.push(block);
+            }
+            Record::FirmwareData(firmware) => {
+                if let Some(block) = blocks.last_mut() {
+                    block.chunks += 1;
+                    block.data_len += firmware.len() as u64;
+                }
+            }
+            Record::Text(text) => {
+                if let Some(region) = text.region() {
+                    match blocks.last_mut() {
+                        Some(block) if block.label.is_none() => {
+                            block.label = Some(region)
+                        }
+                        Some(_) => {}
+                        None => label = Some(region),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
+
+/// Decoded firmware of a [`FirmwareBlock`], all the chunks concatenated.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct FirmwareData {
+    /// Record id of the chunks, the firmware id
+    pub id: RecordId,
+    /// The decoded data
+    pub data: Vec<u8>,
+}
+
+/// A whole firmware block, returned by
+/// [`Parser::read_block`](crate::parser::Parser::read_block), without the
+/// chunk boundaries.
+///
+/// ```
+/// use gcd_rs::parser::Parser;
+/// use gcd_rs::testing::vectors;
+///
+/// let mut parser: Parser<&[u8]> = Parser::new(vectors::MULTI_BLOCK).unwrap();
+/// while let Some(block) = parser.read_block().unwrap() {
+///     assert_eq!(block.descriptor.firmware_id(), Some(block.data.id.get()));
+///     assert_eq!(block.len(), block.descriptor.firmware_len().unwrap());
+/// }
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct FirmwareBlock {
+    pub descriptor: DescriptorRecord,
+    /// Text records inside the block, in the file order
+    pub texts: Vec<TextRecord>,
+    pub data: FirmwareData,
+    /// Len of each firmware chunk in the file, see
+    /// [`FirmwareBlock::chunk_layout`]
+    pub chunks: Vec<u16>,
+}
+
+impl FirmwareBlock {
+    /// Len of the decoded firmware.
+    pub fn len(&self) -> u32 {
+        self.data.data.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.data.is_empty()
+    }
+
+    /// Len of the firmware chunks, in the file order. Empty if unknown, then
+    /// [`Composer::write_block`] split the firmware with the max chunk size.
+    ///
+    /// The lens are of the stored data, if the firmware was decompressed, the
+    /// chunks don't match the data.
+    pub fn chunk_layout(&self) -> &[u16] {
+        &self.chunks
+    }
+
+    /// Region label from the text records, if any.
+    pub fn label(&self) -> Option<RegionLabel> {
+        self.texts.iter().find_map(TextRecord::region)
+    }
+
+    /// Region number of the block, see [`BlockInfo::region`].
+    pub fn region(&self) -> Option<u16> {
+        match self.label() {
+            Some(label) => Some(label.index),
+            None => self.descriptor.firmware_id().map(region::number),
+        }
+    }
+}
+
+/// Create the records of a firmware block, from the firmware data.
+///
+/// ```
+/// use gcd_rs::block::FirmwareBlockBuilder;
+/// use gcd_rs::record::text::TextRecord;
+/// use gcd_rs::Version;
+///
+/// let records = FirmwareBlockBuilder::new(0x0505, vec![0; 0x2800])
+///     .version(Version::new(3, 80))
+///     .xor_key(0x55)
+///     .text(TextRecord::Simple("RGN 14 fw_all".to_string()))
+///     .build()
+///     .unwrap();
+/// //descriptor, text, 3 chunks and the checksum
+/// assert_eq!(records.len(), 6);
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FirmwareBlockBuilder {
+    id: u16,
+    data: Vec<u8>,
+    version: Option<Version>,
+    xor_key: Option<u8>,
+    texts: Vec<TextRecord>,
+    max_chunk_size: u16,
+    checksum: bool,
+}
+
+impl FirmwareBlockBuilder {
+    /// Block with the firmware `id` and the decoded firmware `data`.
+    pub fn new(id: u16, data: Vec<u8>) -> Self {
+        FirmwareBlockBuilder {
+            id,
+            data,
+            version: None,
+            xor_key: None,
+            texts: vec![],
+            max_chunk_size: DEFAULT_MAX_CHUNK_SIZE,
+            checksum: true,
+        }
+    }
+
+    /// Software version declared on the Descriptor.
+    pub fn version(mut self, version: Version) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Encode the firmware with the key, declared on the Descriptor.
+    pub fn xor_key(mut self, key: u8) -> Self {
+        self.xor_key = Some(key);
+        self
+    }
+
+    /// Text record written after the Descriptor, can be called multiple
+    /// times.
+    pub fn text(mut self, text: TextRecord) -> Self {
+        self.texts.push(text);
+        self
+    }
+
+    /// Split the firmware in chunks of up to `max` bytes.
+    pub fn max_chunk_size(mut self, max: u16) -> Self {
+        self.max_chunk_size = max;
+        self
+    }
+
+    /// Write a checksum record after the last chunk, enabled by default.
+    pub fn checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// The Descriptor of the block.
+    pub fn descriptor(&self) -> Result<DescriptorRecord> {
+        if self.data.len() > u32::MAX as usize {
+            return Err(GcdError::invalid_input(
+                "Firmware is bigger than u32::MAX",
+            ));
+        }
+        let mut desc = vec![];
+        if let Some(key) = self.xor_key {
+            desc.push(DescriptorDecoded::XorKey(key).encode());
+        }
+        desc.push(DescriptorDecoded::FirmwareId(self.id).encode());
+        if let Some(version) = self.version {
+            desc.push(DescriptorDecoded::VersionSw(version).encode());
+        }
+        desc.push(
+            DescriptorDecoded::FirmwareLen(self.data.len() as u32).encode(),
+        );
+        desc.push(DescriptorDecoded::End.encode());
+        Ok(DescriptorRecord::Simple(desc))
+    }
+
+    /// All the records of the block, the firmware chunks are not encoded,
+    /// the [`Composer`] encode them when written.
+    pub fn build(&self) -> Result<Vec<Record>> {
+        if self.max_chunk_size == 0 {
+            return Err(GcdError::invalid_input("Chunk size can't be zero"));
+        }
+        let mut records = vec![Record::Descriptor(self.descriptor()?)];
+        records.extend(self.texts.iter().cloned().map(Record::Text));
+        for chunk in self.data.chunks(self.max_chunk_size as usize) {
+            records.push(Record::FirmwareData(FirmwareRecord::new(
+                chunk.to_vec(),
+                RecordId::new(self.id),
+            )));
+        }
+        if self.checksum {
+            records.push(Record::Checksum(ChecksumRecord::Simple));
+        }
+        Ok(records)
+    }
+
+    /// Write all the records of the block.
+    pub fn write<F, B>(&self, composer: &mut Composer<F, B>) -> Result<()>
+    where
+        F: Write,
+        B: ByteOrder,
+    {
+        for record in self.build()? {
+            composer.write_record(&record)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block::{
+        list_blocks, BlockInfo, FirmwareBlock, FirmwareBlockBuilder,
+    };
+    use crate::composer::{compose_all, Composer};
+    use crate::error::GcdError;
+    use crate::lint::Linter;
+    use crate::parser::Parser;
+    use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+    use crate::record::descriptor::DescriptorRecord;
+    use crate::record::firmware::FirmwareRecord;
+    use crate::record::firmware::FONT_ID;
+    use crate::record::main::MainRecord;
+    use crate::record::text::{RegionLabel, TextRecord};
+    use crate::{GcdFile, Record, RecordId, Version};
+
+    #[test]
+    fn region_label() {
+        let label: RegionLabel = "RGN 14 fw_all".parse().unwrap();
+        assert_eq!(label.index, 14);
+        assert_eq!(label.name, "fw_all");
+        let label: RegionLabel = "Region 0x0e: fw_all.bin\0".parse().unwrap();
+        assert_eq!(label.index, 14);
+        assert_eq!(label.name, "fw_all.bin");
+        assert_eq!("rgn14".parse::<RegionLabel>().unwrap().index, 14);
+        //the label has priority over the firmware id
+        let mut block = BlockInfo {
+            id: Some(0x0505),
+            ..Default::default()
+        };
+        assert_eq!(block.region(), Some(14));
+        block.label = Some("RGN 16".parse().unwrap());
+        assert_eq!(block.region(), Some(16));
+        assert_eq!(BlockInfo::default().region(), None);
+        assert!("Regional settings".parse::<RegionLabel>().is_err());
+    }
+
+    #[test]
+    fn blocks_label() {
+        let descriptor = Record::Descriptor(DescriptorRecord::Simple(vec![
+            DescriptorDecoded::FirmwareId(0x0505).encode(),
+            DescriptorDecoded::FirmwareLen(4).encode(),
+            DescriptorDecoded::End.encode(),
+        ]));
+        let records = [
+            descriptor.clone(),
+            Record::Text(TextRecord::Simple("RGN 14 fw_all".to_string())),
+            Record::FirmwareData(FirmwareRecord::new(
+                vec![0; 4],
+                RecordId::new(0x0505),
+            )),
+            descriptor,
+            Record::End,
+        ];
+        let blocks = list_blocks(&records);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].label.as_ref().unwrap().index, 14);
+        assert_eq!(blocks[0].chunks, 1);
+        assert_eq!(blocks[1].label, None);
+    }
+
+    #[test]
+    fn chunk_layout() {
+        let id = RecordId::new(0x0505);
+        let firmware: Vec<u8> = (0..10).collect();
+        let chunk = |range: std::ops::Range<usize>| {
+            Record::FirmwareData(FirmwareRecord::new(
+                firmware[range].to_vec(),
+                id,
+            ))
+        };
+        let records = [
+            Record::MainHeader(MainRecord::DefaultHWID),
+            Record::Descriptor(DescriptorRecord::Simple(vec![
+                DescriptorDecoded::FirmwareId(0x0505).encode(),
+                DescriptorDecoded::XorKey(0x55).encode(),
+                DescriptorDecoded::FirmwareLen(10).encode(),
+                DescriptorDecoded::End.encode(),
+            ])),
+            Record::Text(TextRecord::Simple("RGN 14 fw_all".to_string())),
+            chunk(0..3),
+            chunk(3..8),
+            chunk(8..10),
+            Record::End,
+        ];
+        let file = compose_all(&records).unwrap();
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        let mut block = parser.read_block().unwrap().unwrap();
+        assert_eq!(block.chunk_layout(), [3, 5, 2]);
+        assert_eq!(block.data.data, firmware);
+
+        //same chunk boundaries on the composed file
+        let compose = |block: &FirmwareBlock| {
+            let mut out = vec![];
+            let mut composer: Composer<_> = Composer::new(&mut out).unwrap();
+            composer.write_record(&records[0]).unwrap();
+            composer.set_max_chunk_size(Some(4)).unwrap();
+            composer.write_block(block)?;
+            composer.write_record(&Record::End).unwrap();
+            drop(composer);
+            Ok::<_, GcdError>(out)
+        };
+        assert_eq!(compose(&block).unwrap(), file);
+
+        //without layout, split with the max chunk size
+        block.chunks.clear();
+        let out = compose(&block).unwrap();
+        let mut parser: Parser<&[u8]> = Parser::new(&out[..]).unwrap();
+        let parsed = parser.read_block().unwrap().unwrap();
+        assert_eq!(parsed.chunk_layout(), [4, 4, 2]);
+        assert_eq!(parsed.data, block.data);
+
+        block.chunks = vec![3, 5];
+        assert!(compose(&block).is_err());
+    }
+
+    #[test]
+    fn block_builder() {
+        let firmware: Vec<u8> = (0..1000u32).map(|x| x as u8).collect();
+        let mut file = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut file).unwrap();
+        composer.set_linter(Some(Linter::default()));
+        composer
+            .write_record(&Record::MainHeader(MainRecord::DefaultHWID))
+            .unwrap();
+        FirmwareBlockBuilder::new(0x0505, firmware.clone())
+            .xor_key(0x55)
+            .version(Version::new(3, 80))
+            .max_chunk_size(300)
+            .write(&mut composer)
+            .unwrap();
+        FirmwareBlockBuilder::new(FONT_ID, firmware.clone())
+            .version(Version::new(1, 0))
+            .text(TextRecord::Simple("RGN 14 fw_all".to_string()))
+            .write(&mut composer)
+            .unwrap();
+        composer.write_record(&Record::End).unwrap();
+        assert_eq!(composer.warnings(), []);
+        drop(composer);
+
+        let file: GcdFile = GcdFile::from_bytes(file).unwrap();
+        assert_eq!(file.blocks()[0].chunks.len(), 4);
+        assert_eq!(file.read_block(0).unwrap(), firmware);
+        assert_eq!(file.read_block(1).unwrap(), firmware);
+
+        let builder = FirmwareBlockBuilder::new(0x0505, vec![]);
+        assert!(builder.max_chunk_size(0).build().is_err());
+    }
+}
@@ -0,0 +1,367 @@
+//! Build metadata embedded in the GCD file.
+//!
+//! Allow pipelines to trace which build produced a file. The metadata is
+//! stored using one of the conventions:
+//!
+//! * Text records, one for each field, eg: "BUILD timestamp=1634000000".
+//! * An `Other` descriptor, with id [`DESCRIPTOR_ID`], composed of TLV
+//!   entries, one for each field.
+//!
+//! [`MetadataPolicy`] allow the [`Composer`](crate::composer::Composer) to
+//! omit or fix the metadata, so the output is reproducible.
+
+use byteorder::ByteOrder;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::record::descriptor::descriptor_data::DescriptorData;
+use crate::record::descriptor::tlv::{self, TlvEntry, TlvValue};
+use crate::record::descriptor::DescriptorRecord;
+use crate::record::text::TextRecord;
+use crate::Record;
+
+/// Prefix of the metadata text records.
+pub const TEXT_PREFIX: &str = "BUILD ";
+/// Id of the metadata `Other` descriptor.
+pub const DESCRIPTOR_ID: u16 = 0x0b1d;
+
+const TIMESTAMP: &str = "timestamp";
+const BUILDER: &str = "builder";
+const SOURCE_HASH: &str = "source-hash";
+
+const TAG_TIMESTAMP: u16 = 1;
+const TAG_BUILDER: u16 = 2;
+const TAG_SOURCE_HASH: u16 = 3;
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BuildMetadata {
+    /// Build time, in seconds since UNIX_EPOCH
+    pub timestamp: Option<u64>,
+    /// Identity of the builder, eg: the CI job
+    pub builder: Option<String>,
+    /// Hash of the source, eg: the git commit
+    pub source_hash: Option<String>,
+}
+
+impl BuildMetadata {
+    /// Metadata with the current time as timestamp.
+    pub fn now() -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .ok();
+        BuildMetadata {
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &BuildMetadata::default()
+    }
+
+    /// Metadata as text records, one for each field.
+    pub fn to_text_records(&self) -> Vec<TextRecord> {
+        let timestamp = self.timestamp.map(|x| (TIMESTAMP, x.to_string()));
+        let builder = self.builder.clone().map(|x| (BUILDER, x));
+        let source_hash = self.source_hash.clone().map(|x| (SOURCE_HASH, x));
+        timestamp
+            .into_iter()
+            .chain(builder)
+            .chain(source_hash)
+            .map(|(key, value)| {
+                TextRecord::Simple(format!("{}{}={}", TEXT_PREFIX, key, value))
+            })
+            .collect()
+    }
+
+    /// Metadata as an `Other` descriptor, with TLV entries.
+    pub fn to_descriptor<B: ByteOrder>(&self) -> DescriptorData {
+        let mut entries = vec![];
+        if let Some(timestamp) = self.timestamp {
+            let mut value = [0u8; 8];
+            B::write_u64(&mut value, timestamp);
+            entries.push(TlvEntry {
+                tag: TAG_TIMESTAMP,
+                value: TlvValue::Raw(value.to_vec()),
+            });
+        }
+        let texts = [
+            (TAG_BUILDER, &self.builder),
+            (TAG_SOURCE_HASH, &self.source_hash),
+        ];
+        for (tag, value) in texts.iter() {
+            if let Some(value) = value {
+                entries.push(TlvEntry {
+                    tag: *tag,
+                    value: TlvValue::Raw(value.as_bytes().to_vec()),
+                });
+            }
+        }
+        DescriptorData::Other {
+            id: DESCRIPTOR_ID,
+            data: tlv::to_raw::<B>(&entries),
+        }
+    }
+
+    /// Add the field from a metadata text record, return false if the text is
+    /// not metadata.
+    pub fn merge_text(&mut self, text: &TextRecord) -> bool {
+        let (key, value) = match text_entry(text) {
+            Some(x) => x,
+            None => return false,
+        };
+        match key {
+            TIMESTAMP => match value.parse() {
+                Ok(x) => self.timestamp = Some(x),
+                Err(_) => return false,
+            },
+            BUILDER => self.builder = Some(value.to_string()),
+            SOURCE_HASH => self.source_hash = Some(value.to_string()),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Add the fields from a metadata descriptor, return false if the
+    /// descriptor is not metadata.
+    pub fn merge_descriptor<B: ByteOrder>(
+        &mut self,
+        descriptor: &DescriptorData,
+    ) -> bool {
+        let entries = match descriptor {
+            DescriptorData::Other { id, data } if *id == DESCRIPTOR_ID => {
+                match tlv::parse::<B>(data) {
+                    Some(x) => x,
+                    None => return false,
+                }
+            }
+            _ => return false,
+        };
+        for entry in entries.iter() {
+            //the value may coincidentally match the TLV pattern
+            let value = match &entry.value {
+                TlvValue::Raw(x) => Cow::Borrowed(x),
+                TlvValue::Nested(x) => Cow::Owned(tlv::to_raw::<B>(x)),
+            };
+            match entry.tag {
+                TAG_TIMESTAMP if value.len() == 8 => {
+                    self.timestamp = Some(B::read_u64(&value))
+                }
+                TAG_BUILDER => {
+                    self.builder =
+                        Some(String::from_utf8_lossy(&value).into_owned())
+                }
+                TAG_SOURCE_HASH => {
+                    self.source_hash =
+                        Some(String::from_utf8_lossy(&value).into_owned())
+                }
+                _ => {}
+            }
+        }
+        true
+    }
+
+    /// Extract the metadata from the records, using both conventions.
+    pub fn extract<'a, B, I>(records: I) -> Option<Self>
+    where
+        B: ByteOrder,
+        I: IntoIterator<Item = &'a Record>,
+    {
+        let mut metadata = BuildMetadata::default();
+        let mut found = false;
+        for record in records {
+            match record {
+                Record::Text(text) => found |= metadata.merge_text(text),
+                Record::Descriptor(DescriptorRecord::Simple(descs)) => {
+                    for desc in descs.iter() {
+                        found |= metadata.merge_descriptor::<B>(desc);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if found {
+            Some(metadata)
+        } else {
+            None
+        }
+    }
+}
+
+// split a metadata text record in key and value
+fn text_entry(text: &TextRecord) -> Option<(&str, &str)> {
+    let text = text.as_str()?;
+    let mut entry = text.strip_prefix(TEXT_PREFIX)?.splitn(2, '=');
+    Some((entry.next()?, entry.next()?))
+}
+
+/// How the [`Composer`](crate::composer::Composer) writes the build
+/// metadata records.
+#[derive(
+    Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize,
+)]
+pub enum MetadataPolicy {
+    /// Metadata is written unchanged.
+    #[default]
+    Keep,
+    /// Metadata text records and descriptors are not written.
+    Omit,
+    /// The timestamp is replaced by this value, the other fields are kept.
+    FixedTimestamp(u64),
+}
+
+impl MetadataPolicy {
+    /// `FixedTimestamp` if the `SOURCE_DATE_EPOCH` env variable is set,
+    /// following the reproducible builds convention, otherwise `Keep`.
+    pub fn from_env() -> Self {
+        std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|x| x.trim().parse().ok())
+            .map(MetadataPolicy::FixedTimestamp)
+            .unwrap_or_default()
+    }
+
+    /// Apply the policy to the record, None if the record is omitted.
+    pub fn apply<'a, B: ByteOrder>(
+        &self,
+        record: &'a Record,
+    ) -> Option<Cow<'a, Record>> {
+        let timestamp = match self {
+            MetadataPolicy::Keep => return Some(Cow::Borrowed(record)),
+            MetadataPolicy::Omit => None,
+            MetadataPolicy::FixedTimestamp(x) => Some(*x),
+        };
+        match record {
+            Record::Text(text) => match (text_entry(text), timestamp) {
+                (None, _) => Some(Cow::Borrowed(record)),
+                (Some(_), None) => None,
+                (Some((TIMESTAMP, _)), Some(x)) => {
+                    let metadata = BuildMetadata {
+                        timestamp: Some(x),
+                        ..Default::default()
+                    };
+                    let mut texts = metadata.to_text_records();
+                    Some(Cow::Owned(Record::Text(texts.remove(0))))
+                }
+                (Some(_), Some(_)) => Some(Cow::Borrowed(record)),
+            },
+            Record::Descriptor(DescriptorRecord::Simple(descs)) => {
+                let is_metadata = |x: &DescriptorData| {
+                    BuildMetadata::default().merge_descriptor::<B>(x)
+                };
+                if !descs.iter().any(is_metadata) {
+                    return Some(Cow::Borrowed(record));
+                }
+                let descs = descs
+                    .iter()
+                    .filter_map(|desc| {
+                        let mut metadata = BuildMetadata::default();
+                        if !metadata.merge_descriptor::<B>(desc) {
+                            return Some(desc.clone());
+                        }
+                        metadata.timestamp = Some(timestamp?);
+                        Some(metadata.to_descriptor::<B>())
+                    })
+                    .collect();
+                Some(Cow::Owned(Record::Descriptor(DescriptorRecord::Simple(
+                    descs,
+                ))))
+            }
+            _ => Some(Cow::Borrowed(record)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BuildMetadata, MetadataPolicy};
+    use crate::composer::Composer;
+    use crate::parser::Parser;
+    use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+    use crate::record::descriptor::DescriptorRecord;
+    use crate::record::main::MainRecord;
+    use crate::record::text::TextRecord;
+    use crate::Record;
+    use byteorder::LE;
+
+    fn metadata() -> BuildMetadata {
+        BuildMetadata {
+            timestamp: Some(1_634_000_000),
+            builder: Some("ci-42".to_string()),
+            source_hash: Some("5d0a976".to_string()),
+        }
+    }
+
+    #[test]
+    fn text_and_descriptor() {
+        let texts: Vec<_> = metadata()
+            .to_text_records()
+            .into_iter()
+            .map(Record::Text)
+            .collect();
+        assert_eq!(
+            texts[0],
+            Record::Text(TextRecord::Simple(
+                "BUILD timestamp=1634000000".to_string()
+            ))
+        );
+        assert_eq!(BuildMetadata::extract::<LE, _>(&texts), Some(metadata()));
+
+        let desc = Record::Descriptor(DescriptorRecord::Simple(vec![
+            DescriptorDecoded::FirmwareId(0x0505).encode(),
+            metadata().to_descriptor::<LE>(),
+            DescriptorDecoded::End.encode(),
+        ]));
+        assert_eq!(
+            BuildMetadata::extract::<LE, _>(std::iter::once(&desc)),
+            Some(metadata())
+        );
+        let other = Record::Text(TextRecord::Simple("text".to_string()));
+        assert_eq!(BuildMetadata::extract::<LE, _>(&[other]), None);
+    }
+
+    fn compose(policy: MetadataPolicy) -> Vec<Record> {
+        let mut records = vec![Record::MainHeader(MainRecord::DefaultHWID)];
+        records
+            .extend(metadata().to_text_records().into_iter().map(Record::Text));
+        records.push(Record::Descriptor(DescriptorRecord::Simple(vec![
+            DescriptorDecoded::FirmwareId(0x0505).encode(),
+            DescriptorDecoded::FirmwareLen(0).encode(),
+            metadata().to_descriptor::<LE>(),
+            DescriptorDecoded::End.encode(),
+        ])));
+        let mut file = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut file).unwrap();
+        composer.set_metadata_policy(policy);
+        for record in records.iter() {
+            composer.write_record(record).unwrap();
+        }
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        let mut result = vec![];
+        while let Ok(record) = parser.read_record() {
+            result.push(record);
+        }
+        result
+    }
+
+    #[test]
+    fn policy() {
+        let records = compose(MetadataPolicy::Keep);
+        assert_eq!(BuildMetadata::extract::<LE, _>(&records), Some(metadata()));
+
+        let records = compose(MetadataPolicy::Omit);
+        assert_eq!(records.len(), 2);
+        assert_eq!(BuildMetadata::extract::<LE, _>(&records), None);
+
+        let records = compose(MetadataPolicy::FixedTimestamp(1));
+        let fixed = BuildMetadata {
+            timestamp: Some(1),
+            ..metadata()
+        };
+        assert_eq!(records.len(), 5);
+        assert_eq!(BuildMetadata::extract::<LE, _>(&records), Some(fixed));
+    }
+}
@@ -0,0 +1,125 @@
+//! Find GCD files embedded in other files.
+//!
+//! The firmware is frequently shipped inside installers, disk images or
+//! executable resources. [`scan`] search the data for the signature, the
+//! "GARMIN" magic followed by the version, and [`parser_at`] start parsing
+//! at one of the offsets found.
+//!
+//! ```
+//! use gcd_rs::carve;
+//! use gcd_rs::testing::vectors;
+//! use std::io::Cursor;
+//!
+//! let mut blob = b"MZ installer stub".to_vec();
+//! blob.extend_from_slice(vectors::SINGLE_BLOCK);
+//! let offsets = carve::scan(&blob[..]).unwrap();
+//! assert_eq!(offsets, vec![17]);
+//! let mut parser = carve::parser_at(Cursor::new(&blob), offsets[0]).unwrap();
+//! parser.read_record().unwrap();
+//! ```
+
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+
+use crate::codec::{self, SIGNATURE_LEN};
+use crate::error::Result;
+use crate::parser::Parser;
+use crate::GcdDefaultEndian;
+
+// bytes read from the reader at once
+const BUF_LEN: usize = 0x10000;
+
+/// Offsets of all the GCD signatures in the data, the data is read until
+/// the end, without loading it all in memory.
+///
+/// The signature alone don't guarantee a valid file, parse each offset to
+/// confirm.
+pub fn scan<R: Read>(mut reader: R) -> Result<Vec<u64>> {
+    let signature = codec::encode_signature::<GcdDefaultEndian>(codec::VERSION);
+    let mut offsets = vec![];
+    //the end of the previous read is kept, for signatures between reads
+    let keep = SIGNATURE_LEN - 1;
+    let mut buf = vec![0; BUF_LEN + keep];
+    let mut len = 0;
+    //offset of the buf start in the data
+    let mut base = 0u64;
+    loop {
+        let read = match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        len += read;
+        if len < SIGNATURE_LEN {
+            continue;
+        }
+        let found = buf[..len]
+            .windows(SIGNATURE_LEN)
+            .enumerate()
+            .filter(|(_, x)| *x == signature)
+            .map(|(pos, _)| base + pos as u64);
+        offsets.extend(found);
+        buf.copy_within(len - keep..len, 0);
+        base += (len - keep) as u64;
+        len = keep;
+    }
+    Ok(offsets)
+}
+
+/// Parser of the GCD file at the offset, eg: found by [`scan`].
+pub fn parser_at<R: Read + Seek>(
+    mut reader: R,
+    offset: u64,
+) -> Result<Parser<R>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    Parser::new(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parser_at, scan};
+    use crate::testing::vectors;
+    use crate::Record;
+    use std::io::{Cursor, Read};
+
+    // reader that return a single byte for each read
+    struct Slow<'a>(&'a [u8]);
+
+    impl Read for Slow<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(1);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn carve() {
+        //a signature with other version is not a GCD file
+        let mut blob = b"GARMIN\x65\x00GARMIN".to_vec();
+        let mut expected = vec![];
+        for (_, data) in vectors::ALL {
+            //the gaps force the signatures to cross the reads
+            blob.resize(blob.len() + 0x7ffd, 0xff);
+            expected.push(blob.len() as u64);
+            blob.extend_from_slice(data);
+        }
+        assert_eq!(scan(&blob[..]).unwrap(), expected);
+        assert_eq!(scan(Slow(&blob)).unwrap(), expected);
+        assert!(scan(&blob[..7]).unwrap().is_empty());
+
+        for (offset, (_, data)) in expected.iter().zip(vectors::ALL) {
+            let parser = parser_at(Cursor::new(&blob), *offset).unwrap();
+            let records: Vec<_> = parser
+                .take_while(|x| !matches!(x, Ok(Record::End)))
+                .map(|x| x.unwrap())
+                .collect();
+            let parsed = crate::parser::parse_all(data).unwrap();
+            assert_eq!(records[..], parsed[..parsed.len() - 1]);
+            assert!(records
+                .iter()
+                .any(|x| matches!(x, Record::FirmwareData(_))));
+        }
+    }
+}
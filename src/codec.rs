@@ -0,0 +1,310 @@
+//! Framing layer of the GCD format, without state or checksum context.
+//!
+//! A GCD file is the signature followed by frames, each frame is a
+//! [`RecordHeader`] (u16 id, u16 len) and `len` bytes of payload. The
+//! functions here only encode/decode the framing, the payload is not
+//! interpreted, so they can be used by editors, fuzzers and other tools that
+//! need to handle invalid or unknown records.
+//!
+//! ```
+//! use gcd_rs::codec;
+//! use gcd_rs::RecordHeader;
+//! use byteorder::LE;
+//!
+//! let mut data = vec![];
+//! codec::encode_frame::<LE>(RecordHeader::Text(2), b"hi", &mut data)
+//!     .unwrap();
+//! let (rest, header, payload) = codec::decode_frame::<LE>(&data).unwrap();
+//! assert_eq!(header, RecordHeader::Text(2));
+//! assert_eq!(payload, b"hi");
+//! assert!(rest.is_empty());
+//! ```
+
+use byteorder::ByteOrder;
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind, Read, Write};
+
+use crate::error::{GcdError, Result};
+use crate::{RecordHeader, RecordId, RecordLen, RECORD_HEADER_LEN};
+
+/// Size of the file signature.
+pub const SIGNATURE_LEN: usize = 8;
+/// The only known file version.
+pub const VERSION: u16 = 100;
+
+/// A record header with the payload.
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+pub struct Frame {
+    pub header: RecordHeader,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Create a frame from the id and payload, fail if the payload don't fit
+    /// the u16 len.
+    pub fn new(id: RecordId, payload: Vec<u8>) -> Result<Self> {
+        let len = frame_len(&payload)?;
+        Ok(Frame {
+            header: RecordHeader::from_value(id, len),
+            payload,
+        })
+    }
+
+    /// Size of the frame, including the header.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        RECORD_HEADER_LEN + self.payload.len()
+    }
+
+    pub fn encode<B: ByteOrder>(&self, out: &mut Vec<u8>) -> Result<()> {
+        encode_frame::<B>(self.header, &self.payload, out)
+    }
+}
+
+fn frame_len(payload: &[u8]) -> Result<u16> {
+    Ok(RecordLen::new(payload.len())?.get())
+}
+
+fn check_len(header: RecordHeader, payload: &[u8]) -> Result<()> {
+    if header.len() as usize != payload.len() {
+        return Err(GcdError::invalid_input(
+            "Frame payload len don't match the header",
+        ));
+    }
+    Ok(())
+}
+
+/// Decode the signature, returning the rest of the data and the version.
+pub fn decode_signature<B: ByteOrder>(data: &[u8]) -> Result<(&[u8], u16)> {
+    if data.len() < SIGNATURE_LEN {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "Signature buffer too small",
+        )
+        .into());
+    }
+    if &data[..6] != b"GARMIN" {
+        return Err(GcdError::InvalidSignature);
+    }
+    Ok((&data[SIGNATURE_LEN..], B::read_u16(&data[6..])))
+}
+
+/// Encode the signature with the version.
+pub fn encode_signature<B: ByteOrder>(version: u16) -> [u8; SIGNATURE_LEN] {
+    let mut sign = [0; SIGNATURE_LEN];
+    sign[..6].copy_from_slice(b"GARMIN");
+    B::write_u16(&mut sign[6..], version);
+    sign
+}
+
+/// Decode a single frame from the data, returning the rest of the data, the
+/// header and the payload.
+pub fn decode_frame<B: ByteOrder>(
+    data: &[u8],
+) -> Result<(&[u8], RecordHeader, &[u8])> {
+    let (rest, header) = RecordHeader::from_raw::<B>(data)?;
+    let len = header.len() as usize;
+    if rest.len() < len {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "Frame payload is bigger than the data available",
+        )
+        .into());
+    }
+    Ok((&rest[len..], header, &rest[..len]))
+}
+
+/// Encode the frame at the end of the buffer.
+pub fn encode_frame<B: ByteOrder>(
+    header: RecordHeader,
+    payload: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<()> {
+    check_len(header, payload)?;
+    let mut raw = [0; RECORD_HEADER_LEN];
+    header.to_raw::<B>(&mut raw)?;
+    out.extend_from_slice(&raw);
+    out.extend_from_slice(payload);
+    Ok(())
+}
+
+/// Read a single frame, None if the reader is at EOF.
+pub fn read_frame<R: Read, B: ByteOrder>(
+    reader: &mut R,
+) -> Result<Option<Frame>> {
+    let mut raw = [0; RECORD_HEADER_LEN];
+    //a clean EOF is only allowed before the header
+    let mut read = 0;
+    while read < raw.len() {
+        match reader.read(&mut raw[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "File ended in the middle of a record header",
+                )
+                .into())
+            }
+            Ok(x) => read += x,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let (_, header) = RecordHeader::from_raw::<B>(&raw)?;
+    let mut payload = vec![0; header.len() as usize];
+    reader.read_exact(&mut payload)?;
+    Ok(Some(Frame { header, payload }))
+}
+
+/// Write a single frame.
+pub fn write_frame<W: Write, B: ByteOrder>(
+    writer: &mut W,
+    header: RecordHeader,
+    payload: &[u8],
+) -> Result<()> {
+    check_len(header, payload)?;
+    let mut raw = [0; RECORD_HEADER_LEN];
+    header.to_raw::<B>(&mut raw)?;
+    writer.write_all(&raw)?;
+    writer.write_all(payload)?;
+    Ok(())
+}
+
+/// Iterate over the frames of a buffer, after the signature.
+pub fn frames<B: ByteOrder>(data: &[u8]) -> Frames<'_, B> {
+    Frames {
+        data,
+        endian: std::marker::PhantomData,
+    }
+}
+
+/// Iterator over the frames of a buffer, created by [`frames`].
+///
+/// Return an error and stop if the data ends in the middle of a frame.
+#[derive(Debug, Clone)]
+pub struct Frames<'a, B> {
+    data: &'a [u8],
+    endian: std::marker::PhantomData<B>,
+}
+
+impl<'a, B: ByteOrder> Iterator for Frames<'a, B> {
+    type Item = Result<(RecordHeader, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        match decode_frame::<B>(self.data) {
+            Ok((rest, header, payload)) => {
+                self.data = rest;
+                Some(Ok((header, payload)))
+            }
+            Err(e) => {
+                self.data = &[];
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::vectors;
+    use byteorder::{BE, LE};
+
+    #[test]
+    fn slice_frames() {
+        let (data, version) =
+            decode_signature::<LE>(vectors::SINGLE_BLOCK).unwrap();
+        assert_eq!(version, VERSION);
+        let headers: Vec<_> =
+            frames::<LE>(data).map(|x| x.unwrap().0).collect();
+        assert_eq!(
+            headers,
+            [
+                RecordHeader::MainHeader(2),
+                RecordHeader::DescriptorType(8),
+                RecordHeader::DescriptorData(8),
+                RecordHeader::Unknown {
+                    id: RecordId::new(0x0505),
+                    len: 8
+                },
+                RecordHeader::Checksum,
+                RecordHeader::End,
+            ]
+        );
+
+        //re-encoding the frames result in the same file
+        let mut encoded = encode_signature::<LE>(version).to_vec();
+        for frame in frames::<LE>(data) {
+            let (header, payload) = frame.unwrap();
+            encode_frame::<LE>(header, payload, &mut encoded).unwrap();
+        }
+        assert_eq!(encoded, vectors::SINGLE_BLOCK);
+
+        let truncated = &data[..data.len() - 1];
+        assert!(frames::<LE>(truncated).last().unwrap().is_err());
+    }
+
+    #[test]
+    fn record_ids() {
+        let known = [
+            (RecordId::CHECKSUM, 1, RecordHeader::Checksum),
+            (
+                RecordId::CHECKSUM,
+                4,
+                RecordHeader::Unknown {
+                    id: RecordId::CHECKSUM,
+                    len: 4,
+                },
+            ),
+            (RecordId::FILLER, 3, RecordHeader::Filler(3)),
+            (RecordId::MAIN_HEADER, 2, RecordHeader::MainHeader(2)),
+            (RecordId::TEXT, 5, RecordHeader::Text(5)),
+            (
+                RecordId::DESCRIPTOR_TYPE,
+                6,
+                RecordHeader::DescriptorType(6),
+            ),
+            (
+                RecordId::DESCRIPTOR_DATA,
+                7,
+                RecordHeader::DescriptorData(7),
+            ),
+            (RecordId::END, 0, RecordHeader::End),
+        ];
+        for (id, len, header) in known {
+            assert_eq!(RecordHeader::from_value(id, len), header);
+            assert_eq!((header.id(), header.len()), (id, len));
+        }
+        //the End with data is not the End
+        let id = RecordId::END;
+        let header = RecordHeader::from_value(id, 1);
+        assert_eq!(header, RecordHeader::Unknown { id, len: 1 });
+
+        let id = RecordId::from(0x0505);
+        assert_eq!(id.to_string(), "0x0505");
+        assert_eq!(format!("{:x}", id), "505");
+        assert_eq!(u16::from(id), 0x0505);
+        assert_eq!(RecordId::FILLER.to_string(), "0x0002");
+    }
+
+    #[test]
+    fn reader_frames() {
+        let mut data = vec![];
+        let frame =
+            Frame::new(RecordId::new(0x1234), b"payload".to_vec()).unwrap();
+        write_frame::<_, BE>(&mut data, frame.header, &frame.payload).unwrap();
+        assert_eq!(data.len(), frame.len());
+        assert!(
+            write_frame::<_, BE>(&mut data, RecordHeader::End, b"x").is_err()
+        );
+
+        let mut reader = &data[..];
+        assert_eq!(read_frame::<_, BE>(&mut reader).unwrap(), Some(frame));
+        assert_eq!(read_frame::<_, BE>(&mut reader).unwrap(), None);
+        let mut reader = &data[..2];
+        assert!(read_frame::<_, BE>(&mut reader).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+//! Check if a file is destined to a device, before flashing it.
+//!
+//! The HWID of the target device is compared with all the places a file
+//! declares it: the MainHeader HWID record, the part number "CCCC" digits
+//! and the HWID descriptor (0x1009) of each firmware block.
+//!
+//! ```
+//! use gcd_rs::compat::check_records;
+//! use gcd_rs::testing::vectors;
+//!
+//! let records = gcd_rs::parser::parse_all(vectors::SINGLE_BLOCK).unwrap();
+//! let report = check_records(&records, 0x0037);
+//! println!("{}", report);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+use crate::Record;
+
+/// Where the HWID was declared.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub enum CompatSource {
+    /// The MainHeader HWID record.
+    MainHeader,
+    /// The "CCCC" digits of the MainHeader part number.
+    PartNumber,
+    /// The HWID descriptor of the firmware block, with the block number.
+    Descriptor(usize),
+}
+
+impl Display for CompatSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompatSource::MainHeader => write!(f, "MainHeader"),
+            CompatSource::PartNumber => write!(f, "PartNumber"),
+            CompatSource::Descriptor(block) => write!(f, "Block {}", block),
+        }
+    }
+}
+
+/// Result of the compatibility check, see [`check_records`].
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct CompatReport {
+    /// HWID of the target device.
+    pub hwid: u16,
+    /// Each HWID declared by the file, in order.
+    pub declared: Vec<(CompatSource, u16)>,
+}
+
+impl CompatReport {
+    /// The declared HWIDs that don't match the target.
+    pub fn mismatches(&self) -> impl Iterator<Item = &(CompatSource, u16)> {
+        let hwid = self.hwid;
+        self.declared.iter().filter(move |(_, x)| *x != hwid)
+    }
+
+    /// The file declare at least one HWID, and all match the target.
+    pub fn is_compatible(&self) -> bool {
+        !self.declared.is_empty() && self.mismatches().next().is_none()
+    }
+}
+
+impl Display for CompatReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.declared.is_empty() {
+            return write!(f, "No HWID declared, target {:#06x}", self.hwid);
+        }
+        let status = match self.is_compatible() {
+            true => "Compatible",
+            false => "Incompatible",
+        };
+        write!(f, "{} with {:#06x}", status, self.hwid)?;
+        for (source, hwid) in self.mismatches() {
+            write!(f, "\n  {}: {:#06x}", source, hwid)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the HWIDs declared by the records with the target `hwid`.
+pub fn check_records<'a, I>(records: I, hwid: u16) -> CompatReport
+where
+    I: IntoIterator<Item = &'a Record>,
+{
+    let mut declared = vec![];
+    let mut block = 0;
+    for record in records {
+        match record {
+            Record::MainHeader(main) => {
+                if let Some(x) = main.hwid() {
+                    declared.push((CompatSource::MainHeader, x));
+                }
+                if let Some(x) = main.part_number().and_then(|x| x.hwid()) {
+                    declared.push((CompatSource::PartNumber, x));
+                }
+            }
+            Record::Descriptor(descriptor) => {
+                if let Some(x) = descriptor.hw_id() {
+                    declared.push((CompatSource::Descriptor(block), x));
+                }
+                block += 1;
+            }
+            _ => {}
+        }
+    }
+    CompatReport { hwid, declared }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_records, CompatSource};
+    use crate::parser::parse_all;
+    use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+    use crate::record::descriptor::DescriptorRecord;
+    use crate::record::main::MainRecord;
+    use crate::testing::vectors;
+    use crate::{GcdFile, Record};
+
+    #[test]
+    fn compat_report() {
+        let descriptor = |hwid: Option<u16>| {
+            let mut descs = vec![DescriptorDecoded::FirmwareId(0x0505)];
+            descs.extend(hwid.map(DescriptorDecoded::HWID));
+            descs.push(DescriptorDecoded::End);
+            Record::Descriptor(DescriptorRecord::Simple(
+                descs.into_iter().map(|x| x.encode()).collect(),
+            ))
+        };
+        let mut records = vec![
+            Record::MainHeader(MainRecord::DefaultPartNumber),
+            Record::MainHeader(MainRecord::DefaultHWID),
+            descriptor(Some(0x0037)),
+            descriptor(None),
+            Record::End,
+        ];
+        let report = check_records(&records, 0x0037);
+        assert!(report.is_compatible());
+        assert_eq!(report.declared.len(), 3);
+        assert_eq!(report.to_string(), "Compatible with 0x0037");
+
+        records[3] = descriptor(Some(0x0123));
+        let report = check_records(&records, 0x0037);
+        assert!(!report.is_compatible());
+        let mismatches: Vec<_> = report.mismatches().collect();
+        assert_eq!(mismatches, vec![&(CompatSource::Descriptor(1), 0x0123)]);
+        assert_eq!(
+            report.to_string(),
+            "Incompatible with 0x0037\n  Block 1: 0x0123"
+        );
+
+        let report = check_records(&records, 0x0123);
+        assert_eq!(report.mismatches().count(), 3);
+        //nothing declared, nothing to check
+        assert!(!check_records(&[Record::End], 0x0037).is_compatible());
+
+        //the indexed file check the same records
+        let data = vectors::SINGLE_BLOCK;
+        let file: GcdFile = GcdFile::from_bytes(data.to_vec()).unwrap();
+        let records = parse_all(data).unwrap();
+        assert_eq!(
+            file.is_compatible_with(0x0037),
+            check_records(&records, 0x0037)
+        );
+    }
+}
@@ -1,35 +1,52 @@
 //! Compose new GCD file
 
-use crate::record::checksum::{self, ChecksumRecord};
+use crate::block::{FirmwareBlock, DEFAULT_MAX_CHUNK_SIZE};
+use crate::build_info::MetadataPolicy;
+use crate::codec;
+use crate::config::ComposerConfig;
+use crate::error::{GcdError, Result};
+use crate::lint::{Linter, Warning};
+use crate::parser::RawRecord;
+use crate::progress::{ProgressSink, ProgressTracker};
+use crate::record::checksum::{self, Checksum, ChecksumRecord, Checksums};
+use crate::record::cow::CowRecord;
 use crate::record::descriptor::DescriptorRecord;
 use crate::record::filler::FillerRecord;
+use crate::record::firmware::transform::TransformTable;
 use crate::record::firmware::FirmwareRecord;
 use crate::record::text::TextRecord;
+use crate::region;
+use crate::trailer::Trailer;
 use crate::{
-    GcdDefaultEndian, MainRecord, Record, RecordHeader, RECORD_HEADER_LEN,
+    GcdDefaultEndian, MainRecord, Record, RecordHeader, RecordId, RecordLen,
+    RECORD_HEADER_LEN,
 };
 use byteorder::ByteOrder;
-use std::io::{Result, Write};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::io::{Read, Write};
 use std::marker::PhantomData;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 struct WriteCheckSum<F> {
     file: F,
-    sum: u8,
+    checksums: Checksums,
+    len: u64,
+    // len when the writer was created
+    start: u64,
 }
 impl<F> Write for WriteCheckSum<F>
 where
     F: std::io::Write,
 {
-    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         let len = self.file.write(buf)?;
-        for byte in buf.iter() {
-            self.sum = self.sum.wrapping_add(*byte);
-        }
+        self.checksums.update(&buf[..len]);
+        self.len += len as u64;
         Ok(len)
     }
 
-    fn flush(&mut self) -> Result<()> {
+    fn flush(&mut self) -> std::io::Result<()> {
         self.file.flush()
     }
 }
@@ -37,23 +54,82 @@ impl<F> WriteCheckSum<F>
 where
     F: std::io::Write,
 {
-    fn new(file: F) -> Self {
-        WriteCheckSum { file, sum: 0 }
+    fn new(file: F, checksums: Checksums) -> Self {
+        WriteCheckSum {
+            file,
+            checksums,
+            len: 0,
+            start: 0,
+        }
     }
 }
 
 impl<F> WriteCheckSum<F> {
-    const fn sum(&self) -> u8 {
-        self.sum
+    fn sum(&self) -> u8 {
+        self.checksums.sum.value()
+    }
+    const fn len(&self) -> u64 {
+        self.len
     }
 }
 
+/// Padding added to the file when the End record is written.
+#[derive(
+    Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize,
+)]
+pub enum EndPadding {
+    /// No padding, the End record is written right after the last record.
+    #[default]
+    None,
+    /// Filler records are written before the End record, so the total file
+    /// len is a multiple of the value, eg: the flash page size.
+    Filler(u32),
+}
+
+/// Checksum records written automatically after the firmware chunks.
+#[derive(
+    Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize,
+)]
+pub enum AutoChecksum {
+    /// Only the checksums written by the caller.
+    #[default]
+    None,
+    /// After the firmware chunk that makes the file grow at least this
+    /// number of bytes since the last checksum.
+    Bytes(u32),
+    /// After this number of firmware chunks since the last checksum.
+    Chunks(u32),
+    /// After the last chunk of each firmware block, the block len is taken
+    /// from the Descriptor FirmwareLen. After the Descriptor if the
+    /// FirmwareLen is 0.
+    Block,
+}
+
 pub struct Composer<F, B = GcdDefaultEndian>
 where
     F: std::io::Write,
     B: ByteOrder,
 {
     file: WriteCheckSum<F>,
+    end_padding: EndPadding,
+    linter: Option<Linter>,
+    warnings: Vec<Warning>,
+    metadata_policy: MetadataPolicy,
+    max_chunk_size: Option<u16>,
+    auto_checksum: AutoChecksum,
+    // firmware chunks written after the last checksum
+    chunks_unchecked: u32,
+    // file len after the last checksum
+    checksum_pos: u64,
+    // firmware bytes left in the current block
+    block_left: Option<u32>,
+    encode_firmware: bool,
+    transforms: TransformTable,
+    // firmware id and xor key of the current block
+    block_xor: Option<(u16, u8)>,
+    // the End record was written
+    ended: bool,
+    progress: Option<ProgressTracker>,
     endian: PhantomData<B>,
 }
 
@@ -63,28 +139,469 @@ where
     B: ByteOrder,
 {
     pub fn new(file: F) -> Result<Self> {
+        Self::with_checksums(file, Checksums::default())
+    }
+
+    fn with_checksums(file: F, checksums: Checksums) -> Result<Self> {
         //write signature and version (100)
-        let mut sign = [0; 8];
-        let mut file = WriteCheckSum::new(file);
-        sign[..6].copy_from_slice(b"GARMIN");
-        B::write_u16(&mut sign[6..], 100);
-        file.write_all(&sign)?;
+        let mut file = WriteCheckSum::new(file, checksums);
+        file.write_all(&codec::encode_signature::<B>(codec::VERSION))?;
         Ok(Composer {
             file,
+            end_padding: EndPadding::None,
+            linter: None,
+            warnings: vec![],
+            metadata_policy: MetadataPolicy::Keep,
+            max_chunk_size: None,
+            auto_checksum: AutoChecksum::None,
+            chunks_unchecked: 0,
+            checksum_pos: 0,
+            block_left: None,
+            encode_firmware: true,
+            transforms: TransformTable::default(),
+            block_xor: None,
+            ended: false,
+            progress: None,
             endian: PhantomData,
         })
     }
 
-    /// Write a record composed without any encoding
-    pub fn write_record_raw(&mut self, id: u16, data: &[u8]) -> Result<()> {
-        self.write_record_header(RecordHeader::Unknown {
-            id,
-            len: data.len() as u16,
+    // continue a file, the writer is positioned after `len` bytes with the
+    // `checksums`, the chunks of the firmware `block_xor` are encoded
+    pub(crate) fn resume(
+        file: F,
+        checksums: Checksums,
+        len: u64,
+        block_xor: Option<(u16, u8)>,
+    ) -> Self {
+        Composer {
+            file: WriteCheckSum {
+                file,
+                checksums,
+                len,
+                start: len,
+            },
+            end_padding: EndPadding::None,
+            linter: None,
+            warnings: vec![],
+            metadata_policy: MetadataPolicy::Keep,
+            max_chunk_size: None,
+            auto_checksum: AutoChecksum::None,
+            chunks_unchecked: 0,
+            checksum_pos: len,
+            block_left: None,
+            encode_firmware: true,
+            transforms: TransformTable::default(),
+            block_xor,
+            ended: false,
+            progress: None,
+            endian: PhantomData,
+        }
+    }
+
+    /// Create the composer with the options, see [`ComposerConfig`].
+    pub fn with_config(file: F, config: &ComposerConfig) -> Result<Self> {
+        let checksums = match config.crc {
+            #[cfg(feature = "crc")]
+            true => Checksums::with_crc(),
+            #[cfg(not(feature = "crc"))]
+            true => {
+                return Err(GcdError::invalid_input(
+                    "The CRCs require the feature crc",
+                ))
+            }
+            false => Checksums::default(),
+        };
+        let mut composer = Self::with_checksums(file, checksums)?;
+        composer.set_end_padding(config.end_padding)?;
+        composer.set_max_chunk_size(config.max_chunk_size)?;
+        composer.set_auto_checksum(config.auto_checksum)?;
+        composer.set_metadata_policy(config.metadata_policy);
+        composer.set_encode_firmware(config.encode_firmware);
+        if config.lint {
+            composer.set_linter(Some(Linter::default()));
+        }
+        Ok(composer)
+    }
+
+    /// Offset of the next record, counting from the start of the file
+    /// signature.
+    pub const fn position(&self) -> u64 {
+        self.file.len()
+    }
+
+    /// Bytes written by this composer, including the file signature.
+    ///
+    /// Same as [`Composer::position`], unless the composer continue an
+    /// existing file, eg: [`Session::truncate`](crate::session::Session).
+    pub const fn bytes_written(&self) -> u64 {
+        self.file.len() - self.file.start
+    }
+
+    /// Checksums of all the data written so far, including the signature.
+    /// The CRCs are only calculated if enabled by [`ComposerConfig::crc`].
+    pub const fn checksums(&self) -> Checksums {
+        self.file.checksums
+    }
+
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> F {
+        self.file.file
+    }
+
+    // the file being written, used to take the composed data
+    #[cfg(feature = "async")]
+    pub(crate) fn file_mut(&mut self) -> &mut F {
+        &mut self.file.file
+    }
+
+    /// Set the padding added before the End record.
+    pub fn set_end_padding(&mut self, padding: EndPadding) -> Result<()> {
+        if padding == EndPadding::Filler(0) {
+            return Err(GcdError::invalid_input(
+                "Padding alignment can't be zero",
+            ));
+        }
+        self.end_padding = padding;
+        Ok(())
+    }
+
+    /// Validate the records written with the linter.
+    ///
+    /// Convention deviations don't stop the record from being written, they
+    /// are available with [`Composer::warnings`].
+    pub fn set_linter(&mut self, linter: Option<Linter>) {
+        self.linter = linter;
+    }
+
+    /// Warnings generated by the linter so far. Warnings about the whole file
+    /// are only available after the End record is written.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Set how the build metadata records are written, see
+    /// [`build_info`](crate::build_info).
+    pub fn set_metadata_policy(&mut self, policy: MetadataPolicy) {
+        self.metadata_policy = policy;
+    }
+
+    /// Split the firmware records bigger than `max` in multiple chunks.
+    pub fn set_max_chunk_size(&mut self, max: Option<u16>) -> Result<()> {
+        if max == Some(0) {
+            return Err(GcdError::invalid_input("Chunk size can't be zero"));
+        }
+        self.max_chunk_size = max;
+        Ok(())
+    }
+
+    /// Apply the XOR declared on the last Descriptor to the firmware chunks,
+    /// enabled by default.
+    ///
+    /// Like the data returned by the [`Parser`](crate::parser::Parser), the
+    /// firmware records are expected to be decoded. Disable it to write
+    /// records that are already encoded.
+    pub fn set_encode_firmware(&mut self, encode: bool) {
+        self.encode_firmware = encode;
+    }
+
+    /// Set the transformations used to encode the firmware, by default only
+    /// the font rule, see [`TransformTable`].
+    pub fn set_transforms(&mut self, transforms: TransformTable) {
+        self.transforms = transforms;
+    }
+
+    /// Report the progress after each record written, see
+    /// [`progress`](crate::progress). The `total` is the expected file
+    /// size, if known.
+    pub fn set_progress(
+        &mut self,
+        sink: Box<dyn ProgressSink>,
+        total: Option<u64>,
+    ) {
+        self.progress = Some(ProgressTracker::new(sink, total));
+    }
+
+    /// Write checksum records automatically, see [`AutoChecksum`].
+    pub fn set_auto_checksum(&mut self, auto: AutoChecksum) -> Result<()> {
+        if let AutoChecksum::Bytes(0) | AutoChecksum::Chunks(0) = auto {
+            return Err(GcdError::invalid_input(
+                "Checksum interval can't be zero",
+            ));
+        }
+        self.auto_checksum = auto;
+        Ok(())
+    }
+
+    /// Write a checksum record after every `n_bytes` of file, the checksum
+    /// is written after the firmware chunk that crosses the limit.
+    pub fn write_checksum_every(&mut self, n_bytes: u32) -> Result<()> {
+        self.set_auto_checksum(AutoChecksum::Bytes(n_bytes))
+    }
+
+    /// Write filler records so the next record starts at a file offset that
+    /// is a multiple of `alignment`. Nothing is written if already aligned.
+    ///
+    /// A filler is at least the record header, so the padding can be bigger
+    /// than the alignment.
+    pub fn pad_to(&mut self, alignment: u32) -> Result<()> {
+        if alignment == 0 {
+            return Err(GcdError::invalid_input(
+                "Padding alignment can't be zero",
+            ));
+        }
+        self.write_padding(alignment, 0)
+    }
+
+    /// Write filler records so the next record starts at the file `offset`,
+    /// counting from the start of the file signature.
+    pub fn pad_to_offset(&mut self, offset: u64) -> Result<()> {
+        let padding = offset.checked_sub(self.file.len()).ok_or_else(|| {
+            GcdError::invalid_input("Padding offset already written")
         })?;
-        self.file.write_all(&data)
+        if padding != 0 && padding < RECORD_HEADER_LEN as u64 {
+            return Err(GcdError::invalid_input(
+                "Padding smaller than a filler record",
+            ));
+        }
+        self.write_fillers(padding)
+    }
+
+    /// Write a record composed without any encoding, not validated
+    pub fn write_record_raw(
+        &mut self,
+        id: RecordId,
+        data: &[u8],
+    ) -> Result<()> {
+        let len = RecordLen::new(data.len())?;
+        self.write_record_header(RecordHeader::Unknown { id, len: len.get() })?;
+        self.file.write_all(data)?;
+        Ok(())
+    }
+    /// Write the `header` and copy exactly the header len bytes from `reader`
+    /// as the record body, without encoding or validation.
+    ///
+    /// Any header can be written, including [`RecordHeader::Unknown`], eg: to
+    /// copy the [`Record::Unknown`] returned by a lenient parser. Like
+    /// [`Composer::write_record_raw`], the record is not tracked by the
+    /// composer. Fail if the reader ends before the len, after writing the
+    /// partial body.
+    pub fn write_header_and_copy<R: Read>(
+        &mut self,
+        header: RecordHeader,
+        reader: R,
+    ) -> Result<()> {
+        self.write_record_header(header)?;
+        let len = u64::from(header.len());
+        let copied = std::io::copy(&mut reader.take(len), &mut self.file)?;
+        if copied != len {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("Record body ended after {} of {} bytes", copied, len),
+            )
+            .into());
+        }
+        Ok(())
+    }
+    /// Write the record exactly as it was parsed, see
+    /// [`Parser::read_record_with_raw`].
+    ///
+    /// The raw bytes are not validated, the decoded record is only used to
+    /// track the current firmware block and by the linter.
+    ///
+    /// [`Parser::read_record_with_raw`]:
+    /// crate::parser::Parser::read_record_with_raw
+    pub fn write_raw_record(&mut self, record: &RawRecord) -> Result<()> {
+        self.file.write_all(&record.raw)?;
+        self.record_written(&record.record)
     }
     /// Write a record, encoding its data
     pub fn write_record(&mut self, record: &Record) -> Result<()> {
+        let record = match self.metadata_policy.apply::<B>(record) {
+            Some(record) => record,
+            None => return Ok(()),
+        };
+        match (record.as_ref(), self.max_chunk_size) {
+            (Record::FirmwareData(firm), Some(max))
+                if firm.data().len() > max as usize =>
+            {
+                self.write_chunks(firm.id(), firm.data(), max.into())
+            }
+            (record, _) => self.write_single_record(record),
+        }
+    }
+
+    /// Write the data after the End record, verbatim, eg: the signature of
+    /// the file, see [`Trailer`].
+    pub fn write_trailer(&mut self, trailer: &Trailer) -> Result<()> {
+        if !self.ended {
+            return Err(GcdError::invalid_input(
+                "Trailer written before the End record",
+            ));
+        }
+        self.file.write_all(&trailer.data)?;
+        Ok(())
+    }
+
+    /// Write a record with the payload borrowed, see [`CowRecord`].
+    ///
+    /// The firmware chunks are split like [`Composer::write_record`], only if
+    /// the max chunk size is configured. The chunks that don't need to be
+    /// encoded are written without copying the data. If linting, all the
+    /// records are copied.
+    pub fn write_cow_record(&mut self, record: &CowRecord<'_>) -> Result<()> {
+        match record {
+            CowRecord::FirmwareData { id, data, .. } if !data.is_empty() => {
+                let max = self.max_chunk_size.map_or(data.len(), usize::from);
+                self.write_chunks(*id, data, max)
+            }
+            //texts may be changed by the metadata policy
+            record => self.write_record(&record.clone().into_owned()),
+        }
+    }
+
+    /// Write the firmware data split in chunks of the max chunk size, or
+    /// [`DEFAULT_MAX_CHUNK_SIZE`] if not configured.
+    pub fn write_firmware_data(&mut self, id: u16, data: &[u8]) -> Result<()> {
+        self.write_chunks(RecordId::new(id), data, self.chunk_size())
+    }
+
+    /// Write the firmware data split in chunks with the lens of the `layout`,
+    /// eg: to reproduce the chunks of the original file, see
+    /// [`FirmwareBlock::chunk_layout`]. The layout need to cover exactly the
+    /// data, the max chunk size is ignored.
+    pub fn write_firmware_chunks(
+        &mut self,
+        id: u16,
+        data: &[u8],
+        layout: &[u16],
+    ) -> Result<()> {
+        let layout_len: u64 = layout.iter().map(|x| u64::from(*x)).sum();
+        if layout_len != data.len() as u64 {
+            return Err(GcdError::invalid_input(
+                "Chunk layout don't match the firmware len",
+            ));
+        }
+        let id = RecordId::new(id);
+        let mut data = data;
+        for len in layout {
+            let (chunk, rest) = data.split_at(usize::from(*len));
+            data = rest;
+            self.write_single_record(&Record::FirmwareData(
+                FirmwareRecord::new(chunk.to_vec(), id),
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Write a whole firmware block, the Descriptor, the texts and the
+    /// firmware, with the chunk layout of the block, if any, otherwise like
+    /// [`Composer::write_firmware_data`].
+    pub fn write_block(&mut self, block: &FirmwareBlock) -> Result<()> {
+        self.write_record(&Record::Descriptor(block.descriptor.clone()))?;
+        for text in &block.texts {
+            self.write_record(&Record::Text(text.clone()))?;
+        }
+        let (id, data) = (block.data.id.get(), &block.data.data);
+        match block.chunk_layout() {
+            //descriptor-only block, without the empty chunk
+            [] if data.is_empty() => Ok(()),
+            [] => self.write_firmware_data(id, data),
+            layout => self.write_firmware_chunks(id, data, layout),
+        }
+    }
+
+    /// Write the Descriptor of a firmware block of the region `number`, with
+    /// the firmware id of the region, see [`region::descriptor`].
+    pub fn write_region_descriptor(
+        &mut self,
+        number: u16,
+        len: u32,
+    ) -> Result<()> {
+        let descriptor = region::descriptor(number, len);
+        self.write_record(&Record::Descriptor(descriptor))
+    }
+
+    /// Write a firmware block of the region `number`, the Descriptor and the
+    /// firmware data, see [`Composer::write_firmware_data`].
+    pub fn write_region(&mut self, number: u16, data: &[u8]) -> Result<()> {
+        let len = u32::try_from(data.len()).map_err(|_| {
+            GcdError::invalid_input("Firmware is bigger than u32::MAX")
+        })?;
+        self.write_region_descriptor(number, len)?;
+        self.write_firmware_data(region::firmware_id(number), data)
+    }
+
+    /// Same as [`Composer::write_firmware_data`], reading the firmware
+    /// until the end of `reader`. Return the number of bytes read.
+    pub fn write_firmware_from<R: Read>(
+        &mut self,
+        id: u16,
+        mut reader: R,
+    ) -> Result<u64> {
+        let id = RecordId::new(id);
+        let max = self.chunk_size();
+        let mut chunk = Vec::with_capacity(max);
+        let mut total = 0;
+        loop {
+            chunk.clear();
+            let len = (&mut reader).take(max as u64).read_to_end(&mut chunk)?;
+            //only write an empty chunk if the firmware is empty
+            if len == 0 && total != 0 {
+                return Ok(total);
+            }
+            total += len as u64;
+            self.write_chunks(id, &chunk, max)?;
+            if len == 0 {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// Write exactly `len` bytes of firmware from `reader`, in chunks of
+    /// `chunk_size`, without loading the whole firmware in memory.
+    ///
+    /// The data is encoded with the XorKey of the current Descriptor, like
+    /// [`Composer::write_record`]. Fail with [`GcdError::FirmwareTruncated`]
+    /// if the reader ends before `len` bytes, the partial chunk is not
+    /// written.
+    pub fn write_firmware_stream<R: Read>(
+        &mut self,
+        id: u16,
+        len: u32,
+        mut reader: R,
+        chunk_size: u16,
+    ) -> Result<()> {
+        if chunk_size == 0 {
+            return Err(GcdError::invalid_input("Chunk size can't be zero"));
+        }
+        let id = RecordId::new(id);
+        if len == 0 {
+            let empty = FirmwareRecord::new(vec![], id);
+            return self.write_single_record(&Record::FirmwareData(empty));
+        }
+        let mut written = 0;
+        while written < len {
+            let chunk_len = (len - written).min(chunk_size.into());
+            let mut chunk = Vec::with_capacity(chunk_len as usize);
+            (&mut reader)
+                .take(chunk_len.into())
+                .read_to_end(&mut chunk)?;
+            if chunk.len() < chunk_len as usize {
+                return Err(GcdError::FirmwareTruncated {
+                    received: written + chunk.len() as u32,
+                    expected: len,
+                });
+            }
+            written += chunk_len;
+            self.write_single_record(&Record::FirmwareData(
+                FirmwareRecord::new(chunk, id),
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn write_single_record(&mut self, record: &Record) -> Result<()> {
         match record {
             Record::Checksum(_) => self.write_check_point(),
             Record::Filler(filler) => self.write_filler(filler),
@@ -93,71 +610,288 @@ where
             Record::Descriptor(desc) => self.write_descriptor(desc),
             Record::FirmwareData(firm) => self.write_firmware(firm),
             Record::End => self.write_end(),
+            Record::Unknown { id, data } => self.write_record_raw(*id, data),
+        }?;
+        self.record_written(record)
+    }
+    // update the state after the record is written
+    fn record_written(&mut self, record: &Record) -> Result<()> {
+        self.report_progress(matches!(record, Record::Descriptor(_)));
+        if let Some(linter) = self.linter.as_mut() {
+            linter.check(record);
+            self.warnings.extend(linter.take_warnings());
+            if let Record::End = record {
+                self.warnings.extend(linter.finish());
+            }
+        }
+        match record {
+            Record::Checksum(_) => {
+                self.chunks_unchecked = 0;
+                self.checksum_pos = self.file.len();
+            }
+            Record::Descriptor(desc) => {
+                self.block_left = desc.firmware_len();
+                let xor_key = desc.xor_key().unwrap_or(0);
+                self.block_xor = desc.firmware_id().map(|id| (id, xor_key));
+                //a block without data ends with the Descriptor
+                if self.block_left == Some(0) {
+                    self.block_left = None;
+                    if self.auto_checksum == AutoChecksum::Block {
+                        let checksum = Record::Checksum(ChecksumRecord::Simple);
+                        self.write_single_record(&checksum)?;
+                    }
+                }
+            }
+            Record::FirmwareData(firm) => self.chunk_written(firm.len())?,
+            Record::End => self.ended = true,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // the max chunk size used to split the firmware data
+    fn chunk_size(&self) -> usize {
+        self.max_chunk_size.unwrap_or(DEFAULT_MAX_CHUNK_SIZE).into()
+    }
+
+    // write the firmware data in chunks of up to `max` bytes, an empty
+    // firmware is written as a single empty chunk
+    fn write_chunks(
+        &mut self,
+        id: RecordId,
+        data: &[u8],
+        max: usize,
+    ) -> Result<()> {
+        if data.is_empty() {
+            let empty = FirmwareRecord::new(vec![], id);
+            return self.write_single_record(&Record::FirmwareData(empty));
+        }
+        for chunk in data.chunks(max) {
+            //the linter check the records, the data need to be copied
+            if self.linter.is_some() {
+                self.write_single_record(&Record::FirmwareData(
+                    FirmwareRecord::new(chunk.to_vec(), id),
+                ))?;
+                continue;
+            }
+            let len = RecordLen::new(chunk.len())?;
+            self.write_record_header(RecordHeader::Unknown {
+                id,
+                len: len.get(),
+            })?;
+            match self.block_xor_key(id) {
+                Some(xor_key) => {
+                    let mut chunk = chunk.to_vec();
+                    self.transforms.encode(id.get(), xor_key, &mut chunk);
+                    self.file.write_all(&chunk)?;
+                }
+                None => self.file.write_all(chunk)?,
+            }
+            self.report_progress(false);
+            self.chunk_written(len.get())?;
+        }
+        Ok(())
+    }
+
+    fn report_progress(&mut self, descriptor: bool) {
+        if let Some(progress) = self.progress.as_mut() {
+            progress.record(self.file.len(), descriptor);
+        }
+    }
+
+    // count the chunk, writing a checksum after it if due
+    fn chunk_written(&mut self, len: u16) -> Result<()> {
+        self.chunks_unchecked += 1;
+        if self.auto_checksum_due(len) {
+            let checksum = Record::Checksum(ChecksumRecord::Simple);
+            self.write_single_record(&checksum)?;
+        }
+        Ok(())
+    }
+
+    // the xor key of the chunks of the firmware id, None if the data is
+    // written as is
+    fn block_xor_key(&self, id: RecordId) -> Option<u8> {
+        if !self.encode_firmware {
+            return None;
+        }
+        //the xor key only apply to the chunks of the block
+        let xor_key = match self.block_xor {
+            Some((block_id, key)) if block_id == id.get() => key,
+            _ => 0,
+        };
+        match self.transforms.is_identity(id.get(), xor_key) {
+            true => None,
+            false => Some(xor_key),
+        }
+    }
+
+    // update the block state with the chunk and return if a checksum is
+    // required after it
+    fn auto_checksum_due(&mut self, chunk_len: u16) -> bool {
+        let block_end = match self.block_left.as_mut() {
+            Some(left) => {
+                *left = left.saturating_sub(chunk_len.into());
+                *left == 0
+            }
+            None => false,
+        };
+        if block_end {
+            self.block_left = None;
+        }
+        let unchecked = self.file.len() - self.checksum_pos;
+        match self.auto_checksum {
+            AutoChecksum::None => false,
+            AutoChecksum::Bytes(n) => unchecked >= n.into(),
+            AutoChecksum::Chunks(n) => self.chunks_unchecked == n,
+            AutoChecksum::Block => block_end,
         }
     }
 
     fn write_record_header(&mut self, header: RecordHeader) -> Result<()> {
         let mut data = [0; 4];
-        B::write_u16(&mut data[..2], header.id());
+        B::write_u16(&mut data[..2], header.id().get());
         B::write_u16(&mut data[2..], header.len());
-        self.file.write_all(&data)
+        self.file.write_all(&data)?;
+        Ok(())
     }
     fn write_end(&mut self) -> Result<()> {
+        if let EndPadding::Filler(alignment) = self.end_padding {
+            self.write_padding(alignment, RECORD_HEADER_LEN as u64)?;
+        }
         self.write_record_header(RecordHeader::End)
     }
+    // write fillers so the file len plus the reserved bytes is aligned
+    fn write_padding(&mut self, alignment: u32, reserved: u64) -> Result<()> {
+        let alignment = alignment as u64;
+        let header_len = RECORD_HEADER_LEN as u64;
+        let end = self.file.len() + reserved;
+        let mut padding = (alignment - end % alignment) % alignment;
+        if padding == 0 {
+            return Ok(());
+        }
+        //the smallest filler is only the header
+        while padding < header_len {
+            padding += alignment;
+        }
+        self.write_fillers(padding)
+    }
+    // write fillers with exactly `padding` bytes, headers included
+    fn write_fillers(&mut self, mut padding: u64) -> Result<()> {
+        let header_len = RECORD_HEADER_LEN as u64;
+        while padding != 0 {
+            let mut len = (padding - header_len).min(u16::MAX as u64);
+            //don't leave less then a filler header to pad
+            let left = padding - header_len - len;
+            if left != 0 && left < header_len {
+                len -= header_len;
+            }
+            self.write_filler(&FillerRecord::Zeros(len as u16))?;
+            padding -= header_len + len;
+        }
+        Ok(())
+    }
     fn write_firmware(&mut self, record: &FirmwareRecord) -> Result<()> {
-        let mut data = vec![0; record.len() as usize + RECORD_HEADER_LEN];
+        let len = record.record_len()?;
+        let mut data = vec![0; len.as_usize() + RECORD_HEADER_LEN];
         record.record_to_raw::<B>(&mut data)?;
-        self.file.write_all(&data)
+        if let Some(xor_key) = self.block_xor_key(record.id()) {
+            self.transforms.encode(
+                record.id().get(),
+                xor_key,
+                &mut data[RECORD_HEADER_LEN..],
+            );
+        }
+        self.file.write_all(&data)?;
+        Ok(())
     }
     fn write_check_point(&mut self) -> Result<()> {
         let mut data = [0; checksum::LEN as usize + RECORD_HEADER_LEN];
         ChecksumRecord::record_to_raw::<B>(&mut data, self.file.sum())?;
-        self.file.write_all(&data)
+        self.file.write_all(&data)?;
+        Ok(())
     }
     fn write_filler(&mut self, filler: &FillerRecord) -> Result<()> {
         let mut data = vec![0; filler.len() as usize + RECORD_HEADER_LEN];
         filler.record_to_raw::<B>(&mut data)?;
-        self.file.write_all(&data)
+        self.file.write_all(&data)?;
+        Ok(())
     }
     fn write_main(&mut self, main: &MainRecord) -> Result<()> {
         let mut data = vec![0; main.len() as usize + RECORD_HEADER_LEN];
         main.record_to_raw::<B>(&mut data)?;
-        self.file.write_all(&data)
+        self.file.write_all(&data)?;
+        Ok(())
     }
     fn write_text(&mut self, text: &TextRecord) -> Result<()> {
-        let mut data = vec![0; text.len() as usize + RECORD_HEADER_LEN];
+        let len = text.record_len()?;
+        let mut data = vec![0; len.as_usize() + RECORD_HEADER_LEN];
         text.record_to_raw::<B>(&mut data)?;
-        self.file.write_all(&data)
+        self.file.write_all(&data)?;
+        Ok(())
     }
-    fn write_descriptor<'a>(
+    fn write_descriptor(
         &mut self,
         descriptor: &DescriptorRecord,
     ) -> Result<()> {
-        let desc_type_len = descriptor.record_type_len() as usize;
-        let desc_data_len = descriptor.record_data_len() as usize;
-        let mut data =
-            vec![0; desc_type_len + desc_data_len + (RECORD_HEADER_LEN * 2)];
+        //the list of descriptors is terminated by the End
+        let descriptor = descriptor.terminated();
+        let (type_len, data_len) = descriptor.record_lens()?;
+        let mut data = vec![
+            0;
+            type_len.as_usize()
+                + data_len.as_usize()
+                + (RECORD_HEADER_LEN * 2)
+        ];
 
         let data_current = descriptor.record_type_to_raw::<B>(&mut data)?;
         descriptor.record_data_to_raw::<B>(data_current)?;
-        self.file.write_all(&data)
+        self.file.write_all(&data)?;
+        Ok(())
+    }
+}
+
+/// Compose all the records, the inverse of
+/// [`parse_all`](crate::parser::parse_all).
+pub fn compose_all<'a, I>(records: I) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = &'a Record>,
+{
+    let mut file = vec![];
+    let mut composer: Composer<_> = Composer::new(&mut file)?;
+    for record in records {
+        composer.write_record(record)?;
     }
+    drop(composer);
+    Ok(file)
 }
 
 #[cfg(test)]
 mod tests {
     //use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
-    use crate::composer::{Composer, WriteCheckSum};
+    use crate::block::list_blocks;
+    use crate::codec;
+    use crate::composer::{AutoChecksum, Composer, EndPadding, WriteCheckSum};
+    use crate::config::ComposerConfig;
+    use crate::error::{GcdError, Result};
+    use crate::lint::Linter;
+    use crate::parser::{parse_all, Parser};
+    use crate::record::checksum::{Checksum, Checksums};
+    #[cfg(feature = "crc")]
+    use crate::record::checksum::Crc16;
+    use crate::record::cow::CowRecord;
     use crate::record::descriptor::descriptor_data;
     use crate::record::descriptor::descriptor_data::DescriptorData;
+    use crate::record::descriptor::descriptor_data::DescriptorDecoded;
     use crate::record::descriptor::descriptor_type;
     use crate::record::descriptor::DescriptorRecord;
     use crate::record::filler::FillerRecord;
+    use crate::record::firmware::{FirmwareRecord, FONT_ID, FW_ALL_ID};
     use crate::record::main::{self, MainRecord};
     use crate::record::text::TextRecord;
+    use crate::{PartNumber, Record, RecordHeader, RecordId, RecordLen};
     use byteorder::{ByteOrder, BE, LE};
-    use std::io::{Cursor, Result, Write};
+    use std::io::{Cursor, Write};
 
     fn composer<B: ByteOrder>() -> Result<Composer<Cursor<Vec<u8>>, B>> {
         let file = Cursor::new(Vec::new());
@@ -172,7 +906,10 @@ mod tests {
 
     #[test]
     fn check_sum() {
-        let mut file = WriteCheckSum::new(Cursor::new(vec![0u8; 11]));
+        let mut file = WriteCheckSum::new(
+            Cursor::new(vec![0u8; 11]),
+            Checksums::default(),
+        );
         file.write_all(&[0x1]).unwrap();
         file.write_all(&[0x2]).unwrap();
         file.write_all(&[0x3, 0x4]).unwrap();
@@ -211,6 +948,74 @@ mod tests {
         BE::write_uint128(&mut default_pn_be, main::DEFAULT_PART_NUMBER, 9);
         check_main::<LE>(&main_header_pn, &default_pn_le);
         check_main::<BE>(&main_header_pn, &default_pn_be);
+
+        check_main::<LE>(&MainRecord::HWID(0x1234), &[0x34, 0x12]);
+        check_main::<BE>(&MainRecord::HWID(0x1234), &[0x12, 0x34]);
+    }
+
+    #[test]
+    fn oversized_records() {
+        let big = u16::MAX as usize + 1;
+        assert!(RecordLen::new(big).is_err());
+        assert_eq!(RecordLen::new(big - 1).unwrap(), RecordLen::MAX);
+        assert!(RecordLen::sum(vec![usize::MAX, 1]).is_err());
+        assert_eq!(RecordLen::sum(vec![1, 2]).unwrap().get(), 3);
+
+        let text = TextRecord::Blob(vec![b'a'; big]);
+        let descriptor = DescriptorRecord::Simple(vec![
+            DescriptorData::Other {
+                id: 0x10,
+                data: vec![0; big],
+            },
+            DescriptorData::End,
+        ]);
+        //each descriptor fit u16, but not all of them
+        let many = DescriptorRecord::Simple(vec![
+            DescriptorData::Other {
+                id: 0x10,
+                data: vec![0; big / 2],
+            };
+            3
+        ]);
+        let records = [
+            Record::Text(text),
+            Record::Descriptor(descriptor),
+            Record::Descriptor(many),
+        ];
+        for record in records.iter() {
+            let mut file = vec![];
+            let mut composer = Composer::<_, LE>::new(&mut file).unwrap();
+            assert!(composer.write_record(record).is_err());
+            drop(composer);
+            //nothing is written besides the signature
+            assert_eq!(file.len(), codec::SIGNATURE_LEN);
+        }
+        let mut composer = composer::<LE>().unwrap();
+        assert!(composer
+            .write_record_raw(RecordId::new(0x10), &vec![0; big])
+            .is_err());
+    }
+
+    #[test]
+    fn write_main_part_number() {
+        let pn = "006-11234-00".parse::<PartNumber>().unwrap();
+        let extended = "006-11234-00AB".parse::<PartNumber>().unwrap();
+        for main in [
+            MainRecord::PartNumber(pn),
+            MainRecord::PartNumber(extended),
+            MainRecord::HWID(0x1234),
+        ] {
+            let mut file = vec![];
+            let mut composer = Composer::<_, LE>::new(&mut file).unwrap();
+            composer
+                .write_record(&Record::MainHeader(main.clone()))
+                .unwrap();
+            drop(composer);
+            let mut raw = &file[codec::SIGNATURE_LEN + 4..];
+            let decoded =
+                MainRecord::new::<_, LE>(&mut raw, main.len()).unwrap();
+            assert_eq!(decoded, main);
+        }
     }
 
     fn check_text<B: ByteOrder>(text: &TextRecord) {
@@ -221,8 +1026,8 @@ mod tests {
         let mut result = vec![b'G', b'A', b'R', b'M', b'I', b'N'];
         extend_u16::<B>(&mut result, 100); //header version
         extend_u16::<B>(&mut result, 0x05); //record id
-        extend_u16::<B>(&mut result, text.len() as u16); //record len
-        result.extend(text.value());
+        extend_u16::<B>(&mut result, text.len()); //record len
+        result.extend(text.value().iter());
         extend_u16::<B>(&mut result, 0xffff); //record end id
         extend_u16::<B>(&mut result, 0x0000); //record end len
         assert_eq!(composer.file.file.get_ref(), &result);
@@ -257,6 +1062,92 @@ mod tests {
         check_filler::<BE>(100);
     }
 
+    #[test]
+    fn write_validated() {
+        let mut composer = composer::<LE>().unwrap();
+        composer.set_linter(Some(Linter::default()));
+        composer
+            .write_record(&Record::MainHeader(MainRecord::DefaultHWID))
+            .unwrap();
+        composer.write_record(&Record::End).unwrap();
+        let warnings: Vec<_> = composer
+            .warnings()
+            .iter()
+            .map(|x| x.rule.as_str())
+            .collect();
+        assert_eq!(warnings, vec!["checksum-before-end"]);
+    }
+
+    fn check_end_padding(text_len: usize, alignment: u32) {
+        let mut composer = composer::<LE>().unwrap();
+        composer
+            .set_end_padding(EndPadding::Filler(alignment))
+            .unwrap();
+        let text = TextRecord::Blob(vec![b'a'; text_len]);
+        composer.write_text(&text).unwrap();
+        composer.write_end().unwrap();
+
+        let file = composer.file.file.get_ref();
+        assert_eq!(file.len() % alignment as usize, 0);
+        assert_eq!(&file[file.len() - 4..], &[0xff, 0xff, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn position() {
+        let mut composer = composer::<LE>().unwrap();
+        assert_eq!(composer.position(), 8);
+        assert_eq!(composer.bytes_written(), 8);
+        composer.write_text(&TextRecord::Blob(vec![1; 10])).unwrap();
+        assert_eq!(composer.position(), 22);
+        //the checksum record make the sum zero
+        assert_ne!(composer.checksums().sum.value(), 0);
+        composer.write_check_point().unwrap();
+        assert_eq!(composer.checksums().sum.value(), 0);
+
+        let checksums = composer.checksums();
+        let file = composer.into_inner().into_inner();
+        let resumed: Composer<_> =
+            Composer::resume(vec![], checksums, file.len() as u64, None);
+        assert_eq!(resumed.position(), 27);
+        assert_eq!(resumed.bytes_written(), 0);
+        assert_eq!(resumed.checksums(), checksums);
+    }
+
+    #[test]
+    fn write_pad_to() {
+        let mut composer = composer::<LE>().unwrap();
+        assert!(composer.pad_to(0).is_err());
+        //the signature is already aligned
+        composer.pad_to(8).unwrap();
+        assert_eq!(composer.file.len(), 8);
+        composer.write_text(&TextRecord::Blob(vec![1; 10])).unwrap();
+        composer.pad_to(0x10).unwrap();
+        assert_eq!(composer.file.len(), 0x20);
+        composer.write_text(&TextRecord::Blob(vec![1; 10])).unwrap();
+        //2 bytes to the alignment is less than a filler
+        composer.pad_to(0x10).unwrap();
+        assert_eq!(composer.file.len(), 0x40);
+        composer.pad_to(0x20000).unwrap();
+        assert_eq!(composer.file.len(), 0x20000);
+
+        assert!(composer.pad_to_offset(0x20002).is_err());
+        assert!(composer.pad_to_offset(0x1ffff).is_err());
+        composer.pad_to_offset(0x20000).unwrap();
+        composer.pad_to_offset(0x30004).unwrap();
+        assert_eq!(composer.file.len(), 0x30004);
+        composer.write_end().unwrap();
+        let file = composer.file.file.get_ref();
+        assert_eq!(&file[0x30004..], &[0xff, 0xff, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn write_end_padding() {
+        check_end_padding(100, 0x200);
+        check_end_padding(0x200 - 8 - 4 - 4 - 2, 0x200);
+        check_end_padding(0x200 - 8 - 4 - 4, 0x200);
+        check_end_padding(10, 0x20000);
+    }
+
     fn check_checkpoint<B: ByteOrder>() {
         let mut composer = composer::<B>().unwrap();
         composer.write_check_point().unwrap();
@@ -342,4 +1233,364 @@ mod tests {
         check_descriptor::<LE>(&descriptor.clone());
         check_descriptor::<BE>(&descriptor);
     }
+
+    #[test]
+    fn config_chunks_and_checksums() {
+        let config = ComposerConfig {
+            max_chunk_size: Some(3),
+            auto_checksum: AutoChecksum::Chunks(2),
+            ..Default::default()
+        };
+        let mut file = vec![];
+        let mut composer =
+            Composer::<_, LE>::with_config(&mut file, &config).unwrap();
+        composer
+            .write_record(&Record::FirmwareData(FirmwareRecord::new(
+                b"firmware".to_vec(),
+                RecordId::new(0x0505),
+            )))
+            .unwrap();
+        drop(composer);
+
+        //chunks "fir" "mwa" checksum "re"
+        assert_eq!(&file[8..15], &[0x05, 0x05, 3, 0, b'f', b'i', b'r']);
+        assert_eq!(&file[22..26], &[0x01, 0x00, 1, 0]);
+        assert_eq!(&file[27..33], &[0x05, 0x05, 2, 0, b'r', b'e']);
+        assert_eq!(file.len(), 33);
+        assert_eq!(
+            file.iter().take(27).fold(0u8, |a, x| a.wrapping_add(*x)),
+            0
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "crc")]
+    fn config_crc() {
+        let config = ComposerConfig {
+            crc: true,
+            ..Default::default()
+        };
+        let mut file = vec![];
+        let mut composer =
+            Composer::<_, LE>::with_config(&mut file, &config).unwrap();
+        composer.write_record(&Record::End).unwrap();
+        let checksums = composer.checksums();
+        drop(composer);
+        let mut crc16 = Crc16::default();
+        crc16.update(&file);
+        assert_eq!(checksums.crc16.map(|x| x.value()), Some(crc16.value()));
+        assert!(checksums.crc32.is_some());
+
+        let mut file = vec![];
+        let composer = Composer::<_, LE>::new(&mut file).unwrap();
+        assert_eq!(composer.checksums().crc16, None);
+    }
+
+    #[test]
+    #[cfg(not(feature = "crc"))]
+    fn config_crc_disabled() {
+        let config = ComposerConfig {
+            crc: true,
+            ..Default::default()
+        };
+        assert!(Composer::<_, LE>::with_config(vec![], &config).is_err());
+    }
+
+    #[test]
+    fn write_region() {
+        let mut file = vec![];
+        let mut composer = Composer::<_, LE>::new(&mut file).unwrap();
+        composer
+            .write_record(&Record::MainHeader(MainRecord::DefaultHWID))
+            .unwrap();
+        composer.write_region(14, b"firmware").unwrap();
+        composer.write_region(FONT_ID, b"font").unwrap();
+        composer.write_region(0x0506, b"").unwrap();
+        composer.write_record(&Record::End).unwrap();
+        drop(composer);
+
+        let records = parse_all(&file).unwrap();
+        let blocks = list_blocks(&records);
+        let ids: Vec<_> = blocks.iter().map(|x| x.id).collect();
+        assert_eq!(ids, [Some(FW_ALL_ID), Some(FONT_ID), Some(0x0506)]);
+        let regions: Vec<_> = blocks.iter().map(|x| x.region()).collect();
+        assert_eq!(regions, [Some(14), Some(FONT_ID), Some(0x0506)]);
+        assert_eq!(blocks[0].data_len, 8);
+        //the font is encoded, the parser decode it
+        let font =
+            FirmwareRecord::new(b"font".to_vec(), RecordId::new(FONT_ID));
+        assert!(records.contains(&Record::FirmwareData(font)));
+    }
+
+    // headers of the records written by the composer with auto checksum
+    fn auto_checksum_headers(auto: AutoChecksum) -> Vec<RecordHeader> {
+        let mut file = vec![];
+        let mut composer = Composer::<_, LE>::new(&mut file).unwrap();
+        composer.set_auto_checksum(auto).unwrap();
+        composer
+            .write_record(&Record::MainHeader(MainRecord::DefaultHWID))
+            .unwrap();
+        for id in [0x0505u16, 0x0506].iter() {
+            composer
+                .write_record(&Record::Descriptor(DescriptorRecord::Simple(
+                    vec![
+                        DescriptorDecoded::FirmwareId(*id).encode(),
+                        DescriptorDecoded::FirmwareLen(24).encode(),
+                        DescriptorDecoded::End.encode(),
+                    ],
+                )))
+                .unwrap();
+            for _ in 0..3 {
+                composer
+                    .write_record(&Record::FirmwareData(FirmwareRecord::new(
+                        vec![0; 8],
+                        RecordId::new(*id),
+                    )))
+                    .unwrap();
+            }
+        }
+        composer.write_record(&Record::End).unwrap();
+        drop(composer);
+
+        //all the checksums are valid
+        let mut parser: Parser<&[u8], LE> = Parser::new(&file[..]).unwrap();
+        while parser.read_record().unwrap() != Record::End {}
+
+        codec::frames::<LE>(&file[codec::SIGNATURE_LEN..])
+            .map(|x| x.unwrap().0)
+            .collect()
+    }
+
+    #[test]
+    fn auto_checksum() {
+        use RecordHeader::{
+            Checksum, DescriptorData, DescriptorType, End, MainHeader,
+        };
+        let chunk = |id| RecordHeader::Unknown { id, len: 8 };
+        let (a, b) =
+            (chunk(RecordId::new(0x0505)), chunk(RecordId::new(0x0506)));
+        let (m, t, d) = (MainHeader(2), DescriptorType(6), DescriptorData(6));
+
+        assert_eq!(
+            auto_checksum_headers(AutoChecksum::Block),
+            [m, t, d, a, a, a, Checksum, t, d, b, b, b, Checksum, End]
+        );
+        //the signature, main and descriptor have 34 bytes, each chunk 12
+        assert_eq!(
+            auto_checksum_headers(AutoChecksum::Bytes(50)),
+            [m, t, d, a, a, Checksum, a, t, d, b, b, Checksum, b, End]
+        );
+        //the chunk count is not reset by the block
+        assert_eq!(
+            auto_checksum_headers(AutoChecksum::Chunks(2)),
+            [m, t, d, a, a, Checksum, a, t, d, b, Checksum, b, b, Checksum, End]
+        );
+    }
+
+    #[test]
+    fn auto_checksum_empty_block() {
+        let mut file = vec![];
+        let mut composer = Composer::<_, LE>::new(&mut file).unwrap();
+        composer.set_auto_checksum(AutoChecksum::Block).unwrap();
+        composer
+            .write_record(&Record::MainHeader(MainRecord::DefaultHWID))
+            .unwrap();
+        for id in [0x0601u16, 0x0602].iter() {
+            composer
+                .write_record(&Record::Descriptor(DescriptorRecord::Simple(
+                    vec![
+                        DescriptorDecoded::FirmwareId(*id).encode(),
+                        DescriptorDecoded::FirmwareLen(0).encode(),
+                        DescriptorDecoded::End.encode(),
+                    ],
+                )))
+                .unwrap();
+        }
+        //the empty chunk don't write a second checksum
+        composer
+            .write_record(&Record::FirmwareData(FirmwareRecord::new(
+                vec![],
+                RecordId::new(0x0602),
+            )))
+            .unwrap();
+        composer.write_record(&Record::End).unwrap();
+        drop(composer);
+
+        let headers: Vec<_> = codec::frames::<LE>(&file[codec::SIGNATURE_LEN..])
+            .map(|x| x.unwrap().0)
+            .collect();
+        use RecordHeader::{
+            Checksum, DescriptorData, DescriptorType, End, MainHeader,
+        };
+        let empty = RecordHeader::Unknown {
+            id: RecordId::new(0x0602),
+            len: 0,
+        };
+        let (m, t, d) = (MainHeader(2), DescriptorType(6), DescriptorData(6));
+        assert_eq!(headers, [m, t, d, Checksum, t, d, Checksum, empty, End]);
+    }
+
+    #[test]
+    fn write_header_and_copy() {
+        use RecordHeader::{End, MainHeader};
+        let unknown = RecordHeader::Unknown {
+            id: RecordId::new(0x0123),
+            len: 4,
+        };
+        let descriptor = DescriptorRecord::Simple(vec![
+            DescriptorDecoded::FirmwareId(0x0505).encode(),
+            DescriptorDecoded::FirmwareLen(0).encode(),
+            DescriptorDecoded::End.encode(),
+        ]);
+        //the reader ended before the len
+        let mut short = composer::<LE>().unwrap();
+        assert!(short.write_header_and_copy(unknown, &b"da"[..]).is_err());
+
+        let mut composer = composer::<LE>().unwrap();
+        composer
+            .write_header_and_copy(MainHeader(2), &[0x37, 0x00][..])
+            .unwrap();
+        composer
+            .write_header_and_copy(unknown, &b"data and more"[..])
+            .unwrap();
+        composer
+            .write_record(&Record::Descriptor(descriptor.clone()))
+            .unwrap();
+        composer.write_header_and_copy(End, std::io::empty()).unwrap();
+        let data = composer.into_inner().into_inner();
+
+        //the unknown record is kept by a lenient parser
+        let parser: Parser<&[u8], LE> =
+            Parser::new_lenient(&data[..]).unwrap();
+        let records = parser.collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(
+            records,
+            [
+                Record::MainHeader(MainRecord::DefaultHWID),
+                Record::Unknown {
+                    id: RecordId::new(0x0123),
+                    data: b"data".to_vec(),
+                },
+                Record::Descriptor(descriptor),
+                Record::End,
+            ]
+        );
+
+    }
+
+    #[test]
+    fn encode_firmware() {
+        let file = crate::gcd_file::tests::compose();
+        let recompose = |encode| {
+            let parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+            let mut output = vec![];
+            let mut composer: Composer<_> = Composer::new(&mut output).unwrap();
+            composer.set_encode_firmware(encode);
+            for record in parser {
+                composer.write_record(&record.unwrap()).unwrap();
+            }
+            drop(composer);
+            output
+        };
+        assert_eq!(recompose(true), file);
+        assert_ne!(recompose(false), file);
+    }
+
+    #[test]
+    fn firmware_split() {
+        let data: Vec<u8> = (0..70000u32).map(|x| x as u8).collect();
+        let big = Record::FirmwareData(FirmwareRecord::new(
+            data.clone(),
+            RecordId::new(1),
+        ));
+
+        //a single record can't be bigger than the record len
+        let mut c = composer::<LE>().unwrap();
+        assert_eq!(
+            c.write_record(&big).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+        assert!(c.write_record_raw(RecordId::new(1), &data).is_err());
+
+        //unless it is split by the composer
+        let mut c = composer::<LE>().unwrap();
+        c.set_max_chunk_size(Some(0x1000)).unwrap();
+        c.write_record(&big).unwrap();
+        let split = c.into_inner().into_inner();
+        //signature, 18 chunks of 0x1000 or less
+        assert_eq!(split.len(), 8 + (18 * 4) + data.len());
+
+        let mut c = composer::<LE>().unwrap();
+        c.write_firmware_data(1, &data).unwrap();
+        assert_eq!(c.into_inner().into_inner(), split);
+
+        let mut c = composer::<LE>().unwrap();
+        let len = c.write_firmware_from(1, &data[..]).unwrap();
+        assert_eq!(len, data.len() as u64);
+        assert_eq!(c.into_inner().into_inner(), split);
+
+        //borrowed records are split like the owned ones
+        let cow = CowRecord::FirmwareData {
+            id: RecordId::new(1),
+            part_idx: None,
+            data: data[..].into(),
+        };
+        let mut c = composer::<LE>().unwrap();
+        assert!(c.write_cow_record(&cow).is_err());
+        let mut c = composer::<LE>().unwrap();
+        c.set_max_chunk_size(Some(0x1000)).unwrap();
+        c.write_cow_record(&cow).unwrap();
+        assert_eq!(c.into_inner().into_inner(), split);
+
+        //empty firmware is a single empty chunk
+        let mut c = composer::<LE>().unwrap();
+        assert_eq!(c.write_firmware_from(1, &[][..]).unwrap(), 0);
+        assert_eq!(c.into_inner().into_inner()[8..], [1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn firmware_stream() {
+        let data: Vec<u8> = (0..70000u32).map(|x| x as u8).collect();
+        let len = data.len() as u32;
+        let descriptor = Record::Descriptor(DescriptorRecord::Simple(vec![
+            DescriptorDecoded::FirmwareId(0x0505).encode(),
+            DescriptorDecoded::XorKey(0x5a).encode(),
+            DescriptorDecoded::FirmwareLen(len).encode(),
+        ]));
+        let mut file = vec![];
+        let mut c: Composer<_> = Composer::new(&mut file).unwrap();
+        c.write_record(&Record::MainHeader(MainRecord::DefaultHWID))
+            .unwrap();
+        c.write_record(&descriptor).unwrap();
+        //the reader have more data than the firmware
+        let reader = Cursor::new([&data[..], &[0; 10]].concat());
+        c.write_firmware_stream(0x0505, len, reader, 0x1000)
+            .unwrap();
+        c.write_record(&Record::End).unwrap();
+        drop(c);
+
+        //the chunks are encoded, so the parser decode them back
+        let chunks: Vec<_> = parse_all(&file)
+            .unwrap()
+            .into_iter()
+            .filter_map(|x| match x {
+                Record::FirmwareData(chunk) => Some(chunk),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(chunks.len(), 18);
+        assert!(chunks.iter().all(|x| x.len() <= 0x1000));
+        let parsed: Vec<u8> = chunks
+            .iter()
+            .flat_map(|x| x.data().iter().copied())
+            .collect();
+        assert_eq!(parsed, data);
+
+        let mut c = composer::<LE>().unwrap();
+        assert!(matches!(
+            c.write_firmware_stream(1, len + 1, &data[..], 0x1000),
+            Err(GcdError::FirmwareTruncated { received, .. }) if received == len
+        ));
+        assert!(c.write_firmware_stream(1, 1, &data[..], 0).is_err());
+    }
 }
@@ -0,0 +1,210 @@
+//! Declarative options for the [`Parser`] and [`Composer`].
+//!
+//! The configs can be deserialized from any serde format, so the CLI and
+//! other applications can share profiles. With the feature `config`,
+//! [`GcdConfig`] can be loaded directly from TOML or YAML files.
+//!
+//! ```
+//! use gcd_rs::config::ComposerConfig;
+//! use gcd_rs::composer::EndPadding;
+//!
+//! let config = ComposerConfig {
+//!     end_padding: EndPadding::Filler(0x1000),
+//!     max_chunk_size: Some(0x800),
+//!     ..Default::default()
+//! };
+//! ```
+//!
+//! [`Parser`]: crate::parser::Parser
+//! [`Composer`]: crate::composer::Composer
+
+use serde::{Deserialize, Serialize};
+
+use crate::build_info::MetadataPolicy;
+use crate::composer::{AutoChecksum, EndPadding};
+#[cfg(feature = "config")]
+use crate::error::GcdError;
+use crate::record::text::TextEncoding;
+
+/// Options of the [`Parser`](crate::parser::Parser).
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParserConfig {
+    /// Fail if a checksum record don't match the file data.
+    pub verify_checksums: bool,
+    /// Return the one byte checksums as `ChecksumRecord::Verified`, with the
+    /// result of the verification, instead of failing on a mismatch.
+    pub report_checksums: bool,
+    /// Remove the firmware obfuscation (XOR), otherwise the firmware data
+    /// is returned as stored in the file.
+    pub decode_firmware: bool,
+    /// Fail on any problem, otherwise recoverable problems are reported as
+    /// warnings, see [`Parser::warnings`](crate::parser::Parser::warnings).
+    pub strict: bool,
+    /// Accept firmware blocks without the FirmwareLen descriptor, the
+    /// firmware data is read until the next Descriptor or End record,
+    /// without checking the block len.
+    pub allow_unknown_len: bool,
+    /// How the text records are decoded.
+    pub text_encoding: TextEncoding,
+    /// Require the descriptor list to be terminated by the End (0x5003)
+    /// descriptor.
+    pub require_descriptor_end: bool,
+    /// Fail on records with a body bigger than this, in bytes.
+    pub max_record_len: Option<u16>,
+    /// Fail if the firmware of all the blocks is bigger than this, in bytes.
+    /// The FirmwareLen of each block is counted when the Descriptor is
+    /// parsed, so the limit is checked before the data is read. Useful when
+    /// parsing files from untrusted sources.
+    pub max_total_firmware_len: Option<u64>,
+    /// Max number of MainHeader records, some files have a PartNumber and
+    /// a HWID record.
+    pub max_main_headers: u8,
+    /// On an invalid record, scan the file byte by byte for the next valid
+    /// record header and continue from it, useful for truncated or corrupted
+    /// downloads. The error is kept as a warning and the data skipped is
+    /// reported by [`Parser::skipped`](crate::parser::Parser::skipped).
+    ///
+    /// The problems accepted by a lenient parser are only warnings. Limits
+    /// exceeded are not recovered.
+    pub recover: bool,
+    /// Fail with [`GcdError::TrailingData`] if there is data after the End
+    /// record, checked by [`Parser::finish`].
+    ///
+    /// [`GcdError::TrailingData`]: crate::error::GcdError::TrailingData
+    /// [`Parser::finish`]: crate::parser::Parser::finish
+    pub deny_trailing_data: bool,
+    /// Calculate the CRCs of the file, see [`Parser::checksums`], require the
+    /// feature `crc`.
+    ///
+    /// [`Parser::checksums`]: crate::parser::Parser::checksums
+    pub crc: bool,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        ParserConfig {
+            verify_checksums: true,
+            report_checksums: false,
+            decode_firmware: true,
+            strict: true,
+            allow_unknown_len: false,
+            text_encoding: TextEncoding::Utf8,
+            require_descriptor_end: true,
+            max_record_len: None,
+            max_total_firmware_len: None,
+            max_main_headers: 2,
+            recover: false,
+            deny_trailing_data: false,
+            crc: false,
+        }
+    }
+}
+
+/// Options of the [`Composer`](crate::composer::Composer).
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComposerConfig {
+    /// Filler strategy used before the End record.
+    pub end_padding: EndPadding,
+    /// Firmware records bigger than this are split in multiple chunks.
+    pub max_chunk_size: Option<u16>,
+    /// Write checksum records by file size or at the end of each block.
+    pub auto_checksum: AutoChecksum,
+    /// How the build metadata records are written.
+    pub metadata_policy: MetadataPolicy,
+    /// Lint the records with the default rules.
+    pub lint: bool,
+    /// Apply the firmware obfuscation (XOR) declared on the Descriptor,
+    /// otherwise the firmware data is written as received.
+    pub encode_firmware: bool,
+    /// Calculate the CRCs of the file, see [`Composer::checksums`], require the
+    /// feature `crc`.
+    ///
+    /// [`Composer::checksums`]: crate::composer::Composer::checksums
+    pub crc: bool,
+}
+
+impl Default for ComposerConfig {
+    fn default() -> Self {
+        ComposerConfig {
+            end_padding: EndPadding::default(),
+            max_chunk_size: None,
+            auto_checksum: AutoChecksum::default(),
+            metadata_policy: MetadataPolicy::default(),
+            lint: false,
+            encode_firmware: true,
+            crc: false,
+        }
+    }
+}
+
+/// A profile with all the options.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GcdConfig {
+    pub parser: ParserConfig,
+    pub composer: ComposerConfig,
+}
+
+#[cfg(feature = "config")]
+impl GcdConfig {
+    pub fn from_toml(s: &str) -> crate::error::Result<Self> {
+        toml::from_str(s).map_err(|e| GcdError::invalid_data(e.to_string()))
+    }
+
+    pub fn from_yaml(s: &str) -> crate::error::Result<Self> {
+        serde_yaml::from_str(s)
+            .map_err(|e| GcdError::invalid_data(e.to_string()))
+    }
+
+    /// Load the file, the format is selected by the extension, "toml" or
+    /// "yaml"/"yml".
+    pub fn load<P: AsRef<std::path::Path>>(
+        path: P,
+    ) -> crate::error::Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("toml") => Self::from_toml(&data),
+            Some("yaml") | Some("yml") => Self::from_yaml(&data),
+            _ => Err(GcdError::invalid_input("Unknown config file extension")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GcdConfig;
+    use crate::build_info::MetadataPolicy;
+    use crate::composer::EndPadding;
+
+    #[test]
+    fn partial_yaml() {
+        let config: GcdConfig = serde_yaml::from_str(
+            "parser:\n  verify_checksums: false\n\
+             composer:\n  end_padding:\n    Filler: 512\n  \
+             metadata_policy: Omit\n",
+        )
+        .unwrap();
+        assert!(!config.parser.verify_checksums);
+        assert!(config.parser.decode_firmware);
+        assert_eq!(config.composer.end_padding, EndPadding::Filler(512));
+        assert_eq!(config.composer.metadata_policy, MetadataPolicy::Omit);
+        assert_eq!(config.composer.max_chunk_size, None);
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn toml() {
+        use crate::composer::AutoChecksum;
+        let config = GcdConfig::from_toml(
+            "[composer]\nmax_chunk_size = 4096\n\
+             auto_checksum = { Chunks = 2 }\n",
+        )
+        .unwrap();
+        assert_eq!(config.composer.max_chunk_size, Some(4096));
+        assert_eq!(config.composer.auto_checksum, AutoChecksum::Chunks(2));
+        assert_eq!(config.parser, Default::default());
+    }
+}
@@ -0,0 +1,140 @@
+//! Identification of the device targeted by a GCD file.
+//!
+//! Collect the values that identify a file, spread on the MainHeader, the
+//! firmware descriptors and the text records, into a single struct.
+
+use crate::error::Result;
+use byteorder::ByteOrder;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+use crate::parser::Parser;
+use crate::{GcdDefaultEndian, PartNumber, Record, Version};
+
+/// Information about a single firmware block.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FirmwareInfo {
+    /// Firmware record id
+    pub id: Option<u16>,
+    /// Firmware total len
+    pub len: Option<u32>,
+    /// HWID that this firmware is destined to
+    pub hwid: Option<u16>,
+    pub version_sw: Option<Version>,
+    pub version_remote: Option<Version>,
+}
+
+/// Fingerprint of a GCD file.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceInfo {
+    /// HWID from the MainHeader record
+    pub hwid: Option<u16>,
+    /// PartNumber from the MainHeader record
+    pub part_number: Option<PartNumber>,
+    /// Each firmware block in the file, in order
+    pub firmwares: Vec<FirmwareInfo>,
+    /// Text records that contain valid text, in order
+    pub texts: Vec<String>,
+}
+
+impl DeviceInfo {
+    /// Read all the records from the file, using the default endian.
+    pub fn read<F: std::io::Read>(file: F) -> Result<Self> {
+        let mut parser: Parser<F, GcdDefaultEndian> = Parser::new(file)?;
+        Self::from_parser(&mut parser)
+    }
+
+    /// Read the records until the End record.
+    pub fn from_parser<F, B>(parser: &mut Parser<F, B>) -> Result<Self>
+    where
+        F: std::io::Read,
+        B: ByteOrder,
+    {
+        let mut info = DeviceInfo::default();
+        loop {
+            let record = parser.read_record()?;
+            info.add_record(&record);
+            if let Record::End = record {
+                return Ok(info);
+            }
+        }
+    }
+
+    /// Extract the information from already parsed records.
+    pub fn from_records<'a, I>(records: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Record>,
+    {
+        let mut info = DeviceInfo::default();
+        records.into_iter().for_each(|x| info.add_record(x));
+        info
+    }
+
+    fn add_record(&mut self, record: &Record) {
+        match record {
+            Record::MainHeader(main) => {
+                self.hwid = self.hwid.or_else(|| main.hwid());
+                self.part_number =
+                    self.part_number.take().or_else(|| main.part_number());
+            }
+            Record::Text(text) => {
+                let value = text.value();
+                let text = match text.as_str() {
+                    Some(text) => Cow::Borrowed(text),
+                    None => String::from_utf8_lossy(&value),
+                };
+                let text =
+                    text.trim_matches(|x: char| x == '\0' || x.is_whitespace());
+                if !text.is_empty() {
+                    self.texts.push(text.to_string());
+                }
+            }
+            Record::Descriptor(descriptor) => {
+                self.firmwares.push(FirmwareInfo {
+                    id: descriptor.firmware_id(),
+                    len: descriptor.firmware_len(),
+                    hwid: descriptor.hw_id(),
+                    version_sw: descriptor.sw_version(),
+                    version_remote: descriptor.remote_version(),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+    use crate::record::descriptor::DescriptorRecord;
+    use crate::record::main::MainRecord;
+    use crate::record::text::TextRecord;
+    use crate::{DeviceInfo, Record, Version};
+
+    #[test]
+    fn device_info_from_records() {
+        let records = [
+            Record::Text(TextRecord::Simple("Copyright\0".to_string())),
+            Record::MainHeader(MainRecord::DefaultPartNumber),
+            Record::MainHeader(MainRecord::DefaultHWID),
+            Record::Descriptor(DescriptorRecord::Simple(vec![
+                DescriptorDecoded::HWID(0x0123).encode(),
+                DescriptorDecoded::VersionSw(Version::new(3, 80)).encode(),
+                DescriptorDecoded::FirmwareId(0x0505).encode(),
+                DescriptorDecoded::FirmwareLen(10).encode(),
+                DescriptorDecoded::End.encode(),
+            ])),
+            Record::End,
+        ];
+        let info = DeviceInfo::from_records(&records);
+        assert_eq!(info.hwid, Some(0x0037));
+        assert_eq!(info.part_number.unwrap().to_string(), "010-10037-00");
+        assert_eq!(info.texts, vec!["Copyright".to_string()]);
+        assert_eq!(info.firmwares.len(), 1);
+        assert_eq!(info.firmwares[0].id, Some(0x0505));
+        assert_eq!(info.firmwares[0].hwid, Some(0x0123));
+        assert_eq!(info.firmwares[0].version_sw, Some(Version::new(3, 80)));
+    }
+}
@@ -0,0 +1,457 @@
+//! Compare two parsed GCD files, eg: two consecutive firmware releases.
+//!
+//! The records, excluding the firmware data, are aligned using the longest
+//! common sequence, a removed record followed by an added record of the same
+//! kind is reported as changed. The firmware data is compared by block, the
+//! blocks are matched by firmware id, and the versions of each block are
+//! reported separately.
+//!
+//! ```
+//! use gcd_rs::diff::{self, VersionField};
+//! use gcd_rs::record::descriptor::descriptor_data::DescriptorDecoded;
+//! use gcd_rs::{Record, Version};
+//!
+//! let old = gcd_rs::parser::parse_all(gcd_rs::testing::vectors::SINGLE_BLOCK)
+//!     .unwrap();
+//! let mut new = old.clone();
+//! for record in new.iter_mut() {
+//!     if let Record::Descriptor(desc) = record {
+//!         desc.iter_mut().for_each(|x| {
+//!             if let DescriptorDecoded::VersionSw(_) = x.decode_any() {
+//!                 let version = Version::new(9, 99);
+//!                 *x = DescriptorDecoded::VersionSw(version).encode();
+//!             }
+//!         });
+//!     }
+//! }
+//! let report = diff::diff(&old, &new);
+//! assert_eq!(report.versions.len(), 1);
+//! assert_eq!(report.versions[0].field, VersionField::Sw);
+//! assert!(report.blocks.iter().all(|x| x.ranges.is_empty()));
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::mem::discriminant;
+use std::ops::Range;
+
+use crate::{Record, Version};
+
+/// A record that differ, the indexes are from the original slices.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum RecordChange {
+    Added {
+        index: usize,
+        record: Record,
+    },
+    Removed {
+        index: usize,
+        record: Record,
+    },
+    Changed {
+        old_index: usize,
+        new_index: usize,
+        old: Record,
+        new: Record,
+    },
+}
+
+/// Firmware data differences of a block, see [`diff`].
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct BlockDiff {
+    pub id: Option<u16>,
+    /// Data len on the old file, None if the block was added.
+    pub old_len: Option<u64>,
+    /// Data len on the new file, None if the block was removed.
+    pub new_len: Option<u64>,
+    /// Byte ranges that differ, including the data after the end of the
+    /// smaller block.
+    pub ranges: Vec<Range<u64>>,
+}
+
+impl BlockDiff {
+    /// Number of bytes that differ.
+    pub fn changed_bytes(&self) -> u64 {
+        self.ranges.iter().map(|x| x.end - x.start).sum()
+    }
+}
+
+/// Version descriptor of a firmware block.
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum VersionField {
+    Sw,
+    Remote,
+}
+
+/// A version of a block that changed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct VersionChange {
+    /// Firmware id of the block.
+    pub id: Option<u16>,
+    pub field: VersionField,
+    pub old: Option<Version>,
+    pub new: Option<Version>,
+}
+
+/// All the differences between two files.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct DiffReport {
+    pub records: Vec<RecordChange>,
+    /// A entry for each block, including the identical ones.
+    pub blocks: Vec<BlockDiff>,
+    pub versions: Vec<VersionChange>,
+}
+
+impl DiffReport {
+    pub fn is_identical(&self) -> bool {
+        self.records.is_empty()
+            && self.versions.is_empty()
+            && self.blocks.iter().all(|x| {
+                x.ranges.is_empty()
+                    && x.old_len.is_some()
+                    && x.new_len.is_some()
+            })
+    }
+}
+
+impl Display for DiffReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for change in self.records.iter() {
+            match change {
+                RecordChange::Added { index, record } => {
+                    writeln!(f, "+ record {}: {}", index, record)?
+                }
+                RecordChange::Removed { index, record } => {
+                    writeln!(f, "- record {}: {}", index, record)?
+                }
+                RecordChange::Changed {
+                    old_index,
+                    new_index,
+                    old,
+                    new,
+                } => {
+                    writeln!(f, "- record {}: {}", old_index, old)?;
+                    writeln!(f, "+ record {}: {}", new_index, new)?;
+                }
+            }
+        }
+        let show = |x: Option<u64>| match x {
+            Some(x) => x.to_string(),
+            None => "-".to_string(),
+        };
+        for block in self.blocks.iter() {
+            match block.id {
+                Some(id) => write!(f, "block {:#06x}", id)?,
+                None => write!(f, "block without id")?,
+            }
+            writeln!(
+                f,
+                ": {} -> {} bytes, {} bytes changed in {} ranges",
+                show(block.old_len),
+                show(block.new_len),
+                block.changed_bytes(),
+                block.ranges.len(),
+            )?;
+        }
+        for version in self.versions.iter() {
+            let show = |x: Option<Version>| match x {
+                Some(x) => x.to_string(),
+                None => "-".to_string(),
+            };
+            writeln!(
+                f,
+                "version {:?} of block {:?}: {} -> {}",
+                version.field,
+                version.id,
+                show(version.old),
+                show(version.new),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct Block {
+    id: Option<u16>,
+    sw: Option<Version>,
+    remote: Option<Version>,
+    data: Vec<u8>,
+}
+
+//the Descriptor and the firmware data after it
+fn blocks(records: &[Record]) -> Vec<Block> {
+    let mut blocks: Vec<Block> = vec![];
+    for record in records {
+        match record {
+            Record::Descriptor(desc) => blocks.push(Block {
+                id: desc.firmware_id(),
+                sw: desc.sw_version(),
+                remote: desc.remote_version(),
+                data: vec![],
+            }),
+            Record::FirmwareData(firm) => {
+                if let Some(block) = blocks.last_mut() {
+                    block.data.extend_from_slice(firm.data());
+                }
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
+
+/// Byte ranges that differ between the two buffers.
+pub fn diff_bytes(old: &[u8], new: &[u8]) -> Vec<Range<u64>> {
+    let mut ranges: Vec<Range<u64>> = vec![];
+    let diffs = old.iter().zip(new.iter()).enumerate();
+    for (pos, _) in diffs.filter(|(_, (old, new))| old != new) {
+        let pos = pos as u64;
+        match ranges.last_mut() {
+            Some(last) if last.end == pos => last.end += 1,
+            _ => ranges.push(pos..pos + 1),
+        }
+    }
+    let (min, max) = (old.len().min(new.len()), old.len().max(new.len()));
+    if min != max {
+        match ranges.last_mut() {
+            Some(last) if last.end == min as u64 => last.end = max as u64,
+            _ => ranges.push(min as u64..max as u64),
+        }
+    }
+    ranges
+}
+
+fn diff_records(old: &[Record], new: &[Record]) -> Vec<RecordChange> {
+    //the firmware data is compared by block
+    let old: Vec<_> = old
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| !matches!(x, Record::FirmwareData(_)))
+        .collect();
+    let new: Vec<_> = new
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| !matches!(x, Record::FirmwareData(_)))
+        .collect();
+
+    //longest common sequence, lcs[i][j] is the len for old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i].1 == new[j].1 {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i].1 == new[j].1 {
+            i += 1;
+            j += 1;
+        } else if j < new.len()
+            && (i == old.len() || lcs[i][j + 1] > lcs[i + 1][j])
+        {
+            let (index, record) = new[j];
+            let change = match changes.pop() {
+                //a removed record replaced by a record of the same kind
+                Some(RecordChange::Removed {
+                    index: old_index,
+                    record: old,
+                }) if discriminant(&old) == discriminant(record) => {
+                    RecordChange::Changed {
+                        old_index,
+                        new_index: index,
+                        old,
+                        new: record.clone(),
+                    }
+                }
+                last => {
+                    changes.extend(last);
+                    RecordChange::Added {
+                        index,
+                        record: record.clone(),
+                    }
+                }
+            };
+            changes.push(change);
+            j += 1;
+        } else {
+            let (index, record) = old[i];
+            changes.push(RecordChange::Removed {
+                index,
+                record: record.clone(),
+            });
+            i += 1;
+        }
+    }
+    changes
+}
+
+/// Compare the records of two files, the firmware data is expected to be
+/// decoded, like the data returned by the [`crate::parser::Parser`].
+pub fn diff(old: &[Record], new: &[Record]) -> DiffReport {
+    let mut report = DiffReport {
+        records: diff_records(old, new),
+        ..Default::default()
+    };
+
+    let old_blocks = blocks(old);
+    let mut new_blocks: Vec<Option<Block>> =
+        blocks(new).into_iter().map(Some).collect();
+    for old in old_blocks {
+        //first block with the same id not yet matched
+        let new = new_blocks
+            .iter_mut()
+            .find(|x| matches!(x, Some(x) if x.id == old.id))
+            .and_then(Option::take);
+        let new_data = new.as_ref().map(|x| x.data.as_slice()).unwrap_or(&[]);
+        report.blocks.push(BlockDiff {
+            id: old.id,
+            old_len: Some(old.data.len() as u64),
+            new_len: new.as_ref().map(|x| x.data.len() as u64),
+            ranges: diff_bytes(&old.data, new_data),
+        });
+        let (new_sw, new_remote) = match &new {
+            Some(new) => (new.sw, new.remote),
+            None => (None, None),
+        };
+        let versions = [
+            (VersionField::Sw, old.sw, new_sw),
+            (VersionField::Remote, old.remote, new_remote),
+        ];
+        report.versions.extend(
+            versions.iter().filter(|(_, old, new)| old != new).map(
+                |(field, old_version, new_version)| VersionChange {
+                    id: old.id,
+                    field: *field,
+                    old: *old_version,
+                    new: *new_version,
+                },
+            ),
+        );
+    }
+    for new in new_blocks.into_iter().flatten() {
+        report.blocks.push(BlockDiff {
+            id: new.id,
+            old_len: None,
+            new_len: Some(new.data.len() as u64),
+            ranges: diff_bytes(&[], &new.data),
+        });
+        let versions = [
+            (VersionField::Sw, new.sw),
+            (VersionField::Remote, new.remote),
+        ];
+        report.versions.extend(
+            versions.iter().filter(|(_, x)| x.is_some()).map(
+                |(field, version)| VersionChange {
+                    id: new.id,
+                    field: *field,
+                    old: None,
+                    new: *version,
+                },
+            ),
+        );
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, diff_bytes, RecordChange, VersionField};
+    use crate::block::FirmwareBlockBuilder;
+    use crate::parser::parse_all;
+    use crate::record::main::MainRecord;
+    use crate::record::text::TextRecord;
+    use crate::testing::vectors;
+    use crate::{Record, Version};
+
+    #[test]
+    fn diff_bytes_ranges() {
+        assert!(diff_bytes(&[1, 2, 3], &[1, 2, 3]).is_empty());
+        assert_eq!(diff_bytes(&[1, 2, 3, 4], &[0, 0, 3, 0]), [0..2, 3..4]);
+        assert_eq!(diff_bytes(&[1, 2, 3], &[1, 0, 3, 4, 5]), [1..2, 3..5]);
+        assert_eq!(diff_bytes(&[1, 2, 3], &[1, 2]), vec![2..3]);
+        assert_eq!(diff_bytes(&[1, 2, 3], &[1, 0]), vec![1..3]);
+    }
+
+    #[test]
+    fn diff_files() {
+        let old = parse_all(vectors::MULTI_BLOCK).unwrap();
+        let report = diff(&old, &old);
+        assert!(report.is_identical());
+
+        let block = |id: u16, data: Vec<u8>, version| {
+            FirmwareBlockBuilder::new(id, data)
+                .version(Version::new(1, version))
+                .build()
+                .unwrap()
+        };
+        let mut old = vec![
+            Record::Text(TextRecord::Simple("Release".to_string())),
+            Record::MainHeader(MainRecord::DefaultHWID),
+        ];
+        old.extend(block(0x0505, vec![0; 100], 10));
+        old.extend(block(0x0506, vec![1; 100], 10));
+        old.push(Record::End);
+
+        let mut new_data = vec![0; 120];
+        new_data[10..20].iter_mut().for_each(|x| *x = 0xff);
+        let mut new = vec![
+            Record::Text(TextRecord::Simple("Release 2".to_string())),
+            Record::MainHeader(MainRecord::DefaultHWID),
+        ];
+        new.extend(block(0x0505, new_data, 20));
+        new.extend(block(0x0507, vec![2; 10], 10));
+        new.push(Record::End);
+
+        let report = diff(&old, &new);
+        assert!(!report.is_identical());
+        assert!(matches!(
+            report.records[0],
+            RecordChange::Changed {
+                old_index: 0,
+                new_index: 0,
+                ..
+            }
+        ));
+        assert_eq!(report.blocks.len(), 3);
+        assert_eq!(report.blocks[0].ranges, [10..20, 100..120]);
+        assert_eq!(report.blocks[1].id, Some(0x0506));
+        assert_eq!(report.blocks[1].new_len, None);
+        assert_eq!(report.blocks[2].old_len, None);
+        assert_eq!(report.blocks[2].changed_bytes(), 10);
+        let versions: Vec<_> = report
+            .versions
+            .iter()
+            .map(|x| (x.id, x.field, x.old, x.new))
+            .collect();
+        assert_eq!(
+            versions,
+            [
+                (
+                    Some(0x0505),
+                    VersionField::Sw,
+                    Some(Version::new(1, 10)),
+                    Some(Version::new(1, 20)),
+                ),
+                (
+                    Some(0x0506),
+                    VersionField::Sw,
+                    Some(Version::new(1, 10)),
+                    None,
+                ),
+                (
+                    Some(0x0507),
+                    VersionField::Sw,
+                    None,
+                    Some(Version::new(1, 10)),
+                ),
+            ]
+        );
+        assert!(!report.to_string().is_empty());
+    }
+}
@@ -0,0 +1,264 @@
+//! Cryptographic digests of the firmware blocks.
+//!
+//! Firmware databases identify the firmware by its hash, this calculates the
+//! SHA-256 and MD5 of the decoded data of each block, without the need to
+//! assemble the chunks first. The hashes are from the `sha2` and `md-5`
+//! crates, used as a [`Checksum`].
+//!
+//! ```
+//! use gcd_rs::digest::digest_blocks;
+//! use gcd_rs::parser::Parser;
+//! use gcd_rs::testing::vectors;
+//!
+//! let mut parser: Parser<&[u8]> = Parser::new(vectors::MULTI_BLOCK).unwrap();
+//! let blocks = digest_blocks(&mut parser).unwrap();
+//! assert!(blocks.len() > 1);
+//! println!("{:04x} {}", blocks[0].id, blocks[0].sha256_hex());
+//! ```
+
+use byteorder::ByteOrder;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+use crate::error::{GcdError, Result};
+use crate::parser::{Parser, RecordVisitor};
+use crate::record::checksum::Checksum;
+use crate::record::descriptor::DescriptorRecord;
+use crate::{GcdFile, Record, RecordId, Version};
+
+pub use md5::Md5;
+pub use sha2::Sha256;
+
+impl Checksum for Sha256 {
+    type Value = [u8; 32];
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(self, data);
+    }
+    fn value(&self) -> [u8; 32] {
+        sha2::Digest::finalize(self.clone()).into()
+    }
+}
+
+impl Checksum for Md5 {
+    type Value = [u8; 16];
+    fn update(&mut self, data: &[u8]) {
+        md5::Digest::update(self, data);
+    }
+    fn value(&self) -> [u8; 16] {
+        md5::Digest::finalize(self.clone()).into()
+    }
+}
+
+/// SHA-256 and MD5 calculated at the same time.
+#[derive(Clone, Default)]
+pub struct Digests {
+    pub sha256: Sha256,
+    pub md5: Md5,
+}
+
+impl Digests {
+    pub fn update(&mut self, data: &[u8]) {
+        self.sha256.update(data);
+        self.md5.update(data);
+    }
+}
+
+impl Write for Digests {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Digests of the decoded data of a firmware block.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct FirmwareBlockSummary {
+    /// Firmware id
+    pub id: u16,
+    /// Software version from the Descriptor
+    pub version: Option<Version>,
+    /// Len of the decoded data
+    pub len: u64,
+    pub sha256: [u8; 32],
+    pub md5: [u8; 16],
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+impl FirmwareBlockSummary {
+    fn new(descriptor: &DescriptorRecord, len: u64, digests: &Digests) -> Self {
+        FirmwareBlockSummary {
+            id: descriptor.firmware_id().unwrap_or(0),
+            version: descriptor.sw_version(),
+            len,
+            sha256: digests.sha256.value(),
+            md5: digests.md5.value(),
+        }
+    }
+
+    /// The SHA-256 in lowercase hex.
+    pub fn sha256_hex(&self) -> String {
+        to_hex(&self.sha256)
+    }
+
+    /// The MD5 in lowercase hex.
+    pub fn md5_hex(&self) -> String {
+        to_hex(&self.md5)
+    }
+}
+
+/// [`RecordVisitor`] that calculates the digests of each firmware block,
+/// used by [`digest_blocks`].
+#[derive(Default)]
+pub struct BlockDigests {
+    blocks: Vec<FirmwareBlockSummary>,
+    // descriptor of the current block, with the data len and digests
+    current: Option<(DescriptorRecord, u64, Digests)>,
+}
+
+impl BlockDigests {
+    fn end_block(&mut self) {
+        if let Some((descriptor, len, digests)) = self.current.take() {
+            let summary = FirmwareBlockSummary::new(&descriptor, len, &digests);
+            self.blocks.push(summary);
+        }
+    }
+
+    /// The digests of the blocks, in order. Only complete after the End
+    /// record.
+    pub fn blocks(&self) -> &[FirmwareBlockSummary] {
+        &self.blocks
+    }
+
+    pub fn into_blocks(self) -> Vec<FirmwareBlockSummary> {
+        self.blocks
+    }
+}
+
+impl RecordVisitor for BlockDigests {
+    fn on_descriptor(&mut self, descriptor: &DescriptorRecord) -> Result<()> {
+        self.end_block();
+        self.current = Some((descriptor.clone(), 0, Digests::default()));
+        Ok(())
+    }
+    fn on_firmware_chunk(&mut self, _id: RecordId, data: &[u8]) -> Result<()> {
+        let (_, len, digests) = self.current.as_mut().ok_or_else(|| {
+            GcdError::invalid_data("Firmware chunk without a Descriptor")
+        })?;
+        *len += data.len() as u64;
+        digests.update(data);
+        Ok(())
+    }
+    fn on_end(&mut self) -> Result<()> {
+        self.end_block();
+        Ok(())
+    }
+}
+
+/// Read all the records, calculating the digests of each firmware block.
+pub fn digest_blocks<F, B>(
+    parser: &mut Parser<F, B>,
+) -> Result<Vec<FirmwareBlockSummary>>
+where
+    F: Read,
+    B: ByteOrder,
+{
+    let mut digests = BlockDigests::default();
+    parser.visit(&mut digests)?;
+    Ok(digests.into_blocks())
+}
+
+/// Calculate the digests of each firmware block of an indexed file.
+pub fn digest_file<B: ByteOrder>(
+    file: &GcdFile<B>,
+) -> Result<Vec<FirmwareBlockSummary>> {
+    let mut blocks = vec![];
+    for (i, block) in file.blocks().iter().enumerate() {
+        let descriptor = match file.record(block.descriptor)? {
+            Record::Descriptor(descriptor) => descriptor,
+            _ => {
+                return Err(GcdError::invalid_data("Block without Descriptor"))
+            }
+        };
+        let mut digests = Digests::default();
+        let len = std::io::copy(&mut file.block_reader(i)?, &mut digests)?;
+        blocks.push(FirmwareBlockSummary::new(&descriptor, len, &digests));
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{digest_blocks, digest_file, to_hex, Md5, Sha256};
+    use crate::parser::Parser;
+    use crate::record::checksum::Checksum;
+    use crate::testing::vectors;
+    use crate::GcdFile;
+
+    fn sha256(data: &[u8]) -> String {
+        let mut sha256 = Sha256::default();
+        sha256.update(data);
+        to_hex(&sha256.value())
+    }
+
+    fn md5(data: &[u8]) -> String {
+        let mut md5 = Md5::default();
+        md5.update(data);
+        to_hex(&md5.value())
+    }
+
+    #[test]
+    fn digest_values() {
+        assert_eq!(
+            sha256(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(md5(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(md5(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            md5(b"The quick brown fox jumps over the lazy dog"),
+            "9e107d9d372bb6826bd81d3542a419d6"
+        );
+
+        //the value don't depend on how the data is split
+        let data: Vec<u8> = (0..1000u32).map(|x| x as u8).collect();
+        let mut split = Sha256::default();
+        data.chunks(63).for_each(|x| split.update(x));
+        assert_eq!(to_hex(&split.value()), sha256(&data));
+        let mut split = Md5::default();
+        data.chunks(65).for_each(|x| split.update(x));
+        assert_eq!(to_hex(&split.value()), md5(&data));
+    }
+
+    #[test]
+    fn digest_file_blocks() {
+        let mut parser: Parser<&[u8]> =
+            Parser::new(vectors::MULTI_BLOCK).unwrap();
+        let blocks = digest_blocks(&mut parser).unwrap();
+        let file: GcdFile =
+            GcdFile::from_bytes(vectors::MULTI_BLOCK.to_vec()).unwrap();
+        assert_eq!(blocks.len(), file.blocks().len());
+        for (i, block) in blocks.iter().enumerate() {
+            let data = file.read_block(i).unwrap();
+            assert_eq!(block.id, file.blocks()[i].id);
+            assert_eq!(block.len, data.len() as u64);
+            assert_eq!(block.sha256_hex(), sha256(&data));
+            assert_eq!(block.md5_hex(), md5(&data));
+        }
+        assert_eq!(digest_file(&file).unwrap(), blocks);
+    }
+}
@@ -0,0 +1,271 @@
+//! Human readable dump of a whole GCD file.
+//!
+//! The records are printed as a tree, each firmware block with the decoded
+//! descriptors, the text and checksums inside it and a summary of the
+//! firmware chunks.
+//!
+//! ```
+//! use gcd_rs::display::Tree;
+//! use gcd_rs::testing::vectors;
+//!
+//! let records = gcd_rs::parser::parse_all(vectors::SINGLE_BLOCK).unwrap();
+//! let dump = Tree::new(&records).to_string();
+//! assert!(dump.starts_with("GARMIN v100\n"));
+//! assert!(dump.contains("Block 0: id 0x"));
+//! ```
+
+use std::fmt::{Display, Formatter, Write};
+
+use crate::block::{list_blocks, BlockInfo};
+use crate::codec::VERSION;
+use crate::record::checksum::ChecksumRecord;
+use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+use crate::record::descriptor::schema::DescriptorSchema;
+use crate::record::descriptor::DescriptorRecord;
+use crate::record::main::MainRecord;
+use crate::record::text::TextRecord;
+use crate::Record;
+
+const INDENT: &str = "  ";
+
+/// Tree dump of the records, see the [module](self) documentation.
+///
+/// The checksum status is only meaningful if the records were returned by a
+/// parser that verify the checksums.
+pub struct Tree<'a> {
+    records: &'a [Record],
+    schema: Option<&'a DescriptorSchema>,
+}
+
+impl<'a> Tree<'a> {
+    pub fn new(records: &'a [Record]) -> Self {
+        Tree {
+            records,
+            schema: None,
+        }
+    }
+
+    /// Decode the descriptors with the schema, instead of the default one.
+    pub fn schema(self, schema: &'a DescriptorSchema) -> Self {
+        Tree {
+            schema: Some(schema),
+            ..self
+        }
+    }
+
+    /// Write the dump to any [`std::fmt::Write`].
+    pub fn write_to<W: Write>(&self, out: &mut W) -> std::fmt::Result {
+        let default_schema;
+        let schema = match self.schema {
+            Some(schema) => schema,
+            None => {
+                default_schema = DescriptorSchema::default();
+                &default_schema
+            }
+        };
+        let blocks = list_blocks(self.records);
+        let mut block = None;
+        let mut hwid = None;
+        writeln!(out, "GARMIN v{}", VERSION)?;
+        for record in self.records {
+            let indent = if block.is_some() { INDENT } else { "" };
+            match record {
+                Record::Checksum(checksum) => {
+                    write!(out, "{}Checksum: ", indent)?;
+                    write_checksum(out, checksum)?;
+                }
+                Record::Filler(filler) => {
+                    writeln!(out, "{}Filler: {} bytes", indent, filler.len())?
+                }
+                Record::MainHeader(main) => {
+                    hwid = main.hwid();
+                    write_main(out, main)?;
+                }
+                Record::Text(text) => {
+                    write!(out, "{}Text: ", indent)?;
+                    write_text(out, text)?;
+                }
+                Record::Descriptor(descriptor) => {
+                    let index = block.map(|x| x + 1).unwrap_or(0);
+                    block = Some(index);
+                    write_block(out, &blocks[index])?;
+                    let hwid = descriptor.hw_id().or(hwid);
+                    write_descriptor(out, schema, hwid, descriptor)?;
+                }
+                //summarized on the block line
+                Record::FirmwareData(_) => {}
+                Record::End => writeln!(out, "End")?,
+                Record::Unknown { id, data } => writeln!(
+                    out,
+                    "{}Unknown {:#06x}: {} bytes",
+                    indent,
+                    id,
+                    data.len()
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Tree<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.write_to(f)
+    }
+}
+
+fn write_checksum<W: Write>(
+    out: &mut W,
+    checksum: &ChecksumRecord,
+) -> std::fmt::Result {
+    match checksum {
+        ChecksumRecord::Simple => writeln!(out, "ok"),
+        ChecksumRecord::Verified { ok: true, .. } => writeln!(out, "ok"),
+        ChecksumRecord::Verified {
+            ok: false,
+            expected,
+            found,
+        } => writeln!(
+            out,
+            "mismatch, expected {:#04x}, found {:#04x}",
+            expected, found
+        ),
+    }
+}
+
+fn write_main<W: Write>(out: &mut W, main: &MainRecord) -> std::fmt::Result {
+    write!(out, "MainHeader:")?;
+    if let Some(part_number) = main.part_number() {
+        write!(out, " PartNumber {}", part_number)?;
+    }
+    if let Some(hwid) = main.hwid() {
+        write!(out, " HWID {:#06x}", hwid)?;
+    }
+    writeln!(out)
+}
+
+fn write_text<W: Write>(out: &mut W, text: &TextRecord) -> std::fmt::Result {
+    match text {
+        TextRecord::Simple(text) | TextRecord::Latin1(text) => {
+            let text = text.trim_end_matches('\0');
+            writeln!(out, "{:?}", text)
+        }
+        TextRecord::Blob(data) => writeln!(out, "{} bytes", data.len()),
+    }
+}
+
+fn write_block<W: Write>(out: &mut W, block: &BlockInfo) -> std::fmt::Result {
+    write!(out, "Block {}:", block.index)?;
+    if let Some(id) = block.id {
+        write!(out, " id {:#06x},", id)?;
+    }
+    write!(out, " {} bytes in {} chunks", block.data_len, block.chunks)?;
+    if let Some(label) = &block.label {
+        write!(out, ", {}", label)?;
+    }
+    writeln!(out)
+}
+
+fn write_descriptor<W: Write>(
+    out: &mut W,
+    schema: &DescriptorSchema,
+    hwid: Option<u16>,
+    descriptor: &DescriptorRecord,
+) -> std::fmt::Result {
+    for data in descriptor.iter() {
+        let decoded = schema.decode(hwid, data);
+        if decoded == DescriptorDecoded::End {
+            continue;
+        }
+        write!(out, "{}", INDENT)?;
+        match schema.name(hwid, data) {
+            Some(name) => write!(out, "{}: ", name)?,
+            None => {
+                let desc_type = data.descriptor_type();
+                write!(
+                    out,
+                    "Unknown({}, {:#05x}): ",
+                    desc_type.kind(),
+                    desc_type.id()
+                )?
+            }
+        }
+        match (&decoded, data.as_str()) {
+            //unknown strings are shown as text
+            (DescriptorDecoded::Unknown { .. }, Some(text)) => {
+                write!(out, "{:?}", text)?
+            }
+            _ => write_decoded(out, &decoded)?,
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn write_decoded<W: Write>(
+    out: &mut W,
+    decoded: &DescriptorDecoded,
+) -> std::fmt::Result {
+    match decoded {
+        //the strings are quoted
+        DescriptorDecoded::DeviceModel(x)
+        | DescriptorDecoded::Text { value: x, .. } => write!(out, "{:?}", x),
+        decoded => {
+            let description = decoded.describe();
+            write!(out, "{}", description.value)?;
+            match description.unit {
+                Some(unit) => write!(out, " {}", unit),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tree;
+    use crate::config::ParserConfig;
+    use crate::parser::Parser;
+    use crate::record::descriptor::descriptor_data::Field;
+    use crate::record::descriptor::schema::{DescriptorSchema, SchemaEntry};
+    use crate::testing::vectors;
+    use crate::Record;
+
+    #[test]
+    fn tree_dump() {
+        let config = ParserConfig {
+            report_checksums: true,
+            ..Default::default()
+        };
+        let parser: Parser<&[u8]> =
+            Parser::with_config(vectors::FILLERS_CHECKSUMS, config).unwrap();
+        let records: Vec<Record> = parser.collect::<Result<_, _>>().unwrap();
+        let dump = Tree::new(&records).to_string();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines[0], "GARMIN v100");
+        assert_eq!(lines.last(), Some(&"End"));
+        let block = lines.iter().position(|x| x.starts_with("Block 0:"));
+        let block = block.unwrap();
+        assert!(lines[..block].iter().any(|x| x.starts_with("Checksum: ok")));
+        assert!(lines[..block].iter().any(|x| x.starts_with("MainHeader:")));
+        //the block records are indented
+        assert!(lines[block + 1..lines.len() - 1]
+            .iter()
+            .all(|x| x.starts_with("  ")));
+        assert!(lines.iter().any(|x| x.starts_with("  FirmwareId: 0x")));
+        assert!(lines.contains(&"  FirmwareLen: 8 bytes"));
+        assert!(lines.contains(&"  Checksum: ok"));
+
+        //a custom schema change the descriptor names
+        let mut schema = DescriptorSchema::default();
+        let id = Field::FirmwareId.info();
+        schema.register(SchemaEntry::new(None, id.kind, id.id, "Component"));
+        let mut custom = String::new();
+        Tree::new(&records)
+            .schema(&schema)
+            .write_to(&mut custom)
+            .unwrap();
+        assert!(custom.contains("  Component: "));
+        assert!(!custom.contains("FirmwareId"));
+    }
+}
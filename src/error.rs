@@ -0,0 +1,183 @@
+//! Errors returned by this crate.
+//!
+//! [`GcdError`] can be converted from and into `std::io::Error`, so it can be
+//! used with the `?` operator on functions returning any of the two. A
+//! `GcdError` converted into `std::io::Error` and back is preserved.
+
+use std::fmt::{Display, Formatter};
+use std::io;
+
+use crate::RecordHeader;
+
+/// Result with [`GcdError`] as the default error.
+pub type Result<T, E = GcdError> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum GcdError {
+    /// Error from the underlying reader/writer.
+    Io(io::Error),
+    /// The file don't start with the "GARMIN" signature.
+    InvalidSignature,
+    /// The file version is not 100.
+    UnknownVersion(u16),
+    /// The checksum record don't match the file data.
+    ChecksumMismatch { header: RecordHeader },
+    /// Record not allowed at the current parser state.
+    UnexpectedRecord { state: String, header: RecordHeader },
+    /// Tried to read a record after the End record.
+    ReadAfterEnd,
+    /// Data after the End record, with the number of bytes, see
+    /// [`Parser::finish`](crate::parser::Parser::finish).
+    TrailingData(u64),
+    /// A required field is missing from the Descriptor, eg: "Firmware Id".
+    MissingDescriptor(&'static str),
+    /// Firmware chunk with an id different from the Descriptor FirmwareId.
+    FirmwareIdMismatch { expected: u16, found: u16 },
+    /// The firmware chunks are bigger than the Descriptor FirmwareLen.
+    FirmwareOverflow { expected: u32 },
+    /// The firmware ended before receiving FirmwareLen bytes.
+    FirmwareTruncated { received: u32, expected: u32 },
+    /// A size is bigger than the limit configured in the
+    /// [`ParserConfig`](crate::config::ParserConfig).
+    LimitExceeded { limit: &'static str, max: u64 },
+    /// Malformed data.
+    InvalidData(String),
+    /// Invalid value provided by the caller.
+    InvalidInput(String),
+}
+
+impl GcdError {
+    pub(crate) fn invalid_data<S: Into<String>>(msg: S) -> Self {
+        GcdError::InvalidData(msg.into())
+    }
+
+    pub(crate) fn invalid_input<S: Into<String>>(msg: S) -> Self {
+        GcdError::InvalidInput(msg.into())
+    }
+
+    /// The equivalent `std::io::ErrorKind`.
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            GcdError::Io(e) => e.kind(),
+            GcdError::FirmwareTruncated { .. } => io::ErrorKind::UnexpectedEof,
+            GcdError::InvalidInput(_) => io::ErrorKind::InvalidInput,
+            _ => io::ErrorKind::InvalidData,
+        }
+    }
+}
+
+impl Display for GcdError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GcdError::Io(e) => write!(f, "{}", e),
+            GcdError::InvalidSignature => {
+                write!(f, "Invalid/Unknown Header Signature")
+            }
+            GcdError::UnknownVersion(x) => {
+                write!(f, "Invalid/Unknown Header Version {}", x)
+            }
+            GcdError::ChecksumMismatch { header } => {
+                write!(f, "Invalid {:?} Value", header)
+            }
+            GcdError::UnexpectedRecord { state, header } => {
+                write!(f, "State {} record received {:?}", state, header)
+            }
+            GcdError::ReadAfterEnd => {
+                write!(f, "Unable to read after End Record")
+            }
+            GcdError::TrailingData(x) => {
+                write!(f, "{} bytes after the End Record", x)
+            }
+            GcdError::MissingDescriptor(x) => write!(f, "{} not found", x),
+            GcdError::FirmwareIdMismatch { expected, found } => write!(
+                f,
+                "Firmware id expected {:#x} found {:#x}",
+                expected, found
+            ),
+            GcdError::FirmwareOverflow { expected } => write!(
+                f,
+                "Firmware Chunk is bigger than expected {} bytes",
+                expected
+            ),
+            GcdError::FirmwareTruncated { received, expected } => write!(
+                f,
+                "Firmware Chunk too small, received {} from {} bytes",
+                received, expected
+            ),
+            GcdError::LimitExceeded { limit, max } => {
+                write!(f, "{} bigger than the limit of {} bytes", limit, max)
+            }
+            GcdError::InvalidData(x) | GcdError::InvalidInput(x) => {
+                write!(f, "{}", x)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GcdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GcdError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for GcdError {
+    fn from(error: io::Error) -> Self {
+        //unwrap a GcdError that was converted into io::Error
+        if !error.get_ref().is_some_and(|x| x.is::<GcdError>()) {
+            return GcdError::Io(error);
+        }
+        let kind = error.kind();
+        match error.into_inner().map(|x| x.downcast::<GcdError>()) {
+            Some(Ok(inner)) => *inner,
+            //already checked, keep it as a generic io error anyway
+            Some(Err(inner)) => GcdError::Io(io::Error::new(kind, inner)),
+            None => GcdError::Io(kind.into()),
+        }
+    }
+}
+
+impl From<GcdError> for io::Error {
+    fn from(error: GcdError) -> Self {
+        match error {
+            GcdError::Io(e) => e,
+            error => io::Error::new(error.kind(), error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GcdError;
+    use crate::RecordHeader;
+    use std::io;
+
+    #[test]
+    fn io_round_trip() {
+        let error = GcdError::FirmwareIdMismatch {
+            expected: 1,
+            found: 2,
+        };
+        let io_error: io::Error = error.into();
+        assert_eq!(io_error.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(
+            GcdError::from(io_error),
+            GcdError::FirmwareIdMismatch {
+                expected: 1,
+                found: 2
+            }
+        ));
+
+        let error = GcdError::from(io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        let error = io::Error::new(io::ErrorKind::Other, "other");
+        assert!(matches!(GcdError::from(error), GcdError::Io(_)));
+        let error = GcdError::ChecksumMismatch {
+            header: RecordHeader::Checksum,
+        };
+        assert_eq!(error.to_string(), "Invalid Checksum Value");
+    }
+}
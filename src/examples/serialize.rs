@@ -1,21 +1,4 @@
-use gcd_rs::Record;
-use serde::{Deserialize, Serialize};
+//! The serialized format is now [`gcd_rs::manifest`], the names are kept
+//! for the code based on the examples.
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
-pub struct ExtFirmware {
-    pub filename: String,
-    pub id: u16,
-    pub offset: u64,
-    pub lenght: u64,
-}
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
-pub enum RecordSerialized {
-    Internal(Record),
-    External(ExtFirmware),
-}
-
-impl From<Record> for RecordSerialized {
-    fn from(x: Record) -> Self {
-        RecordSerialized::Internal(x)
-    }
-}
+pub use gcd_rs::manifest::ManifestRecord as RecordSerialized;
@@ -0,0 +1,176 @@
+//! Export the information shown by the Garmin updater.
+//!
+//! The updater shows a summary of the file before flashing it: the product
+//! name, software version, part number, supported HWIDs and the changelog.
+//! Those values are spread on the text records, MainHeader and descriptors,
+//! [`updater_info`] collect them into a [`UpdateInfo`].
+//!
+//! ```
+//! use gcd_rs::export::from_records;
+//! use gcd_rs::testing::vectors;
+//!
+//! let records = gcd_rs::parser::parse_all(vectors::SINGLE_BLOCK).unwrap();
+//! let info = from_records(&records);
+//! println!("{:?} {:?}", info.software_version, info.hwids);
+//! ```
+
+use byteorder::ByteOrder;
+use serde::{Deserialize, Serialize};
+
+use crate::record::text::RegionLabel;
+use crate::{build_info, compat, GcdFile, PartNumber, Record, Version};
+use crate::{DeviceInfo, VersionExt};
+
+/// Summary of the file, as shown by the updater.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateInfo {
+    /// DeviceModel descriptor, or the first text record
+    pub product_name: Option<String>,
+    /// Version of the first block, or the first version in the texts
+    pub software_version: Option<Version>,
+    /// PartNumber from the MainHeader record
+    pub part_number: Option<PartNumber>,
+    /// All the HWIDs declared, without duplicates
+    pub hwids: Vec<u16>,
+    /// The other text records, one per line
+    pub changelog: Option<String>,
+}
+
+/// Extract the [`UpdateInfo`] from an indexed file.
+pub fn updater_info<B: ByteOrder>(file: &GcdFile<B>) -> UpdateInfo {
+    from_records(file.index().records())
+}
+
+/// Extract the [`UpdateInfo`] from already parsed records.
+pub fn from_records<'a, I>(records: I) -> UpdateInfo
+where
+    I: IntoIterator<Item = &'a Record>,
+{
+    let records: Vec<&Record> = records.into_iter().collect();
+    let device = DeviceInfo::from_records(records.iter().copied());
+
+    //build metadata and region labels are not shown by the updater
+    let mut texts: Vec<String> = device
+        .texts
+        .into_iter()
+        .filter(|x| !x.starts_with(build_info::TEXT_PREFIX))
+        .filter(|x| x.parse::<RegionLabel>().is_err())
+        .collect();
+
+    let model = records.iter().find_map(|x| match x {
+        Record::Descriptor(descriptor) => descriptor
+            .device_model()
+            .filter(|x| !x.trim_matches('\0').is_empty()),
+        _ => None,
+    });
+    let product_name = match model {
+        Some(model) => Some(model.trim_matches('\0').to_string()),
+        None if !texts.is_empty() => Some(texts.remove(0)),
+        None => None,
+    };
+
+    let software_version = device
+        .firmwares
+        .iter()
+        .find_map(|x| x.version_sw.filter(|x| *x != Version::None))
+        .or_else(|| {
+            texts
+                .iter()
+                .find_map(|x| VersionExt::find(x))
+                .map(|x| x.version())
+        });
+
+    let mut hwids = vec![];
+    let declared = compat::check_records(records.iter().copied(), 0).declared;
+    for (_, hwid) in declared {
+        if !hwids.contains(&hwid) {
+            hwids.push(hwid);
+        }
+    }
+
+    let changelog = match texts.is_empty() {
+        true => None,
+        false => Some(texts.join("\n")),
+    };
+    UpdateInfo {
+        product_name,
+        software_version,
+        part_number: device.part_number,
+        hwids,
+        changelog,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_records, updater_info, UpdateInfo};
+    use crate::parser::parse_all;
+    use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+    use crate::record::descriptor::DescriptorRecord;
+    use crate::record::main::MainRecord;
+    use crate::record::text::TextRecord;
+    use crate::testing::vectors;
+    use crate::{GcdFile, Record, Version};
+
+    fn text(x: &str) -> Record {
+        Record::Text(TextRecord::Simple(format!("{}\0", x)))
+    }
+
+    fn descriptor(descs: Vec<DescriptorDecoded>) -> Record {
+        Record::Descriptor(DescriptorRecord::Simple(
+            descs.into_iter().map(|x| x.encode()).collect(),
+        ))
+    }
+
+    #[test]
+    fn update_info() {
+        let mut records = vec![
+            text("Forerunner 945"),
+            text("BUILD builder=ci"),
+            text("RGN 14 fw_all"),
+            text("Software Version 2.60.1"),
+            text("Fixed the GPS"),
+            Record::MainHeader(MainRecord::DefaultPartNumber),
+            Record::MainHeader(MainRecord::DefaultHWID),
+            descriptor(vec![
+                DescriptorDecoded::HWID(0x0123),
+                DescriptorDecoded::FirmwareId(0x0505),
+                DescriptorDecoded::End,
+            ]),
+            Record::End,
+        ];
+        let info = from_records(&records);
+        assert_eq!(info.product_name.as_deref(), Some("Forerunner 945"));
+        //the descriptor has no version, found in the texts
+        assert_eq!(info.software_version, Some(Version::new(2, 60)));
+        assert_eq!(info.part_number.unwrap().to_string(), "010-10037-00");
+        assert_eq!(info.hwids, vec![0x0037, 0x0123]);
+        assert_eq!(
+            info.changelog.as_deref(),
+            Some("Software Version 2.60.1\nFixed the GPS")
+        );
+
+        //the descriptors take priority over the texts
+        records[7] = descriptor(vec![
+            DescriptorDecoded::DeviceModel("Edge 1030".to_string()),
+            DescriptorDecoded::VersionSw(Version::new(3, 80)),
+            DescriptorDecoded::HWID(0x0037),
+            DescriptorDecoded::End,
+        ]);
+        let info = from_records(&records);
+        assert_eq!(info.product_name.as_deref(), Some("Edge 1030"));
+        assert_eq!(info.software_version, Some(Version::new(3, 80)));
+        assert_eq!(info.hwids, vec![0x0037]);
+        assert_eq!(info.changelog.unwrap().lines().count(), 3);
+
+        assert_eq!(from_records(&[Record::End]), UpdateInfo::default());
+
+        //the indexed file export the same information
+        for (name, data) in vectors::ALL {
+            let file: GcdFile = GcdFile::from_bytes(data.to_vec()).unwrap();
+            let records = parse_all(data).unwrap();
+            assert_eq!(updater_info(&file), from_records(&records), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! Conversion of other firmware containers into GCD records.
+//!
+//! The opposite conversion is in the module of each container, eg:
+//! [`rgn::from_gcd`](crate::rgn::from_gcd).
+
+use crate::block::FirmwareBlockBuilder;
+use crate::error::Result;
+use crate::record::main::MainRecord;
+use crate::region;
+use crate::rgn::RgnRecord;
+use crate::{Record, Version};
+
+/// Convert the RGN records into GCD records, each Region is converted into a
+/// firmware block with the firmware id of the region number, see
+/// [`region::firmware_id`] and [`FirmwareBlockBuilder`].
+///
+/// The version of the Application record, if any, is used as the firmware
+/// version. The Region delay is lost.
+pub fn from_rgn<'a, I>(records: I) -> Result<Vec<Record>>
+where
+    I: IntoIterator<Item = &'a RgnRecord>,
+{
+    let mut gcd = vec![Record::MainHeader(MainRecord::DefaultHWID)];
+    let mut version = None;
+    for record in records {
+        match record {
+            RgnRecord::Application { version: x, .. } => {
+                version = Some(Version::new_raw(*x))
+            }
+            RgnRecord::Region { id, data, .. } => {
+                let id = region::firmware_id(*id);
+                let mut block = FirmwareBlockBuilder::new(id, data.clone());
+                if let Some(version) = version {
+                    block = block.version(version);
+                }
+                gcd.extend(block.build()?);
+            }
+            _ => {}
+        }
+    }
+    gcd.push(Record::End);
+    Ok(gcd)
+}
@@ -0,0 +1,721 @@
+//! Random access to an already parsed GCD file.
+//!
+//! The file is parsed once, building a [`GcdIndex`] with the position of each
+//! record, the firmware data is not kept in memory. After that, any record or
+//! firmware block can be read directly from the file.
+//!
+//! [`GcdFile`] is `Send + Sync`, so it can be shared with an `Arc` and the
+//! firmware blocks read concurrently by multiple threads, each one with its
+//! own [`BlockReader`].
+//!
+//! [`IndexedParser`] offer the same random access directly over a
+//! `Read + Seek` reader.
+
+use byteorder::ByteOrder;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::compat::{self, CompatReport};
+use crate::error::{GcdError, Result};
+use crate::parser::Parser;
+use crate::progress::ProgressSink;
+use crate::record::firmware::transform::{Decompressor, TransformTable};
+use crate::record::firmware::FirmwareRecord;
+use crate::record::main::MainRecord;
+use crate::storage::{GcdSource, SourceReader};
+use crate::{GcdDefaultEndian, Record, RecordId, RECORD_HEADER_LEN};
+
+#[cfg(feature = "index-cache")]
+pub mod cache;
+
+/// Content of an indexed record.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum IndexedRecord {
+    /// Decoded record, kept in the index.
+    Record(Record),
+    /// Firmware chunk, the data is only read on demand.
+    FirmwareChunk { id: RecordId, len: u16 },
+}
+
+/// Position and content of a record in the file.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Offset of the record header, from the start of the file
+    pub offset: u64,
+    /// Record size, including the headers
+    pub len: u64,
+    pub record: IndexedRecord,
+}
+
+/// A firmware block, composed of a Descriptor and its firmware chunks.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct BlockEntry {
+    /// Index of the Descriptor entry
+    pub descriptor: usize,
+    /// Firmware id
+    pub id: u16,
+    /// Firmware total len
+    pub len: u32,
+    /// XOR key used to decode the firmware, 0 is no key
+    pub xor_key: u8,
+    /// Index of each firmware chunk entry
+    pub chunks: Vec<usize>,
+}
+
+/// Position of all the records of a file.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct GcdIndex {
+    pub entries: Vec<IndexEntry>,
+    pub blocks: Vec<BlockEntry>,
+}
+
+// count the bytes consumed by the parser
+struct CountRead<F> {
+    file: F,
+    pos: Rc<Cell<u64>>,
+}
+
+impl<F: Read> Read for CountRead<F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.file.read(buf)?;
+        self.pos.set(self.pos.get() + read as u64);
+        Ok(read)
+    }
+}
+
+impl GcdIndex {
+    /// Parse the whole file, indexing each record.
+    pub fn build<F, B>(file: F) -> Result<Self>
+    where
+        F: Read,
+        B: ByteOrder,
+    {
+        Self::build_inner::<F, B>(file, None)
+    }
+
+    /// Same as [`GcdIndex::build`], reporting the progress, see
+    /// [`progress`](crate::progress).
+    pub fn build_with_progress<F, B>(
+        file: F,
+        sink: Box<dyn ProgressSink>,
+        total: Option<u64>,
+    ) -> Result<Self>
+    where
+        F: Read,
+        B: ByteOrder,
+    {
+        Self::build_inner::<F, B>(file, Some((sink, total)))
+    }
+
+    fn build_inner<F, B>(
+        file: F,
+        progress: Option<(Box<dyn ProgressSink>, Option<u64>)>,
+    ) -> Result<Self>
+    where
+        F: Read,
+        B: ByteOrder,
+    {
+        let pos = Rc::new(Cell::new(0));
+        let file = CountRead {
+            file,
+            pos: Rc::clone(&pos),
+        };
+        let mut parser: Parser<_, B> = Parser::new(file)?;
+        parser.discard_chunks();
+        if let Some((sink, total)) = progress {
+            parser.set_progress(sink, total);
+        }
+        let mut index = GcdIndex::default();
+        loop {
+            let offset = pos.get();
+            let record = parser.read_record()?;
+            let len = pos.get() - offset;
+            let record = match record {
+                Record::FirmwareData(firmware) => {
+                    let block = index.blocks.last_mut().ok_or_else(|| {
+                        GcdError::invalid_data(
+                            "Firmware chunk without Descriptor",
+                        )
+                    })?;
+                    block.chunks.push(index.entries.len());
+                    //the data is discarded, the len is the bytes consumed
+                    IndexedRecord::FirmwareChunk {
+                        id: firmware.id(),
+                        len: (len - RECORD_HEADER_LEN as u64) as u16,
+                    }
+                }
+                Record::Descriptor(descriptor) => {
+                    index.blocks.push(BlockEntry {
+                        descriptor: index.entries.len(),
+                        id: descriptor.firmware_id().unwrap_or(0),
+                        len: descriptor.firmware_len().unwrap_or(0),
+                        xor_key: descriptor.xor_key().unwrap_or(0),
+                        chunks: vec![],
+                    });
+                    IndexedRecord::Record(Record::Descriptor(descriptor))
+                }
+                record => IndexedRecord::Record(record),
+            };
+            let end = record == IndexedRecord::Record(Record::End);
+            index.entries.push(IndexEntry {
+                offset,
+                len,
+                record,
+            });
+            if end {
+                return Ok(index);
+            }
+        }
+    }
+
+    // the decoded records, without the firmware chunks
+    pub(crate) fn records(&self) -> impl Iterator<Item = &Record> {
+        self.entries.iter().filter_map(|x| match &x.record {
+            IndexedRecord::Record(record) => Some(record),
+            IndexedRecord::FirmwareChunk { .. } => None,
+        })
+    }
+
+    // read the record, using `read_at` to read the firmware chunks, decoded
+    // with `transforms`
+    pub(crate) fn read_record<R>(
+        &self,
+        index: usize,
+        transforms: &TransformTable,
+        read_at: R,
+    ) -> Result<Record>
+    where
+        R: FnOnce(u64, &mut [u8]) -> std::io::Result<()>,
+    {
+        let entry = self.entries.get(index).ok_or_else(|| {
+            GcdError::invalid_input("Record index out of range")
+        })?;
+        match &entry.record {
+            IndexedRecord::Record(record) => Ok(record.clone()),
+            IndexedRecord::FirmwareChunk { id, len } => {
+                let block = self
+                    .blocks
+                    .iter()
+                    .find(|x| x.chunks.contains(&index))
+                    .ok_or_else(|| {
+                        GcdError::invalid_data(
+                            "Firmware chunk without Descriptor",
+                        )
+                    })?;
+                let mut data = vec![0; *len as usize];
+                read_at(entry.offset + RECORD_HEADER_LEN as u64, &mut data)?;
+                transforms.decode(block.id, block.xor_key, &mut data);
+                Ok(Record::FirmwareData(FirmwareRecord::new(data, *id)))
+            }
+        }
+    }
+}
+
+/// Indexed GCD file, allowing random access to records and firmware blocks.
+pub struct GcdFile<B = GcdDefaultEndian>
+where
+    B: ByteOrder,
+{
+    source: Box<dyn GcdSource + Send + Sync>,
+    index: GcdIndex,
+    transforms: TransformTable,
+    endian: PhantomData<B>,
+}
+
+impl<B: ByteOrder> GcdFile<B> {
+    /// Open and index the file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_source(File::open(path)?)
+    }
+
+    /// Index the file already in memory.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        Self::from_source(data)
+    }
+
+    /// Index the file from any storage backend.
+    pub fn from_source<S>(source: S) -> Result<Self>
+    where
+        S: GcdSource + Send + Sync + 'static,
+    {
+        let index = GcdIndex::build::<_, B>(BufReader::new(
+            SourceReader::new(&source),
+        ))?;
+        Ok(Self::with_index(source, index))
+    }
+
+    /// Same as [`GcdFile::from_source`], reporting the indexing progress,
+    /// see [`progress`](crate::progress).
+    pub fn from_source_with_progress<S>(
+        source: S,
+        sink: Box<dyn ProgressSink>,
+    ) -> Result<Self>
+    where
+        S: GcdSource + Send + Sync + 'static,
+    {
+        let total = source.len().ok();
+        let index = GcdIndex::build_with_progress::<_, B>(
+            BufReader::new(SourceReader::new(&source)),
+            sink,
+            total,
+        )?;
+        Ok(Self::with_index(source, index))
+    }
+
+    /// Use an already built index, eg: one stored by the user.
+    ///
+    /// The index is not validated, if it don't match the source, reading
+    /// the records will result in errors or invalid data.
+    pub fn with_index<S>(source: S, index: GcdIndex) -> Self
+    where
+        S: GcdSource + Send + Sync + 'static,
+    {
+        GcdFile {
+            source: Box::new(source),
+            index,
+            transforms: TransformTable::default(),
+            endian: PhantomData,
+        }
+    }
+
+    /// Set the transformations used to decode the firmware, by default only
+    /// the font rule, see [`TransformTable`].
+    pub fn set_transforms(&mut self, transforms: TransformTable) {
+        self.transforms = transforms;
+    }
+
+    pub fn index(&self) -> &GcdIndex {
+        &self.index
+    }
+
+    pub fn blocks(&self) -> &[BlockEntry] {
+        &self.index.blocks
+    }
+
+    /// The MainHeader records, in the file order.
+    pub fn main_headers(&self) -> Vec<&MainRecord> {
+        self.index
+            .records()
+            .filter_map(|x| match x {
+                Record::MainHeader(main) => Some(main),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Compare the HWIDs declared by the file with the target device, see
+    /// [`compat`].
+    pub fn is_compatible_with(&self, hwid: u16) -> CompatReport {
+        compat::check_records(self.index.records(), hwid)
+    }
+
+    /// Read the record, the firmware chunks are read from the file and
+    /// decoded.
+    pub fn record(&self, index: usize) -> Result<Record> {
+        self.index.read_record(index, &self.transforms, |offset, buf| {
+            self.source.read_exact_at(offset, buf)
+        })
+    }
+
+    /// Reader for the decoded data of the firmware block.
+    pub fn block_reader(&self, block: usize) -> Result<BlockReader<&Self>> {
+        BlockReader::new(self, block)
+    }
+
+    /// Reader for the decoded data of the firmware block, that owns a
+    /// reference to the file, so it can be sent to other threads.
+    pub fn shared_block_reader(
+        self: &Arc<Self>,
+        block: usize,
+    ) -> Result<BlockReader<Arc<Self>>> {
+        BlockReader::new(Arc::clone(self), block)
+    }
+
+    /// Read the whole decoded firmware block.
+    pub fn read_block(&self, block: usize) -> Result<Vec<u8>> {
+        let mut data = vec![];
+        self.block_reader(block)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+    /// Read the whole decoded firmware block, decompressed if the
+    /// `decompressor` recognize the data, see [`Decompressor`].
+    pub fn read_block_decompressed(
+        &self,
+        block: usize,
+        decompressor: &Decompressor,
+    ) -> Result<Vec<u8>> {
+        decompressor.decompress_or_keep(self.read_block(block)?)
+    }
+}
+
+/// Read the decoded data of a firmware block, hiding the chunk boundaries.
+///
+/// `R` is a reference to the [`GcdFile`], eg: `&GcdFile` or `Arc<GcdFile>`.
+pub struct BlockReader<R> {
+    file: R,
+    block: usize,
+    // current chunk, and the bytes already read from it
+    chunk: usize,
+    chunk_pos: u16,
+}
+
+impl<R, B> BlockReader<R>
+where
+    R: Deref<Target = GcdFile<B>>,
+    B: ByteOrder,
+{
+    fn new(file: R, block: usize) -> Result<Self> {
+        if block >= file.index.blocks.len() {
+            return Err(GcdError::invalid_input("Block index out of range"));
+        }
+        Ok(BlockReader {
+            file,
+            block,
+            chunk: 0,
+            chunk_pos: 0,
+        })
+    }
+
+    /// Firmware id of the block.
+    pub fn id(&self) -> u16 {
+        self.file.index.blocks[self.block].id
+    }
+}
+
+impl<R, B> Read for BlockReader<R>
+where
+    R: Deref<Target = GcdFile<B>>,
+    B: ByteOrder,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let file = &*self.file;
+        let block = &file.index.blocks[self.block];
+        loop {
+            let entry = match block.chunks.get(self.chunk) {
+                Some(x) => &file.index.entries[*x],
+                None => return Ok(0),
+            };
+            let chunk_len = match entry.record {
+                IndexedRecord::FirmwareChunk { len, .. } => len,
+                IndexedRecord::Record(_) => 0,
+            };
+            if self.chunk_pos >= chunk_len {
+                self.chunk += 1;
+                self.chunk_pos = 0;
+                continue;
+            }
+            let len = buf.len().min((chunk_len - self.chunk_pos) as usize);
+            let offset =
+                entry.offset + RECORD_HEADER_LEN as u64 + self.chunk_pos as u64;
+            file.source.read_exact_at(offset, &mut buf[..len])?;
+            file.transforms
+                .decode(block.id, block.xor_key, &mut buf[..len]);
+            self.chunk_pos += len as u16;
+            return Ok(len);
+        }
+    }
+}
+
+/// Random access parser over a seekable reader.
+///
+/// The whole file is parsed once when created, after that the records and
+/// firmware blocks are read directly from their position. Unlike
+/// [`GcdFile`], the reader is used directly and can be borrowed, eg:
+/// `&mut File`, but can't be shared between threads.
+pub struct IndexedParser<F, B = GcdDefaultEndian>
+where
+    F: Read + Seek,
+    B: ByteOrder,
+{
+    file: F,
+    // position of the signature in the reader
+    start: u64,
+    index: GcdIndex,
+    transforms: TransformTable,
+    endian: PhantomData<B>,
+}
+
+impl<F, B> IndexedParser<F, B>
+where
+    F: Read + Seek,
+    B: ByteOrder,
+{
+    /// Index the file, starting at the current reader position.
+    pub fn new(mut file: F) -> Result<Self> {
+        let start = file.stream_position()?;
+        let index = GcdIndex::build::<_, B>(BufReader::new(&mut file))?;
+        Ok(IndexedParser {
+            file,
+            start,
+            index,
+            transforms: TransformTable::default(),
+            endian: PhantomData,
+        })
+    }
+
+    /// Set the transformations used to decode the firmware, by default only
+    /// the font rule, see [`TransformTable`].
+    pub fn set_transforms(&mut self, transforms: TransformTable) {
+        self.transforms = transforms;
+    }
+
+    pub fn index(&self) -> &GcdIndex {
+        &self.index
+    }
+
+    /// Number of records in the file, including the End.
+    pub fn len(&self) -> usize {
+        self.index.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.entries.is_empty()
+    }
+
+    /// Read the record, the firmware chunks are read from the file and
+    /// decoded.
+    pub fn get_record(&mut self, index: usize) -> Result<Record> {
+        let (file, start) = (&mut self.file, self.start);
+        self.index.read_record(index, &self.transforms, |offset, buf| {
+            file.seek(SeekFrom::Start(start + offset))?;
+            file.read_exact(buf)
+        })
+    }
+
+    pub fn firmware_blocks(&self) -> &[BlockEntry] {
+        &self.index.blocks
+    }
+
+    /// Read the whole decoded firmware block.
+    pub fn read_firmware(&mut self, block: usize) -> Result<Vec<u8>> {
+        let block = self.index.blocks.get(block).ok_or_else(|| {
+            GcdError::invalid_input("Block index out of range")
+        })?;
+        let mut data = Vec::with_capacity(block.len as usize);
+        for chunk in block.chunks.iter() {
+            let entry = &self.index.entries[*chunk];
+            let len = match entry.record {
+                IndexedRecord::FirmwareChunk { len, .. } => len as usize,
+                IndexedRecord::Record(_) => 0,
+            };
+            let offset = self.start + entry.offset + RECORD_HEADER_LEN as u64;
+            self.file.seek(SeekFrom::Start(offset))?;
+            let pos = data.len();
+            data.resize(pos + len, 0);
+            self.file.read_exact(&mut data[pos..])?;
+        }
+        self.transforms.decode(block.id, block.xor_key, &mut data);
+        Ok(data)
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{GcdFile, GcdIndex, IndexedParser, IndexedRecord};
+    use crate::composer::Composer;
+    use crate::record::checksum::ChecksumRecord;
+    use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+    use crate::record::descriptor::DescriptorRecord;
+    use crate::record::firmware::transform::{
+        Decompressor, Transform, TransformTable,
+    };
+    use crate::record::firmware::FirmwareRecord;
+    use crate::record::main::MainRecord;
+    use crate::{Record, RecordId};
+    use byteorder::LE;
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+    use std::sync::Arc;
+    use std::thread;
+
+    const XOR_KEY: u8 = 0x5a;
+
+    pub(crate) fn firmware(id: u16) -> Vec<u8> {
+        (0..1000u16).map(|x| (x + id) as u8).collect()
+    }
+
+    // two xored firmware blocks, each splited in multiple chunks, the
+    // composer encode the firmware
+    pub(crate) fn compose() -> Vec<u8> {
+        let mut file = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut file).unwrap();
+        composer
+            .write_record(&Record::MainHeader(MainRecord::DefaultHWID))
+            .unwrap();
+        for id in [0x0505u16, 0x0506].iter() {
+            let firmware = firmware(*id);
+            composer
+                .write_record(&Record::Descriptor(DescriptorRecord::Simple(
+                    vec![
+                        DescriptorDecoded::FirmwareId(*id).encode(),
+                        DescriptorDecoded::XorKey(XOR_KEY).encode(),
+                        DescriptorDecoded::FirmwareLen(firmware.len() as u32)
+                            .encode(),
+                        DescriptorDecoded::End.encode(),
+                    ],
+                )))
+                .unwrap();
+            for chunk in firmware.chunks(300) {
+                composer
+                    .write_record(&Record::FirmwareData(FirmwareRecord::new(
+                        chunk.to_vec(),
+                        RecordId::new(*id),
+                    )))
+                    .unwrap();
+            }
+        }
+        composer
+            .write_record(&Record::Checksum(ChecksumRecord::Simple))
+            .unwrap();
+        composer.write_record(&Record::End).unwrap();
+        file
+    }
+
+    #[test]
+    fn records() {
+        let file: GcdFile = GcdFile::from_bytes(compose()).unwrap();
+        assert_eq!(file.blocks().len(), 2);
+        assert_eq!(file.blocks()[1].chunks.len(), 4);
+        let chunk = file.blocks()[1].chunks[1];
+        assert_eq!(
+            file.record(chunk).unwrap(),
+            Record::FirmwareData(FirmwareRecord::new(
+                firmware(0x0506)[300..600].to_vec(),
+                RecordId::new(0x0506)
+            ))
+        );
+        let last = file.index().entries.len() - 1;
+        assert_eq!(file.record(last).unwrap(), Record::End);
+        assert!(file.record(last + 1).is_err());
+
+        //only the block 0x0506 is "compressed"
+        let mut decompressor = Decompressor::empty();
+        decompressor.push(|data| (data[0] == 0x06).then(|| Ok(vec![0x06])));
+        let block = file.read_block_decompressed(1, &decompressor).unwrap();
+        assert_eq!(block, [0x06]);
+        let block = file.read_block_decompressed(0, &decompressor).unwrap();
+        assert_eq!(block, firmware(0x0505));
+    }
+
+    #[test]
+    fn index_chunks() {
+        let data = compose();
+        let index = GcdIndex::build::<_, LE>(&data[..]).unwrap();
+        let chunks: Vec<_> = index.blocks[0]
+            .chunks
+            .iter()
+            .map(|x| (index.entries[*x].len, &index.entries[*x].record))
+            .collect();
+        let chunk = |len| IndexedRecord::FirmwareChunk {
+            id: RecordId::new(0x0505),
+            len,
+        };
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[0], (304, &chunk(300)));
+        assert_eq!(chunks[3], (104, &chunk(100)));
+        //the chunks are still checked
+        assert!(GcdIndex::build::<_, LE>(&data[..300]).is_err());
+    }
+
+    #[test]
+    fn concurrent_readers() {
+        fn is_send_sync<T: Send + Sync>() {}
+        is_send_sync::<GcdFile>();
+
+        let file: Arc<GcdFile> =
+            Arc::new(GcdFile::from_bytes(compose()).unwrap());
+        let threads: Vec<_> = (0..file.blocks().len())
+            .map(|block| {
+                let mut reader = file.shared_block_reader(block).unwrap();
+                thread::spawn(move || {
+                    let mut data = vec![];
+                    let mut buf = [0u8; 77];
+                    loop {
+                        let read = reader.read(&mut buf).unwrap();
+                        if read == 0 {
+                            break;
+                        }
+                        data.extend_from_slice(&buf[..read]);
+                    }
+                    (reader.id(), data)
+                })
+            })
+            .collect();
+        for thread in threads {
+            let (id, data) = thread.join().unwrap();
+            assert_eq!(data, firmware(id));
+        }
+        assert_eq!(file.read_block(0).unwrap(), firmware(0x0505));
+    }
+
+    #[test]
+    fn indexed_parser() {
+        //the file don't start at the beginning of the reader
+        let mut data = vec![0xff; 16];
+        data.extend(compose());
+        let mut reader = Cursor::new(data);
+        reader.seek(SeekFrom::Start(16)).unwrap();
+
+        let mut parser: IndexedParser<_> =
+            IndexedParser::new(&mut reader).unwrap();
+        assert_eq!(parser.firmware_blocks().len(), 2);
+        assert_eq!(parser.read_firmware(1).unwrap(), firmware(0x0506));
+        let chunk = parser.firmware_blocks()[0].chunks[3];
+        assert_eq!(
+            parser.get_record(chunk).unwrap(),
+            Record::FirmwareData(FirmwareRecord::new(
+                firmware(0x0505)[900..].to_vec(),
+                RecordId::new(0x0505)
+            ))
+        );
+        let last = parser.len() - 1;
+        assert_eq!(parser.get_record(last).unwrap(), Record::End);
+        assert!(parser.get_record(last + 1).is_err());
+        assert!(parser.read_firmware(2).is_err());
+    }
+
+    #[test]
+    fn custom_transforms() {
+        let mut table = TransformTable::empty();
+        let negate = |x: &mut [u8]| x.iter_mut().for_each(|x| *x = !*x);
+        table.insert(0x0506, Transform::new(negate, negate));
+        //composed with the default table, the custom one negate 0x0506
+        let data = compose();
+        let mut file: GcdFile = GcdFile::from_bytes(data.clone()).unwrap();
+        let negated: Vec<u8> = firmware(0x0506).iter().map(|x| !x).collect();
+        assert_eq!(file.read_block(1).unwrap(), firmware(0x0506));
+
+        file.set_transforms(table.clone());
+        assert_eq!(file.read_block(0).unwrap(), firmware(0x0505));
+        assert_eq!(file.read_block(1).unwrap(), negated);
+        let chunk = file.blocks()[1].chunks[0];
+        let expected = FirmwareRecord::new(
+            negated[..300].to_vec(),
+            RecordId::new(0x0506),
+        );
+        assert_eq!(
+            file.record(chunk).unwrap(),
+            Record::FirmwareData(expected.clone())
+        );
+
+        let mut parser: IndexedParser<_> =
+            IndexedParser::new(Cursor::new(data)).unwrap();
+        parser.set_transforms(table);
+        assert_eq!(parser.read_firmware(1).unwrap(), negated);
+        assert_eq!(
+            parser.get_record(chunk).unwrap(),
+            Record::FirmwareData(expected)
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! On disk cache of [`GcdIndex`], so repeated opens of the same file don't
+//! need to parse it again.
+//!
+//! Each entry is keyed by a hash of the file path, size, modification time
+//! and the endian used to parse it. The metadata is also saved with the
+//! index and verified when the entry is loaded, any change to the file will
+//! result in a cache miss.
+
+use byteorder::ByteOrder;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use super::{GcdFile, GcdIndex};
+use crate::error::{GcdError, Result};
+use crate::record::checksum::{Checksum, Crc32};
+
+/// Metadata used to identify a file.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct CacheKey {
+    pub path: PathBuf,
+    pub len: u64,
+    /// Modification time, in nanoseconds since UNIX_EPOCH, if available
+    pub modified: Option<u128>,
+    /// Type name of the endian used to parse the file
+    pub endian: String,
+}
+
+impl CacheKey {
+    /// Read the metadata of the file.
+    pub fn new<B: ByteOrder, P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = fs::canonicalize(path)?;
+        let metadata = fs::metadata(&path)?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+            .map(|x| x.as_nanos());
+        Ok(CacheKey {
+            path,
+            len: metadata.len(),
+            modified,
+            endian: std::any::type_name::<B>().to_string(),
+        })
+    }
+
+    /// Hash of the metadata, used as the cache entry name.
+    pub fn hash(&self) -> u32 {
+        let mut hash = Crc32::default();
+        hash.update(self.path.to_string_lossy().as_bytes());
+        hash.update(&self.len.to_le_bytes());
+        hash.update(&self.modified.unwrap_or(0).to_le_bytes());
+        hash.update(self.endian.as_bytes());
+        hash.value()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    key: CacheKey,
+    index: GcdIndex,
+}
+
+/// Directory where the indexes are stored.
+#[derive(Debug, Clone)]
+pub struct IndexCache {
+    dir: PathBuf,
+}
+
+impl IndexCache {
+    /// Use the directory as cache, creating it if necessary.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(IndexCache { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn entry_path(&self, key: &CacheKey) -> PathBuf {
+        self.dir
+            .join(format!("{:08x}-{}.json", key.hash(), key.len))
+    }
+
+    /// Get the cached index, if the file was not modified.
+    pub fn load(&self, key: &CacheKey) -> Option<GcdIndex> {
+        let file = File::open(self.entry_path(key)).ok()?;
+        let entry: CacheEntry =
+            serde_json::from_reader(BufReader::new(file)).ok()?;
+        if &entry.key == key {
+            Some(entry.index)
+        } else {
+            None
+        }
+    }
+
+    /// Save the index of the file.
+    pub fn store(&self, key: &CacheKey, index: &GcdIndex) -> Result<()> {
+        //write to a temporary file, so a concurrent load never sees a
+        //partially written entry
+        let path = self.entry_path(key);
+        let tmp = path.with_extension("tmp");
+        let entry = CacheEntry {
+            key: key.clone(),
+            index: index.clone(),
+        };
+        let file = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(file, &entry)
+            .map_err(|e| GcdError::invalid_data(e.to_string()))?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Remove the cached index of the file, if any.
+    pub fn remove(&self, key: &CacheKey) -> Result<()> {
+        match fs::remove_file(self.entry_path(key)) {
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            x => Ok(x?),
+        }
+    }
+}
+
+impl<B: ByteOrder> GcdFile<B> {
+    /// Open the file, using the cached index if available, otherwise parse
+    /// the file and cache the index.
+    ///
+    /// Failing to write to the cache is not an error, the file is only
+    /// opened without caching the index.
+    pub fn open_cached<P: AsRef<Path>>(
+        path: P,
+        cache: &IndexCache,
+    ) -> Result<Self> {
+        let key = CacheKey::new::<B, _>(&path)?;
+        let file = File::open(&key.path)?;
+        let index = match cache.load(&key) {
+            Some(index) => index,
+            None => {
+                let index = GcdIndex::build::<_, B>(BufReader::new(&file))?;
+                let _ = cache.store(&key, &index);
+                index
+            }
+        };
+        Ok(GcdFile::with_index(file, index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CacheKey, IndexCache};
+    use crate::gcd_file::tests::compose;
+    use crate::{GcdDefaultEndian, GcdFile};
+    use std::fs;
+
+    #[test]
+    fn cache_hit_and_miss() {
+        let dir = std::env::temp_dir()
+            .join(format!("gcd-rs-cache-test-{}", std::process::id()));
+        let gcd = dir.join("file.gcd");
+        let cache = IndexCache::new(dir.join("cache")).unwrap();
+        fs::write(&gcd, compose()).unwrap();
+
+        let key = CacheKey::new::<GcdDefaultEndian, _>(&gcd).unwrap();
+        assert!(cache.load(&key).is_none());
+        let file: GcdFile = GcdFile::open_cached(&gcd, &cache).unwrap();
+        assert_eq!(cache.load(&key).as_ref(), Some(file.index()));
+
+        let cached: GcdFile = GcdFile::open_cached(&gcd, &cache).unwrap();
+        assert_eq!(cached.index(), file.index());
+        assert_eq!(cached.read_block(1).unwrap(), file.read_block(1).unwrap());
+
+        //a different file len is a miss
+        let mut changed = key.clone();
+        changed.len += 1;
+        assert!(cache.load(&changed).is_none());
+
+        cache.remove(&key).unwrap();
+        assert!(cache.load(&key).is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,212 @@
+//! Hexdump of raw GCD data, annotated with the record structure.
+//!
+//! The annotation is done without the [`Parser`](crate::parser::Parser)
+//! state machine, so it can be used to inspect files that the parser refuses.
+
+use byteorder::ByteOrder;
+use std::fmt::{Display, Formatter};
+use std::io::{Result, Write};
+
+use crate::record::descriptor::descriptor_data::DescriptorData;
+use crate::record::descriptor::descriptor_type::DescriptorType;
+use crate::record::descriptor::resolve_types;
+use crate::{RecordHeader, RECORD_HEADER_LEN};
+
+const LINE_LEN: usize = 16;
+
+/// Format the data in the classic hexdump format, 16 bytes per line with the
+/// offset and the ASCII representation.
+#[derive(Debug, Clone, Copy)]
+pub struct HexDump<'a> {
+    data: &'a [u8],
+    offset: u64,
+    limit: Option<usize>,
+}
+
+impl<'a> HexDump<'a> {
+    /// Dump the data, using `offset` as the address of the first byte.
+    pub const fn new(data: &'a [u8], offset: u64) -> Self {
+        HexDump {
+            data,
+            offset,
+            limit: None,
+        }
+    }
+    /// Only print the first `limit` bytes.
+    pub const fn limit(self, limit: usize) -> Self {
+        HexDump {
+            limit: Some(limit),
+            ..self
+        }
+    }
+}
+
+impl<'a> Display for HexDump<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let len = self.limit.unwrap_or(usize::MAX).min(self.data.len());
+        for (i, line) in self.data[..len].chunks(LINE_LEN).enumerate() {
+            write!(f, "{:08x} ", self.offset + (i * LINE_LEN) as u64)?;
+            for j in 0..LINE_LEN {
+                match line.get(j) {
+                    Some(byte) => write!(f, " {:02x}", byte)?,
+                    None => write!(f, "   ")?,
+                }
+            }
+            write!(f, "  |")?;
+            for byte in line {
+                let c = if byte.is_ascii_graphic() || *byte == b' ' {
+                    *byte as char
+                } else {
+                    '.'
+                };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f, "|")?;
+        }
+        if len < self.data.len() {
+            writeln!(f, "... {} bytes omitted", self.data.len() - len)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write the hexdump of the whole file, with the record boundaries, headers
+/// and descriptor fields annotated in lines starting with `#`.
+///
+/// Each record body is dumped up to `limit` bytes, if any.
+pub fn annotate<B: ByteOrder, W: Write>(
+    data: &[u8],
+    limit: Option<usize>,
+    out: &mut W,
+) -> Result<()> {
+    let dump = |data, offset| HexDump {
+        data,
+        offset,
+        limit,
+    };
+    if data.len() < 8 {
+        writeln!(out, "# 0x{:08x} Signature truncated", 0)?;
+        return write!(out, "{}", dump(data, 0));
+    }
+    writeln!(
+        out,
+        "# 0x{:08x} Signature {:?} version {}",
+        0,
+        String::from_utf8_lossy(&data[..6]),
+        B::read_u16(&data[6..8]),
+    )?;
+    write!(out, "{}", dump(&data[..8], 0))?;
+
+    let mut offset = 8;
+    let mut index = 0;
+    let mut descriptor_types = vec![];
+    while offset < data.len() {
+        let current = &data[offset..];
+        let header = match RecordHeader::from_raw::<B>(current) {
+            Ok((_, header)) => header,
+            Err(_) => {
+                writeln!(out, "# 0x{:08x} Header truncated", offset)?;
+                return write!(out, "{}", dump(current, offset as u64));
+            }
+        };
+        writeln!(
+            out,
+            "# 0x{:08x} Record {}: {:?} id 0x{:04x} len 0x{:04x}",
+            offset,
+            index,
+            header,
+            header.id(),
+            header.len(),
+        )?;
+        let record_len = RECORD_HEADER_LEN + header.len() as usize;
+        if current.len() < record_len {
+            writeln!(
+                out,
+                "# record truncated, {} bytes missing",
+                record_len - current.len()
+            )?;
+            return write!(out, "{}", dump(current, offset as u64));
+        }
+        let body = &current[RECORD_HEADER_LEN..record_len];
+        write!(out, "{}", dump(&current[..record_len], offset as u64))?;
+        match header {
+            RecordHeader::DescriptorType(_) => {
+                descriptor_types = annotate_descriptor_type::<B, W>(body, out)?;
+            }
+            RecordHeader::DescriptorData(_) => {
+                annotate_descriptor_data::<B, W>(body, &descriptor_types, out)?
+            }
+            _ => {}
+        }
+        offset += record_len;
+        index += 1;
+        if header == RecordHeader::End {
+            break;
+        }
+    }
+    if offset < data.len() {
+        writeln!(
+            out,
+            "# 0x{:08x} {} bytes after the End record",
+            offset,
+            data.len() - offset
+        )?;
+        write!(out, "{}", dump(&data[offset..], offset as u64))?;
+    }
+    Ok(())
+}
+
+fn annotate_descriptor_type<B: ByteOrder, W: Write>(
+    mut body: &[u8],
+    out: &mut W,
+) -> Result<Vec<DescriptorType>> {
+    let mut types = vec![];
+    while !body.is_empty() {
+        match DescriptorType::from_raw::<B>(body) {
+            Ok((next, desc)) => {
+                writeln!(out, "#   0x{:04x} {:?}", desc.value(), desc)?;
+                types.push(desc);
+                body = next;
+            }
+            Err(err) => {
+                writeln!(out, "#   invalid: {}", err)?;
+                break;
+            }
+        }
+    }
+    Ok(types)
+}
+
+fn annotate_descriptor_data<B: ByteOrder, W: Write>(
+    mut body: &[u8],
+    types: &[DescriptorType],
+    out: &mut W,
+) -> Result<()> {
+    let types = resolve_types::<B>(types, body);
+    let expected: usize = types.iter().map(|x| x.data_len() as usize).sum();
+    if expected != body.len() {
+        writeln!(
+            out,
+            "#   descriptor type expect {} bytes, found {}",
+            expected,
+            body.len()
+        )?;
+    }
+    for desc_type in &types {
+        let desc = match DescriptorData::from_raw::<B>(desc_type, body) {
+            Ok((next, desc)) => {
+                body = next;
+                desc
+            }
+            Err(err) => {
+                writeln!(out, "#   invalid: {}", err)?;
+                break;
+            }
+        };
+        match desc.decode() {
+            Some(decoded) => writeln!(out, "#   {:?}", decoded)?,
+            None => writeln!(out, "#   {:?}", desc)?,
+        }
+    }
+    Ok(())
+}
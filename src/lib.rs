@@ -1,16 +1,61 @@
+#[macro_use]
+mod trace;
+
+pub mod assembler;
+#[cfg(feature = "async")]
+pub mod async_io;
+pub mod batch;
+pub mod block;
+pub mod build_info;
+pub mod carve;
+pub mod codec;
+pub mod compat;
 pub mod composer;
+pub mod config;
+pub mod diff;
+#[cfg(feature = "digest")]
+pub mod digest;
+pub mod display;
+pub mod error;
+pub mod export;
+pub mod gcd;
+pub mod gcd_file;
+pub mod hexdump;
+pub mod lint;
+pub mod manifest;
+#[cfg(feature = "package")]
+pub mod package;
 pub mod parser;
+pub mod patcher;
+pub mod progress;
+pub mod rechunk;
+pub mod region;
+pub mod rgn;
+pub mod roundtrip;
+pub mod session;
+pub mod stats;
+pub mod storage;
+pub mod testing;
+pub mod trailer;
 
 use byteorder::ByteOrder;
+use error::Result;
 use serde::{Deserialize, Serialize};
-use std::fmt::{Display, Formatter};
-use std::io::{Error, ErrorKind, Result};
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter, LowerHex, UpperHex};
 
 mod version;
-pub use version::Version;
+pub use version::{Version, VersionExt};
 
 mod part_number;
-pub use part_number::PartNumber;
+pub use part_number::{PartNumber, PnExtended, PnSimple};
+
+mod device_info;
+pub use device_info::{DeviceInfo, FirmwareInfo};
+
+pub use error::GcdError;
+pub use gcd_file::{GcdFile, GcdIndex, IndexedParser};
+pub use stats::GcdStats;
 
 pub mod record;
 use record::main::MainRecord;
@@ -26,6 +71,7 @@ use record::main;
 use record::text;
 
 use self::record::text::TextRecord;
+use hexdump::HexDump;
 
 const RECORD_HEADER_LEN: usize = 4;
 
@@ -35,7 +81,9 @@ const RECORD_HEADER_LEN: usize = 4;
 /// requiring the file format to never use BigEndian
 ///
 /// Functions in this lib accept the user to specify the Endian as a future
-/// proof.
+/// proof. [`Parser::new_autodetect`] detect the Endian from the file.
+///
+/// [`Parser::new_autodetect`]: crate::parser::Parser::new_autodetect
 pub type GcdDefaultEndian = byteorder::LE;
 
 /// Known Record Headers, based on the current knowledge.
@@ -63,28 +111,29 @@ pub enum RecordHeader {
     End, //always size 0
     /// Header with Unknown ID, can be a Firmware block, if the ID is described
     /// on the Descriptor header, or Unknown/Undefined Record.
-    Unknown { id: u16, len: u16 },
+    Unknown { id: RecordId, len: u16 },
 }
 
 impl RecordHeader {
     /// Return the id from the Header
-    pub const fn id(&self) -> u16 {
+    pub const fn id(&self) -> RecordId {
         match self {
             RecordHeader::Unknown { id, .. } => *id,
-            RecordHeader::Checksum => checksum::ID,
-            RecordHeader::Filler(_) => filler::ID,
-            RecordHeader::MainHeader(_) => main::ID,
-            RecordHeader::Text(_) => text::ID,
-            RecordHeader::DescriptorType(_) => descriptor::descriptor_type::ID,
-            RecordHeader::DescriptorData(_) => descriptor::descriptor_data::ID,
-            RecordHeader::End => 0xffff,
+            RecordHeader::Checksum => RecordId::CHECKSUM,
+            RecordHeader::Filler(_) => RecordId::FILLER,
+            RecordHeader::MainHeader(_) => RecordId::MAIN_HEADER,
+            RecordHeader::Text(_) => RecordId::TEXT,
+            RecordHeader::DescriptorType(_) => RecordId::DESCRIPTOR_TYPE,
+            RecordHeader::DescriptorData(_) => RecordId::DESCRIPTOR_DATA,
+            RecordHeader::End => RecordId::END,
         }
     }
     /// Return the len from the Header, obs: not the len of the Header itself
+    #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u16 {
         match self {
             RecordHeader::Unknown { len, .. } => *len,
-            RecordHeader::Checksum => 0x0001,
+            RecordHeader::Checksum => checksum::LEN,
             RecordHeader::Filler(len) => *len,
             RecordHeader::MainHeader(len) => *len,
             RecordHeader::Text(len) => *len,
@@ -94,31 +143,28 @@ impl RecordHeader {
         }
     }
     /// Create a header using the id and len values.
-    pub const fn from_value(id: u16, len: u16) -> Self {
+    pub const fn from_value(id: RecordId, len: u16) -> Self {
         match id {
-            checksum::ID if len == 1 => RecordHeader::Checksum,
-            filler::ID => RecordHeader::Filler(len),
-            main::ID => RecordHeader::MainHeader(len),
-            text::ID => RecordHeader::Text(len),
-            descriptor::descriptor_type::ID => {
-                RecordHeader::DescriptorType(len)
+            RecordId::CHECKSUM if len == checksum::LEN => {
+                RecordHeader::Checksum
             }
-            descriptor::descriptor_data::ID => {
-                RecordHeader::DescriptorData(len)
-            }
-            0xFFFF if len == 0 => RecordHeader::End,
+            RecordId::FILLER => RecordHeader::Filler(len),
+            RecordId::MAIN_HEADER => RecordHeader::MainHeader(len),
+            RecordId::TEXT => RecordHeader::Text(len),
+            RecordId::DESCRIPTOR_TYPE => RecordHeader::DescriptorType(len),
+            RecordId::DESCRIPTOR_DATA => RecordHeader::DescriptorData(len),
+            RecordId::END if len == 0 => RecordHeader::End,
             _ => RecordHeader::Unknown { id, len },
         }
     }
     /// Create the Header using raw bytes
     pub fn from_raw<B: ByteOrder>(data: &[u8]) -> Result<(&[u8], Self)> {
         if data.len() < 4 {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
+            return Err(GcdError::invalid_data(
                 "Record hreader buffer too small",
             ));
         }
-        let id = B::read_u16(&data[..2]);
+        let id = RecordId(B::read_u16(&data[..2]));
         let len = B::read_u16(&data[2..]);
         Ok((&data[4..], RecordHeader::from_value(id, len)))
     }
@@ -128,19 +174,153 @@ impl RecordHeader {
         data: &'a mut [u8],
     ) -> Result<&'a mut [u8]> {
         if data.len() < 4 {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
+            return Err(GcdError::invalid_data(
                 "Record hreader buffer too small",
             ));
         }
-        B::write_u16(data, self.id());
+        B::write_u16(data, self.id().get());
         B::write_u16(&mut data[2..], self.len());
         Ok(&mut data[4..])
     }
 }
 
+/// Id of a record, the first u16 of the record header.
+///
+/// Firmware blocks use the firmware id, from the Descriptor, as record id.
+#[derive(
+    Debug,
+    Default,
+    PartialEq,
+    Hash,
+    Eq,
+    PartialOrd,
+    Ord,
+    Copy,
+    Clone,
+    Serialize,
+    Deserialize,
+)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct RecordId(u16);
+
+impl RecordId {
+    pub const CHECKSUM: RecordId = RecordId(checksum::ID);
+    pub const FILLER: RecordId = RecordId(filler::ID);
+    pub const MAIN_HEADER: RecordId = RecordId(main::ID);
+    pub const TEXT: RecordId = RecordId(text::ID);
+    pub const DESCRIPTOR_TYPE: RecordId =
+        RecordId(descriptor::descriptor_type::ID);
+    pub const DESCRIPTOR_DATA: RecordId =
+        RecordId(descriptor::descriptor_data::ID);
+    pub const END: RecordId = RecordId(0xffff);
+
+    pub const fn new(id: u16) -> Self {
+        RecordId(id)
+    }
+    pub const fn get(self) -> u16 {
+        self.0
+    }
+}
+
+impl Display for RecordId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#06x}", self.0)
+    }
+}
+
+impl LowerHex for RecordId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl UpperHex for RecordId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        UpperHex::fmt(&self.0, f)
+    }
+}
+
+impl From<u16> for RecordId {
+    fn from(id: u16) -> Self {
+        RecordId(id)
+    }
+}
+
+impl From<RecordId> for u16 {
+    fn from(id: RecordId) -> Self {
+        id.0
+    }
+}
+
+/// Len of a record body, always fit the u16 of the record header.
+#[derive(
+    Debug,
+    Default,
+    PartialEq,
+    Hash,
+    Eq,
+    PartialOrd,
+    Ord,
+    Copy,
+    Clone,
+    Serialize,
+    Deserialize,
+)]
+pub struct RecordLen(u16);
+
+impl RecordLen {
+    pub const MAX: RecordLen = RecordLen(u16::MAX);
+
+    /// Fail if `len` is bigger than u16::MAX.
+    pub fn new(len: usize) -> Result<Self> {
+        match u16::try_from(len) {
+            Ok(len) => Ok(RecordLen(len)),
+            Err(_) => {
+                Err(GcdError::invalid_input("Record bigger than u16::MAX"))
+            }
+        }
+    }
+    /// Len of a record composed of the parts, fail if bigger than u16::MAX.
+    pub fn sum<I: IntoIterator<Item = usize>>(lens: I) -> Result<Self> {
+        let len = lens
+            .into_iter()
+            .try_fold(0usize, |acc, x| acc.checked_add(x))
+            .ok_or_else(|| {
+                GcdError::invalid_input("Record bigger than u16::MAX")
+            })?;
+        Self::new(len)
+    }
+    pub const fn get(self) -> u16 {
+        self.0
+    }
+    pub const fn as_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl From<u16> for RecordLen {
+    fn from(len: u16) -> Self {
+        RecordLen(len)
+    }
+}
+
+impl From<RecordLen> for u16 {
+    fn from(len: RecordLen) -> Self {
+        len.0
+    }
+}
+
+impl TryFrom<usize> for RecordLen {
+    type Error = GcdError;
+
+    fn try_from(len: usize) -> Result<Self> {
+        Self::new(len)
+    }
+}
+
 /// All known Records.
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Record {
     Checksum(ChecksumRecord),
     Filler(FillerRecord),
@@ -149,6 +329,22 @@ pub enum Record {
     Descriptor(DescriptorRecord),
     FirmwareData(FirmwareRecord),
     End,
+    /// Record with an id this crate don't understand, returned by a lenient
+    /// parser, see [`Record::hexdump`].
+    Unknown {
+        id: RecordId,
+        data: Vec<u8>,
+    },
+}
+
+impl Record {
+    /// Hexdump of the body of a [`Record::Unknown`], see [`HexDump`].
+    pub fn hexdump(&self) -> Option<HexDump<'_>> {
+        match self {
+            Record::Unknown { data, .. } => Some(HexDump::new(data, 0)),
+            _ => None,
+        }
+    }
 }
 
 impl Display for Record {
@@ -161,6 +357,9 @@ impl Display for Record {
             Record::Descriptor(x) => write!(f, "{}", x),
             Record::FirmwareData(x) => write!(f, "{}", x),
             Record::End => write!(f, "Record:End"),
+            Record::Unknown { id, data } => {
+                write!(f, "Record:Unknown id: {:#06x}, len: {}", id, data.len())
+            }
         }
     }
 }
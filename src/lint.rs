@@ -0,0 +1,468 @@
+//! Check a sequence of records against the conventions of the known GCD
+//! files.
+//!
+//! The parser only refuse files that can't be interpreted, but a file can be
+//! valid and still differ from what the factory files look like. Each
+//! convention is a [`LintRule`], identified by name, that can be individually
+//! enabled or disabled on the [`Linter`]. Users can implement their own rules,
+//! eg: "chunk size must be 0x1000".
+//!
+//! ```
+//! use gcd_rs::lint::{LintRule, Linter};
+//! use gcd_rs::Record;
+//!
+//! struct ChunkSize;
+//! impl LintRule for ChunkSize {
+//!     fn name(&self) -> &str {
+//!         "chunk-size"
+//!     }
+//!     fn check(&mut self, _index: usize, record: &Record) -> Option<String> {
+//!         match record {
+//!             Record::FirmwareData(x) if x.len() > 0x1000 => {
+//!                 Some(format!("Chunk with {:#x} bytes", x.len()))
+//!             }
+//!             _ => None,
+//!         }
+//!     }
+//! }
+//!
+//! let mut linter = Linter::default();
+//! linter.add_rule(ChunkSize);
+//! linter.set_enabled("end-record", false);
+//! let warnings = linter.lint(&[Record::End]);
+//! assert!(warnings.iter().any(|x| x.rule == "main-header"));
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+use crate::Record;
+
+/// A convention that is not followed, found by a [`LintRule`].
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+pub struct Warning {
+    /// Name of the rule that generated the warning.
+    pub rule: String,
+    /// Index of the record, None if the warning is about the whole file.
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.index {
+            Some(index) => {
+                write!(f, "{}: record {}: {}", self.rule, index, self.message)
+            }
+            None => write!(f, "{}: {}", self.rule, self.message),
+        }
+    }
+}
+
+/// A single convention check, receive each record in the file order.
+pub trait LintRule {
+    /// Unique name, used to enable/disable the rule.
+    fn name(&self) -> &str;
+    /// Check the record, return a message if the convention is not followed.
+    fn check(&mut self, index: usize, record: &Record) -> Option<String>;
+    /// Called after the last record, for conventions about the whole file.
+    fn finish(&mut self) -> Option<String> {
+        None
+    }
+}
+
+struct RuleEntry {
+    rule: Box<dyn LintRule>,
+    enabled: bool,
+}
+
+/// Run a set of [`LintRule`] over the records.
+///
+/// `Linter::default()` include all the built-in rules, enabled.
+pub struct Linter {
+    rules: Vec<RuleEntry>,
+    index: usize,
+    warnings: Vec<Warning>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        let mut linter = Linter::empty();
+        linter.add_rule(MainHeaderRule::default());
+        linter.add_rule(EndRecordRule::default());
+        linter.add_rule(FirmwareDescriptorRule);
+        linter.add_rule(FirmwareLenRule::default());
+        linter.add_rule(ChecksumBeforeEndRule::default());
+        linter.add_rule(DescriptorVersionRule);
+        linter
+    }
+}
+
+impl Linter {
+    /// Linter without any rule.
+    pub fn empty() -> Self {
+        Linter {
+            rules: vec![],
+            index: 0,
+            warnings: vec![],
+        }
+    }
+    /// Add a rule, enabled. A rule with the same name is replaced.
+    pub fn add_rule<R: LintRule + 'static>(&mut self, rule: R) {
+        let entry = RuleEntry {
+            rule: Box::new(rule),
+            enabled: true,
+        };
+        match self.position(entry.rule.name()) {
+            Some(pos) => self.rules[pos] = entry,
+            None => self.rules.push(entry),
+        }
+    }
+    /// Enable/disable the rule, return false if the rule don't exist.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.position(name) {
+            Some(pos) => {
+                self.rules[pos].enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+    /// List the rules names and if they are enabled.
+    pub fn rules(&self) -> impl Iterator<Item = (&str, bool)> {
+        self.rules.iter().map(|x| (x.rule.name(), x.enabled))
+    }
+    /// Check the next record.
+    pub fn check(&mut self, record: &Record) {
+        let index = self.index;
+        for entry in self.rules.iter_mut().filter(|x| x.enabled) {
+            if let Some(message) = entry.rule.check(index, record) {
+                self.warnings.push(Warning {
+                    rule: entry.rule.name().to_string(),
+                    index: Some(index),
+                    message,
+                });
+            }
+        }
+        self.index += 1;
+    }
+    /// Warnings found so far.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+    /// Remove and return the warnings found so far.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+    /// Finish the file, returning all the warnings.
+    pub fn finish(&mut self) -> Vec<Warning> {
+        for entry in self.rules.iter_mut().filter(|x| x.enabled) {
+            if let Some(message) = entry.rule.finish() {
+                self.warnings.push(Warning {
+                    rule: entry.rule.name().to_string(),
+                    index: None,
+                    message,
+                });
+            }
+        }
+        self.index = 0;
+        std::mem::take(&mut self.warnings)
+    }
+    /// Check all the records of a file.
+    pub fn lint<'a, I>(&mut self, records: I) -> Vec<Warning>
+    where
+        I: IntoIterator<Item = &'a Record>,
+    {
+        records.into_iter().for_each(|x| self.check(x));
+        self.finish()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.rules.iter().position(|x| x.rule.name() == name)
+    }
+}
+
+/// "main-header": The MainHeader is present before any firmware block, with
+/// at most one HWID and one PartNumber record.
+#[derive(Debug, Default)]
+pub struct MainHeaderRule {
+    found: bool,
+    reported: bool,
+    hwid: bool,
+    part_number: bool,
+}
+
+impl LintRule for MainHeaderRule {
+    fn name(&self) -> &str {
+        "main-header"
+    }
+    fn check(&mut self, _index: usize, record: &Record) -> Option<String> {
+        match record {
+            Record::MainHeader(main) => {
+                self.found = true;
+                let seen = match main.hwid() {
+                    Some(_) => &mut self.hwid,
+                    None => &mut self.part_number,
+                };
+                std::mem::replace(seen, true)
+                    .then(|| "Multiple MainHeader records".to_string())
+            }
+            Record::Descriptor(_) | Record::End
+                if !self.found && !self.reported =>
+            {
+                self.reported = true;
+                Some("Record before the MainHeader".to_string())
+            }
+            _ => None,
+        }
+    }
+    fn finish(&mut self) -> Option<String> {
+        let found = self.found || self.reported;
+        *self = Self::default();
+        (!found).then(|| "MainHeader record missing".to_string())
+    }
+}
+
+/// "end-record": The file finish with a single End record.
+#[derive(Debug, Default)]
+pub struct EndRecordRule {
+    found: bool,
+}
+
+impl LintRule for EndRecordRule {
+    fn name(&self) -> &str {
+        "end-record"
+    }
+    fn check(&mut self, _index: usize, record: &Record) -> Option<String> {
+        if self.found {
+            return Some("Record after the End record".to_string());
+        }
+        self.found = matches!(record, Record::End);
+        None
+    }
+    fn finish(&mut self) -> Option<String> {
+        let found = self.found;
+        *self = Self::default();
+        (!found).then(|| "End record missing".to_string())
+    }
+}
+
+/// "firmware-descriptor": Each Descriptor declare the firmware id and len.
+#[derive(Debug, Default)]
+pub struct FirmwareDescriptorRule;
+
+impl LintRule for FirmwareDescriptorRule {
+    fn name(&self) -> &str {
+        "firmware-descriptor"
+    }
+    fn check(&mut self, _index: usize, record: &Record) -> Option<String> {
+        let descriptor = match record {
+            Record::Descriptor(x) => x,
+            _ => return None,
+        };
+        let decoded: Vec<_> = descriptor.iter().map(|x| x.decode()).collect();
+        let id = decoded
+            .iter()
+            .any(|x| matches!(x, Some(DescriptorDecoded::FirmwareId(_))));
+        let len = decoded.iter().any(|x| {
+            matches!(
+                x,
+                Some(DescriptorDecoded::FirmwareLen(_))
+                    | Some(DescriptorDecoded::Firmware2000P1Len(_))
+                    | Some(DescriptorDecoded::Firmware2000P2Len(_))
+                    | Some(DescriptorDecoded::Firmware2000P3Len(_))
+            )
+        });
+        match (id, len) {
+            (true, true) => None,
+            (false, _) => Some("Descriptor without Firmware Id".to_string()),
+            (_, false) => Some("Descriptor without Firmware Len".to_string()),
+        }
+    }
+}
+
+/// "firmware-len": The firmware chunks match the id and len from the
+/// Descriptor.
+#[derive(Debug, Default)]
+pub struct FirmwareLenRule {
+    // id, declared len and received len of the current firmware
+    current: Option<(u16, u32, u64)>,
+}
+
+impl FirmwareLenRule {
+    fn end_firmware(&mut self) -> Option<String> {
+        match self.current.take() {
+            Some((id, expected, received)) if expected as u64 != received => {
+                Some(format!(
+                    "Firmware {:#x} declared {} bytes, but has {}",
+                    id, expected, received
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl LintRule for FirmwareLenRule {
+    fn name(&self) -> &str {
+        "firmware-len"
+    }
+    fn check(&mut self, _index: usize, record: &Record) -> Option<String> {
+        match record {
+            Record::Descriptor(descriptor) => {
+                let result = self.end_firmware();
+                let mut id = None;
+                let mut len = None;
+                for desc in descriptor.iter() {
+                    match desc.decode() {
+                        Some(DescriptorDecoded::FirmwareId(x)) => id = Some(x),
+                        Some(DescriptorDecoded::FirmwareLen(x)) => {
+                            len = Some(x)
+                        }
+                        _ => {}
+                    }
+                }
+                if let (Some(id), Some(len)) = (id, len) {
+                    self.current = Some((id, len, 0));
+                }
+                result
+            }
+            Record::FirmwareData(firmware) => match &mut self.current {
+                Some((id, _, _)) if *id != firmware.id().get() => Some(format!(
+                    "Firmware chunk id {:#x}, but the Descriptor declared {:#x}",
+                    firmware.id(),
+                    id
+                )),
+                Some((_, _, received)) => {
+                    *received += firmware.len() as u64;
+                    None
+                }
+                None => Some("Firmware chunk without Descriptor".to_string()),
+            },
+            Record::End => self.end_firmware(),
+            _ => None,
+        }
+    }
+    fn finish(&mut self) -> Option<String> {
+        self.end_firmware()
+    }
+}
+
+/// "checksum-before-end": A Checksum record is written right before the End.
+#[derive(Debug, Default)]
+pub struct ChecksumBeforeEndRule {
+    last_checksum: bool,
+}
+
+impl LintRule for ChecksumBeforeEndRule {
+    fn name(&self) -> &str {
+        "checksum-before-end"
+    }
+    fn check(&mut self, _index: usize, record: &Record) -> Option<String> {
+        let last_checksum = self.last_checksum;
+        self.last_checksum = matches!(record, Record::Checksum(_));
+        match record {
+            Record::End if !last_checksum => {
+                Some("End record without a Checksum before it".to_string())
+            }
+            _ => None,
+        }
+    }
+    fn finish(&mut self) -> Option<String> {
+        self.last_checksum = false;
+        None
+    }
+}
+
+/// "descriptor-sw-version": Each Descriptor declare the software version.
+#[derive(Debug, Default)]
+pub struct DescriptorVersionRule;
+
+impl LintRule for DescriptorVersionRule {
+    fn name(&self) -> &str {
+        "descriptor-sw-version"
+    }
+    fn check(&mut self, _index: usize, record: &Record) -> Option<String> {
+        match record {
+            Record::Descriptor(descriptor)
+                if !descriptor.iter().any(|x| {
+                    matches!(x.decode(), Some(DescriptorDecoded::VersionSw(_)))
+                }) =>
+            {
+                Some("Descriptor without Software Version".to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lint::Linter;
+    use crate::record::checksum::ChecksumRecord;
+    use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+    use crate::record::descriptor::DescriptorRecord;
+    use crate::record::firmware::FirmwareRecord;
+    use crate::record::main::MainRecord;
+    use crate::{Record, RecordId, Version};
+
+    fn records(firmware_len: u32) -> Vec<Record> {
+        vec![
+            Record::MainHeader(MainRecord::DefaultHWID),
+            Record::Descriptor(DescriptorRecord::Simple(vec![
+                DescriptorDecoded::FirmwareId(0x0505).encode(),
+                DescriptorDecoded::FirmwareLen(firmware_len).encode(),
+                DescriptorDecoded::VersionSw(Version::new(1, 0)).encode(),
+                DescriptorDecoded::End.encode(),
+            ])),
+            Record::FirmwareData(FirmwareRecord::new(
+                vec![0; 10],
+                RecordId::new(0x0505),
+            )),
+            Record::Checksum(ChecksumRecord::Simple),
+            Record::End,
+        ]
+    }
+
+    #[test]
+    fn lint_valid() {
+        let warnings = Linter::default().lint(&records(10));
+        assert_eq!(warnings, vec![]);
+    }
+
+    #[test]
+    fn lint_firmware_len() {
+        let mut linter = Linter::default();
+        let warnings = linter.lint(&records(20));
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].rule, "firmware-len");
+        assert_eq!(warnings[0].index, Some(4));
+
+        assert!(linter.set_enabled("firmware-len", false));
+        assert_eq!(linter.lint(&records(20)), vec![]);
+    }
+
+    #[test]
+    fn lint_missing_records() {
+        let warnings = Linter::default().lint(&records(10)[1..3]);
+        let rules: Vec<_> = warnings.iter().map(|x| x.rule.as_str()).collect();
+        assert_eq!(rules, vec!["main-header", "end-record"]);
+    }
+
+    #[test]
+    fn lint_main_headers() {
+        //a PartNumber and a HWID are allowed
+        let mut records = records(10);
+        let part_number = Record::MainHeader(MainRecord::DefaultPartNumber);
+        records.insert(0, part_number.clone());
+        assert_eq!(Linter::default().lint(&records), vec![]);
+
+        records.insert(2, Record::MainHeader(MainRecord::HWID(0x0036)));
+        records.insert(0, part_number);
+        let warnings = Linter::default().lint(&records);
+        let found: Vec<_> = warnings.iter().map(|x| x.index).collect();
+        assert_eq!(found, vec![Some(1), Some(3)]);
+        assert!(warnings.iter().all(|x| x.rule == "main-header"));
+    }
+}
@@ -0,0 +1,201 @@
+//! Editable representation of a GCD file.
+//!
+//! The records are kept in a serializable list, except the firmware data,
+//! that is stored in external files, one for each firmware block. After
+//! editing the files, the GCD file can be composed again with [`create`].
+
+use byteorder::ByteOrder;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::composer::Composer;
+use crate::error::{GcdError, Result};
+use crate::parser::Parser;
+use crate::record::firmware::{self, FirmwareRecord};
+use crate::{Record, RecordId};
+
+/// Firmware chunk stored in an external file.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ExtFirmware {
+    /// File with the decoded firmware, relative to the manifest directory.
+    pub filename: String,
+    pub id: u16,
+    pub offset: u64,
+    pub lenght: u64,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum ManifestRecord {
+    Internal(Record),
+    External(ExtFirmware),
+}
+
+impl From<Record> for ManifestRecord {
+    fn from(x: Record) -> Self {
+        ManifestRecord::Internal(x)
+    }
+}
+
+/// Read all the records, writing the firmware blocks to files in `dir`.
+///
+/// The files are named with the block number and firmware id, the extension
+/// is from the payload type, see [`firmware::classify`].
+pub fn extract<F, B>(
+    parser: &mut Parser<F, B>,
+    dir: &Path,
+) -> Result<Vec<ManifestRecord>>
+where
+    F: Read,
+    B: ByteOrder,
+{
+    let mut records = vec![];
+    //id of the current block, the file is created with the first chunk
+    let mut block_id = None;
+    //file of the current block, and the next chunk
+    let mut firmware_out: Option<(File, ExtFirmware)> = None;
+    //some files have multiple firmware with the same id, so also have a
+    //counter to create a unique filename
+    let mut fw_num = 0;
+    loop {
+        match parser.read_record()? {
+            Record::Descriptor(descriptor) => {
+                block_id = Some(
+                    descriptor
+                        .firmware_id()
+                        .ok_or(GcdError::MissingDescriptor("Firmware Id"))?,
+                );
+                //close the last file, if it exists
+                firmware_out = None;
+                records.push(Record::Descriptor(descriptor).into());
+            }
+            Record::FirmwareData(chunk) => {
+                if firmware_out.is_none() {
+                    let id = block_id.ok_or_else(|| {
+                        GcdError::invalid_data(
+                            "Firmware chunk without Descriptor",
+                        )
+                    })?;
+                    let kind = firmware::classify(id, chunk.data());
+                    let filename = format!(
+                        "fw{}_0x{:04x}.{}",
+                        fw_num,
+                        id,
+                        kind.extension()
+                    );
+                    let file = File::create(dir.join(&filename))?;
+                    let ext = ExtFirmware {
+                        filename,
+                        id,
+                        offset: 0,
+                        lenght: 0,
+                    };
+                    firmware_out = Some((file, ext));
+                    fw_num += 1;
+                }
+                let (file, ext) = firmware_out.as_mut().unwrap();
+                file.write_all(chunk.data())?;
+                ext.lenght = chunk.len() as u64;
+                records.push(ManifestRecord::External(ext.clone()));
+                ext.offset += ext.lenght;
+            }
+            Record::End => {
+                records.push(Record::End.into());
+                return Ok(records);
+            }
+            record => records.push(record.into()),
+        }
+    }
+}
+
+/// Compose the records, reading the firmware files from `dir`.
+pub fn create<W, B>(
+    records: &[ManifestRecord],
+    dir: &Path,
+    composer: &mut Composer<W, B>,
+) -> Result<()>
+where
+    W: Write,
+    B: ByteOrder,
+{
+    //keep the last firmware file open
+    let mut current: Option<(&str, File)> = None;
+    for record in records {
+        let ext = match record {
+            ManifestRecord::Internal(record) => {
+                composer.write_record(record)?;
+                continue;
+            }
+            ManifestRecord::External(ext) => ext,
+        };
+        if ext.lenght > u16::MAX as u64 {
+            return Err(GcdError::invalid_input(
+                "Firmware chunk bigger than u16::MAX",
+            ));
+        }
+        let file = match &mut current {
+            Some((name, file)) if *name == ext.filename => file,
+            current => {
+                let file = File::open(dir.join(&ext.filename))?;
+                &mut current.insert((&ext.filename, file)).1
+            }
+        };
+        let mut data = vec![0; ext.lenght as usize];
+        file.seek(SeekFrom::Start(ext.offset))?;
+        file.read_exact(&mut data)?;
+        composer.write_record(&Record::FirmwareData(FirmwareRecord::new(
+            data,
+            RecordId::new(ext.id),
+        )))?;
+    }
+    Ok(())
+}
+
+/// Remove the firmware files referenced by the records.
+pub fn remove_files(records: &[ManifestRecord], dir: &Path) -> Result<()> {
+    let mut names: Vec<_> = records
+        .iter()
+        .filter_map(|x| match x {
+            ManifestRecord::External(ext) => Some(&ext.filename),
+            ManifestRecord::Internal(_) => None,
+        })
+        .collect();
+    names.dedup();
+    for name in names {
+        fs::remove_file(dir.join(name))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create, extract, remove_files, ManifestRecord};
+    use crate::composer::Composer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn extract_create() {
+        let file = crate::gcd_file::tests::compose();
+        let dir = std::env::temp_dir()
+            .join(format!("gcd-rs-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        let records = extract(&mut parser, &dir).unwrap();
+        let external = records
+            .iter()
+            .filter(|x| matches!(x, ManifestRecord::External(_)))
+            .count();
+        assert_eq!(external, 8);
+
+        let mut output = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut output).unwrap();
+        create(&records, &dir, &mut composer).unwrap();
+        drop(composer);
+        assert_eq!(output, file);
+
+        remove_files(&records, &dir).unwrap();
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -0,0 +1,168 @@
+//! Locate GCD files inside Garmin Express/WebUpdater packages.
+//!
+//! The updater packages are zip files, possibly with an executable stub
+//! before the archive. The GCD payloads are found by the signature in each
+//! archive entry, and only the ones that can be parsed until the End record
+//! are returned.
+//!
+//! Data that is not a zip file, eg: an installer executable, is scanned as a
+//! single blob.
+
+use byteorder::ByteOrder;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::error::{GcdError, Result};
+use crate::parser::Parser;
+use crate::{GcdFile, GcdIndex};
+
+const SIGNATURE: &[u8] = b"GARMIN";
+
+/// A GCD file found inside a package.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PackageEntry {
+    /// Name of the archive entry that contains the file
+    pub name: String,
+    /// Offset of the GCD file inside the archive entry
+    pub offset: u64,
+    data: Vec<u8>,
+}
+
+impl PackageEntry {
+    /// The whole GCD file.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Parse the GCD file.
+    pub fn parser<B: ByteOrder>(&self) -> Result<Parser<&[u8], B>> {
+        Parser::new(self.data.as_slice())
+    }
+
+    /// Index the GCD file, for random access.
+    pub fn gcd_file<B: ByteOrder>(&self) -> Result<GcdFile<B>> {
+        GcdFile::from_bytes(self.data.clone())
+    }
+}
+
+/// Find all the valid GCD files in the data, by the signature.
+pub fn find_gcds<B: ByteOrder>(name: &str, data: &[u8]) -> Vec<PackageEntry> {
+    let mut entries = vec![];
+    let mut start = 0;
+    while let Some(pos) = data[start..]
+        .windows(SIGNATURE.len())
+        .position(|x| x == SIGNATURE)
+    {
+        let offset = start + pos;
+        //the index offsets are relative to the signature, the last record is
+        //the End
+        let len = GcdIndex::build::<_, B>(&data[offset..])
+            .ok()
+            .and_then(|index| index.entries.last().map(|x| x.offset + x.len));
+        match len {
+            Some(len) => {
+                let len = len as usize;
+                entries.push(PackageEntry {
+                    name: name.to_string(),
+                    offset: offset as u64,
+                    data: data[offset..offset + len].to_vec(),
+                });
+                start = offset + len;
+            }
+            None => start = offset + 1,
+        }
+    }
+    entries
+}
+
+/// Find all the GCD files inside the package, that can be a zip file or any
+/// other blob.
+pub fn scan_package<R, B>(mut reader: R) -> Result<Vec<PackageEntry>>
+where
+    R: Read + Seek,
+    B: ByteOrder,
+{
+    let mut archive = match zip::ZipArchive::new(&mut reader) {
+        Ok(archive) => archive,
+        Err(zip::result::ZipError::Io(e)) => return Err(e.into()),
+        //not a zip file, scan the raw data
+        Err(_) => {
+            let mut data = vec![];
+            reader.seek(SeekFrom::Start(0))?;
+            reader.read_to_end(&mut data)?;
+            return Ok(find_gcds::<B>("", &data));
+        }
+    };
+    let mut entries = vec![];
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| match e {
+            zip::result::ZipError::Io(e) => e.into(),
+            e => GcdError::invalid_data(e.to_string()),
+        })?;
+        if file.is_dir() {
+            continue;
+        }
+        let name = file.name().to_string();
+        let mut data = vec![];
+        file.read_to_end(&mut data)?;
+        entries.extend(find_gcds::<B>(&name, &data));
+    }
+    Ok(entries)
+}
+
+/// Open the package file and find all the GCD files inside it.
+pub fn open_package<P, B>(path: P) -> Result<Vec<PackageEntry>>
+where
+    P: AsRef<Path>,
+    B: ByteOrder,
+{
+    scan_package::<_, B>(BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_gcds, scan_package};
+    use crate::testing::vectors;
+    use byteorder::LE;
+    use std::io::{Cursor, Write};
+
+    #[test]
+    fn blob() {
+        let mut data = b"MZ stub GARMIN not a gcd".to_vec();
+        data.extend_from_slice(vectors::SINGLE_BLOCK);
+        data.extend_from_slice(b"padding");
+        data.extend_from_slice(vectors::MULTI_BLOCK);
+
+        let entries = find_gcds::<LE>("blob", &data);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].offset, 24);
+        assert_eq!(entries[0].data(), vectors::SINGLE_BLOCK);
+        assert_eq!(entries[1].data(), vectors::MULTI_BLOCK);
+        assert_eq!(entries[1].gcd_file::<LE>().unwrap().blocks().len(), 2);
+
+        let entries = scan_package::<_, LE>(Cursor::new(&data)).unwrap();
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn zip() {
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        let options = zip::write::FileOptions::default();
+        zip.start_file("readme.txt", options).unwrap();
+        zip.write_all(b"nothing here").unwrap();
+        zip.start_file("Garmin/GUPDATE.GCD", options).unwrap();
+        zip.write_all(vectors::FILLERS_CHECKSUMS).unwrap();
+        let data = zip.finish().unwrap().into_inner();
+
+        let entries = scan_package::<_, LE>(Cursor::new(data)).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "Garmin/GUPDATE.GCD");
+        assert_eq!(entries[0].offset, 0);
+        assert_eq!(entries[0].data(), vectors::FILLERS_CHECKSUMS);
+    }
+}
@@ -1,16 +1,28 @@
 //! Parse an existing GCD file.
 
-use byteorder::ByteOrder;
-use std::io::{Error, ErrorKind, Read, Result};
+use byteorder::{ByteOrder, BE, LE};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind, Read, Write};
+use std::path::Path;
 
-use crate::record::checksum::ChecksumRecord;
-use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+use crate::block::{self, FirmwareBlock};
+use crate::config::ParserConfig;
+use crate::error::{GcdError, Result};
+use crate::progress::{ProgressSink, ProgressTracker};
+use crate::record::checksum::{Checksum, ChecksumRecord, Checksums};
+use crate::record::cow::CowRecord;
 use crate::record::descriptor::{DescriptorRecord, DescriptorTypeRecord};
 use crate::record::filler::FillerRecord;
-use crate::record::firmware::FirmwareRecord;
+use crate::record::firmware::transform::{Decompressor, TransformTable};
+use crate::record::firmware::{FirmwareRecord, MultiPartFirmware};
 use crate::record::main::MainRecord;
-use crate::record::text::TextRecord;
-use crate::{GcdDefaultEndian, Record, RecordHeader};
+use crate::record::text::{TextEncoding, TextRecord};
+use crate::RECORD_HEADER_LEN;
+use crate::{GcdDefaultEndian, Record, RecordHeader, RecordId};
 
 use std::marker::PhantomData;
 
@@ -23,19 +35,33 @@ use std::marker::PhantomData;
 // E  => EndRecord
 //
 // File: C* M C* (DT DD FD* C*)+ E
-#[derive(Debug, PartialEq, Copy, Clone)]
-enum ParseState {
+/// Position of the parser in the file structure, see [`Parser::state`].
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum ParsePhase {
+    /// Before the MainHeader, only text records are allowed.
     TextGlobal,
+    /// After the first MainHeader, before the first firmware block.
     Main,
+    /// After a DescriptorType record, the DescriptorData is next.
     DescriptorType,
+    /// After the Descriptor, before the firmware chunks.
     DescriptorData,
+    /// After the first firmware chunk of the block.
     FirmwareData,
+    /// After the End record.
     End,
 }
 
 struct ReadCheckSum<F> {
     file: F,
-    sum: u8,
+    checksums: Checksums,
+    len: u64,
+    // copy of the bytes read, if enabled
+    raw: Option<Vec<u8>>,
+    // checksums at the mark and the bytes read after it, see rewind
+    mark: Option<(Checksums, Vec<u8>)>,
+    // bytes read from the file, but not yet consumed
+    ahead: VecDeque<u8>,
 }
 
 impl<F> Read for ReadCheckSum<F>
@@ -43,51 +69,252 @@ where
     F: std::io::Read,
 {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let read = self.file.read(buf)?;
-        for byte in buf[0..read].iter() {
-            self.sum = self.sum.wrapping_add(*byte);
-        }
+        let read = match self.ahead.is_empty() {
+            true => self.file.read(buf)?,
+            false => self.ahead.read(buf)?,
+        };
+        self.consumed(&buf[..read]);
         Ok(read)
     }
 }
 
+impl<'a> ReadCheckSum<&'a [u8]> {
+    // the next `len` bytes borrowed from the file, the bytes ahead are not
+    // considered, only used if there is none
+    fn read_borrowed(&mut self, len: usize) -> std::io::Result<&'a [u8]> {
+        if self.file.len() < len {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        let (data, rest) = self.file.split_at(len);
+        self.file = rest;
+        self.consumed(data);
+        Ok(data)
+    }
+}
+
 impl<F> ReadCheckSum<F>
 where
     F: std::io::Read,
 {
     fn new(file: F) -> Self {
-        ReadCheckSum { file, sum: 0 }
+        ReadCheckSum {
+            file,
+            checksums: Checksums::default(),
+            len: 0,
+            raw: None,
+            mark: None,
+            ahead: VecDeque::new(),
+        }
+    }
+
+    // the next `len` bytes, without consuming it, less if the file ends
+    fn peek(&mut self, len: usize) -> std::io::Result<&[u8]> {
+        let mut buf = [0; 0x1000];
+        while self.ahead.len() < len {
+            let buf_len = buf.len().min(len - self.ahead.len());
+            match self.file.read(&mut buf[..buf_len]) {
+                Ok(0) => break,
+                Ok(read) => self.ahead.extend(&buf[..read]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let ahead = self.ahead.make_contiguous();
+        Ok(&ahead[..len.min(ahead.len())])
+    }
+
+    // keep the bytes read from now on, to be read again by rewind
+    fn mark(&mut self) {
+        self.mark = Some((self.checksums, vec![]));
+    }
+
+    // go back to the mark, the bytes are read again
+    fn rewind(&mut self) {
+        if let Some((checksums, data)) = self.mark.take() {
+            self.checksums = checksums;
+            self.len -= data.len() as u64;
+            for byte in data.iter().rev() {
+                self.ahead.push_front(*byte);
+            }
+        }
     }
 }
 
 impl<F> ReadCheckSum<F> {
-    const fn sum(&self) -> u8 {
-        self.sum
+    fn sum(&self) -> u8 {
+        self.checksums.sum.value()
+    }
+
+    // account the bytes read
+    fn consumed(&mut self, data: &[u8]) {
+        self.checksums.update(data);
+        self.len += data.len() as u64;
+        if let Some(raw) = self.raw.as_mut() {
+            raw.extend_from_slice(data);
+        }
+        if let Some((_, mark)) = self.mark.as_mut() {
+            mark.extend_from_slice(data);
+        }
     }
 }
 
 // information extracted from Descriptor used to process the firmware chunk
-#[derive(Default)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
 struct FirmwareData {
     // id of the firmware record
-    id: u16,
+    id: RecordId,
     // xor key used to decode the firmware, 0 is no key
     xor_key: u8,
     // firmware total lenght
     lenght: u32,
     // firmware len that need to be consumend before the end
     lenght_left: u32,
+    // the block have no FirmwareLen, lenght and lenght_left are not used
+    unknown_len: bool,
+    // the block is split in parts, the chunks are emitted as parts
+    parts: Option<MultiPartFirmware>,
+    // part of the current chunk
+    chunk_part: Option<u8>,
+    // bytes from the current chunk not yet consumed by a FirmwareReader
+    chunk_left: u16,
+    // firmware len of all the blocks, declared or received if bigger
+    total_len: u64,
+}
+
+// parser state before a record, see Parser::checkpoint
+struct Checkpoint {
+    phase: ParsePhase,
+    descriptor_type: DescriptorTypeRecord,
+    firmware: FirmwareData,
+    main_headers: u8,
+    warnings: usize,
+}
+
+/// A problem found by a lenient parser, see [`ParserConfig::strict`].
+#[derive(Debug)]
+pub struct ParseWarning {
+    /// Offset of the record in the file.
+    pub offset: u64,
+    /// The error a strict parser would return.
+    pub error: GcdError,
+}
+
+impl Display for ParseWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "offset {:#x}: {}", self.offset, self.error)
+    }
+}
+
+/// Data skipped by a parser in recovery mode, see [`ParserConfig::recover`].
+///
+/// The error that caused the skip is in the [`Parser::warnings`], with the
+/// same offset.
+#[derive(
+    Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize,
+)]
+pub struct SkippedSpan {
+    /// Offset of the invalid record header.
+    pub offset: u64,
+    /// Number of bytes skipped, until the next valid record header.
+    pub len: u64,
 }
 
+/// Location of a record in the file, see [`Parser::read_record_with_meta`].
+///
+/// A [`Record::Descriptor`] is composed of two records, the DescriptorType
+/// and the DescriptorData, the location is of the DescriptorData.
+#[derive(
+    Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize,
+)]
+pub struct RecordMeta {
+    /// Offset of the record body.
+    pub offset: u64,
+    /// Offset of the record header.
+    pub header_offset: u64,
+    /// Len of the record body, from the record header.
+    pub body_len: u16,
+}
+
+/// Snapshot of a [`Parser`], to continue the parsing later with
+/// [`Parser::resume`], can be serialized.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ParserState {
+    phase: ParsePhase,
+    position: u64,
+    checksums: Checksums,
+    descriptor_type: DescriptorTypeRecord,
+    firmware: FirmwareData,
+    pending: Option<RecordHeader>,
+    last_header: [u8; RECORD_HEADER_LEN],
+    config: ParserConfig,
+    #[serde(default)]
+    main_headers: u8,
+}
+
+impl ParserState {
+    pub const fn phase(&self) -> ParsePhase {
+        self.phase
+    }
+    /// Number of bytes consumed from the file, including the signature.
+    /// The file need to be at this position to resume the parsing.
+    pub const fn position(&self) -> u64 {
+        self.position
+    }
+}
+
+/// A record with the exact bytes it was parsed from, returned by
+/// [`Parser::read_record_with_raw`].
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct RawRecord {
+    pub record: Record,
+    /// The record headers and bodies, as stored in the file. The Descriptor
+    /// include the DescriptorType and DescriptorData records.
+    pub raw: Vec<u8>,
+}
+
+/// Streaming reader of the GCD records.
+///
+/// Malformed data is returned as an error, no input can make the parser
+/// panic, see [`testing::fuzz`](crate::testing::fuzz).
+///
+/// The record headers and small records are read a few bytes at a time, the
+/// `file` should be buffered, eg: a slice or a [`BufReader`], use
+/// [`Parser::open`] to parse a file from disk. The parser only use the
+/// [`Read`] of the file, the records are always copied from the file buffer,
+/// a slice can also be parsed without copies with [`Parser::read_cow_record`].
+/// The recovery, see [`ParserConfig::recover`], keep its own copy of the data
+/// read ahead.
 pub struct Parser<F, B = GcdDefaultEndian>
 where
     F: std::io::Read,
     B: ByteOrder,
 {
-    state: ParseState,
+    state: ParsePhase,
     file: ReadCheckSum<F>,
     descriptor_type: DescriptorTypeRecord,
     firmware: FirmwareData,
+    // number of MainHeader records received
+    main_headers: u8,
+    // header already read from the file, but not yet processed
+    pending: Option<RecordHeader>,
+    // bytes of the last record header read
+    last_header: [u8; RECORD_HEADER_LEN],
+    config: ParserConfig,
+    transforms: TransformTable,
+    decompressor: Option<Decompressor>,
+    // firmware ids to read, the chunks of the others are skipped
+    firmware_filter: Option<Vec<RecordId>>,
+    // the chunks data is not read into memory, the chunks are returned empty
+    discard_chunks: bool,
+    warnings: Vec<ParseWarning>,
+    skipped: Vec<SkippedSpan>,
+    // offset of the last record header read
+    record_offset: u64,
+    // len of the last record header read
+    record_len: u16,
+    // the iterator returned an error, stop the iteration
+    iter_failed: bool,
+    progress: Option<ProgressTracker>,
     endian: PhantomData<B>,
 }
 
@@ -97,52 +324,367 @@ where
     B: ByteOrder,
 {
     pub fn new(file: F) -> Result<Self> {
-        let state = ParseState::TextGlobal;
-        let mut file = ReadCheckSum::new(file);
-
-        let mut header_sign = [0u8; 8];
-        file.read_exact(&mut header_sign)?;
-        match &header_sign[..6] {
-            b"GARMIN" => {}
-            _ => {
-                return Err(Error::new(
-                    ErrorKind::InvalidData,
-                    "Invalid/Unknown Header Signature",
-                ))
-            }
-        }
+        Self::with_config(file, ParserConfig::default())
+    }
 
+    /// Create the parser with the options, see [`ParserConfig`].
+    pub fn with_config(file: F, config: ParserConfig) -> Result<Self> {
+        let mut file = checksum_reader(file, &config)?;
+        let header_sign = read_signature(&mut file)?;
         let header_version = B::read_u16(&header_sign[6..]);
         match header_version {
             100 => {}
-            _ => {
-                return Err(Error::new(
-                    ErrorKind::InvalidData,
-                    "Invalid/Unknown Header Version",
-                ))
-            }
+            version => return Err(GcdError::UnknownVersion(version)),
         }
+        Ok(Self::after_signature(file, config))
+    }
 
-        Ok(Self {
-            state,
+    // the parser with the signature already consumed from the `file`
+    fn after_signature(file: ReadCheckSum<F>, config: ParserConfig) -> Self {
+        Self {
+            state: ParsePhase::TextGlobal,
             file,
             descriptor_type: Default::default(),
             firmware: Default::default(),
+            main_headers: 0,
+            pending: None,
+            last_header: [0; RECORD_HEADER_LEN],
+            config,
+            transforms: TransformTable::default(),
+            decompressor: None,
+            firmware_filter: None,
+            discard_chunks: false,
+            warnings: vec![],
+            skipped: vec![],
+            record_offset: 0,
+            record_len: 0,
+            iter_failed: false,
+            progress: None,
             endian: PhantomData,
-        })
+        }
+    }
+
+    /// Continue the parsing from a [`ParserState`], the `file` need to be at
+    /// the [`ParserState::position`] of the original file.
+    ///
+    /// The transformations are reset to the default, see
+    /// [`Parser::set_transforms`], the decompressor and the firmware filter
+    /// are removed and the warnings are not kept.
+    pub fn resume(file: F, state: ParserState) -> Self {
+        let mut file = ReadCheckSum::new(file);
+        file.checksums = state.checksums;
+        file.len = state.position;
+        Self {
+            state: state.phase,
+            file,
+            descriptor_type: state.descriptor_type,
+            firmware: state.firmware,
+            main_headers: state.main_headers,
+            pending: state.pending,
+            last_header: state.last_header,
+            config: state.config,
+            transforms: TransformTable::default(),
+            decompressor: None,
+            firmware_filter: None,
+            discard_chunks: false,
+            warnings: vec![],
+            skipped: vec![],
+            record_offset: state.position,
+            record_len: 0,
+            iter_failed: false,
+            progress: None,
+            endian: PhantomData,
+        }
+    }
+
+    /// Phase of the file structure the parser is in.
+    pub fn state(&self) -> ParsePhase {
+        self.state
+    }
+
+    /// Snapshot of the parser, to continue later with [`Parser::resume`].
+    pub fn save_state(&self) -> ParserState {
+        ParserState {
+            phase: self.state,
+            position: self.file.len,
+            checksums: self.file.checksums,
+            descriptor_type: self.descriptor_type.clone(),
+            firmware: self.firmware.clone(),
+            pending: self.pending,
+            last_header: self.last_header,
+            config: self.config.clone(),
+            main_headers: self.main_headers,
+        }
+    }
+
+    /// Create a parser that continue past recoverable problems, see
+    /// [`ParserConfig::strict`].
+    pub fn new_lenient(file: F) -> Result<Self> {
+        let config = ParserConfig {
+            strict: false,
+            ..ParserConfig::default()
+        };
+        Self::with_config(file, config)
+    }
+
+    pub fn config(&self) -> &ParserConfig {
+        &self.config
+    }
+
+    /// Set the transformations used to decode the firmware, by default only
+    /// the font rule, see [`TransformTable`].
+    pub fn set_transforms(&mut self, transforms: TransformTable) {
+        self.transforms = transforms;
+    }
+
+    pub fn transforms(&self) -> &TransformTable {
+        &self.transforms
+    }
+
+    /// Decompress the firmware blocks copied by
+    /// [`Parser::read_firmware_into`], none by default. The firmware chunk
+    /// records are not decompressed.
+    pub fn set_decompressor(&mut self, decompressor: Option<Decompressor>) {
+        self.decompressor = decompressor;
+    }
+
+    /// Only read the firmware chunks of the firmware `ids`, the chunks of the
+    /// other firmwares are skipped without being allocated.
+    ///
+    /// The skipped data is still read, to verify the checksums. The
+    /// Descriptors of all the blocks are returned, the blocks not selected
+    /// have no firmware chunks, eg: [`Parser::read_block`] return them
+    /// empty.
+    pub fn skip_firmware_except(&mut self, ids: &[u16]) {
+        let ids = ids.iter().copied().map(RecordId::new).collect();
+        self.firmware_filter = Some(ids);
+    }
+
+    // the firmware chunks are returned as FirmwareRecord::EmptyChunk, the
+    // data is skipped, used to index the file
+    pub(crate) fn discard_chunks(&mut self) {
+        self.discard_chunks = true;
+    }
+
+    /// Number of bytes read from the file, including the signature.
+    pub fn position(&self) -> u64 {
+        self.file.len
+    }
+
+    /// Checksums of the data read so far, including the signature. The CRCs
+    /// are only calculated if enabled by [`ParserConfig::crc`].
+    pub fn checksums(&self) -> Checksums {
+        self.file.checksums
+    }
+
+    /// Report the progress after each record read, see
+    /// [`progress`](crate::progress). The `total` is the file size, if
+    /// known.
+    pub fn set_progress(
+        &mut self,
+        sink: Box<dyn ProgressSink>,
+        total: Option<u64>,
+    ) {
+        self.progress = Some(ProgressTracker::new(sink, total));
+    }
+
+    /// Problems found so far by a lenient parser, a strict parser return
+    /// them as errors.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// Data skipped so far by a parser in recovery mode, see
+    /// [`ParserConfig::recover`].
+    pub fn skipped(&self) -> &[SkippedSpan] {
+        &self.skipped
+    }
+
+    // the file being parsed, used to feed the data of the next record
+    #[cfg(feature = "async")]
+    pub(crate) fn file_mut(&mut self) -> &mut F {
+        &mut self.file.file
+    }
+
+    // return the error if strict, otherwise save it as a warning
+    fn warn(&mut self, error: GcdError) -> Result<()> {
+        if self.config.strict && !self.config.recover {
+            return Err(error);
+        }
+        trace_event!(warn, offset = self.record_offset, %error, "recovered");
+        self.warnings.push(ParseWarning {
+            offset: self.record_offset,
+            error,
+        });
+        Ok(())
     }
 
     /// Read the next available record
     pub fn read_record(&mut self) -> Result<Record> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "read_record",
+            offset = self.file.len,
+            phase = ?self.state,
+        )
+        .entered();
+        let record = loop {
+            let checkpoint = self.checkpoint();
+            let error = match self.next_record() {
+                Ok(record) => break record,
+                Err(error) => error,
+            };
+            trace_event!(
+                debug,
+                offset = self.record_offset,
+                phase = ?self.state,
+                last_header = ?self.last_header,
+                %error,
+                "record failed",
+            );
+            if !self.can_recover(&error) {
+                return Err(error);
+            }
+            //the failed record have no effect
+            if let Some(checkpoint) = checkpoint {
+                self.restore(checkpoint);
+            }
+            if !self.resync()? {
+                return Err(error);
+            }
+            trace_event!(warn, offset = self.record_offset, %error, "skipped");
+            self.warnings.push(ParseWarning {
+                offset: self.record_offset,
+                error,
+            });
+        };
+        let descriptor = matches!(record, Record::Descriptor(_));
+        self.record_progress(descriptor);
+        Ok(record)
+    }
+
+    // state before a record, restored if the record fail in recovery mode
+    fn checkpoint(&self) -> Option<Checkpoint> {
+        if !self.config.recover {
+            return None;
+        }
+        Some(Checkpoint {
+            phase: self.state,
+            descriptor_type: self.descriptor_type.clone(),
+            firmware: self.firmware.clone(),
+            main_headers: self.main_headers,
+            warnings: self.warnings.len(),
+        })
+    }
+
+    fn restore(&mut self, checkpoint: Checkpoint) {
+        self.state = checkpoint.phase;
+        self.descriptor_type = checkpoint.descriptor_type;
+        self.firmware = checkpoint.firmware;
+        self.main_headers = checkpoint.main_headers;
+        self.warnings.truncate(checkpoint.warnings);
+    }
+
+    fn can_recover(&self, error: &GcdError) -> bool {
+        let fatal = matches!(
+            error,
+            GcdError::ReadAfterEnd | GcdError::LimitExceeded { .. }
+        );
+        self.config.recover && !fatal && self.state != ParsePhase::End
+    }
+
+    // skip to the next valid record header, after the start of the last
+    // record, return false if not found
+    fn resync(&mut self) -> Result<bool> {
+        //scan again the last record, after the first byte
+        self.file.rewind();
+        if self.file.read(&mut [0])? == 0 {
+            return Ok(false);
+        }
+        loop {
+            let header = self.file.peek(RECORD_HEADER_LEN)?;
+            if header.len() < RECORD_HEADER_LEN {
+                return Ok(false);
+            }
+            if self.is_valid_header()? {
+                break;
+            }
+            self.file.read_exact(&mut [0])?;
+        }
+        let len = self.file.len - self.record_offset;
+        trace_event!(debug, offset = self.record_offset, len, "resync");
+        self.skipped.push(SkippedSpan {
+            offset: self.record_offset,
+            len,
+        });
+        //the raw bytes are of the next record only
+        if let Some(raw) = self.file.raw.as_mut() {
+            raw.clear();
+        }
+        self.pending = None;
+        self.firmware.chunk_left = 0;
+        Ok(true)
+    }
+
+    // the record header at the current position is known and is the End, or
+    // is followed by other known record header
+    fn is_valid_header(&mut self) -> Result<bool> {
+        let data = self.file.peek(RECORD_HEADER_LEN)?;
+        let (_, header) = RecordHeader::from_raw::<B>(data)?;
+        if !self.is_known_header(header) {
+            return Ok(false);
+        }
+        if header == RecordHeader::End {
+            return Ok(true);
+        }
+        let next = RECORD_HEADER_LEN + header.len() as usize;
+        let data = self.file.peek(next + RECORD_HEADER_LEN)?;
+        if data.len() < next + RECORD_HEADER_LEN {
+            return Ok(false);
+        }
+        let (_, next) = RecordHeader::from_raw::<B>(&data[next..])?;
+        Ok(self.is_known_header(next))
+    }
+
+    fn is_known_header(&self, header: RecordHeader) -> bool {
+        match header {
+            //only the chunks of the current firmware
+            RecordHeader::Unknown { id, .. } => {
+                id == self.firmware.id
+                    && matches!(
+                        self.state,
+                        ParsePhase::DescriptorData | ParsePhase::FirmwareData
+                    )
+            }
+            RecordHeader::DescriptorType(len) => len != 0 && len % 2 == 0,
+            RecordHeader::MainHeader(len)
+            | RecordHeader::Text(len)
+            | RecordHeader::DescriptorData(len) => len != 0,
+            _ => true,
+        }
+    }
+
+    fn set_phase(&mut self, phase: ParsePhase) {
+        trace_event!(trace, from = ?self.state, to = ?phase, "parser phase");
+        self.state = phase;
+    }
+
+    fn record_progress(&mut self, descriptor: bool) {
+        if let Some(progress) = self.progress.as_mut() {
+            progress.record(self.file.len, descriptor);
+        }
+    }
+
+    fn next_record(&mut self) -> Result<Record> {
+        //the rest of a chunk partially consumed by a FirmwareReader
+        if self.firmware.chunk_left != 0 {
+            return Ok(Record::FirmwareData(self.read_firmware_chunk()?));
+        }
         //loop until error or return a record
         loop {
-            if let ParseState::End = self.state {
-                //TODO check if there is more data after the End Record and return
-                //Err if there is.
-                return Err(Error::new(
-                    ErrorKind::InvalidData,
-                    "Unable to read after End Record",
-                ));
+            if let ParsePhase::End = self.state {
+                //the data after the End is checked by finish
+                return Err(GcdError::ReadAfterEnd);
             }
 
             let state = self.state; //avoid multiple borrows
@@ -160,84 +702,101 @@ where
                 }
 
                 //Didn't Received the MainHeader yet
-                (ParseState::TextGlobal, RecordHeader::Text(len)) => {
+                (ParsePhase::TextGlobal, RecordHeader::Text(len)) => {
                     return Ok(Record::Text(self.parse_text(len)?));
                 }
-                (ParseState::TextGlobal, RecordHeader::MainHeader(len)) => {
-                    //Main Header, change state so we refuse a second one
-                    self.state = ParseState::Main;
-                    return Ok(Record::MainHeader(
-                        self.parse_main_header(len)?,
-                    ));
+                (
+                    ParsePhase::TextGlobal | ParsePhase::Main,
+                    RecordHeader::MainHeader(len),
+                ) if self.main_headers < self.config.max_main_headers => {
+                    //Main Header, counted so we refuse more than the max
+                    self.main_headers += 1;
+                    self.set_phase(ParsePhase::Main);
+                    match self.parse_main_header(len)? {
+                        Ok(main) => return Ok(Record::MainHeader(main)),
+                        //lenient, the unknown Main Header is skipped
+                        Err(error) => self.warn(error)?,
+                    }
                 }
 
                 //Received MainHeader
-                (ParseState::Main, RecordHeader::DescriptorType(len)) => {
+                (ParsePhase::Main, RecordHeader::DescriptorType(len)) => {
                     //first firmware block, no more global data
-                    self.state = ParseState::DescriptorType;
+                    self.set_phase(ParsePhase::DescriptorType);
                     //at this state descriptor_type is sure to be NONE
                     self.descriptor_type = self.parse_descriptor_type(len)?;
                 }
-                (ParseState::Main, RecordHeader::Text(len)) => {
+                (ParsePhase::Main, RecordHeader::Text(len)) => {
                     // Text(after Main Header)
                     return Ok(Record::Text(self.parse_text(len)?));
                 }
 
                 //Received the firmware descriptor type
                 (
-                    ParseState::DescriptorType,
+                    ParsePhase::DescriptorType,
                     RecordHeader::DescriptorData(len),
                 ) => {
-                    self.state = ParseState::DescriptorData;
+                    self.set_phase(ParsePhase::DescriptorData);
                     //at this state is garantied that descriptor_type is Some()
                     return Ok(Record::Descriptor(
                         self.parse_descriptor_data(len)?,
                     ));
                 }
 
+                //a chunk from other firmware, or an unknown record
+                (
+                    ParsePhase::DescriptorData | ParsePhase::FirmwareData,
+                    RecordHeader::Unknown { id, len },
+                ) if id != self.firmware.id => {
+                    self.warn(GcdError::FirmwareIdMismatch {
+                        expected: self.firmware.id.get(),
+                        found: id.get(),
+                    })?;
+                    return self.parse_unknown(id, len);
+                }
+
                 //received the firmware descriptor type and data
                 (
-                    ParseState::DescriptorData,
+                    ParsePhase::DescriptorData,
                     RecordHeader::DescriptorType(len),
                 ) => {
-                    //received a new firmware, Firmware Data Record missing
-                    self.state = ParseState::DescriptorType;
-                    //TODO: allow Firmware Data Record missing?
-                    //is garantied that self.firmware in Some at this state
+                    //received a new firmware, Firmware Data Record missing,
+                    //valid only if the firmware is empty, FirmwareLen 0
+                    self.set_phase(ParsePhase::DescriptorType);
                     self.check_firmware_end()?;
                     self.descriptor_type = self.parse_descriptor_type(len)?;
                 }
                 (
-                    ParseState::DescriptorData,
+                    ParsePhase::DescriptorData,
                     RecordHeader::Unknown { id, len },
                 ) => {
                     //first data chunk received
-                    self.state = ParseState::FirmwareData;
+                    self.set_phase(ParsePhase::FirmwareData);
                     //send this data chunk
                     return Ok(Record::FirmwareData(
                         self.parse_firmware_data(id, len)?,
                     ));
                 }
-                (ParseState::DescriptorData, RecordHeader::Text(len)) => {
+                (ParsePhase::DescriptorData, RecordHeader::Text(len)) => {
                     //firmware text, no firmware data received yet
                     return Ok(Record::Text(self.parse_text(len)?));
                 }
-                (ParseState::DescriptorData, RecordHeader::End) => {
+                (ParsePhase::DescriptorData, RecordHeader::End) => {
                     //firmware block only had descriptor
                     //current block don't have data or text
-                    self.state = ParseState::End;
+                    self.set_phase(ParsePhase::End);
                     //end this firmware
                     self.check_firmware_end()?;
                     return Ok(Record::End);
                 }
 
                 // text or firmware data
-                (ParseState::FirmwareData, RecordHeader::Text(len)) => {
+                (ParsePhase::FirmwareData, RecordHeader::Text(len)) => {
                     let text = self.parse_text(len)?;
                     return Ok(Record::Text(text));
                 }
                 (
-                    ParseState::FirmwareData,
+                    ParsePhase::FirmwareData,
                     RecordHeader::Unknown { id, len },
                 ) => {
                     //second or more data chunk received
@@ -246,64 +805,353 @@ where
                         self.parse_firmware_data(id, len)?,
                     ));
                 }
-                (ParseState::FirmwareData, RecordHeader::End) => {
+                (ParsePhase::FirmwareData, RecordHeader::End) => {
                     //not more Firmware Data
-                    self.state = ParseState::End;
+                    self.set_phase(ParsePhase::End);
                     //end this firmware
                     self.check_firmware_end()?;
                     return Ok(Record::End);
                 }
                 (
-                    ParseState::FirmwareData,
+                    ParsePhase::FirmwareData,
                     RecordHeader::DescriptorType(len),
                 ) => {
                     //received a new firmware after receiving a firmware
                     //block, with at least text
-                    self.state = ParseState::DescriptorType;
+                    self.set_phase(ParsePhase::DescriptorType);
                     //end this firmware
                     self.check_firmware_end()?;
-                    self.parse_descriptor_type(len)?;
+                    self.descriptor_type = self.parse_descriptor_type(len)?;
                 }
 
+                //lenient, the record is returned as is
+                (state, RecordHeader::Unknown { id, len }) => {
+                    self.warn(GcdError::UnexpectedRecord {
+                        state: format!("{:?}", state),
+                        header: RecordHeader::Unknown { id, len },
+                    })?;
+                    return self.parse_unknown(id, len);
+                }
                 (state, record) => {
-                    return Err(Error::new(
-                        ErrorKind::InvalidInput,
-                        format!(
-                            "State {:?} record received {:?}",
-                            state, record
-                        ),
-                    ));
+                    return Err(GcdError::UnexpectedRecord {
+                        state: format!("{:?}", state),
+                        header: record,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Read the next available record, with its location in the file.
+    pub fn read_record_with_meta(&mut self) -> Result<(Record, RecordMeta)> {
+        let record = self.read_record()?;
+        let meta = RecordMeta {
+            offset: self.record_offset + RECORD_HEADER_LEN as u64,
+            header_offset: self.record_offset,
+            body_len: self.record_len,
+        };
+        Ok((record, meta))
+    }
+
+    /// Read the next available record, with the bytes it was parsed from.
+    ///
+    /// Writing the raw bytes with [`Composer::write_raw_record`] result in
+    /// the same bytes, even for data that the record normalize, eg: the
+    /// filler content. If the record is the rest of a chunk partially
+    /// consumed by a [`FirmwareReader`], only the rest is included.
+    ///
+    /// [`Composer::write_raw_record`]:
+    /// crate::composer::Composer::write_raw_record
+    pub fn read_record_with_raw(&mut self) -> Result<RawRecord> {
+        //the header was read before, looking for firmware chunks
+        let raw = match self.pending {
+            Some(_) => self.last_header.to_vec(),
+            None => vec![],
+        };
+        self.file.raw = Some(raw);
+        let record = self.read_record();
+        let raw = self.file.raw.take().unwrap_or_default();
+        Ok(RawRecord {
+            record: record?,
+            raw,
+        })
+    }
+
+    /// Read the data after the End record, until the end of the file.
+    ///
+    /// Should be called after the [`Record::End`] is returned, return the
+    /// trailing data, empty if the End record is the last one. Some files
+    /// have data appended after the End, eg: a signature blob. Fail with
+    /// [`GcdError::TrailingData`] if
+    /// [`ParserConfig::deny_trailing_data`] is set and there is data.
+    pub fn finish(&mut self) -> Result<Vec<u8>> {
+        if !matches!(self.state, ParsePhase::End) {
+            return Err(GcdError::invalid_input(
+                "finish called before the End record",
+            ));
+        }
+        let mut trailing = vec![];
+        self.file.read_to_end(&mut trailing)?;
+        if self.config.deny_trailing_data && !trailing.is_empty() {
+            return Err(GcdError::TrailingData(trailing.len() as u64));
+        }
+        Ok(trailing)
+    }
+
+    /// Stream the decoded data of the current firmware block.
+    ///
+    /// Should be called after the [`Record::Descriptor`] is returned, the
+    /// reader will consume the firmware chunks that follow it.
+    pub fn firmware_reader(&mut self) -> FirmwareReader<'_, F, B> {
+        FirmwareReader { parser: self }
+    }
+
+    /// Copy the decoded data of the current firmware block to the writer,
+    /// returning the number of bytes copied.
+    ///
+    /// The data is copied using a fixed size buffer, without allocating the
+    /// chunks. Like [`Parser::firmware_reader`], should be called after the
+    /// [`Record::Descriptor`] is returned.
+    ///
+    /// With a decompressor, see [`Parser::set_decompressor`], the whole block
+    /// is read into memory and the decompressed data is copied.
+    pub fn read_firmware_into<W: Write>(
+        &mut self,
+        mut writer: W,
+    ) -> Result<u64> {
+        match self.decompressor.clone() {
+            Some(decompressor) if self.config.decode_firmware => {
+                let mut data = vec![];
+                self.firmware_reader().read_to_end(&mut data)?;
+                let data = decompressor.decompress_or_keep(data)?;
+                writer.write_all(&data)?;
+                Ok(data.len() as u64)
+            }
+            _ => Ok(std::io::copy(&mut self.firmware_reader(), &mut writer)?),
+        }
+    }
+
+    /// Read the next firmware block, the Descriptor, the text records and
+    /// all the firmware chunks, return None after the End record.
+    ///
+    /// Records before the Descriptor, like the MainHeader and the global
+    /// texts, are skipped, use [`Parser::read_record`] to read them first.
+    /// The Checksum and Filler records are verified and skipped.
+    ///
+    /// The len of each chunk is kept, see [`FirmwareBlock::chunk_layout`]. The
+    /// firmware is decompressed, if a decompressor is set, see
+    /// [`Parser::read_firmware_into`].
+    pub fn read_block(&mut self) -> Result<Option<FirmwareBlock>> {
+        let descriptor = loop {
+            if self.state == ParsePhase::End {
+                return Ok(None);
+            }
+            if let Record::Descriptor(descriptor) = self.read_record()? {
+                break descriptor;
+            }
+        };
+        let mut texts = vec![];
+        let mut data = vec![];
+        let mut chunks = vec![];
+        loop {
+            while self.next_firmware_chunk()? {
+                chunks.push(self.firmware.chunk_left);
+                data.extend(self.read_firmware_chunk()?.data());
+            }
+            if !matches!(self.pending, Some(RecordHeader::Text(_))) {
+                break;
+            }
+            match self.read_record()? {
+                Record::Text(text) => texts.push(text),
+                //the text was skipped by the recovery
+                _ => {
+                    return Err(GcdError::invalid_data(
+                        "Unexpected record inside the firmware block",
+                    ))
+                }
+            }
+        }
+        if let Some(decompressor) = &self.decompressor {
+            if self.config.decode_firmware {
+                data = decompressor.decompress_or_keep(data)?;
+            }
+        }
+        let data = block::FirmwareData {
+            id: self.firmware.id,
+            data,
+        };
+        Ok(Some(FirmwareBlock {
+            descriptor,
+            texts,
+            data,
+            chunks,
+        }))
+    }
+
+    /// Read all the records until the End, calling the `visitor` for each
+    /// one, see [`RecordVisitor`].
+    ///
+    /// The firmware chunks are read in a single buffer, reused for all the
+    /// chunks, instead of allocating a [`FirmwareRecord`] for each one.
+    pub fn visit<V: RecordVisitor>(&mut self, visitor: &mut V) -> Result<()> {
+        let mut buf = vec![];
+        loop {
+            let in_block = matches!(
+                self.state,
+                ParsePhase::DescriptorData | ParsePhase::FirmwareData
+            );
+            if in_block && self.firmware.chunk_left == 0 {
+                match self.parse_record()? {
+                    RecordHeader::Unknown { id, len }
+                        if id == self.firmware.id =>
+                    {
+                        self.start_firmware_chunk(id, len)?;
+                        self.set_phase(ParsePhase::FirmwareData);
+                        buf.resize(len as usize, 0);
+                        self.file.read_exact(&mut buf)?;
+                        self.firmware.chunk_left = 0;
+                        self.decode_firmware(&mut buf);
+                        self.record_progress(false);
+                        visitor.on_firmware_chunk(id, &buf)?;
+                        continue;
+                    }
+                    //not a firmware chunk, processed by read_record
+                    header => self.pending = Some(header),
+                }
+            }
+            match self.read_record()? {
+                Record::MainHeader(main) => visitor.on_header(&main)?,
+                Record::Text(text) => visitor.on_text(&text)?,
+                Record::Descriptor(desc) => visitor.on_descriptor(&desc)?,
+                //the rest of a chunk partially consumed by a FirmwareReader
+                Record::FirmwareData(firmware) => {
+                    visitor.on_firmware_chunk(firmware.id(), firmware.data())?
+                }
+                Record::Checksum(checksum) => visitor.on_checksum(&checksum)?,
+                Record::Filler(filler) => visitor.on_filler(&filler)?,
+                Record::End => return visitor.on_end(),
+                Record::Unknown { id, data } => {
+                    visitor.on_unknown(id, &data)?
                 }
             }
         }
     }
 
     fn parse_record(&mut self) -> Result<RecordHeader> {
-        let mut header = [0; 4];
+        if let Some(header) = self.pending.take() {
+            return Ok(header);
+        }
+        loop {
+            match self.parse_record_header()? {
+                RecordHeader::Unknown { id, len } if self.is_skipped(id) => {
+                    self.skip_firmware_chunk(id, len)?
+                }
+                header => return Ok(header),
+            }
+        }
+    }
+
+    // the record is a firmware chunk not selected by the firmware filter
+    fn is_skipped(&self, id: RecordId) -> bool {
+        let in_block = matches!(
+            self.state,
+            ParsePhase::DescriptorData | ParsePhase::FirmwareData
+        );
+        match &self.firmware_filter {
+            Some(ids) => {
+                in_block && id == self.firmware.id && !ids.contains(&id)
+            }
+            None => false,
+        }
+    }
+
+    fn skip_firmware_chunk(&mut self, id: RecordId, len: u16) -> Result<()> {
+        self.start_firmware_chunk(id, len)?;
+        self.set_phase(ParsePhase::FirmwareData);
+        self.discard_chunk_data()?;
+        //the raw bytes are of the next record only
+        if let Some(raw) = self.file.raw.as_mut() {
+            raw.clear();
+        }
+        self.record_progress(false);
+        Ok(())
+    }
+
+    fn parse_record_header(&mut self) -> Result<RecordHeader> {
+        self.record_offset = self.file.len;
+        if self.config.recover {
+            self.file.mark();
+        }
+        let mut header = [0; RECORD_HEADER_LEN];
         self.file.read_exact(&mut header)?;
-        let (_, ret) = RecordHeader::from_raw::<B>(&mut header)?;
+        self.last_header = header;
+        let (_, ret) = RecordHeader::from_raw::<B>(&header)?;
+        trace_event!(trace, offset = self.record_offset, header = ?ret, "record");
+        self.record_len = ret.len();
+        if let Some(max) = self.config.max_record_len {
+            if ret.len() > max {
+                return Err(GcdError::LimitExceeded {
+                    limit: "Record",
+                    max: max.into(),
+                });
+            }
+        }
         Ok(ret)
     }
 
     fn parse_checksum(&mut self) -> Result<ChecksumRecord> {
         let mut data = [0];
         self.file.read_exact(&mut data)?;
-        let checksum = self.file.sum();
-        return Ok(ChecksumRecord::new(&data, checksum)?);
+        if self.config.report_checksums {
+            return ChecksumRecord::new_verified(&data, self.file.sum());
+        }
+        if !self.config.verify_checksums {
+            return Ok(ChecksumRecord::Simple);
+        }
+        let checksum = ChecksumRecord::new(&data, self.file.sum());
+        trace_event!(
+            debug,
+            offset = self.record_offset,
+            valid = checksum.is_ok(),
+            "checksum verified",
+        );
+        match checksum {
+            Ok(checksum) => Ok(checksum),
+            Err(error) => {
+                self.warn(error)?;
+                Ok(ChecksumRecord::Simple)
+            }
+        }
     }
 
     fn parse_filler(&mut self, lenght: u16) -> Result<FillerRecord> {
         let mut data = vec![0; lenght as usize];
         self.file.read_exact(&mut data)?;
-        return Ok(FillerRecord::new(&data)?);
+        match FillerRecord::new(&data) {
+            Ok(filler) => Ok(filler),
+            Err(error) => {
+                self.warn(error)?;
+                Ok(FillerRecord::Zeros(lenght))
+            }
+        }
+    }
+
+    // the outer error is fatal, the inner can be recovered by skipping the
+    // record
+    fn parse_main_header(&mut self, lenght: u16) -> Result<Result<MainRecord>> {
+        let mut data = vec![0; lenght as usize];
+        self.file.read_exact(&mut data)?;
+        Ok(MainRecord::new::<_, B>(&mut &data[..], lenght))
     }
 
-    fn parse_main_header(&mut self, lenght: u16) -> Result<MainRecord> {
-        MainRecord::new::<ReadCheckSum<F>, B>(&mut self.file, lenght)
+    fn parse_unknown(&mut self, id: RecordId, lenght: u16) -> Result<Record> {
+        let mut data = vec![0; lenght as usize];
+        self.file.read_exact(&mut data)?;
+        Ok(Record::Unknown { id, data })
     }
 
     fn parse_text(&mut self, lenght: u16) -> Result<TextRecord> {
-        TextRecord::new(&mut self.file, lenght)
+        TextRecord::new_with(&mut self.file, lenght, self.config.text_encoding)
     }
 
     fn parse_descriptor_type(
@@ -322,109 +1170,1901 @@ where
             lenght,
             &self.descriptor_type,
         )?;
-
-        //find extract necessary data
-        let mut firmware_id = None;
-        let mut firmware_lenght = None;
-        let mut xor_key = None;
-        //TODO make a more elegant data extraction
-        for desc in descriptor.iter() {
-            match desc.decode() {
-                Some(DescriptorDecoded::FirmwareId(x)) => {
-                    firmware_id = Some(x);
-                }
-                Some(DescriptorDecoded::FirmwareLen(x)) => {
-                    firmware_lenght = Some(x);
-                }
-                Some(DescriptorDecoded::XorKey(x)) => {
-                    xor_key = Some(x);
-                }
-                Some(DescriptorDecoded::Firmware2000P1Len(x))
-                | Some(DescriptorDecoded::Firmware2000P2Len(x))
-                | Some(DescriptorDecoded::Firmware2000P3Len(x)) => {
-                    //TODO is the size for each part?
-                    //each part is separated? in sequence?
-                    firmware_lenght = Some(x);
-                }
-                Some(_) => {}
-                None => {}
-            }
+        if self.config.require_descriptor_end && !descriptor.is_terminated() {
+            self.warn(GcdError::MissingDescriptor("End"))?;
         }
+
         //TODO check if those values exist on Firmware Descriptor Type parsing
-        match firmware_id {
-            None => {
-                return Err(Error::new(
-                    ErrorKind::InvalidData,
-                    "Firmware Id not found",
-                ))
+        match descriptor.firmware_id() {
+            None => return Err(GcdError::MissingDescriptor("Firmware Id")),
+            Some(x) => self.firmware.id = RecordId::new(x),
+        }
+        match descriptor.firmware_len() {
+            None if self.config.allow_unknown_len => {
+                self.firmware.lenght = 0;
+                self.firmware.unknown_len = true;
+            }
+            None => return Err(GcdError::MissingDescriptor("Firmware Lenght")),
+            Some(x) => {
+                self.add_firmware_len(x.into())?;
+                self.firmware.lenght = x;
+                self.firmware.unknown_len = false;
             }
-            Some(x) => self.firmware.id = x,
         }
-        match firmware_lenght {
-            None => {
-                return Err(Error::new(
-                    ErrorKind::InvalidData,
-                    "Firmware Lenght not found",
-                ))
+        //the Firmware2000 parts are stored in sequence
+        self.firmware.parts = match descriptor.firmware_parts() {
+            Ok(parts) => parts,
+            Err(error) => {
+                self.warn(error)?;
+                None
+            }
+        };
+        if let Some(parts) = self.firmware.parts {
+            if parts.total_len() != Some(self.firmware.lenght) {
+                self.warn(GcdError::invalid_data(
+                    "FirmwareLen differ from the Firmware2000 parts",
+                ))?;
             }
-            Some(x) => self.firmware.lenght = x,
         }
-        self.firmware.xor_key = xor_key.unwrap_or(0);
+        self.firmware.xor_key = descriptor.xor_key().unwrap_or(0);
         self.firmware.lenght_left = self.firmware.lenght;
         Ok(descriptor)
     }
 
     fn parse_firmware_data(
         &mut self,
-        record_id: u16,
+        record_id: RecordId,
         record_len: u16,
-    ) -> Result<FirmwareRecord>
-    where
-        F: std::io::Read,
-    {
+    ) -> Result<FirmwareRecord> {
+        self.start_firmware_chunk(record_id, record_len)?;
+        self.read_firmware_chunk()
+    }
+
+    fn start_firmware_chunk(
+        &mut self,
+        record_id: RecordId,
+        record_len: u16,
+    ) -> Result<()> {
         if record_id != self.firmware.id {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
-                format!(
-                    "Firmware id expected {:#x} found {:#x}",
-                    self.firmware.id, record_id,
-                ),
-            ));
+            return Err(GcdError::FirmwareIdMismatch {
+                expected: self.firmware.id.get(),
+                found: record_id.get(),
+            });
+        }
+        //after the last part is an overflow, checked bellow
+        let offset = self.firmware.lenght - self.firmware.lenght_left;
+        let part = self.firmware.parts.and_then(|x| x.part_at(offset.into()));
+        self.firmware.chunk_part = part.map(|(part_idx, _)| part_idx);
+        if let Some((_, left)) = part {
+            if left < record_len.into() {
+                self.warn(GcdError::invalid_data(
+                    "Firmware chunk cross a part boundary",
+                ))?;
+            }
         }
         //subtract the current consumed firmware chunk
-        if self.firmware.lenght_left < record_len as u32 {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
-                "Firmware Chunk is bigger than expected",
-            ));
+        if self.firmware.unknown_len {
+            self.add_firmware_len(record_len.into())?;
+        } else if self.firmware.lenght_left < record_len as u32 {
+            self.warn(GcdError::FirmwareOverflow {
+                expected: self.firmware.lenght,
+            })?;
+            //only the data after the FirmwareLen was not counted
+            let extra = record_len as u32 - self.firmware.lenght_left;
+            self.add_firmware_len(extra.into())?;
         }
-        self.firmware.lenght_left -= record_len as u32;
-        //send chunk to handle
-        let mut buf = vec![0u8; record_len as usize];
-        self.file.read_exact(&mut buf)?;
-        if self.firmware.xor_key != 0 {
-            buf.iter_mut().for_each(|x| *x = *x ^ self.firmware.xor_key);
+        self.firmware.lenght_left =
+            self.firmware.lenght_left.saturating_sub(record_len as u32);
+        self.firmware.chunk_left = record_len;
+        Ok(())
+    }
+
+    fn add_firmware_len(&mut self, len: u64) -> Result<()> {
+        self.firmware.total_len += len;
+        match self.config.max_total_firmware_len {
+            Some(max) if self.firmware.total_len > max => {
+                Err(GcdError::LimitExceeded {
+                    limit: "Firmware",
+                    max,
+                })
+            }
+            _ => Ok(()),
         }
-        match self.firmware.id {
-            // TrueType font file, XORed with 0x76
-            0x05A5 => buf.iter_mut().for_each(|x| *x = *x ^ 0x76),
-            _ => {}
+    }
+
+    fn read_firmware_chunk(&mut self) -> Result<FirmwareRecord> {
+        if self.discard_chunks {
+            self.discard_chunk_data()?;
+            return Ok(FirmwareRecord::new(vec![], self.firmware.id));
         }
-        Ok(FirmwareRecord::new(buf, record_id))
+        let mut buf = vec![0u8; self.firmware.chunk_left as usize];
+        self.file.read_exact(&mut buf)?;
+        self.firmware.chunk_left = 0;
+        self.decode_firmware(&mut buf);
+        Ok(match self.firmware.chunk_part {
+            Some(part_idx) => {
+                FirmwareRecord::new_part(buf, self.firmware.id, part_idx)
+            }
+            None => FirmwareRecord::new(buf, self.firmware.id),
+        })
     }
 
-    fn check_firmware_end(&mut self) -> Result<()> {
-        //check if the firmware was fully received
-        if self.firmware.lenght_left != 0 {
+    // consume the rest of the chunk, without allocating it
+    fn discard_chunk_data(&mut self) -> Result<()> {
+        let len = self.firmware.chunk_left.into();
+        let mut chunk = (&mut self.file).take(len);
+        if std::io::copy(&mut chunk, &mut std::io::sink())? != len {
             return Err(Error::new(
-                ErrorKind::InvalidInput,
-                format!(
-                    "Firmware Chunk too small, received {} from {} bytes",
-                    self.firmware.lenght - self.firmware.lenght_left,
-                    self.firmware.lenght
-                ),
-            ));
+                ErrorKind::UnexpectedEof,
+                "File ended in the middle of a Firmware Chunk",
+            )
+            .into());
         }
+        self.firmware.chunk_left = 0;
         Ok(())
     }
+
+    fn decode_firmware(&self, buf: &mut [u8]) {
+        if !self.config.decode_firmware {
+            return;
+        }
+        let (id, xor_key) = (self.firmware.id, self.firmware.xor_key);
+        self.transforms.decode(id.get(), xor_key, buf);
+    }
+
+    // Find the next firmware chunk, return false if the next record is not
+    // firmware data, keeping it to be processed by read_record.
+    fn next_firmware_chunk(&mut self) -> Result<bool> {
+        match self.state {
+            ParsePhase::DescriptorData | ParsePhase::FirmwareData => {}
+            _ => return Ok(false),
+        }
+        loop {
+            match self.parse_record()? {
+                RecordHeader::Unknown { id, len } if id == self.firmware.id => {
+                    self.start_firmware_chunk(id, len)?;
+                    self.set_phase(ParsePhase::FirmwareData);
+                    //reported before the reader consume the chunk data
+                    self.record_progress(false);
+                    return Ok(true);
+                }
+                RecordHeader::Checksum => {
+                    self.parse_checksum()?;
+                    self.record_progress(false);
+                }
+                RecordHeader::Filler(len) => {
+                    self.parse_filler(len)?;
+                    self.record_progress(false);
+                }
+                header => {
+                    self.pending = Some(header);
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    fn check_firmware_end(&mut self) -> Result<()> {
+        //check if the firmware was fully received
+        if !self.firmware.unknown_len && self.firmware.lenght_left != 0 {
+            self.warn(GcdError::FirmwareTruncated {
+                received: self.firmware.lenght - self.firmware.lenght_left,
+                expected: self.firmware.lenght,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl<F> Parser<F>
+where
+    F: std::io::Read,
+{
+    /// Create the parser with the endian of the `file` and the options, see
+    /// [`AutoParser`] and [`ParserConfig`].
+    ///
+    /// The version 100 is only valid in one endian, 0x6400 is read in the
+    /// other, so the version select the endian. The records are checked by
+    /// the parser, like [`Parser::with_config`].
+    pub fn new_autodetect(
+        file: F,
+        config: ParserConfig,
+    ) -> Result<AutoParser<F>> {
+        let mut file = checksum_reader(file, &config)?;
+        let header_sign = read_signature(&mut file)?;
+        let le_version = LE::read_u16(&header_sign[6..]);
+        let be_version = BE::read_u16(&header_sign[6..]);
+        match (le_version, be_version) {
+            (100, _) => {
+                Ok(AutoParser::Little(Parser::after_signature(file, config)))
+            }
+            (_, 100) => {
+                Ok(AutoParser::Big(Parser::after_signature(file, config)))
+            }
+            (version, _) => Err(GcdError::UnknownVersion(version)),
+        }
+    }
+}
+
+// wrap the file, with the checksums enabled by the config
+fn checksum_reader<F>(file: F, config: &ParserConfig) -> Result<ReadCheckSum<F>>
+where
+    F: std::io::Read,
+{
+    let checksums = match config.crc {
+        #[cfg(feature = "crc")]
+        true => Checksums::with_crc(),
+        #[cfg(not(feature = "crc"))]
+        true => {
+            return Err(GcdError::invalid_input(
+                "The CRCs require the feature crc",
+            ))
+        }
+        false => Checksums::default(),
+    };
+    let mut file = ReadCheckSum::new(file);
+    file.checksums = checksums;
+    Ok(file)
+}
+
+// read and check the signature, the version is not checked
+fn read_signature<F>(file: &mut ReadCheckSum<F>) -> Result<[u8; 8]>
+where
+    F: std::io::Read,
+{
+    let mut header_sign = [0u8; 8];
+    file.read_exact(&mut header_sign)?;
+    match &header_sign[..6] {
+        b"GARMIN" => Ok(header_sign),
+        _ => Err(GcdError::InvalidSignature),
+    }
+}
+
+/// Endian of a GCD file, see [`Parser::new_autodetect`].
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// Parser with the endian detected from the file, returned by
+/// [`Parser::new_autodetect`].
+pub enum AutoParser<F>
+where
+    F: std::io::Read,
+{
+    Little(Parser<F, LE>),
+    Big(Parser<F, BE>),
+}
+
+// call the same method on the parser of any endian
+macro_rules! auto_parser {
+    ($self:expr, $parser:ident => $call:expr) => {
+        match $self {
+            AutoParser::Little($parser) => $call,
+            AutoParser::Big($parser) => $call,
+        }
+    };
+}
+
+impl<F> AutoParser<F>
+where
+    F: std::io::Read,
+{
+    /// The endian selected.
+    pub fn endian(&self) -> Endian {
+        match self {
+            AutoParser::Little(_) => Endian::Little,
+            AutoParser::Big(_) => Endian::Big,
+        }
+    }
+
+    /// See [`Parser::state`].
+    pub fn state(&self) -> ParsePhase {
+        auto_parser!(self, parser => parser.state())
+    }
+
+    /// See [`Parser::save_state`].
+    pub fn save_state(&self) -> ParserState {
+        auto_parser!(self, parser => parser.save_state())
+    }
+
+    /// See [`Parser::config`].
+    pub fn config(&self) -> &ParserConfig {
+        auto_parser!(self, parser => parser.config())
+    }
+
+    /// See [`Parser::set_transforms`].
+    pub fn set_transforms(&mut self, transforms: TransformTable) {
+        auto_parser!(self, parser => parser.set_transforms(transforms))
+    }
+
+    /// See [`Parser::set_decompressor`].
+    pub fn set_decompressor(&mut self, decompressor: Option<Decompressor>) {
+        auto_parser!(self, parser => parser.set_decompressor(decompressor))
+    }
+
+    /// See [`Parser::skip_firmware_except`].
+    pub fn skip_firmware_except(&mut self, ids: &[u16]) {
+        auto_parser!(self, parser => parser.skip_firmware_except(ids))
+    }
+
+    /// See [`Parser::set_progress`].
+    pub fn set_progress(
+        &mut self,
+        sink: Box<dyn ProgressSink>,
+        total: Option<u64>,
+    ) {
+        auto_parser!(self, parser => parser.set_progress(sink, total))
+    }
+
+    /// See [`Parser::position`].
+    pub fn position(&self) -> u64 {
+        auto_parser!(self, parser => parser.position())
+    }
+
+    /// See [`Parser::checksums`].
+    pub fn checksums(&self) -> Checksums {
+        auto_parser!(self, parser => parser.checksums())
+    }
+
+    /// See [`Parser::warnings`].
+    pub fn warnings(&self) -> &[ParseWarning] {
+        auto_parser!(self, parser => parser.warnings())
+    }
+
+    /// See [`Parser::skipped`].
+    pub fn skipped(&self) -> &[SkippedSpan] {
+        auto_parser!(self, parser => parser.skipped())
+    }
+
+    /// See [`Parser::read_record`].
+    pub fn read_record(&mut self) -> Result<Record> {
+        auto_parser!(self, parser => parser.read_record())
+    }
+
+    /// See [`Parser::read_record_with_meta`].
+    pub fn read_record_with_meta(&mut self) -> Result<(Record, RecordMeta)> {
+        auto_parser!(self, parser => parser.read_record_with_meta())
+    }
+
+    /// See [`Parser::read_record_with_raw`].
+    pub fn read_record_with_raw(&mut self) -> Result<RawRecord> {
+        auto_parser!(self, parser => parser.read_record_with_raw())
+    }
+
+    /// See [`Parser::finish`].
+    pub fn finish(&mut self) -> Result<Vec<u8>> {
+        auto_parser!(self, parser => parser.finish())
+    }
+
+    /// See [`Parser::firmware_reader`].
+    pub fn firmware_reader(&mut self) -> Box<dyn Read + '_> {
+        auto_parser!(self, parser => Box::new(parser.firmware_reader()))
+    }
+
+    /// See [`Parser::read_firmware_into`].
+    pub fn read_firmware_into<W: Write>(&mut self, writer: W) -> Result<u64> {
+        auto_parser!(self, parser => parser.read_firmware_into(writer))
+    }
+
+    /// See [`Parser::read_block`].
+    pub fn read_block(&mut self) -> Result<Option<FirmwareBlock>> {
+        auto_parser!(self, parser => parser.read_block())
+    }
+
+    /// See [`Parser::visit`].
+    pub fn visit<V: RecordVisitor>(&mut self, visitor: &mut V) -> Result<()> {
+        auto_parser!(self, parser => parser.visit(visitor))
+    }
+}
+
+/// Iterate over the records, see [`Parser`].
+impl<F> Iterator for AutoParser<F>
+where
+    F: std::io::Read,
+{
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        auto_parser!(self, parser => parser.next())
+    }
+}
+
+impl<B> Parser<BufReader<File>, B>
+where
+    B: ByteOrder,
+{
+    /// Open the file at `path`, wrapped in a [`BufReader`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<'a, B> Parser<&'a [u8], B>
+where
+    B: ByteOrder,
+{
+    /// Read the next record, borrowing the firmware chunks and the UTF-8
+    /// texts from the data, see [`CowRecord`].
+    ///
+    /// The firmware is copied only if it need to be decoded. In recovery
+    /// mode, see [`ParserConfig::recover`], all the records are copied.
+    pub fn read_cow_record(&mut self) -> Result<CowRecord<'a>> {
+        match self.next_borrowed()? {
+            Some(record) => Ok(record),
+            None => self.read_record().map(CowRecord::from),
+        }
+    }
+
+    // the next record, if it can be borrowed, otherwise the header is kept
+    // to be processed by read_record
+    fn next_borrowed(&mut self) -> Result<Option<CowRecord<'a>>> {
+        if self.config.recover
+            || self.firmware.chunk_left != 0
+            || !self.file.ahead.is_empty()
+            || self.state == ParsePhase::End
+        {
+            return Ok(None);
+        }
+        let in_block = matches!(
+            self.state,
+            ParsePhase::DescriptorData | ParsePhase::FirmwareData
+        );
+        let utf8 = matches!(
+            self.config.text_encoding,
+            TextEncoding::Utf8 | TextEncoding::Auto
+        );
+        match self.parse_record()? {
+            RecordHeader::Unknown { id, len }
+                if in_block && id == self.firmware.id =>
+            {
+                self.start_firmware_chunk(id, len)?;
+                self.set_phase(ParsePhase::FirmwareData);
+                self.firmware.chunk_left = 0;
+                let mut data =
+                    Cow::Borrowed(self.file.read_borrowed(len.into())?);
+                let xor_key = self.firmware.xor_key;
+                if self.config.decode_firmware
+                    && !self.transforms.is_identity(id.get(), xor_key)
+                {
+                    self.decode_firmware(data.to_mut());
+                }
+                self.record_progress(false);
+                Ok(Some(CowRecord::FirmwareData {
+                    id,
+                    part_idx: self.firmware.chunk_part,
+                    data,
+                }))
+            }
+            RecordHeader::Text(len)
+                if utf8 && self.state != ParsePhase::DescriptorType =>
+            {
+                let data = self.file.read_borrowed(len.into())?;
+                self.record_progress(false);
+                Ok(Some(match std::str::from_utf8(data) {
+                    Ok(text) => CowRecord::Text(Cow::Borrowed(text)),
+                    Err(_) => {
+                        CowRecord::from(Record::Text(TextRecord::from_bytes(
+                            data.to_vec(),
+                            self.config.text_encoding,
+                        )))
+                    }
+                }))
+            }
+            header => {
+                self.pending = Some(header);
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Parse all the records of the file, until the End record.
+pub fn parse_all(bytes: &[u8]) -> Result<Vec<Record>> {
+    let parser: Parser<&[u8]> = Parser::new(bytes)?;
+    parser.collect()
+}
+
+/// Iterate over the records, until the End record.
+///
+/// The iteration stops after the End record or the first error.
+impl<F, B> Iterator for Parser<F, B>
+where
+    F: std::io::Read,
+    B: ByteOrder,
+{
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.iter_failed || self.state == ParsePhase::End {
+            return None;
+        }
+        let record = self.read_record();
+        self.iter_failed = record.is_err();
+        Some(record)
+    }
+}
+
+/// Stream the firmware data of a block, created by
+/// [`Parser::firmware_reader`].
+///
+/// The data is read chunk by chunk directly from the file and decoded, so it
+/// can be piped to other writers without allocating the firmware. Checksum and
+/// Filler records between the chunks are verified and skipped. The read return
+/// EOF at the first record that is not part of the firmware, this record is
+/// returned by the next [`Parser::read_record`].
+pub struct FirmwareReader<'a, F, B = GcdDefaultEndian>
+where
+    F: std::io::Read,
+    B: ByteOrder,
+{
+    parser: &'a mut Parser<F, B>,
+}
+
+impl<'a, F, B> FirmwareReader<'a, F, B>
+where
+    F: std::io::Read,
+    B: ByteOrder,
+{
+    /// Id of the firmware being read
+    pub fn id(&self) -> RecordId {
+        self.parser.firmware.id
+    }
+}
+
+impl<'a, F, B> Read for FirmwareReader<'a, F, B>
+where
+    F: std::io::Read,
+    B: ByteOrder,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.parser.firmware.chunk_left == 0 {
+            if !self.parser.next_firmware_chunk()? {
+                return Ok(0);
+            }
+        }
+        let len = buf.len().min(self.parser.firmware.chunk_left as usize);
+        let read = self.parser.file.read(&mut buf[..len])?;
+        if read == 0 {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "File ended in the middle of a Firmware Chunk",
+            ));
+        }
+        self.parser.firmware.chunk_left -= read as u16;
+        self.parser.decode_firmware(&mut buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Push style processing of a file, with [`Parser::visit`].
+///
+/// Each method is called with the record as it is read, by default doing
+/// nothing. Returning an error stops the parsing.
+pub trait RecordVisitor {
+    fn on_header(&mut self, _main: &MainRecord) -> Result<()> {
+        Ok(())
+    }
+    fn on_text(&mut self, _text: &TextRecord) -> Result<()> {
+        Ok(())
+    }
+    fn on_descriptor(&mut self, _descriptor: &DescriptorRecord) -> Result<()> {
+        Ok(())
+    }
+    /// Decoded data of a firmware chunk, `id` is the firmware id.
+    fn on_firmware_chunk(&mut self, _id: RecordId, _data: &[u8]) -> Result<()> {
+        Ok(())
+    }
+    fn on_checksum(&mut self, _checksum: &ChecksumRecord) -> Result<()> {
+        Ok(())
+    }
+    fn on_filler(&mut self, _filler: &FillerRecord) -> Result<()> {
+        Ok(())
+    }
+    /// Record not understood, only returned by a lenient parser.
+    fn on_unknown(&mut self, _id: RecordId, _data: &[u8]) -> Result<()> {
+        Ok(())
+    }
+    /// Called after the End record, the last call.
+    fn on_end(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block::list_blocks;
+    use crate::composer::{compose_all, Composer};
+    use crate::config::ParserConfig;
+    use crate::error::{GcdError, Result};
+    use crate::parser::SkippedSpan;
+    use crate::parser::{
+        parse_all, AutoParser, Endian, ParsePhase, ParseWarning, Parser,
+        ParserState,
+    };
+    use crate::record::checksum::ChecksumRecord;
+    #[cfg(feature = "crc")]
+    use crate::record::checksum::{Checksum, Crc32};
+    use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+    use crate::record::descriptor::DescriptorRecord;
+    use crate::record::firmware::FirmwareRecord;
+    use crate::record::main::MainRecord;
+    use crate::record::text::TextRecord;
+    use crate::testing::vectors;
+    use crate::{Record, RecordId, RECORD_HEADER_LEN};
+    use byteorder::BE;
+    use std::io::Read;
+
+    const XOR_KEY: u8 = 0x5a;
+
+    fn firmware() -> Vec<u8> {
+        (0..300u16).map(|x| x as u8).collect()
+    }
+
+    // file with a single xored firmware, splited in two chunks, the composer
+    // encode the firmware
+    fn compose() -> Vec<u8> {
+        let firmware = firmware();
+        let mut file = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut file).unwrap();
+        let records = [
+            Record::MainHeader(MainRecord::DefaultHWID),
+            Record::Descriptor(DescriptorRecord::Simple(vec![
+                DescriptorDecoded::FirmwareId(0x0505).encode(),
+                DescriptorDecoded::XorKey(XOR_KEY).encode(),
+                DescriptorDecoded::FirmwareLen(firmware.len() as u32).encode(),
+                DescriptorDecoded::End.encode(),
+            ])),
+            Record::FirmwareData(FirmwareRecord::new(
+                firmware[..200].to_vec(),
+                RecordId::new(0x0505),
+            )),
+            Record::Checksum(ChecksumRecord::Simple),
+            Record::FirmwareData(FirmwareRecord::new(
+                firmware[200..].to_vec(),
+                RecordId::new(0x0505),
+            )),
+            Record::Text(TextRecord::Simple("after".to_string())),
+            Record::End,
+        ];
+        for record in records.iter() {
+            composer.write_record(record).unwrap();
+        }
+        file
+    }
+
+    fn parser_at_firmware(file: &[u8]) -> Parser<&[u8]> {
+        let mut parser: Parser<&[u8]> = Parser::new(file).unwrap();
+        assert!(matches!(
+            parser.read_record().unwrap(),
+            Record::MainHeader(_)
+        ));
+        assert!(matches!(
+            parser.read_record().unwrap(),
+            Record::Descriptor(_)
+        ));
+        parser
+    }
+
+    #[test]
+    fn firmware_reader() {
+        let file = compose();
+        let mut parser = parser_at_firmware(&file);
+
+        let mut data = vec![];
+        let mut reader = parser.firmware_reader();
+        assert_eq!(reader.id(), RecordId::new(0x0505));
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, firmware());
+
+        // the record that ended the firmware is still available
+        assert_eq!(
+            parser.read_record().unwrap(),
+            Record::Text(TextRecord::Simple("after".to_string()))
+        );
+        assert_eq!(parser.read_record().unwrap(), Record::End);
+    }
+
+    #[test]
+    #[cfg(feature = "crc")]
+    fn checksums_crc() {
+        let file = compose();
+        let mut crc32 = Crc32::default();
+        crc32.update(&file);
+
+        let config = ParserConfig {
+            crc: true,
+            ..ParserConfig::default()
+        };
+        let mut parser: Parser<_> =
+            Parser::with_config(&file[..], config).unwrap();
+        while parser.read_record().unwrap() != Record::End {}
+        let checksums = parser.checksums();
+        assert_eq!(checksums.crc32.map(|x| x.value()), Some(crc32.value()));
+        assert!(checksums.crc16.is_some());
+        //not calculated by default
+        let parser: Parser<_> = Parser::new(&file[..]).unwrap();
+        assert_eq!(parser.checksums().crc32, None);
+    }
+
+    #[test]
+    #[cfg(not(feature = "crc"))]
+    fn checksums_crc_disabled() {
+        let file = compose();
+        let config = ParserConfig {
+            crc: true,
+            ..ParserConfig::default()
+        };
+        assert!(Parser::<_>::with_config(&file[..], config).is_err());
+    }
+
+    #[test]
+    fn firmware_reader_partial() {
+        let file = compose();
+        let mut parser = parser_at_firmware(&file);
+
+        let mut data = [0u8; 10];
+        parser.firmware_reader().read_exact(&mut data).unwrap();
+        assert_eq!(&data[..], &firmware()[..10]);
+
+        // the rest of the chunk is returned as a record
+        assert_eq!(
+            parser.read_record().unwrap(),
+            Record::FirmwareData(FirmwareRecord::new(
+                firmware()[10..200].to_vec(),
+                RecordId::new(0x0505)
+            ))
+        );
+    }
+
+    #[test]
+    fn config() {
+        //corrupt the checksum
+        let mut file = crate::testing::vectors::SINGLE_BLOCK.to_vec();
+        let checksum = file.len() - 5;
+        file[checksum] ^= 0xff;
+        let read_all = |config: ParserConfig| -> Result<Vec<Record>> {
+            let mut parser: Parser<&[u8]> =
+                Parser::with_config(&file[..], config)?;
+            (0..5).map(|_| parser.read_record()).collect()
+        };
+        assert!(matches!(
+            read_all(ParserConfig::default()),
+            Err(GcdError::ChecksumMismatch { .. })
+        ));
+        let config = ParserConfig {
+            verify_checksums: false,
+            ..Default::default()
+        };
+        assert_eq!(read_all(config).unwrap()[4], Record::End);
+
+        //firmware as stored in the file
+        let file = compose();
+        let config = ParserConfig {
+            decode_firmware: false,
+            ..Default::default()
+        };
+        let mut parser: Parser<&[u8]> =
+            Parser::with_config(&file[..], config).unwrap();
+        parser.read_record().unwrap();
+        parser.read_record().unwrap();
+        let mut data = vec![];
+        parser.firmware_reader().read_to_end(&mut data).unwrap();
+        assert_eq!(data[1], firmware()[1] ^ XOR_KEY);
+    }
+
+    #[test]
+    fn unknown_records() {
+        let firmware = firmware();
+        let records = vec![
+            Record::MainHeader(MainRecord::DefaultHWID),
+            Record::Unknown {
+                id: RecordId::new(0x1234),
+                data: b"unknown record".to_vec(),
+            },
+            Record::Descriptor(DescriptorRecord::Simple(vec![
+                DescriptorDecoded::FirmwareId(0x0505).encode(),
+                DescriptorDecoded::FirmwareLen(firmware.len() as u32).encode(),
+                DescriptorDecoded::End.encode(),
+            ])),
+            Record::FirmwareData(FirmwareRecord::new(
+                firmware.clone(),
+                RecordId::new(0x0505),
+            )),
+            //not a chunk of this block
+            Record::Unknown {
+                id: RecordId::new(0x0506),
+                data: vec![0, 1, 2],
+            },
+            Record::End,
+        ];
+        let file = compose_all(&records).unwrap();
+        assert!(matches!(
+            parse_all(&file),
+            Err(GcdError::UnexpectedRecord { .. })
+        ));
+
+        let mut parser: Parser<&[u8]> = Parser::new_lenient(&file[..]).unwrap();
+        let parsed: Result<Vec<_>> = (&mut parser).collect();
+        assert_eq!(parsed.unwrap(), records);
+        assert!(matches!(
+            parser.warnings(),
+            [
+                ParseWarning {
+                    error: GcdError::UnexpectedRecord { .. },
+                    ..
+                },
+                ParseWarning {
+                    error: GcdError::FirmwareIdMismatch {
+                        expected: 0x0505,
+                        found: 0x0506
+                    },
+                    ..
+                },
+            ]
+        ));
+
+        assert_eq!(records[0].hexdump().map(|x| x.to_string()), None);
+        let dump = records[1].hexdump().unwrap().to_string();
+        assert!(dump.starts_with("00000000  75 6e 6b 6e 6f 77 6e 20"));
+        assert!(dump.ends_with("  |unknown record|\n"));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_events() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record as SpanRecord};
+        use tracing::{Event, Metadata, Subscriber};
+
+        // collect the message of each event
+        struct Messages(Arc<Mutex<Vec<String>>>);
+        impl Visit for Messages {
+            fn record_debug(&mut self, field: &Field, x: &dyn std::fmt::Debug) {
+                if field.name() == "message" {
+                    self.0.lock().unwrap().push(format!("{:?}", x));
+                }
+            }
+        }
+        struct Collect(Arc<Mutex<Vec<String>>>);
+        impl Subscriber for Collect {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, _: &SpanRecord<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                event.record(&mut Messages(Arc::clone(&self.0)));
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        //corrupt the checksum
+        let mut file = crate::testing::vectors::SINGLE_BLOCK.to_vec();
+        let checksum = file.len() - 5;
+        file[checksum] ^= 0xff;
+        let messages = Arc::new(Mutex::new(vec![]));
+        let collect = Collect(Arc::clone(&messages));
+        tracing::subscriber::with_default(collect, || {
+            assert!(parse_all(&file).is_err());
+            let mut parser: Parser<&[u8]> =
+                Parser::new_lenient(&file[..]).unwrap();
+            parser.by_ref().for_each(|x| drop(x.unwrap()));
+        });
+        let messages = messages.lock().unwrap();
+        for expected in [
+            "parser phase",
+            "record",
+            "checksum verified",
+            "record failed",
+            "recovered",
+        ] {
+            assert!(messages.iter().any(|x| x == expected), "{}", expected);
+        }
+    }
+
+    #[test]
+    fn limits() {
+        //two blocks of 1000 bytes, in chunks of 300 bytes
+        let file = crate::gcd_file::tests::compose();
+        let read_all = |config: ParserConfig| -> Result<Vec<Record>> {
+            let mut parser: Parser<&[u8]> =
+                Parser::with_config(&file[..], config)?;
+            (&mut parser).collect()
+        };
+        let config = ParserConfig {
+            max_record_len: Some(300),
+            max_total_firmware_len: Some(2000),
+            ..ParserConfig::default()
+        };
+        assert!(read_all(config.clone()).is_ok());
+
+        let small = ParserConfig {
+            max_record_len: Some(299),
+            ..config.clone()
+        };
+        assert!(matches!(
+            read_all(small),
+            Err(GcdError::LimitExceeded {
+                limit: "Record",
+                max: 299
+            })
+        ));
+        //the second Descriptor is refused before reading the data
+        let small = ParserConfig {
+            max_total_firmware_len: Some(1999),
+            ..config
+        };
+        let mut parser: Parser<&[u8]> =
+            Parser::with_config(&file[..], small).unwrap();
+        let records: Vec<_> = (&mut parser).collect();
+        let received: usize = records
+            .iter()
+            .filter_map(|x| match x {
+                Ok(Record::FirmwareData(chunk)) => Some(chunk.len() as usize),
+                _ => None,
+            })
+            .sum();
+        assert_eq!(received, 1000);
+        assert!(matches!(
+            records.last(),
+            Some(Err(GcdError::LimitExceeded {
+                limit: "Firmware",
+                max: 1999
+            }))
+        ));
+    }
+
+    #[test]
+    fn main_headers() {
+        let firmware = firmware();
+        let mut records = vec![
+            Record::MainHeader(MainRecord::DefaultPartNumber),
+            Record::Text(TextRecord::Simple("between".to_string())),
+            Record::MainHeader(MainRecord::DefaultHWID),
+            Record::Descriptor(DescriptorRecord::Simple(vec![
+                DescriptorDecoded::FirmwareId(0x0505).encode(),
+                DescriptorDecoded::FirmwareLen(firmware.len() as u32).encode(),
+                DescriptorDecoded::End.encode(),
+            ])),
+            Record::FirmwareData(FirmwareRecord::new(
+                firmware,
+                RecordId::new(0x0505),
+            )),
+            Record::End,
+        ];
+        let file = compose_all(&records).unwrap();
+        assert_eq!(parse_all(&file).unwrap(), records);
+        let gcd: crate::GcdFile = crate::GcdFile::from_bytes(file).unwrap();
+        assert_eq!(
+            gcd.main_headers(),
+            vec![&MainRecord::DefaultPartNumber, &MainRecord::DefaultHWID]
+        );
+
+        //the state keep the count
+        let file = compose_all(&records).unwrap();
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        parser.read_record().unwrap();
+        let state = parser.save_state();
+        let position = state.position() as usize;
+        let config = ParserConfig {
+            max_main_headers: 1,
+            ..ParserConfig::default()
+        };
+        let state = ParserState { config, ..state };
+        let mut parser = Parser::<_>::resume(&file[position..], state);
+        parser.read_record().unwrap();
+        assert!(matches!(
+            parser.read_record(),
+            Err(GcdError::UnexpectedRecord { .. })
+        ));
+
+        //more than the max is refused
+        records.insert(2, Record::MainHeader(MainRecord::HWID(0x0036)));
+        let file = compose_all(&records).unwrap();
+        assert!(matches!(
+            parse_all(&file),
+            Err(GcdError::UnexpectedRecord { .. })
+        ));
+        let config = ParserConfig {
+            max_main_headers: 3,
+            ..ParserConfig::default()
+        };
+        let parser: Parser<&[u8]> =
+            Parser::with_config(&file[..], config).unwrap();
+        let parsed: Vec<_> = parser.map(|x| x.unwrap()).collect();
+        assert_eq!(parsed, records);
+    }
+
+    #[test]
+    fn iterator() {
+        let file = compose();
+        let parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        let records = parser.collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(records.len(), 7);
+        assert_eq!(records.last(), Some(&Record::End));
+
+        //stop at the first error
+        let parser: Parser<&[u8]> =
+            Parser::new(&file[..file.len() - 1]).unwrap();
+        let records: Vec<_> = parser.collect();
+        assert_eq!(records.len(), 7);
+        assert!(records[6].is_err());
+    }
+
+    #[test]
+    fn finish() {
+        use crate::testing::vectors::SINGLE_BLOCK;
+
+        let mut file = SINGLE_BLOCK.to_vec();
+        file.extend_from_slice(b"SIG");
+        for deny_trailing_data in [false, true] {
+            let config = ParserConfig {
+                deny_trailing_data,
+                ..ParserConfig::default()
+            };
+            let mut parser: Parser<&[u8]> =
+                Parser::with_config(&file[..], config.clone()).unwrap();
+            assert!(parser.finish().is_err());
+            while parser.read_record().unwrap() != Record::End {}
+            match parser.finish() {
+                Ok(trailing) if !deny_trailing_data => {
+                    assert_eq!(trailing, b"SIG")
+                }
+                Err(GcdError::TrailingData(3)) if deny_trailing_data => {}
+                x => panic!("unexpected result {:?}", x),
+            }
+            assert_eq!(parser.position(), file.len() as u64);
+
+            //the End is the last record
+            let mut parser: Parser<&[u8]> =
+                Parser::with_config(SINGLE_BLOCK, config).unwrap();
+            while parser.read_record().unwrap() != Record::End {}
+            assert_eq!(parser.finish().unwrap(), b"");
+        }
+    }
+
+    #[test]
+    fn read_firmware_decompressed() {
+        use crate::record::firmware::transform::Decompressor;
+
+        //"decompress" the data starting with [0, 1, 2] reversing it
+        let mut decompressor = Decompressor::empty();
+        decompressor.push(|data| {
+            data.starts_with(&[0, 1, 2])
+                .then(|| Ok(data.iter().rev().copied().collect()))
+        });
+        let file = compose();
+        let mut parser = parser_at_firmware(&file);
+        parser.set_decompressor(Some(decompressor.clone()));
+        let mut data = vec![];
+        assert_eq!(parser.read_firmware_into(&mut data).unwrap(), 300);
+        let expected: Vec<u8> = firmware().into_iter().rev().collect();
+        assert_eq!(data, expected);
+        assert_eq!(
+            parser.read_record().unwrap(),
+            Record::Text(TextRecord::Simple("after".to_string()))
+        );
+
+        //limited decompressed len
+        decompressor.set_max_len(Some(299));
+        let mut parser = parser_at_firmware(&file);
+        parser.set_decompressor(Some(decompressor));
+        assert!(matches!(
+            parser.read_firmware_into(std::io::sink()),
+            Err(GcdError::LimitExceeded { max: 299, .. })
+        ));
+    }
+
+    #[test]
+    fn read_block() {
+        let file = compose();
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        let block = parser.read_block().unwrap().unwrap();
+        assert_eq!(block.descriptor.xor_key(), Some(XOR_KEY));
+        assert_eq!(block.data.id, RecordId::new(0x0505));
+        assert_eq!(block.data.data, firmware());
+        assert_eq!(block.chunk_layout(), [200, 100]);
+        assert_eq!(block.texts, [TextRecord::Simple("after".to_string())]);
+        assert_eq!(block.region(), Some(14));
+        assert_eq!(parser.read_block().unwrap(), None);
+        assert_eq!(parser.read_block().unwrap(), None);
+        assert!(parser.finish().unwrap().is_empty());
+
+        //same blocks as the records
+        for &(_, data) in vectors::ALL {
+            let records = parse_all(data).unwrap();
+            let infos = list_blocks(&records);
+            let mut parser: Parser<&[u8]> = Parser::new(data).unwrap();
+            let mut blocks = vec![];
+            while let Some(block) = parser.read_block().unwrap() {
+                blocks.push(block);
+            }
+            assert_eq!(blocks.len(), infos.len());
+            for (block, info) in blocks.iter().zip(infos) {
+                assert_eq!(block.descriptor.firmware_id(), info.id);
+                assert_eq!(u64::from(block.len()), info.data_len);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_blocks() {
+        let data = vectors::EMPTY_BLOCKS;
+        let mut parser: Parser<&[u8]> = Parser::new(data).unwrap();
+        let mut blocks = vec![];
+        while let Some(block) = parser.read_block().unwrap() {
+            blocks.push(block);
+        }
+        let layouts: Vec<_> = blocks.iter().map(|x| x.chunk_layout()).collect();
+        assert_eq!(layouts, [&[][..], &[8], &[0]]);
+        assert!(blocks[0].is_empty() && blocks[2].is_empty());
+
+        //the blocks are composed with the same records
+        let mut file = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut file).unwrap();
+        composer
+            .write_record(&Record::MainHeader(MainRecord::DefaultHWID))
+            .unwrap();
+        for block in &blocks {
+            composer.write_block(block).unwrap();
+        }
+        composer
+            .write_record(&Record::Checksum(ChecksumRecord::Simple))
+            .unwrap();
+        composer.write_record(&Record::End).unwrap();
+        drop(composer);
+        assert_eq!(file, data);
+
+        //the reader of an empty block consume the empty chunk
+        let mut parser: Parser<&[u8]> = Parser::new(data).unwrap();
+        let mut firmwares = vec![];
+        loop {
+            match parser.read_record().unwrap() {
+                Record::Descriptor(_) => {
+                    let mut firmware = vec![];
+                    parser.read_firmware_into(&mut firmware).unwrap();
+                    firmwares.push(firmware);
+                }
+                Record::End => break,
+                _ => {}
+            }
+        }
+        assert_eq!(firmwares, [vec![], b"firmware".to_vec(), vec![]]);
+    }
+
+    #[test]
+    fn skip_firmware_except() {
+        let read_blocks = |ids: &[u16]| {
+            let mut parser: Parser<&[u8]> =
+                Parser::new(vectors::MULTI_BLOCK).unwrap();
+            parser.skip_firmware_except(ids);
+            let mut blocks = vec![];
+            while let Some(block) = parser.read_block().unwrap() {
+                blocks.push((block.data.data, block.chunks));
+            }
+            assert!(parser.finish().unwrap().is_empty());
+            blocks
+        };
+        assert_eq!(
+            read_blocks(&[0x0506]),
+            [(vec![], vec![]), (b"font".to_vec(), vec![4])]
+        );
+        assert_eq!(
+            read_blocks(&[0x0505]),
+            [(b"firmware".to_vec(), vec![4, 4]), (vec![], vec![])]
+        );
+        assert_eq!(read_blocks(&[]), [(vec![], vec![]), (vec![], vec![])]);
+
+        //the skipped chunks are not returned as records
+        let mut parser: Parser<&[u8]> =
+            Parser::new(vectors::MULTI_BLOCK).unwrap();
+        parser.skip_firmware_except(&[0x0506]);
+        let chunks: Vec<_> = parser
+            .map(|x| x.unwrap())
+            .filter_map(|x| match x {
+                Record::FirmwareData(x) => Some(x.id()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(chunks, [RecordId::new(0x0506)]);
+
+        //the checksums still cover the skipped data
+        let mut file = vectors::MULTI_BLOCK.to_vec();
+        //a byte of the skipped firmware
+        file[44] ^= 0xff;
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        parser.skip_firmware_except(&[0x0506]);
+        assert!(matches!(
+            parser.find_map(|x| x.err()),
+            Some(GcdError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn open() {
+        let path = std::env::temp_dir()
+            .join(format!("gcd-rs-open-{}.gcd", std::process::id()));
+        std::fs::write(&path, vectors::MULTI_BLOCK).unwrap();
+        let parser: Parser<_> = Parser::open(&path).unwrap();
+        let records = parser.collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(records, parse_all(vectors::MULTI_BLOCK).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            Parser::<_>::open(&path),
+            Err(GcdError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound
+        ));
+    }
+
+    #[test]
+    fn new_autodetect() {
+        let records = parse_all(vectors::MULTI_BLOCK).unwrap();
+        let mut file = vec![];
+        let mut composer: Composer<_, BE> = Composer::new(&mut file).unwrap();
+        records.iter().for_each(|x| composer.write_record(x).unwrap());
+        drop(composer);
+
+        fn autodetect(data: &[u8]) -> Result<AutoParser<&[u8]>> {
+            Parser::new_autodetect(data, ParserConfig::default())
+        }
+        let parser = autodetect(vectors::MULTI_BLOCK).unwrap();
+        assert_eq!(parser.endian(), Endian::Little);
+        assert_eq!(parser.collect::<Result<Vec<_>>>().unwrap(), records);
+        let parser = autodetect(&file[..]).unwrap();
+        assert_eq!(parser.endian(), Endian::Big);
+        assert_eq!(parser.collect::<Result<Vec<_>>>().unwrap(), records);
+
+        //the methods of the parser are available in both endians
+        for data in [vectors::MULTI_BLOCK, &file[..]].iter() {
+            let mut parser = autodetect(data).unwrap();
+            let (record, meta) = parser.read_record_with_meta().unwrap();
+            assert_eq!(record, records[0]);
+            assert_eq!(meta.header_offset, 8);
+            while parser.state() != ParsePhase::FirmwareData {
+                parser.read_record().unwrap();
+            }
+            let mut data = vec![];
+            parser.firmware_reader().read_to_end(&mut data).unwrap();
+            assert!(!data.is_empty());
+            while parser.read_record().unwrap() != Record::End {}
+            assert_eq!(parser.finish().unwrap(), b"");
+        }
+
+        //the config is used
+        let config = ParserConfig {
+            report_checksums: true,
+            ..ParserConfig::default()
+        };
+        let parser = Parser::new_autodetect(&file[..], config.clone());
+        assert_eq!(parser.unwrap().config(), &config);
+
+        //the endian with the known version is selected, the parser fail
+        let mut invalid = vectors::MULTI_BLOCK.to_vec();
+        invalid[8..12].copy_from_slice(&[0x06, 0x00, 0x00, 0x00]);
+        let mut parser = autodetect(&invalid[..]).unwrap();
+        assert_eq!(parser.endian(), Endian::Little);
+        assert!(parser.read_record().is_err());
+        invalid[6..8].copy_from_slice(&[0x65, 0x00]);
+        assert!(matches!(
+            autodetect(&invalid[..]),
+            Err(GcdError::UnknownVersion(0x65))
+        ));
+        assert!(matches!(
+            autodetect(&b"GARMAN\x64\x00"[..]),
+            Err(GcdError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn read_firmware_into() {
+        let file = compose();
+        let mut parser = parser_at_firmware(&file);
+        let mut data = vec![];
+        assert_eq!(parser.read_firmware_into(&mut data).unwrap(), 300);
+        assert_eq!(data, firmware());
+        assert_eq!(
+            parser.read_record().unwrap(),
+            Record::Text(TextRecord::Simple("after".to_string()))
+        );
+    }
+
+    #[test]
+    fn lenient() {
+        let mut file = crate::testing::vectors::FILLERS_CHECKSUMS.to_vec();
+        //non-zero filler, HWID 0x0036 and FirmwareLen 6, the sum of the
+        //changes is zero, so the checksums are still valid
+        file[25] = 1;
+        file[26] = 2;
+        file[33] = 0x36;
+        file[55] = 6;
+
+        let strict: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        let records: Vec<_> = strict.collect();
+        assert!(records.last().unwrap().is_err());
+
+        let mut parser: Parser<&[u8]> = Parser::new_lenient(&file[..]).unwrap();
+        let records = parser.by_ref().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(records.len(), 11);
+        assert_eq!(records[3], Record::MainHeader(MainRecord::HWID(0x36)));
+        let warnings: Vec<_> =
+            parser.warnings().iter().map(|x| x.offset).collect();
+        assert_eq!(warnings, [21, 78]);
+        assert!(matches!(
+            parser.warnings()[1].error,
+            GcdError::FirmwareOverflow { expected: 6 }
+        ));
+    }
+
+    #[test]
+    fn recover() {
+        let original = crate::testing::vectors::MULTI_BLOCK;
+        let expected = parse_all(original).unwrap();
+        let config = ParserConfig {
+            recover: true,
+            ..ParserConfig::default()
+        };
+        //a chunk header with a huge len and garbage, before the second
+        //chunk, the sum is zero, so the checksum is still valid
+        let mut file = original.to_vec();
+        file.splice(46..46, [0x05, 0x05, 0xff, 0x7f, 0x78]);
+        assert!(parse_all(&file).is_err());
+
+        let mut parser: Parser<&[u8]> =
+            Parser::with_config(&file[..], config.clone()).unwrap();
+        let records = parser.by_ref().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(records, expected);
+        assert_eq!(parser.skipped(), [SkippedSpan { offset: 46, len: 5 }]);
+        assert_eq!(parser.warnings().len(), 1);
+        assert_eq!(parser.warnings()[0].offset, 46);
+        assert_eq!(parser.position(), file.len() as u64);
+
+        //the raw bytes are of the record found
+        let mut parser: Parser<&[u8]> =
+            Parser::with_config(&file[..], config.clone()).unwrap();
+        for _ in 0..3 {
+            parser.read_record().unwrap();
+        }
+        let raw = parser.read_record_with_raw().unwrap();
+        assert_eq!(raw.record, expected[3]);
+        assert_eq!(raw.raw, &original[46..54]);
+
+        //a corrupted DescriptorType id, the second block is lost
+        let mut file = original.to_vec();
+        file[54] = 0x09;
+        let mut parser: Parser<&[u8]> =
+            Parser::with_config(&file[..], config.clone()).unwrap();
+        let records = parser.by_ref().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(records[..4], expected[..4]);
+        assert!(matches!(records[4], Record::Unknown { id, .. }
+            if id == RecordId::new(9)));
+        assert_eq!(records[5..], expected[6..]);
+        assert_eq!(
+            parser.skipped(),
+            [SkippedSpan {
+                offset: 66,
+                len: 20
+            }]
+        );
+        let warnings: Vec<_> =
+            parser.warnings().iter().map(|x| x.offset).collect();
+        assert_eq!(warnings, [54, 66, 86]);
+
+        //truncated, no record to continue from
+        let file = &original[..original.len() - 10];
+        let parser: Parser<&[u8]> = Parser::with_config(file, config).unwrap();
+        let records: Vec<_> = parser.collect();
+        assert!(records.last().unwrap().is_err());
+    }
+
+    #[test]
+    fn record_meta() {
+        let file = crate::testing::vectors::FILLERS_CHECKSUMS;
+        let mut parser: Parser<&[u8]> = Parser::new(file).unwrap();
+        assert_eq!(parser.position(), 8);
+        let mut metas = vec![];
+        loop {
+            let (record, meta) = parser.read_record_with_meta().unwrap();
+            assert_eq!(meta.offset, meta.header_offset + 4);
+            metas.push((meta.header_offset, meta.body_len));
+            if record == Record::End {
+                break;
+            }
+        }
+        //the descriptor is the DescriptorData at 47
+        assert_eq!(
+            metas,
+            [
+                (8, 4),
+                (16, 1),
+                (21, 4),
+                (29, 2),
+                (47, 8),
+                (59, 4),
+                (67, 1),
+                (72, 2),
+                (78, 4),
+                (86, 1),
+                (91, 0),
+            ]
+        );
+        assert_eq!(parser.position(), file.len() as u64);
+    }
+
+    #[test]
+    fn report_checksums() {
+        let mut file = crate::testing::vectors::FILLERS_CHECKSUMS.to_vec();
+        //the checksum at 67 is wrong, the one at 86 compensate it
+        let original = file[71];
+        file[71] = original.wrapping_add(1);
+        file[90] = file[90].wrapping_sub(1);
+        let config = ParserConfig {
+            report_checksums: true,
+            ..Default::default()
+        };
+        let mut parser: Parser<&[u8]> =
+            Parser::with_config(&file[..], config).unwrap();
+        let mut checksums = vec![];
+        loop {
+            match parser.read_record_with_meta().unwrap() {
+                (Record::Checksum(checksum), meta) => {
+                    checksums.push((meta.header_offset, checksum))
+                }
+                (Record::End, _) => break,
+                _ => {}
+            }
+        }
+        assert_eq!(checksums.len(), 3);
+        assert!(matches!(
+            checksums[0].1,
+            ChecksumRecord::Verified { ok: true, .. }
+        ));
+        assert_eq!(
+            checksums[1],
+            (
+                67,
+                ChecksumRecord::Verified {
+                    ok: false,
+                    expected: original,
+                    found: original.wrapping_add(1),
+                }
+            )
+        );
+        assert!(matches!(
+            checksums[2].1,
+            ChecksumRecord::Verified { ok: true, .. }
+        ));
+        //composed as a valid checksum
+        let mut output = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut output).unwrap();
+        for (_, checksum) in checksums.iter() {
+            composer
+                .write_record(&Record::Checksum(checksum.clone()))
+                .unwrap();
+        }
+        drop(composer);
+        let sum = output[..13].iter().fold(0u8, |x, y| x.wrapping_add(*y));
+        assert_eq!(sum, 0);
+    }
+
+    #[test]
+    fn unknown_len() {
+        let firmware = firmware();
+        let mut file = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut file).unwrap();
+        let mut records = vec![Record::MainHeader(MainRecord::DefaultHWID)];
+        for id in [0x0505u16, 0x0506] {
+            records.push(Record::Descriptor(DescriptorRecord::Simple(vec![
+                DescriptorDecoded::FirmwareId(id).encode(),
+                DescriptorDecoded::End.encode(),
+            ])));
+            records.extend(firmware.chunks(200).map(|x| {
+                Record::FirmwareData(FirmwareRecord::new(
+                    x.to_vec(),
+                    RecordId::new(id),
+                ))
+            }));
+        }
+        records.push(Record::End);
+        for record in records.iter() {
+            composer.write_record(record).unwrap();
+        }
+        drop(composer);
+
+        assert!(matches!(
+            parse_all(&file),
+            Err(GcdError::MissingDescriptor(_))
+        ));
+        let config = ParserConfig {
+            allow_unknown_len: true,
+            ..Default::default()
+        };
+        let parser: Parser<&[u8]> =
+            Parser::with_config(&file[..], config.clone()).unwrap();
+        let parsed: Vec<Record> = parser.collect::<Result<_>>().unwrap();
+        assert_eq!(parsed, records);
+
+        //the reader stop at the next descriptor
+        let mut parser: Parser<&[u8]> =
+            Parser::with_config(&file[..], config).unwrap();
+        parser.read_record().unwrap();
+        parser.read_record().unwrap();
+        let mut data = vec![];
+        parser.read_firmware_into(&mut data).unwrap();
+        assert_eq!(data, firmware);
+        assert!(parser.warnings().is_empty());
+    }
+
+    #[test]
+    fn resume() {
+        use super::{ParsePhase, ParserState};
+        use crate::gcd_file::tests::{compose, firmware};
+
+        let file = compose();
+        let records = parse_all(&file).unwrap();
+        for i in 0..records.len() - 1 {
+            let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+            for _ in 0..=i {
+                parser.read_record().unwrap();
+            }
+            let state = serde_yaml::to_string(&parser.save_state()).unwrap();
+            let state: ParserState = serde_yaml::from_str(&state).unwrap();
+            assert_eq!(state.phase(), parser.state());
+            let rest = &file[state.position() as usize..];
+            let parser: Parser<&[u8]> = Parser::resume(rest, state);
+            let parsed: Vec<Record> = parser.collect::<Result<_>>().unwrap();
+            assert_eq!(&parsed[..], &records[i + 1..]);
+        }
+
+        //in the middle of a firmware chunk
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        assert_eq!(parser.state(), ParsePhase::TextGlobal);
+        while !matches!(parser.read_record().unwrap(), Record::Descriptor(_)) {}
+        assert_eq!(parser.state(), ParsePhase::DescriptorData);
+        let mut data = vec![0; 150];
+        parser.firmware_reader().read_exact(&mut data).unwrap();
+        let state = parser.save_state();
+        let rest = &file[state.position() as usize..];
+        let mut parser: Parser<&[u8]> = Parser::resume(rest, state);
+        parser.read_firmware_into(&mut data).unwrap();
+        assert_eq!(data, firmware(0x0505));
+        let parsed: Vec<Record> = parser.collect::<Result<_>>().unwrap();
+        assert_eq!(parsed.last(), Some(&Record::End));
+    }
+
+    #[test]
+    fn raw_records() {
+        use crate::record::filler::FillerRecord;
+
+        for (name, data) in crate::testing::vectors::ALL.iter() {
+            let mut parser: Parser<&[u8]> = Parser::new(*data).unwrap();
+            let mut file = vec![];
+            let mut composer: Composer<_> = Composer::new(&mut file).unwrap();
+            loop {
+                let raw = parser.read_record_with_raw().unwrap();
+                composer.write_raw_record(&raw).unwrap();
+                if raw.record == Record::End {
+                    break;
+                }
+            }
+            drop(composer);
+            assert_eq!(&file, data, "{}", name);
+        }
+
+        //the filler content is normalized to zeros
+        let mut file = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut file).unwrap();
+        composer
+            .write_record_raw(RecordId::FILLER, &[1, 2, 3])
+            .unwrap();
+        drop(composer);
+        let mut parser: Parser<&[u8]> = Parser::new_lenient(&file[..]).unwrap();
+        let raw = parser.read_record_with_raw().unwrap();
+        assert_eq!(raw.record, Record::Filler(FillerRecord::Zeros(3)));
+        assert_eq!(&raw.raw[..], &file[8..]);
+
+        //the header read by the firmware reader is included
+        let file = compose();
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        parser.read_record().unwrap();
+        parser.read_record().unwrap();
+        parser.read_firmware_into(std::io::sink()).unwrap();
+        let start = parser.position() as usize - RECORD_HEADER_LEN;
+        let raw = parser.read_record_with_raw().unwrap();
+        let end = parser.position() as usize;
+        assert_eq!(
+            raw.record,
+            Record::Text(TextRecord::Simple("after".into()))
+        );
+        assert_eq!(&raw.raw[..], &file[start..end]);
+    }
+
+    #[test]
+    fn custom_transforms() {
+        use crate::record::firmware::transform::{Transform, TransformTable};
+        use crate::record::firmware::FONT_ID;
+
+        let data = firmware();
+        let records = vec![
+            Record::MainHeader(MainRecord::DefaultHWID),
+            Record::Descriptor(DescriptorRecord::Simple(vec![
+                DescriptorDecoded::FirmwareId(FONT_ID).encode(),
+                DescriptorDecoded::FirmwareLen(data.len() as u32).encode(),
+                DescriptorDecoded::End.encode(),
+            ])),
+            Record::FirmwareData(FirmwareRecord::new(
+                data.clone(),
+                RecordId::new(FONT_ID),
+            )),
+            Record::End,
+        ];
+        let mut table = TransformTable::empty();
+        let negate = |x: &mut [u8]| x.iter_mut().for_each(|x| *x = !*x);
+        table.insert(FONT_ID, Transform::new(negate, negate));
+
+        let mut file = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut file).unwrap();
+        composer.set_transforms(table.clone());
+        records
+            .iter()
+            .for_each(|x| composer.write_record(x).unwrap());
+        drop(composer);
+        let stored: Vec<u8> = data.iter().map(|x| !x).collect();
+        assert!(file.windows(stored.len()).any(|x| x == stored));
+
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        parser.set_transforms(table);
+        let parsed: Vec<Record> =
+            parser.by_ref().collect::<Result<_>>().unwrap();
+        assert_eq!(parsed, records);
+        //the default font rule don't match
+        assert_ne!(parse_all(&file).unwrap(), records);
+    }
+
+    #[test]
+    fn visit() {
+        use super::RecordVisitor;
+        use crate::gcd_file::tests::{compose, firmware};
+
+        #[derive(Default)]
+        struct Collect {
+            records: Vec<Record>,
+            // firmware id and the concatenated data
+            blocks: Vec<(u16, Vec<u8>)>,
+        }
+        impl RecordVisitor for Collect {
+            fn on_text(&mut self, text: &TextRecord) -> Result<()> {
+                self.records.push(Record::Text(text.clone()));
+                Ok(())
+            }
+            fn on_descriptor(
+                &mut self,
+                descriptor: &DescriptorRecord,
+            ) -> Result<()> {
+                let id = descriptor.firmware_id().unwrap();
+                self.blocks.push((id, vec![]));
+                self.records.push(Record::Descriptor(descriptor.clone()));
+                Ok(())
+            }
+            fn on_firmware_chunk(
+                &mut self,
+                id: RecordId,
+                data: &[u8],
+            ) -> Result<()> {
+                let block = self.blocks.last_mut().unwrap();
+                assert_eq!(block.0, id.get());
+                block.1.extend_from_slice(data);
+                Ok(())
+            }
+            fn on_end(&mut self) -> Result<()> {
+                self.records.push(Record::End);
+                Ok(())
+            }
+        }
+
+        let file = compose();
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        let mut collect = Collect::default();
+        parser.visit(&mut collect).unwrap();
+        let expected: Vec<Record> = parse_all(&file)
+            .unwrap()
+            .into_iter()
+            .filter(|x| {
+                matches!(
+                    x,
+                    Record::Text(_) | Record::Descriptor(_) | Record::End
+                )
+            })
+            .collect();
+        assert_eq!(collect.records, expected);
+        assert_eq!(
+            collect.blocks,
+            vec![(0x0505, firmware(0x0505)), (0x0506, firmware(0x0506))]
+        );
+        assert!(parser.read_record().is_err());
+
+        //the visitor can stop the parsing
+        struct Stop;
+        impl RecordVisitor for Stop {
+            fn on_descriptor(&mut self, _: &DescriptorRecord) -> Result<()> {
+                Err(GcdError::MissingDescriptor("Stop"))
+            }
+        }
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        assert!(matches!(
+            parser.visit(&mut Stop),
+            Err(GcdError::MissingDescriptor("Stop"))
+        ));
+    }
+
+    #[test]
+    fn multi_part_firmware() {
+        let firmware = firmware();
+        let records = |lens: &[DescriptorDecoded], chunk: usize| {
+            let mut descs = vec![DescriptorDecoded::FirmwareId(0x2000)];
+            descs.extend_from_slice(lens);
+            descs.push(DescriptorDecoded::End);
+            let mut records = vec![
+                Record::MainHeader(MainRecord::DefaultHWID),
+                Record::Descriptor(DescriptorRecord::Simple(
+                    descs.into_iter().map(|x| x.encode()).collect(),
+                )),
+            ];
+            let mut part_idx = 0;
+            let mut part_left = 150;
+            for data in firmware.chunks(chunk) {
+                records.push(Record::FirmwareData(FirmwareRecord::new_part(
+                    data.to_vec(),
+                    RecordId::new(0x2000),
+                    part_idx,
+                )));
+                part_left -= data.len().min(part_left);
+                if part_left == 0 {
+                    part_idx += 1;
+                    part_left = 75;
+                }
+            }
+            records.push(Record::End);
+            records
+        };
+        let lens = [
+            DescriptorDecoded::Firmware2000P1Len(150),
+            DescriptorDecoded::Firmware2000P2Len(75),
+            DescriptorDecoded::Firmware2000P3Len(75),
+        ];
+        let expected = records(&lens, 75);
+        let file = compose_all(&expected).unwrap();
+        assert_eq!(parse_all(&file).unwrap(), expected);
+
+        //all the parts are required
+        let file = compose_all(&records(&lens[..2], 75)).unwrap();
+        assert!(matches!(
+            parse_all(&file),
+            Err(GcdError::MissingDescriptor("Firmware2000P3Len"))
+        ));
+        //a chunk in two parts
+        let file = compose_all(&records(&lens, 90)).unwrap();
+        assert!(parse_all(&file).is_err());
+        let config = ParserConfig {
+            strict: false,
+            ..Default::default()
+        };
+        let mut parser: Parser<&[u8]> =
+            Parser::with_config(&file[..], config).unwrap();
+        let parsed: Vec<Record> =
+            parser.by_ref().collect::<Result<_>>().unwrap();
+        let parts: Vec<_> = parsed
+            .iter()
+            .filter_map(|x| match x {
+                Record::FirmwareData(x) => x.part_idx(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(parts, vec![0, 0, 1, 2]);
+        assert_eq!(parser.warnings().len(), 2);
+    }
+
+    #[test]
+    fn descriptor_without_end() {
+        use crate::GcdDefaultEndian;
+        let descriptor = DescriptorRecord::Simple(vec![
+            DescriptorDecoded::FirmwareId(0x0505).encode(),
+            DescriptorDecoded::FirmwareLen(0).encode(),
+        ]);
+        let (type_len, data_len) = descriptor.record_lens().unwrap();
+        let mut raw = vec![
+            0;
+            type_len.as_usize()
+                + data_len.as_usize()
+                + RECORD_HEADER_LEN * 2
+        ];
+        let data_raw = descriptor
+            .record_type_to_raw::<GcdDefaultEndian>(&mut raw)
+            .unwrap();
+        descriptor
+            .record_data_to_raw::<GcdDefaultEndian>(data_raw)
+            .unwrap();
+        let type_end = RECORD_HEADER_LEN + type_len.as_usize();
+        let data_start = type_end + RECORD_HEADER_LEN;
+
+        //the composer can't write it, the End is added automatically
+        let mut file = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut file).unwrap();
+        let main = Record::MainHeader(MainRecord::DefaultHWID);
+        composer.write_record(&main).unwrap();
+        composer
+            .write_record_raw(
+                RecordId::DESCRIPTOR_TYPE,
+                &raw[RECORD_HEADER_LEN..type_end],
+            )
+            .unwrap();
+        composer
+            .write_record_raw(RecordId::DESCRIPTOR_DATA, &raw[data_start..])
+            .unwrap();
+        composer.write_record(&Record::End).unwrap();
+        drop(composer);
+
+        assert!(matches!(
+            parse_all(&file),
+            Err(GcdError::MissingDescriptor("End"))
+        ));
+        let config = ParserConfig {
+            strict: false,
+            ..Default::default()
+        };
+        let mut parser: Parser<&[u8]> =
+            Parser::with_config(&file[..], config).unwrap();
+        let parsed: Vec<Record> =
+            parser.by_ref().collect::<Result<_>>().unwrap();
+        assert_eq!(parsed[1], Record::Descriptor(descriptor.clone()));
+        assert_eq!(parser.warnings().len(), 1);
+        let config = ParserConfig {
+            require_descriptor_end: false,
+            ..Default::default()
+        };
+        let parser: Parser<&[u8]> =
+            Parser::with_config(&file[..], config).unwrap();
+        assert!(parser.collect::<Result<Vec<_>>>().is_ok());
+
+        //composed with the End
+        let records = vec![main, Record::Descriptor(descriptor), Record::End];
+        let parsed = parse_all(&compose_all(&records).unwrap()).unwrap();
+        let terminated = DescriptorRecord::Simple(vec![
+            DescriptorDecoded::FirmwareId(0x0505).encode(),
+            DescriptorDecoded::FirmwareLen(0).encode(),
+            DescriptorDecoded::End.encode(),
+        ]);
+        assert_eq!(parsed[1], Record::Descriptor(terminated));
+    }
+
+    #[test]
+    fn parse_all_damaged() {
+        for (name, data) in crate::testing::vectors::ALL.iter() {
+            let records = parse_all(data).unwrap();
+            assert_eq!(compose_all(&records).unwrap(), *data, "{}", name);
+            //damaged files return an error, never panic
+            for len in 0..data.len() {
+                assert!(parse_all(&data[..len]).is_err(), "{} {}", name, len);
+            }
+            for pos in 0..data.len() {
+                for bit in 0..8 {
+                    let mut data = data.to_vec();
+                    data[pos] ^= 1 << bit;
+                    if let Ok(records) = parse_all(&data) {
+                        //the composer fix the checksums
+                        let composed = compose_all(&records).unwrap();
+                        assert_eq!(parse_all(&composed).unwrap(), records);
+                    }
+                }
+            }
+        }
+    }
 }
@@ -13,21 +13,27 @@
 //!
 //! It is represented as [u8; 9], is basically a string but each char is
 //! (including '-') calculated but subtracting 0x20 and is 6 bits.
+//!
+//! Some part numbers have an alphanumeric suffix, eg: "010-10037-00A", those
+//! are represented with more bytes, 10 bytes for 13 chars.
 
+use crate::error::{GcdError, Result};
 use byteorder::ByteOrder;
-use nom::bytes::complete::*;
-use nom::character::is_digit;
-use nom::combinator::map_res;
-use nom::sequence::tuple;
-use nom::IResult;
 use serde::{Deserialize, Serialize};
-use std::{
-    io::{Error, ErrorKind, Result},
-    str::FromStr,
-};
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Len of the "AAA-BCCCC-DD" string.
+const SIMPLE_LEN: usize = 12;
+/// Max raw len, the value is read as a u128.
+pub const MAX_RAW_LEN: usize = 16;
+/// Max len of the [`PnExtended`] suffix, so the value fit [`MAX_RAW_LEN`].
+pub const MAX_SUFFIX_LEN: usize = (MAX_RAW_LEN * 8 / 6) - SIMPLE_LEN;
 
 /// The only know representation of PartNumber
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+#[serde(try_from = "PnSimpleValue")]
 pub struct PnSimple {
     kind: u16,
     hw_kind: u8,
@@ -35,105 +41,285 @@ pub struct PnSimple {
     rel: u8,
 }
 
+// the deserialized value, checked by PnSimple::new
+#[derive(Deserialize)]
+struct PnSimpleValue {
+    kind: u16,
+    hw_kind: u8,
+    hw_id: u16,
+    rel: u8,
+}
+
+impl TryFrom<PnSimpleValue> for PnSimple {
+    type Error = GcdError;
+    fn try_from(value: PnSimpleValue) -> Result<Self> {
+        PnSimple::new(value.kind, value.hw_kind, value.hw_id, value.rel)
+    }
+}
+
+impl PnSimple {
+    /// Create the "AAA-BCCCC-DD" part number, fail if any value is out of
+    /// range: kind 0..1000, hw_kind 0..10, hw_id 0..10000 and rel 0..100.
+    pub fn new(kind: u16, hw_kind: u8, hw_id: u16, rel: u8) -> Result<Self> {
+        if kind >= 1000 || hw_kind >= 10 || hw_id >= 10000 || rel >= 100 {
+            return Err(GcdError::invalid_input(
+                "PartNumber value out of range",
+            ));
+        }
+        Ok(PnSimple {
+            kind,
+            hw_kind,
+            hw_id,
+            rel,
+        })
+    }
+    /// The "AAA" value, possibly the product kind.
+    pub const fn kind(&self) -> u16 {
+        self.kind
+    }
+    /// The "B" value, possibly the hardware type.
+    pub const fn hw_kind(&self) -> u8 {
+        self.hw_kind
+    }
+    /// The "CCCC" value, possibly the hardware id.
+    pub const fn hw_id(&self) -> u16 {
+        self.hw_id
+    }
+    /// The "DD" value, possibly the release/variation.
+    pub const fn rel(&self) -> u8 {
+        self.rel
+    }
+    /// The HWID, the "CCCC" digits read as hex, eg: "010-10037-00" is the
+    /// HWID 0x0037, the same from the default MainHeader HWID record.
+    pub fn hwid(&self) -> Option<u16> {
+        u16::from_str_radix(&format!("{:04}", self.hw_id), 16).ok()
+    }
+}
+
+/// The "AAA-BCCCC-DD" format followed by a suffix, eg: "010-10037-00A".
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+#[serde(try_from = "PnExtendedValue")]
+pub struct PnExtended {
+    base: PnSimple,
+    suffix: String,
+}
+
+// the deserialized value, checked by PnExtended::new
+#[derive(Deserialize)]
+struct PnExtendedValue {
+    base: PnSimple,
+    suffix: String,
+}
+
+impl TryFrom<PnExtendedValue> for PnExtended {
+    type Error = GcdError;
+    fn try_from(value: PnExtendedValue) -> Result<Self> {
+        PnExtended::new(value.base, value.suffix)
+    }
+}
+
+impl PnExtended {
+    /// Create the part number, fail if the suffix is empty, bigger than
+    /// `MAX_SUFFIX_LEN` (9) or is not composed of uppercase letters and
+    /// digits.
+    pub fn new(base: PnSimple, suffix: String) -> Result<Self> {
+        let valid = |x: &u8| x.is_ascii_uppercase() || x.is_ascii_digit();
+        if suffix.is_empty()
+            || suffix.len() > MAX_SUFFIX_LEN
+            || !suffix.bytes().all(|x| valid(&x))
+        {
+            return Err(GcdError::invalid_input("Invalid PartNumber suffix"));
+        }
+        Ok(PnExtended { base, suffix })
+    }
+    pub const fn base(&self) -> &PnSimple {
+        &self.base
+    }
+    pub fn suffix(&self) -> &str {
+        &self.suffix
+    }
+}
+
+// the values are generated in range, the derive would create invalid ones
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for PnSimple {
+    fn arbitrary(
+        u: &mut arbitrary::Unstructured<'a>,
+    ) -> arbitrary::Result<Self> {
+        Ok(PnSimple {
+            kind: u.int_in_range(0..=999)?,
+            hw_kind: u.int_in_range(0..=9)?,
+            hw_id: u.int_in_range(0..=9999)?,
+            rel: u.int_in_range(0..=99)?,
+        })
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for PnExtended {
+    fn arbitrary(
+        u: &mut arbitrary::Unstructured<'a>,
+    ) -> arbitrary::Result<Self> {
+        const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+        let base = PnSimple::arbitrary(u)?;
+        let len = u.int_in_range(1..=MAX_SUFFIX_LEN)?;
+        let suffix = (0..len)
+            .map(|_| u.choose(CHARS).map(|x| char::from(*x)))
+            .collect::<arbitrary::Result<String>>()?;
+        PnExtended::new(base, suffix)
+            .map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
 /// PartNumber could represent, software, device, or part of a device.
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 //TODO Simple is not good, I need to check more PNs.
 pub enum PartNumber {
     /// The simple AAA-BCCCC-DD format
     Simple(PnSimple),
+    /// The AAA-BCCCC-DD format with a suffix
+    Extended(PnExtended),
 }
 impl PartNumber {
-    fn parse(input: &[u8]) -> IResult<&[u8], Self> {
-        //parsers
-        let sep = tag(b"-");
-        let is_kind = take_while_m_n(3, 3, is_digit);
-        let hw_kind = take(1usize);
-        let is_hw_id = take_while_m_n(4, 4, is_digit);
-        let hw_id = map_res(is_hw_id, |x: &[u8]| {
-            u16::from_str(&String::from_utf8_lossy(x))
-        });
-        let is_rel = take_while_m_n(2, 2, is_digit);
-
-        let (input, kind) = map_res(is_kind, |x: &[u8]| {
-            u16::from_str(&String::from_utf8_lossy(x))
-        })(input)?;
-        let (input, _) = sep(input)?;
-        let (input, (hw_kind, hw_id)) = tuple((hw_kind, hw_id))(input)?;
-        let (input, _) = sep(input)?;
-        let (input, rel) = map_res(is_rel, |x: &[u8]| {
-            u8::from_str(&String::from_utf8_lossy(x))
-        })(input)?;
-
-        Ok((
-            input,
-            PartNumber::Simple(PnSimple {
-                kind,
-                hw_kind: hw_kind[0] - b'0',
-                hw_id,
-                rel,
-            }),
-        ))
+    /// The "AAA-BCCCC-DD" part, without the suffix.
+    pub const fn base(&self) -> &PnSimple {
+        match self {
+            PartNumber::Simple(x) => x,
+            PartNumber::Extended(x) => &x.base,
+        }
     }
-
-    pub fn from_raw<B: ByteOrder>(x: &[u8]) -> Result<(&[u8], PartNumber)> {
-        if x.len() < 9 {
-            Err(Error::new(
-                ErrorKind::InvalidData,
-                "Part number buffer too small",
-            ))
-        } else {
-            const fn base6(x: u128, byte: u8) -> u8 {
-                (((x & (0b111111 << (6 * byte))) >> (6 * byte)) & 0xffu128)
-                    as u8
+    /// The HWID from the "CCCC" digits, see [`PnSimple::hwid`].
+    pub fn hwid(&self) -> Option<u16> {
+        self.base().hwid()
+    }
+    //parse the "AAA-BCCCC-DD" format, followed by an optional suffix
+    fn parse(input: &[u8]) -> Result<Self> {
+        fn number(digits: &[u8]) -> Option<u16> {
+            digits.iter().try_fold(0u16, |acc, x| {
+                if x.is_ascii_digit() {
+                    Some(acc * 10 + (x - b'0') as u16)
+                } else {
+                    None
+                }
+            })
+        }
+        let parse = || {
+            if input.len() < 12 || input[3] != b'-' || input[9] != b'-' {
+                return None;
             }
-            const fn get_value(x: u128) -> [u8; 12] {
-                let (mut ret, mut i) = ([0; 12], 0);
-                while i < 12 {
-                    ret[i] = base6(x, 11 - i as u8).wrapping_add(0x20);
-                    i += 1;
+            let base = PnSimple {
+                kind: number(&input[0..3])?,
+                hw_kind: number(&input[4..5])? as u8,
+                hw_id: number(&input[5..9])?,
+                rel: number(&input[10..12])? as u8,
+            };
+            match &input[SIMPLE_LEN..] {
+                [] => Some(PartNumber::Simple(base)),
+                suffix => {
+                    let suffix = std::str::from_utf8(suffix).ok()?;
+                    PnExtended::new(base, suffix.to_string())
+                        .ok()
+                        .map(PartNumber::Extended)
                 }
-                ret
             }
-            let num = B::read_uint128(x, 9);
-            let buff = get_value(num);
-            let (_, ret) = PartNumber::parse(&buff).map_err(|_| {
-                Error::new(ErrorKind::InvalidData, "Unable to parse PartNumber")
-            })?;
-            Ok((&x[9..], ret))
+        };
+        parse()
+            .ok_or_else(|| GcdError::invalid_data("Unable to parse PartNumber"))
+    }
+
+    /// Decode the 9 bytes part number.
+    pub fn from_raw<B: ByteOrder>(x: &[u8]) -> Result<(&[u8], PartNumber)> {
+        Self::from_raw_len::<B>(x, 9)
+    }
+
+    /// Decode the part number with `len` bytes, each 6 bits is a char.
+    pub fn from_raw_len<B: ByteOrder>(
+        x: &[u8],
+        len: usize,
+    ) -> Result<(&[u8], PartNumber)> {
+        if len == 0 || len > MAX_RAW_LEN {
+            return Err(GcdError::invalid_input("Invalid part number len"));
         }
+        if x.len() < len {
+            return Err(GcdError::invalid_data("Part number buffer too small"));
+        }
+        let num = B::read_uint128(x, len);
+        let chars = len * 8 / 6;
+        //the first char is the most significant
+        let buff: Vec<u8> = (0..chars)
+            .rev()
+            .map(|i| (((num >> (6 * i)) & 0b111111) as u8).wrapping_add(0x20))
+            .collect();
+        //the bytes can fit one char more than encoded, eg: 15 chars use 12
+        //bytes, the unused most significant bits are decoded as a space
+        let start = buff.iter().position(|x| *x != b' ').unwrap_or(0);
+        Ok((&x[len..], PartNumber::parse(&buff[start..])?))
     }
 
-    pub fn from_str(s: &str) -> Result<Self> {
-        let bytes = s.as_bytes();
-        if bytes.len() < 12 {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "PartNumber Invalid size",
+    /// Number of bytes used by [`PartNumber::to_raw`], 9 for the simple
+    /// format.
+    pub fn raw_len(&self) -> usize {
+        let chars = match self {
+            PartNumber::Simple(_) => SIMPLE_LEN,
+            PartNumber::Extended(x) => SIMPLE_LEN + x.suffix.len(),
+        };
+        (chars * 6).div_ceil(8)
+    }
+
+    /// Encode the part number, the inverse of [`PartNumber::from_raw_len`].
+    pub fn to_raw<B: ByteOrder>(&self, x: &mut [u8]) -> Result<()> {
+        let len = self.raw_len();
+        if x.len() < len {
+            return Err(GcdError::invalid_input(
+                "Part number buffer too small",
             ));
         }
-        let (_, ret) = PartNumber::parse(s.as_bytes()).map_err(|_| {
-            Error::new(ErrorKind::InvalidData, "Unable to parse PartNumber")
-        })?;
-        Ok(ret)
+        //each char is 6 bits, from 0x20, the first is the most significant
+        let num = self.to_string().bytes().fold(0u128, |acc, c| {
+            (acc << 6) | (c.wrapping_sub(0x20) & 0b111111) as u128
+        });
+        B::write_uint128(x, num, len);
+        Ok(())
     }
 }
 
-impl ToString for PartNumber {
-    fn to_string(&self) -> String {
+impl FromStr for PartNumber {
+    type Err = GcdError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let bytes = s.as_bytes();
+        if bytes.len() < SIMPLE_LEN {
+            return Err(GcdError::invalid_data("PartNumber Invalid size"));
+        }
+        PartNumber::parse(bytes)
+    }
+}
+
+impl Display for PnSimple {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:03}-{}{:04}-{:02}",
+            self.kind, self.hw_kind, self.hw_id, self.rel
+        )
+    }
+}
+
+impl Display for PartNumber {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            PartNumber::Simple(PnSimple {
-                kind,
-                hw_kind,
-                hw_id,
-                rel,
-            }) => format!("{:03}-{}{:04}-{:02}", kind, hw_kind, hw_id, rel),
+            PartNumber::Simple(pn) => write!(f, "{}", pn),
+            PartNumber::Extended(pn) => write!(f, "{}{}", pn.base, pn.suffix),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::part_number::{PnExtended, PnSimple, MAX_SUFFIX_LEN};
     use crate::PartNumber;
+    use std::str::FromStr;
 
     /// Check if Part number is decoding raw data correctly
     #[test]
@@ -152,6 +338,28 @@ mod tests {
         }
     }
 
+    /// Encode the part number back to raw data
+    #[test]
+    fn part_number_to_bytes() {
+        let pn = PartNumber::from_str("006-11234-07").unwrap();
+        let mut raw = [0u8; 9];
+        pn.to_raw::<byteorder::LE>(&mut raw).unwrap();
+        let (_, decoded) = PartNumber::from_raw::<byteorder::LE>(&raw).unwrap();
+        assert_eq!(decoded, pn);
+        pn.to_raw::<byteorder::BE>(&mut raw).unwrap();
+        let (_, decoded) = PartNumber::from_raw::<byteorder::BE>(&raw).unwrap();
+        assert_eq!(decoded, pn);
+    }
+
+    /// Create a part number from its values
+    #[test]
+    fn part_number_new() {
+        let pn = PartNumber::Simple(PnSimple::new(10, 1, 37, 0).unwrap());
+        assert_eq!(pn.to_string(), "010-10037-00");
+        assert_eq!(PartNumber::from_str("010-10037-00").unwrap(), pn);
+        assert!(PnSimple::new(10, 10, 37, 0).is_err());
+    }
+
     /// Parse invalid text to partnumber
     #[test]
     #[should_panic]
@@ -166,4 +374,110 @@ mod tests {
         let text = "010-0037-00";
         PartNumber::from_str(text).unwrap();
     }
+    /// Part number with a suffix, encoded with more bytes
+    #[test]
+    fn part_number_extended() {
+        let pn: PartNumber = "010-10037-00A".parse().unwrap();
+        let base = PnSimple::new(10, 1, 37, 0).unwrap();
+        assert_eq!(
+            pn,
+            PartNumber::Extended(
+                PnExtended::new(base.clone(), "A".to_string()).unwrap()
+            )
+        );
+        assert_eq!(pn.to_string(), "010-10037-00A");
+        assert_eq!(pn.raw_len(), 10);
+        let mut raw = [0u8; 10];
+        pn.to_raw::<byteorder::LE>(&mut raw).unwrap();
+        let (_, decoded) =
+            PartNumber::from_raw_len::<byteorder::LE>(&raw, 10).unwrap();
+        assert_eq!(decoded, pn);
+        assert!(pn.to_raw::<byteorder::LE>(&mut raw[..9]).is_err());
+
+        let long = format!("010-10037-00{}", "X1".repeat(4));
+        let pn: PartNumber = long.parse().unwrap();
+        assert_eq!(pn.raw_len(), 15);
+        assert!(PnExtended::new(base.clone(), "a".to_string()).is_err());
+        assert!(PnExtended::new(base, "X".repeat(10)).is_err());
+        assert!("010-10037-00-".parse::<PartNumber>().is_err());
+    }
+
+    /// Every allowed char, on every position, survive the MainRecord encoding
+    #[test]
+    fn part_number_alphabet() {
+        use crate::record::main::MainRecord;
+        use byteorder::{ByteOrder, BE, LE};
+
+        fn round_trip<B: ByteOrder>(text: &str) {
+            let pn: PartNumber = text.parse().unwrap();
+            let main = MainRecord::PartNumber(pn);
+            let mut raw = vec![0u8; main.len() as usize + 4];
+            main.record_to_raw::<B>(&mut raw).unwrap();
+            let decoded = MainRecord::new::<_, B>(&mut &raw[4..], main.len());
+            assert_eq!(
+                decoded.unwrap().part_number().unwrap().to_string(),
+                text
+            );
+        }
+
+        let digits = "0123456789";
+        let suffix: String = ('A'..='Z').chain('0'..='9').collect();
+        //each digit of the base, the dashes are fixed
+        for pos in [0, 1, 2, 4, 5, 6, 7, 8, 10, 11].iter() {
+            for digit in digits.chars() {
+                let mut text: Vec<char> = "006-11234-07".chars().collect();
+                text[*pos] = digit;
+                let text: String = text.into_iter().collect();
+                round_trip::<LE>(&text);
+                round_trip::<BE>(&text);
+            }
+        }
+        //each suffix char, on the first and last position
+        for c in suffix.chars() {
+            for len in 1..=MAX_SUFFIX_LEN {
+                let text = format!("006-11234-07{:X<1$}", c, len);
+                round_trip::<LE>(&text);
+                round_trip::<BE>(&text);
+            }
+            let text = format!("006-11234-07{}{}", "Z".repeat(8), c);
+            round_trip::<LE>(&text);
+        }
+    }
+
+    #[test]
+    fn part_number_extended_serde() {
+        let pn: PartNumber = "010-10037-00AB".parse().unwrap();
+        let yaml = serde_yaml::to_string(&pn).unwrap();
+        let decoded: PartNumber = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(decoded, pn);
+        assert_eq!(decoded.raw_len(), pn.raw_len());
+        //the suffix is checked
+        let invalid = yaml.replace("AB", "ab");
+        assert!(serde_yaml::from_str::<PartNumber>(&invalid).is_err());
+        //and the base values
+        let invalid = yaml.replace("hw_kind: 1", "hw_kind: 10");
+        assert_ne!(invalid, yaml);
+        assert!(serde_yaml::from_str::<PartNumber>(&invalid).is_err());
+    }
+
+    /// Parse invalid text to partnumber
+    #[test]
+    fn part_number_invalid_str3() {
+        for text in ["010-A0037-00", "010+10037-00", "010-10037-0x"].iter() {
+            assert!(PartNumber::from_str(text).is_err());
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn part_number_arbitrary() {
+        use arbitrary::{Arbitrary, Unstructured};
+        for seed in 0..=255u8 {
+            let data: Vec<u8> =
+                (0..64).map(|x| x ^ seed.wrapping_mul(31)).collect();
+            let pn = PartNumber::arbitrary(&mut Unstructured::new(&data))
+                .unwrap();
+            assert_eq!(PartNumber::from_str(&pn.to_string()).unwrap(), pn);
+        }
+    }
 }
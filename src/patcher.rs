@@ -0,0 +1,296 @@
+//! Modify records of an existing file.
+//!
+//! The file is parsed, the records replaced and the file composed again, so
+//! the record lens, the Descriptor FirmwareLen and all the checksums after
+//! the change are recalculated.
+//!
+//! ```
+//! use gcd_rs::patcher::Patcher;
+//! use gcd_rs::record::text::TextRecord;
+//! use gcd_rs::testing::vectors;
+//! use gcd_rs::Record;
+//!
+//! let file = vectors::FILLERS_CHECKSUMS;
+//! let mut patcher: Patcher = Patcher::new(file).unwrap();
+//! let text = patcher.find_text(|_| true).unwrap();
+//! let new = TextRecord::Simple("Patched".to_string());
+//! patcher.replace_record(text, Record::Text(new.clone())).unwrap();
+//!
+//! let file = patcher.to_bytes().unwrap();
+//! let patched: Patcher = Patcher::new(&file).unwrap();
+//! assert_eq!(patched.records()[text], Record::Text(new));
+//! ```
+
+use byteorder::ByteOrder;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use crate::block::DEFAULT_MAX_CHUNK_SIZE;
+use crate::composer::{Composer, EndPadding};
+use crate::error::{GcdError, Result};
+use crate::parser::Parser;
+use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+use crate::record::firmware::FirmwareRecord;
+use crate::record::text::TextRecord;
+use crate::{GcdDefaultEndian, Record, RecordId};
+
+pub struct Patcher<B = GcdDefaultEndian>
+where
+    B: ByteOrder,
+{
+    records: Vec<Record>,
+    end_padding: EndPadding,
+    endian: PhantomData<B>,
+}
+
+impl<B: ByteOrder> Patcher<B> {
+    /// Parse all the records of the file.
+    pub fn new(data: &[u8]) -> Result<Self> {
+        let parser: Parser<&[u8], B> = Parser::new(data)?;
+        Ok(Self::from_records(parser.collect::<Result<_>>()?))
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new(&std::fs::read(path)?)
+    }
+
+    /// The firmware data is expected to be decoded, like the data returned
+    /// by the [`Parser`].
+    pub fn from_records(records: Vec<Record>) -> Self {
+        Patcher {
+            records,
+            end_padding: EndPadding::None,
+            endian: PhantomData,
+        }
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    pub fn into_records(self) -> Vec<Record> {
+        self.records
+    }
+
+    /// Index of the first Text record accepted by `predicate`.
+    pub fn find_text<P>(&self, predicate: P) -> Option<usize>
+    where
+        P: Fn(&TextRecord) -> bool,
+    {
+        self.records.iter().position(|x| match x {
+            Record::Text(text) => predicate(text),
+            _ => false,
+        })
+    }
+
+    /// Index of the Descriptor of the first firmware block with the `id`.
+    pub fn find_block(&self, id: u16) -> Option<usize> {
+        self.records.iter().position(|x| match x {
+            Record::Descriptor(desc) => desc.firmware_id() == Some(id),
+            _ => false,
+        })
+    }
+
+    /// Replace the record at `index`, the End record can't be replaced and
+    /// the firmware data should be replaced with
+    /// [`Patcher::replace_firmware`].
+    pub fn replace_record(
+        &mut self,
+        index: usize,
+        record: Record,
+    ) -> Result<()> {
+        let old = self.records.get_mut(index).ok_or_else(|| {
+            GcdError::invalid_input("Record index out of range")
+        })?;
+        match (&*old, &record) {
+            (Record::End, _) | (_, Record::End) => {
+                Err(GcdError::invalid_input("The End record can't be replaced"))
+            }
+            (Record::FirmwareData(_), _) | (_, Record::FirmwareData(_)) => {
+                Err(GcdError::invalid_input(
+                    "Firmware data can only be replaced by block",
+                ))
+            }
+            _ => {
+                *old = record;
+                Ok(())
+            }
+        }
+    }
+
+    /// Replace the data of the first firmware block with the `id`.
+    ///
+    /// The new data fill the original chunks, keeping their len, so the
+    /// checksums and other records between the chunks are kept in place.
+    /// The remaining data is added in chunks after the last one.
+    pub fn replace_firmware(&mut self, id: u16, data: &[u8]) -> Result<()> {
+        if data.len() > u32::MAX as usize {
+            return Err(GcdError::invalid_input(
+                "Firmware is bigger than u32::MAX",
+            ));
+        }
+        let desc_index = self
+            .find_block(id)
+            .ok_or(GcdError::MissingDescriptor("Firmware Id"))?;
+        if let Record::Descriptor(desc) = &mut self.records[desc_index] {
+            let len = desc.iter_mut().find(|x| {
+                matches!(x.decode(), Some(DescriptorDecoded::FirmwareLen(_)))
+            });
+            match len {
+                Some(len) => {
+                    *len = DescriptorDecoded::FirmwareLen(data.len() as u32)
+                        .encode()
+                }
+                None => {
+                    return Err(GcdError::MissingDescriptor("Firmware Lenght"))
+                }
+            }
+        }
+
+        //the block end at the next Descriptor or End
+        let block_end = self.records[desc_index + 1..]
+            .iter()
+            .position(|x| matches!(x, Record::Descriptor(_) | Record::End))
+            .map(|x| x + desc_index + 1)
+            .unwrap_or(self.records.len());
+        let chunk_id = RecordId::new(id);
+        let mut data = data;
+        let mut records = vec![];
+        let mut max_chunk = 0;
+        let mut last_chunk = None;
+        for record in self.records.drain(desc_index + 1..block_end) {
+            match record {
+                Record::FirmwareData(chunk) => {
+                    let len = (chunk.len() as usize).min(data.len());
+                    max_chunk = max_chunk.max(chunk.len());
+                    if len != 0 {
+                        let (chunk_data, rest) = data.split_at(len);
+                        data = rest;
+                        records.push(Record::FirmwareData(
+                            FirmwareRecord::new(chunk_data.to_vec(), chunk_id),
+                        ));
+                        last_chunk = Some(records.len());
+                    }
+                }
+                record => records.push(record),
+            }
+        }
+        let max_chunk = match max_chunk {
+            0 => DEFAULT_MAX_CHUNK_SIZE,
+            x => x,
+        };
+        let extra: Vec<_> = data
+            .chunks(max_chunk as usize)
+            .map(|x| {
+                Record::FirmwareData(FirmwareRecord::new(x.to_vec(), chunk_id))
+            })
+            .collect();
+        //after the last chunk, or before the block records if no chunk left
+        let pos = last_chunk.unwrap_or(0);
+        records.splice(pos..pos, extra);
+        self.records.splice(desc_index + 1..desc_index + 1, records);
+        Ok(())
+    }
+
+    /// Replace the fillers before the End record with the padding, see
+    /// [`EndPadding`].
+    pub fn set_end_padding(&mut self, padding: EndPadding) {
+        self.end_padding = padding;
+    }
+
+    /// Compose the file with the changes.
+    pub fn write<W: Write>(&self, writer: W) -> Result<()> {
+        let mut composer: Composer<W, B> = Composer::new(writer)?;
+        composer.set_end_padding(self.end_padding)?;
+        //the fillers before the End are replaced by the padding
+        let padding_start = match self.end_padding {
+            EndPadding::None => self.records.len(),
+            EndPadding::Filler(_) => {
+                let end = self
+                    .records
+                    .iter()
+                    .position(|x| *x == Record::End)
+                    .unwrap_or(self.records.len());
+                self.records[..end]
+                    .iter()
+                    .rposition(|x| !matches!(x, Record::Filler(_)))
+                    .map(|x| x + 1)
+                    .unwrap_or(0)
+            }
+        };
+        for (i, record) in self.records.iter().enumerate() {
+            if i >= padding_start && matches!(record, Record::Filler(_)) {
+                continue;
+            }
+            composer.write_record(record)?;
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut file = vec![];
+        self.write(&mut file)?;
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Patcher;
+    use crate::composer::EndPadding;
+    use crate::gcd_file::tests::{compose, firmware};
+    use crate::record::filler::FillerRecord;
+    use crate::record::text::TextRecord;
+    use crate::testing::vectors;
+    use crate::{GcdFile, Record};
+
+    #[test]
+    fn patch_firmware() {
+        let file = compose();
+        let patcher: Patcher = Patcher::new(&file).unwrap();
+        //no changes
+        assert_eq!(patcher.to_bytes().unwrap(), file);
+
+        //original chunks: 300, 300, 300 and 100
+        let cases = [(1500usize, 6), (1000, 4), (700, 3), (0, 0)];
+        for (len, chunks) in cases.iter() {
+            let new: Vec<u8> = (0..*len).map(|x| (x * 7) as u8).collect();
+            let mut patcher: Patcher = Patcher::new(&file).unwrap();
+            patcher.replace_firmware(0x0505, &new).unwrap();
+            //the parser verify the checksums and the FirmwareLen
+            let patched: GcdFile =
+                GcdFile::from_bytes(patcher.to_bytes().unwrap()).unwrap();
+            assert_eq!(patched.read_block(0).unwrap(), new);
+            assert_eq!(patched.read_block(1).unwrap(), firmware(0x0506));
+            assert_eq!(patched.blocks()[0].chunks.len(), *chunks);
+        }
+
+        let mut patcher: Patcher = Patcher::new(&file).unwrap();
+        assert!(patcher.replace_firmware(0x0507, &[]).is_err());
+        assert!(patcher.replace_record(1, Record::End).is_err());
+    }
+
+    #[test]
+    fn patch_text_padding() {
+        let mut patcher: Patcher =
+            Patcher::new(vectors::FILLERS_CHECKSUMS).unwrap();
+        let index = patcher.find_text(|_| true).unwrap();
+        let text = TextRecord::Simple("A longer text record".to_string());
+        patcher
+            .replace_record(index, Record::Text(text.clone()))
+            .unwrap();
+        patcher.set_end_padding(EndPadding::Filler(64));
+        let patched = patcher.to_bytes().unwrap();
+        assert_eq!(patched.len() % 64, 0);
+
+        let records = Patcher::<byteorder::LE>::new(&patched)
+            .unwrap()
+            .into_records();
+        assert_eq!(records[index], Record::Text(text));
+        assert!(matches!(
+            records[records.len() - 2],
+            Record::Filler(FillerRecord::Zeros(_))
+        ));
+    }
+}
@@ -0,0 +1,157 @@
+//! Progress of the parsing and composing, for progress bars.
+//!
+//! A [`ProgressSink`] can be set on the [`Parser`], the [`Composer`] and
+//! when indexing a [`GcdFile`], it's called after each record. Any
+//! `FnMut(&Progress)` closure is a sink.
+//!
+//! ```
+//! use gcd_rs::parser::Parser;
+//! use gcd_rs::progress::Progress;
+//! use gcd_rs::testing::vectors;
+//!
+//! let data = vectors::SINGLE_BLOCK;
+//! let mut parser: Parser<&[u8]> = Parser::new(data).unwrap();
+//! let total = Some(data.len() as u64);
+//! parser.set_progress(
+//!     Box::new(|progress: &Progress| {
+//!         let percent = progress.fraction().unwrap_or(0.0) * 100.0;
+//!         println!("{:.0}% {} records", percent, progress.records);
+//!     }),
+//!     total,
+//! );
+//! for record in parser {
+//!     record.unwrap();
+//! }
+//! ```
+//!
+//! [`Parser`]: crate::parser::Parser
+//! [`Composer`]: crate::composer::Composer
+//! [`GcdFile`]: crate::GcdFile
+
+/// Snapshot of the progress, see [`ProgressSink`].
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Progress {
+    /// Bytes read or written, including the signature.
+    pub bytes: u64,
+    /// Size of the file, if known.
+    pub total: Option<u64>,
+    /// Number of records read or written.
+    pub records: u64,
+    /// Index of the current firmware block, None before the first
+    /// Descriptor.
+    pub block: Option<usize>,
+}
+
+impl Progress {
+    /// Fraction of the total processed, from 0.0 to 1.0, if the total is
+    /// known.
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total {
+            Some(0) => Some(1.0),
+            Some(total) => Some((self.bytes as f64 / total as f64).min(1.0)),
+            None => None,
+        }
+    }
+}
+
+/// Receive the progress after each record.
+pub trait ProgressSink: Send {
+    fn update(&mut self, progress: &Progress);
+}
+
+impl<F: FnMut(&Progress) + Send> ProgressSink for F {
+    fn update(&mut self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+// the sink with the progress so far
+pub(crate) struct ProgressTracker {
+    sink: Box<dyn ProgressSink>,
+    progress: Progress,
+}
+
+impl ProgressTracker {
+    pub(crate) fn new(sink: Box<dyn ProgressSink>, total: Option<u64>) -> Self {
+        ProgressTracker {
+            sink,
+            progress: Progress {
+                total,
+                ..Progress::default()
+            },
+        }
+    }
+
+    // a record was processed, `bytes` is the file position after it
+    pub(crate) fn record(&mut self, bytes: u64, descriptor: bool) {
+        let progress = &mut self.progress;
+        progress.bytes = bytes;
+        progress.records += 1;
+        if descriptor {
+            progress.block = Some(progress.block.map_or(0, |x| x + 1));
+        }
+        self.sink.update(progress);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Progress, ProgressSink};
+    use crate::composer::Composer;
+    use crate::parser::Parser;
+    use crate::testing::vectors;
+    use crate::GcdFile;
+    use std::sync::{Arc, Mutex};
+
+    // sink that store all the updates
+    fn collect() -> (Arc<Mutex<Vec<Progress>>>, Box<dyn ProgressSink>) {
+        let updates = Arc::new(Mutex::new(vec![]));
+        let sink = Arc::clone(&updates);
+        let sink = Box::new(move |x: &Progress| sink.lock().unwrap().push(*x));
+        (updates, sink)
+    }
+
+    #[test]
+    fn progress() {
+        let data = vectors::MULTI_BLOCK;
+        let total = Some(data.len() as u64);
+        let (updates, sink) = collect();
+        let mut parser: Parser<&[u8]> = Parser::new(data).unwrap();
+        parser.set_progress(sink, total);
+        let records: Vec<_> = parser.map(|x| x.unwrap()).collect();
+
+        let updates = updates.lock().unwrap().clone();
+        assert_eq!(updates.len(), records.len());
+        let last = updates.last().unwrap();
+        assert_eq!(last.bytes, data.len() as u64);
+        assert_eq!(last.records, records.len() as u64);
+        assert_eq!(last.block, Some(1));
+        assert_eq!(last.fraction(), Some(1.0));
+        assert!(updates.windows(2).all(|x| x[0].bytes < x[1].bytes));
+        assert_eq!(updates[0].block, None);
+
+        //the composer report the same progress, without the total
+        let (composed, sink) = collect();
+        let mut file = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut file).unwrap();
+        composer.set_progress(sink, None);
+        for record in records.iter() {
+            composer.write_record(record).unwrap();
+        }
+        let composed = composed.lock().unwrap().clone();
+        let expected: Vec<_> = updates
+            .iter()
+            .map(|x| Progress { total: None, ..*x })
+            .collect();
+        assert_eq!(composed, expected);
+
+        //indexing the file
+        let (indexed, sink) = collect();
+        GcdFile::<byteorder::LE>::from_source_with_progress(
+            data.to_vec(),
+            sink,
+        )
+        .unwrap();
+        assert_eq!(*indexed.lock().unwrap(), updates);
+    }
+}
@@ -0,0 +1,185 @@
+//! Rewrite the firmware blocks with a uniform chunk size.
+//!
+//! Some flashing tools and devices expect all the firmware chunks, except
+//! the last one of each block, to have the same size. Hand-edited files
+//! often violate this.
+//!
+//! The firmware data is re-encoded and the checksums are recalculated by the
+//! [`Composer`], the fillers inside the firmware blocks are removed, use
+//! [`Composer::set_end_padding`] to align the file.
+
+use byteorder::ByteOrder;
+use std::io::{Read, Write};
+
+use crate::composer::Composer;
+use crate::error::{GcdError, Result};
+use crate::parser::Parser;
+use crate::record::checksum::ChecksumRecord;
+use crate::record::firmware::FirmwareRecord;
+use crate::{Record, RecordId};
+
+// the firmware block being rechunked
+struct Block {
+    id: u16,
+    data: Vec<u8>,
+    // received at least one chunk
+    chunks: bool,
+    // wrote at least one chunk
+    written: bool,
+    // last checksum received inside the block
+    checksum: Option<ChecksumRecord>,
+}
+
+/// Copy all the records from the parser to the composer, until the End,
+/// writing the firmware data in chunks of `chunk_size` bytes.
+///
+/// Checksum records inside a firmware block are merged in a single one,
+/// written after the last chunk.
+pub fn rechunk<F, W, B>(
+    parser: &mut Parser<F, B>,
+    composer: &mut Composer<W, B>,
+    chunk_size: u16,
+) -> Result<()>
+where
+    F: Read,
+    W: Write,
+    B: ByteOrder,
+{
+    if chunk_size == 0 {
+        return Err(GcdError::invalid_input("Chunk size can't be zero"));
+    }
+    let chunk_size = chunk_size as usize;
+    let mut block: Option<Block> = None;
+    loop {
+        let record = parser.read_record()?;
+        match (&mut block, record) {
+            (Some(block), Record::FirmwareData(chunk)) => {
+                block.data.extend_from_slice(chunk.data());
+                block.chunks = true;
+                while block.data.len() >= chunk_size {
+                    let rest = block.data.split_off(chunk_size);
+                    let data = std::mem::replace(&mut block.data, rest);
+                    write_chunk(composer, block, data)?;
+                }
+            }
+            (Some(block), Record::Checksum(checksum)) => {
+                block.checksum = Some(checksum)
+            }
+            (Some(_), Record::Filler(_)) => {}
+            (_, record) => {
+                if let Some(block) = block.take() {
+                    finish_block(composer, block)?;
+                }
+                if let Record::Descriptor(descriptor) = &record {
+                    block = Some(Block {
+                        id: descriptor.firmware_id().unwrap_or(0),
+                        data: vec![],
+                        chunks: false,
+                        written: false,
+                        checksum: None,
+                    });
+                }
+                composer.write_record(&record)?;
+                if record == Record::End {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn write_chunk<W, B>(
+    composer: &mut Composer<W, B>,
+    block: &mut Block,
+    data: Vec<u8>,
+) -> Result<()>
+where
+    W: Write,
+    B: ByteOrder,
+{
+    block.written = true;
+    composer.write_record(&Record::FirmwareData(FirmwareRecord::new(
+        data,
+        RecordId::new(block.id),
+    )))
+}
+
+fn finish_block<W, B>(
+    composer: &mut Composer<W, B>,
+    mut block: Block,
+) -> Result<()>
+where
+    W: Write,
+    B: ByteOrder,
+{
+    //the last chunk, or the empty chunk if the block had one
+    if !block.data.is_empty() || (block.chunks && !block.written) {
+        let data = std::mem::take(&mut block.data);
+        write_chunk(composer, &mut block, data)?;
+    }
+    if let Some(checksum) = &block.checksum {
+        composer.write_record(&Record::Checksum(checksum.clone()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rechunk;
+    use crate::composer::Composer;
+    use crate::gcd_file::GcdFile;
+    use crate::parser::Parser;
+    use crate::testing::vectors;
+    use crate::Record;
+
+    fn rechunked(file: &[u8], chunk_size: u16) -> Vec<u8> {
+        let mut parser: Parser<&[u8]> = Parser::new(file).unwrap();
+        let mut output = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut output).unwrap();
+        rechunk(&mut parser, &mut composer, chunk_size).unwrap();
+        output
+    }
+
+    fn chunk_lens(file: &[u8]) -> Vec<Vec<u16>> {
+        let file: GcdFile = GcdFile::from_bytes(file.to_vec()).unwrap();
+        file.blocks()
+            .iter()
+            .map(|block| {
+                block
+                    .chunks
+                    .iter()
+                    .map(|x| match file.record(*x).unwrap() {
+                        Record::FirmwareData(x) => x.len(),
+                        _ => unreachable!(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn blocks(file: &[u8]) -> Vec<Vec<u8>> {
+        let file: GcdFile = GcdFile::from_bytes(file.to_vec()).unwrap();
+        (0..file.blocks().len())
+            .map(|x| file.read_block(x).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn uniform_chunks() {
+        let output = rechunked(vectors::MULTI_BLOCK, 3);
+        assert_eq!(chunk_lens(&output), [vec![3, 3, 2], vec![3, 1]]);
+        assert_eq!(blocks(&output), blocks(vectors::MULTI_BLOCK));
+
+        let output = rechunked(vectors::FILLERS_CHECKSUMS, 0x1000);
+        assert_eq!(chunk_lens(&output), [vec![8]]);
+        assert_eq!(output.len(), vectors::FILLERS_CHECKSUMS.len() - 15);
+    }
+
+    #[test]
+    fn xored_chunks() {
+        let file = crate::gcd_file::tests::compose();
+        let output = rechunked(&file, 0x100);
+        assert_eq!(chunk_lens(&output)[0], [0x100, 0x100, 0x100, 0xe8]);
+        assert_eq!(blocks(&output), blocks(&file));
+    }
+}
@@ -1,4 +1,5 @@
 pub mod checksum;
+pub mod cow;
 pub mod descriptor;
 pub mod filler;
 pub mod firmware;
@@ -1,21 +1,52 @@
+//! Integrity records, verifying all the file bytes before it.
+//!
+//! The usual record is the one byte sum, that result in a sum of 0 for all the
+//! bytes of the file until the end of the record.
+//!
+//! With the feature `crc`, the CRC-16/CCITT-FALSE and CRC-32/ISO-HDLC of the
+//! file can also be calculated by the parser and composer, with
+//! [`Checksums`], eg: to be compared with the CRC published with the file.
+//! No known GCD file store a CRC, those are not records.
+
+use crate::error::{GcdError, Result};
 use byteorder::ByteOrder;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
-use std::io::{Error, ErrorKind, Result};
 
 use crate::RecordHeader;
 use crate::RECORD_HEADER_LEN;
 
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[non_exhaustive]
 pub enum ChecksumRecord {
     Simple,
+    /// One byte sum with the verification result, returned by the parser if
+    /// [`ParserConfig::report_checksums`](crate::config::ParserConfig) is
+    /// enabled, recalculated when composed, like `Simple`.
+    Verified {
+        ok: bool,
+        /// Value that result in a sum of 0.
+        expected: u8,
+        /// Value in the file.
+        found: u8,
+    },
 }
 
 impl Display for ChecksumRecord {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             ChecksumRecord::Simple => write!(f, "ChecksumRecord:Simple"),
+            ChecksumRecord::Verified {
+                ok,
+                expected,
+                found,
+            } => write!(
+                f,
+                "ChecksumRecord:Verified(ok: {}, expected: {:#04x}, \
+                 found: {:#04x})",
+                ok, expected, found
+            ),
         }
     }
 }
@@ -25,17 +56,38 @@ pub const LEN: u16 = 1;
 impl ChecksumRecord {
     pub fn new(data: &[u8], checksum: u8) -> Result<Self> {
         if data.len() != 1 || checksum != 0 {
-            Err(Error::new(
-                ErrorKind::InvalidInput,
-                "Invalid Checksum Value",
-            ))
+            Err(GcdError::ChecksumMismatch {
+                header: RecordHeader::Checksum,
+            })
         } else {
             Ok(ChecksumRecord::Simple)
         }
     }
+    /// Create the Verified record, `checksum` is the sum of the file data,
+    /// including the record.
+    pub fn new_verified(data: &[u8], checksum: u8) -> Result<Self> {
+        match data {
+            [found] => Ok(ChecksumRecord::Verified {
+                ok: checksum == 0,
+                expected: found.wrapping_sub(checksum),
+                found: *found,
+            }),
+            _ => Err(GcdError::ChecksumMismatch {
+                header: RecordHeader::Checksum,
+            }),
+        }
+    }
+    #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u16 {
         match self {
-            ChecksumRecord::Simple => LEN,
+            ChecksumRecord::Simple | ChecksumRecord::Verified { .. } => LEN,
+        }
+    }
+    pub const fn header(&self) -> RecordHeader {
+        match self {
+            ChecksumRecord::Simple | ChecksumRecord::Verified { .. } => {
+                RecordHeader::Checksum
+            }
         }
     }
     pub fn record_to_raw<B: ByteOrder>(
@@ -54,3 +106,196 @@ impl ChecksumRecord {
         Ok(())
     }
 }
+
+/// Algorithm that calculate a value from all the bytes of the file.
+pub trait Checksum: Default + Clone {
+    type Value;
+    fn update(&mut self, data: &[u8]);
+    fn value(&self) -> Self::Value;
+}
+
+/// Wrapping sum of all bytes.
+#[derive(
+    Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize,
+)]
+pub struct Sum8(u8);
+
+impl Checksum for Sum8 {
+    type Value = u8;
+    fn update(&mut self, data: &[u8]) {
+        //sum 32 independent lanes, so the compiler can use SIMD
+        let mut lanes = [0u8; 32];
+        let mut chunks = data.chunks_exact(lanes.len());
+        for chunk in &mut chunks {
+            for (lane, byte) in lanes.iter_mut().zip(chunk) {
+                *lane = lane.wrapping_add(*byte);
+            }
+        }
+        self.0 = lanes
+            .iter()
+            .chain(chunks.remainder())
+            .fold(self.0, |acc, x| acc.wrapping_add(*x));
+    }
+    fn value(&self) -> u8 {
+        self.0
+    }
+}
+
+// the 16 bytes at a time table of the crc crate
+#[cfg(feature = "crc")]
+static CRC16: crc::Crc<u16, crc::Table<16>> =
+    crc::Crc::<u16, crc::Table<16>>::new(&crc::CRC_16_IBM_3740);
+
+/// CRC-16/CCITT-FALSE, calculated by the crc crate.
+#[cfg(feature = "crc")]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct Crc16(u16);
+
+#[cfg(feature = "crc")]
+impl Default for Crc16 {
+    fn default() -> Self {
+        Crc16(CRC16.algorithm.init)
+    }
+}
+
+#[cfg(feature = "crc")]
+impl Checksum for Crc16 {
+    type Value = u16;
+    fn update(&mut self, data: &[u8]) {
+        //without reflection and xorout, the value is the CRC state
+        let mut digest = CRC16.digest_with_initial(self.0);
+        digest.update(data);
+        self.0 = digest.finalize();
+    }
+    fn value(&self) -> u16 {
+        self.0
+    }
+}
+
+/// CRC-32/ISO-HDLC, the same used by zip, calculated by the crc32fast crate.
+#[cfg(feature = "crc")]
+#[derive(
+    Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize,
+)]
+pub struct Crc32(u32);
+
+#[cfg(feature = "crc")]
+impl Checksum for Crc32 {
+    type Value = u32;
+    fn update(&mut self, data: &[u8]) {
+        let mut hasher = crc32fast::Hasher::new_with_initial(self.0);
+        hasher.update(data);
+        self.0 = hasher.finalize();
+    }
+    fn value(&self) -> u32 {
+        self.0
+    }
+}
+
+/// All the known checksums, calculated at the same time.
+///
+/// The CRCs are only available with the feature `crc` and calculated if
+/// enabled, see `Checksums::with_crc`, the default is only the one byte sum,
+/// used by the checksum records.
+#[derive(
+    Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize,
+)]
+pub struct Checksums {
+    pub sum: Sum8,
+    #[cfg(feature = "crc")]
+    pub crc16: Option<Crc16>,
+    #[cfg(feature = "crc")]
+    pub crc32: Option<Crc32>,
+}
+
+impl Checksums {
+    /// The one byte sum and the CRCs.
+    #[cfg(feature = "crc")]
+    pub fn with_crc() -> Self {
+        Checksums {
+            sum: Sum8::default(),
+            crc16: Some(Crc16::default()),
+            crc32: Some(Crc32::default()),
+        }
+    }
+    pub fn update(&mut self, data: &[u8]) {
+        self.sum.update(data);
+        #[cfg(feature = "crc")]
+        if let Some(crc16) = self.crc16.as_mut() {
+            crc16.update(data);
+        }
+        #[cfg(feature = "crc")]
+        if let Some(crc32) = self.crc32.as_mut() {
+            crc32.update(data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::record::checksum::{Checksum, Sum8};
+    #[cfg(feature = "crc")]
+    use crate::record::checksum::{Checksums, Crc16, Crc32};
+
+    #[test]
+    fn sum8_lanes() {
+        let data: Vec<u8> = (0..300u32).map(|x| (x * 37) as u8).collect();
+        for len in 0..data.len() {
+            let mut sum = Sum8::default();
+            sum.update(&data[..len]);
+            let expected: u32 = data[..len].iter().map(|x| u32::from(*x)).sum();
+            assert_eq!(u32::from(sum.value()), expected % 256);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "crc")]
+    fn crc_check_values() {
+        let mut crc16 = Crc16::default();
+        let mut crc32 = Crc32::default();
+        crc16.update(b"12345");
+        crc32.update(b"12345");
+        crc16.update(b"6789");
+        crc32.update(b"6789");
+        assert_eq!(crc16.value(), 0x29b1);
+        assert_eq!(crc32.value(), 0xcbf4_3926);
+    }
+
+    #[test]
+    #[cfg(feature = "crc")]
+    fn crc_any_split() {
+        //the CRC of the whole data is the same, independent of the split
+        let data: Vec<u8> = (0..100u8).map(|x| x.wrapping_mul(37)).collect();
+        let (mut crc16, mut crc32) = (Crc16::default(), Crc32::default());
+        for byte in data.chunks(1) {
+            crc16.update(byte);
+            crc32.update(byte);
+        }
+        for split in 0..data.len() {
+            let (a, b) = data.split_at(split);
+            let (mut split16, mut split32) =
+                (Crc16::default(), Crc32::default());
+            split16.update(a);
+            split16.update(b);
+            split32.update(a);
+            split32.update(b);
+            assert_eq!(split16, crc16);
+            assert_eq!(split32, crc32);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "crc")]
+    fn checksums_crc() {
+        let mut checksums = Checksums::default();
+        checksums.update(b"123456789");
+        assert_eq!(checksums.sum.value(), 0xdd);
+        assert_eq!((checksums.crc16, checksums.crc32), (None, None));
+
+        let mut checksums = Checksums::with_crc();
+        checksums.update(b"123456789");
+        assert_eq!(checksums.sum.value(), 0xdd);
+        assert_eq!(checksums.crc16.map(|x| x.value()), Some(0x29b1));
+        assert_eq!(checksums.crc32.map(|x| x.value()), Some(0xcbf4_3926));
+    }
+}
@@ -0,0 +1,242 @@
+//! Records that borrow the firmware and text payloads.
+//!
+//! A [`Record`] owns all its data, so a pipeline that parse, filter and
+//! compose a file in memory copies every firmware chunk. [`CowRecord`] keep
+//! the payloads borrowed from the file when they are not modified, the
+//! firmware is only copied if it need to be decoded.
+//!
+//! ```
+//! use gcd_rs::composer::Composer;
+//! use gcd_rs::parser::Parser;
+//! use gcd_rs::record::cow::CowRecord;
+//! use gcd_rs::testing::vectors;
+//!
+//! let data = vectors::SINGLE_BLOCK;
+//! let mut parser: Parser<&[u8]> = Parser::new(data).unwrap();
+//! let mut out = vec![];
+//! let mut composer: Composer<_> = Composer::new(&mut out).unwrap();
+//! loop {
+//!     let record = parser.read_cow_record().unwrap();
+//!     if let CowRecord::FirmwareData { data: chunk, .. } = &record {
+//!         assert!(matches!(chunk, std::borrow::Cow::Borrowed(_)));
+//!     }
+//!     composer.write_cow_record(&record).unwrap();
+//!     if record.is_end() {
+//!         break;
+//!     }
+//! }
+//! drop(composer);
+//! assert_eq!(out, data);
+//! ```
+
+use std::borrow::Cow;
+
+use super::firmware::FirmwareRecord;
+use super::text::TextRecord;
+use crate::{Record, RecordId};
+
+/// A [`Record`] with the firmware and text payloads borrowed, if possible.
+///
+/// Returned by [`Parser::read_cow_record`] and written by
+/// [`Composer::write_cow_record`].
+///
+/// [`Parser::read_cow_record`]: crate::parser::Parser::read_cow_record
+/// [`Composer::write_cow_record`]:
+/// crate::composer::Composer::write_cow_record
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CowRecord<'a> {
+    /// Decoded firmware chunk, see [`FirmwareRecord`].
+    FirmwareData {
+        id: RecordId,
+        part_idx: Option<u8>,
+        data: Cow<'a, [u8]>,
+    },
+    /// UTF-8 text, see [`TextRecord::Simple`].
+    Text(Cow<'a, str>),
+    /// Any other record, those are small and always owned.
+    Other(Record),
+}
+
+impl CowRecord<'_> {
+    /// The owned record, copying the payload if borrowed.
+    pub fn into_owned(self) -> Record {
+        match self {
+            CowRecord::FirmwareData {
+                id,
+                part_idx: Some(part_idx),
+                data,
+            } => Record::FirmwareData(FirmwareRecord::new_part(
+                data.into_owned(),
+                id,
+                part_idx,
+            )),
+            CowRecord::FirmwareData {
+                id,
+                part_idx: None,
+                data,
+            } => {
+                Record::FirmwareData(FirmwareRecord::new(data.into_owned(), id))
+            }
+            CowRecord::Text(text) => {
+                Record::Text(TextRecord::Simple(text.into_owned()))
+            }
+            CowRecord::Other(record) => record,
+        }
+    }
+    /// True if this is the End record.
+    pub fn is_end(&self) -> bool {
+        matches!(self, CowRecord::Other(Record::End))
+    }
+}
+
+impl From<Record> for CowRecord<'_> {
+    fn from(record: Record) -> Self {
+        match record {
+            Record::FirmwareData(firmware) => {
+                let (id, part_idx) = (firmware.id(), firmware.part_idx());
+                let data = match firmware {
+                    FirmwareRecord::EmptyChunk { .. } => vec![],
+                    FirmwareRecord::Chunk { data, .. }
+                    | FirmwareRecord::Part { data, .. } => data,
+                };
+                CowRecord::FirmwareData {
+                    id,
+                    part_idx,
+                    data: Cow::Owned(data),
+                }
+            }
+            Record::Text(TextRecord::Simple(text)) => {
+                CowRecord::Text(Cow::Owned(text))
+            }
+            record => CowRecord::Other(record),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CowRecord;
+    use crate::composer::{compose_all, Composer};
+    use crate::config::{ComposerConfig, ParserConfig};
+    use crate::parser::{parse_all, Parser};
+    use crate::record::descriptor::descriptor_data::{
+        DescriptorData, DescriptorDecoded,
+    };
+    use crate::record::descriptor::DescriptorRecord;
+    use crate::record::firmware::FirmwareRecord;
+    use crate::record::main::MainRecord;
+    use crate::testing::vectors;
+    use crate::{Record, RecordId};
+    use std::borrow::Cow;
+
+    fn read_all(parser: &mut Parser<&[u8]>) -> Vec<CowRecord<'static>> {
+        let mut records = vec![];
+        loop {
+            let record = parser.read_cow_record().unwrap();
+            let end = record.is_end();
+            records.push(CowRecord::from(record.into_owned()));
+            if end {
+                return records;
+            }
+        }
+    }
+
+    #[test]
+    fn cow_records() {
+        for (name, data) in vectors::ALL {
+            let expected = parse_all(data).unwrap();
+            let mut parser: Parser<&[u8]> = Parser::new(*data).unwrap();
+            let mut records = vec![];
+            let mut out = vec![];
+            let mut composer: Composer<_> = Composer::new(&mut out).unwrap();
+            loop {
+                let record = parser.read_cow_record().unwrap();
+                if let CowRecord::FirmwareData { data, .. } = &record {
+                    assert!(matches!(data, Cow::Borrowed(_)), "{}", name);
+                }
+                composer.write_cow_record(&record).unwrap();
+                let end = record.is_end();
+                records.push(record.into_owned());
+                if end {
+                    break;
+                }
+            }
+            drop(composer);
+            assert_eq!(records, expected, "{}", name);
+            assert_eq!(out, compose_all(&expected).unwrap(), "{}", name);
+            assert_eq!(parser.position(), data.len() as u64);
+        }
+
+        //the XorKey force the data to be copied and decoded
+        let chunk =
+            FirmwareRecord::new(b"data".to_vec(), RecordId::new(0x0505));
+        let descriptor = DescriptorRecord::Simple(vec![
+            DescriptorDecoded::XorKey(0x11).encode(),
+            DescriptorDecoded::FirmwareId(0x0505).encode(),
+            DescriptorDecoded::FirmwareLen(4).encode(),
+            DescriptorData::End,
+        ]);
+        let file = compose_all(&[
+            Record::MainHeader(MainRecord::DefaultHWID),
+            Record::Descriptor(descriptor),
+            Record::FirmwareData(chunk.clone()),
+            Record::End,
+        ])
+        .unwrap();
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        parser.read_cow_record().unwrap();
+        parser.read_cow_record().unwrap();
+        let record = parser.read_cow_record().unwrap();
+        assert!(matches!(
+            &record,
+            CowRecord::FirmwareData {
+                data: Cow::Owned(_),
+                ..
+            }
+        ));
+        assert_eq!(record.into_owned(), Record::FirmwareData(chunk));
+
+        let file = vectors::MULTI_BLOCK;
+        let mut parser: Parser<&[u8]> = Parser::new(file).unwrap();
+        let records = read_all(&mut parser);
+
+        //split in chunks by the composer
+        let config = ComposerConfig {
+            max_chunk_size: Some(3),
+            ..ComposerConfig::default()
+        };
+        let mut out = vec![];
+        let mut composer: Composer<_> =
+            Composer::with_config(&mut out, &config).unwrap();
+        for record in &records {
+            composer.write_cow_record(record).unwrap();
+        }
+        drop(composer);
+        let chunks = parse_all(&out)
+            .unwrap()
+            .iter()
+            .filter(|x| matches!(x, Record::FirmwareData(_)))
+            .count();
+        assert_eq!(chunks, 6);
+
+        //not decoded, the raw data is borrowed
+        let config = ParserConfig {
+            decode_firmware: false,
+            ..ParserConfig::default()
+        };
+        let mut parser: Parser<&[u8]> =
+            Parser::with_config(file, config).unwrap();
+        for _ in 0..3 {
+            parser.read_cow_record().unwrap();
+        }
+        let record = parser.read_cow_record().unwrap();
+        assert_eq!(
+            record,
+            CowRecord::FirmwareData {
+                id: RecordId::new(0x0505),
+                part_idx: None,
+                data: Cow::Borrowed(&file[50..54]),
+            }
+        );
+    }
+}
@@ -5,27 +5,42 @@
 //! kind could be:
 //! 0..4 => , with "1 >> kind" is the data size.
 //!
-//! 4 => A variable data size, the next 2 bytes after the descritod type is the
-//! size. But this need more investigation.
+//! 4 => A variable data size, stored as u16 in one of two places, see
+//! [`LenLocation`]. Usually the next 2 bytes after the descriptor type are the
+//! size. Some files have only the type, with the size being the first 2 bytes
+//! of the descriptor data. The size after the type is tried first, if the
+//! types don't match the data record size, the types are read again with
+//! the size in the data. A record don't mix the two.
 //!
 //! 5 => End of the list, possibly only ID 0x003 is valid.
 //!
+//! 6..16 => Unknown, the data is kept as raw bytes. Because the data size is
+//! unknown, only one of those is allowed per descriptor, using the data left.
 //!
-//! The order or descriptors seems to be irrelevant.
-
-//TODO doc this
+//! The order or descriptors seems to be irrelevant. The parser and composer
+//! keep the order of the file, so a parsed file is composed byte by byte
+//! equal. [`DescriptorRecord::canonicalize`] sort the descriptors in the
+//! usual order, for a deterministic output.
+//!
+//! The list is terminated by the first End, the descriptors are added before
+//! it, see [`DescriptorRecord::is_terminated`].
 
-use crate::{RecordHeader, RECORD_HEADER_LEN};
+use crate::error::{GcdError, Result};
+use crate::{RecordHeader, RecordLen, Version, RECORD_HEADER_LEN};
 use byteorder::ByteOrder;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
-use std::io::{Error, ErrorKind, Result};
 
 pub mod descriptor_data;
 pub mod descriptor_type;
+pub mod fields;
+pub mod schema;
+pub mod tlv;
 
-use descriptor_data::DescriptorData;
-use descriptor_type::DescriptorType;
+use super::firmware::{MultiPartFirmware, PARTS};
+use descriptor_data::{DescriptorData, DescriptorDecoded};
+use descriptor_type::{DescriptorType, LenLocation};
 
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
 pub enum DescriptorTypeRecord {
@@ -49,8 +64,7 @@ impl DescriptorTypeRecord {
         B: ByteOrder,
     {
         if lenght % 2 != 0 {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
+            return Err(GcdError::invalid_input(
                 "Record Descriptor type size need to be multiple of 2",
             ));
         }
@@ -58,37 +72,38 @@ impl DescriptorTypeRecord {
         let mut data = vec![0u8; lenght as usize];
         file.read_exact(&mut data)?;
 
-        // Obs for each Other sized, we allocate 2 bytes more then necessary.
-        // Is very rare to have a Other sized, so the shrink is realy necessary?
-        let mut descriptors = Vec::with_capacity(lenght as usize / 2);
-
-        let mut current = data.as_slice();
-        while current.len() != 0 {
-            let (next, descriptor_type) =
-                DescriptorType::from_raw::<B>(current)?;
-            descriptors.push(descriptor_type);
-            current = next;
-        }
+        //a kind 4 at the end without the size, it's stored in the data
+        let descriptors = parse_types::<B>(&data, LenLocation::Type)
+            .or_else(|_| parse_types::<B>(&data, LenLocation::Data))?;
         Ok(DescriptorTypeRecord::Simple(descriptors))
     }
+    /// Where the kind 4 data sizes are stored, None if there is no kind 4
+    /// descriptor.
+    pub fn len_location(&self) -> Option<LenLocation> {
+        self.iter().find_map(DescriptorType::len_location)
+    }
     pub fn len(&self) -> usize {
         match self {
             DescriptorTypeRecord::Simple(descs) => descs.len(),
         }
     }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Sum of the descriptors data len, saturated at `u16::MAX`.
     pub fn data_len(&self) -> u16 {
         match self {
-            DescriptorTypeRecord::Simple(descs) => {
-                descs.iter().map(|x| x.data_len()).sum::<u16>()
-            }
+            DescriptorTypeRecord::Simple(descs) => descs
+                .iter()
+                .fold(0, |sum, x| sum.saturating_add(x.data_len())),
         }
     }
-    pub fn iter(&self) -> std::slice::Iter<DescriptorType> {
+    pub fn iter(&self) -> std::slice::Iter<'_, DescriptorType> {
         match self {
             DescriptorTypeRecord::Simple(descs) => descs.iter(),
         }
     }
-    pub fn iter_mut(&mut self) -> std::slice::IterMut<DescriptorType> {
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, DescriptorType> {
         match self {
             DescriptorTypeRecord::Simple(descs) => descs.iter_mut(),
         }
@@ -102,6 +117,7 @@ impl Default for DescriptorTypeRecord {
 }
 
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum DescriptorRecord {
     Simple(Vec<DescriptorData>),
 }
@@ -125,20 +141,21 @@ impl DescriptorRecord {
         F: std::io::Read,
         B: ByteOrder,
     {
+        //read the descriptor data
+        let mut data = vec![0u8; lenght as usize];
+        file.read_exact(&mut data)?;
+
         // Check if Descriptor Type record expect this data size
-        if desc_type.data_len() != lenght {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
+        let types: Vec<DescriptorType> = desc_type.iter().cloned().collect();
+        let types = resolve_types::<B>(&types, &data);
+        if data_len(&types) != u32::from(lenght) {
+            return Err(GcdError::invalid_input(
                 "Record Descriptor data is Invalid/Unexpected",
             ));
         }
 
-        //read the descriptor data
-        let mut data = vec![0u8; lenght as usize];
-        file.read_exact(&mut data)?;
-
         let mut current = data.as_slice();
-        let descriptors = desc_type
+        let descriptors = types
             .iter()
             .map(|x| {
                 let (next, desc_data) =
@@ -148,22 +165,239 @@ impl DescriptorRecord {
             })
             .collect::<Result<_>>()?;
 
-        if current.len() != 0 {
-            panic!("Programing Error on Descriptor Data parsing");
+        if !current.is_empty() {
+            return Err(GcdError::invalid_data(
+                "Record Descriptor data bigger than the Descriptor types",
+            ));
         }
 
         Ok(DescriptorRecord::Simple(descriptors))
     }
-    pub fn iter(&self) -> std::slice::Iter<DescriptorData> {
+    pub fn iter(&self) -> std::slice::Iter<'_, DescriptorData> {
         match self {
             DescriptorRecord::Simple(descs) => descs.iter(),
         }
     }
-    pub fn iter_mut(&mut self) -> std::slice::IterMut<DescriptorData> {
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, DescriptorData> {
         match self {
             DescriptorRecord::Simple(descs) => descs.iter_mut(),
         }
     }
+    /// Where the kind 4 data sizes are stored, None if there is no kind 4
+    /// descriptor.
+    pub fn len_location(&self) -> Option<LenLocation> {
+        self.iter().find_map(DescriptorData::len_location)
+    }
+    /// True if there is an End descriptor. The descriptors after it, if
+    /// any, are kept as is.
+    pub fn is_terminated(&self) -> bool {
+        self.iter().any(|x| *x == DescriptorData::End)
+    }
+    /// The descriptor with the End added at the end, if not
+    /// [terminated](DescriptorRecord::is_terminated).
+    pub fn terminated(&self) -> Cow<'_, DescriptorRecord> {
+        if self.is_terminated() {
+            return Cow::Borrowed(self);
+        }
+        let mut descriptor = self.clone();
+        let DescriptorRecord::Simple(descs) = &mut descriptor;
+        descs.push(DescriptorData::End);
+        Cow::Owned(descriptor)
+    }
+    //index of the last descriptor with the type value
+    fn position(&self, value: u16) -> Option<usize> {
+        self.iter()
+            .rposition(|x| x.descriptor_type().value() == value)
+    }
+    /// Replace the descriptor with the same type of `value`, if repeated the
+    /// last one is replaced, otherwise `value` is added with
+    /// [`DescriptorRecord::push`]. Return the replaced descriptor.
+    ///
+    /// The DescriptorType record and the record lens are calculated from the
+    /// data, so they are kept consistent.
+    pub fn set(&mut self, value: DescriptorDecoded) -> Option<DescriptorData> {
+        let value = value.encode();
+        match self.position(value.descriptor_type().value()) {
+            Some(index) => {
+                let DescriptorRecord::Simple(descs) = self;
+                Some(std::mem::replace(&mut descs[index], value))
+            }
+            None => {
+                self.push_data(value);
+                None
+            }
+        }
+    }
+    /// Add the descriptor before the End descriptor.
+    pub fn push(&mut self, value: DescriptorDecoded) {
+        self.push_data(value.encode())
+    }
+    fn push_data(&mut self, value: DescriptorData) {
+        let DescriptorRecord::Simple(descs) = self;
+        let end = descs
+            .iter()
+            .position(|x| *x == DescriptorData::End)
+            .unwrap_or(descs.len());
+        descs.insert(end, value);
+    }
+    /// Remove the last descriptor with the type value (format 0xABBB, see
+    /// [`DescriptorType::value`]), the End descriptor can't be removed.
+    pub fn remove(&mut self, value: u16) -> Option<DescriptorData> {
+        if value == DescriptorType::End.value() {
+            return None;
+        }
+        let index = self.position(value)?;
+        let DescriptorRecord::Simple(descs) = self;
+        Some(descs.remove(index))
+    }
+    /// Sort the descriptors in the conventional order: FirmwareId, HWID, the
+    /// versions, the lens, the other descriptors and End. Inside each group
+    /// the descriptors are sorted by the type value, repeated descriptors
+    /// keep their order.
+    pub fn canonicalize(&mut self) {
+        let DescriptorRecord::Simple(descs) = self;
+        descs.sort_by_key(|x| {
+            let value = x.descriptor_type().value();
+            let group = match value {
+                _ if *x == DescriptorData::End => 5,
+                fields::FIELD_FIRMWARE_ID => 0,
+                fields::FIELD_HWID => 1,
+                fields::FIELD_VERSION_ID12
+                | fields::FIELD_VERSION_SW
+                | fields::FIELD_VERSION_ID20
+                | fields::FIELD_VERSION_REMOTE => 2,
+                fields::FIELD_FW_LEN
+                | fields::FIELD_FW_2000_P1_LEN
+                | fields::FIELD_FW_2000_P2_LEN
+                | fields::FIELD_FW_2000_P3_LEN => 3,
+                _ => 4,
+            };
+            (group, value)
+        });
+    }
+    //if repeated, the last value is used
+    fn find<T, P>(&self, predicate: P) -> Option<T>
+    where
+        P: Fn(DescriptorDecoded) -> Option<T>,
+    {
+        self.iter()
+            .rev()
+            .filter_map(|x| x.decode())
+            .find_map(predicate)
+    }
+    pub fn firmware_id(&self) -> Option<u16> {
+        self.find(|x| match x {
+            DescriptorDecoded::FirmwareId(x) => Some(x),
+            _ => None,
+        })
+    }
+    /// Len of the firmware block, from the FirmwareLen or the sum of the
+    /// Firmware2000 parts lens.
+    pub fn firmware_len(&self) -> Option<u32> {
+        let len = self.find(|x| match x {
+            DescriptorDecoded::FirmwareLen(x) => Some(x),
+            _ => None,
+        });
+        len.or_else(|| {
+            let lens = self.part_lens();
+            if lens.iter().all(Option::is_none) {
+                return None;
+            }
+            lens.iter()
+                .try_fold(0u32, |acc, x| acc.checked_add(x.unwrap_or(0)))
+        })
+    }
+    fn part_lens(&self) -> [Option<u32>; PARTS] {
+        [
+            self.find(|x| match x {
+                DescriptorDecoded::Firmware2000P1Len(x) => Some(x),
+                _ => None,
+            }),
+            self.find(|x| match x {
+                DescriptorDecoded::Firmware2000P2Len(x) => Some(x),
+                _ => None,
+            }),
+            self.find(|x| match x {
+                DescriptorDecoded::Firmware2000P3Len(x) => Some(x),
+                _ => None,
+            }),
+        ]
+    }
+    /// The Firmware2000 parts lens, None if the block is not split in parts.
+    /// All the three parts lens are required.
+    pub fn firmware_parts(&self) -> Result<Option<MultiPartFirmware>> {
+        const NAMES: [&str; PARTS] = [
+            "Firmware2000P1Len",
+            "Firmware2000P2Len",
+            "Firmware2000P3Len",
+        ];
+        let lens = self.part_lens();
+        if lens.iter().all(Option::is_none) {
+            return Ok(None);
+        }
+        let mut parts = [0; PARTS];
+        for (i, len) in lens.iter().enumerate() {
+            parts[i] = len.ok_or(GcdError::MissingDescriptor(NAMES[i]))?;
+        }
+        let parts = MultiPartFirmware::new(parts);
+        if parts.total_len().is_none() {
+            return Err(GcdError::invalid_data("Firmware parts too big"));
+        }
+        Ok(Some(parts))
+    }
+    /// Address where the firmware is loaded, see
+    /// [`assembler`](crate::assembler).
+    pub fn firmware_addr(&self) -> Option<u32> {
+        self.find(|x| match x {
+            DescriptorDecoded::FirmwareAddr(x) => Some(x),
+            _ => None,
+        })
+    }
+    pub fn xor_key(&self) -> Option<u8> {
+        self.find(|x| match x {
+            DescriptorDecoded::XorKey(x) => Some(x),
+            _ => None,
+        })
+    }
+    pub fn sw_version(&self) -> Option<Version> {
+        self.find(|x| match x {
+            DescriptorDecoded::VersionSw(x) => Some(x),
+            _ => None,
+        })
+    }
+    pub fn remote_version(&self) -> Option<Version> {
+        self.find(|x| match x {
+            DescriptorDecoded::VersionRemote(x) => Some(x),
+            _ => None,
+        })
+    }
+    pub fn hw_id(&self) -> Option<u16> {
+        self.find(|x| match x {
+            DescriptorDecoded::HWID(x) => Some(x),
+            _ => None,
+        })
+    }
+    pub fn device_model(&self) -> Option<String> {
+        self.find(|x| match x {
+            DescriptorDecoded::DeviceModel(x) => Some(x),
+            _ => None,
+        })
+    }
+    /// Len of the DescriptorType and DescriptorData records, fail if any is
+    /// bigger than u16::MAX.
+    pub fn record_lens(&self) -> Result<(RecordLen, RecordLen)> {
+        let type_len = RecordLen::sum(
+            self.iter().map(|x| x.descriptor_type().len() as usize),
+        )?;
+        //the Other descriptor type store the data len as u16
+        if self.iter().any(|x| x.body_len() > u16::MAX as usize) {
+            return Err(GcdError::invalid_input(
+                "Descriptor data bigger than u16::MAX",
+            ));
+        }
+        let data_len = RecordLen::sum(self.iter().map(|x| x.body_len()))?;
+        Ok((type_len, data_len))
+    }
     pub fn record_type_len(&self) -> u16 {
         match self {
             DescriptorRecord::Simple(x) => {
@@ -180,9 +414,14 @@ impl DescriptorRecord {
         &self,
         data: &'a mut [u8],
     ) -> Result<&'a mut [u8]> {
+        let (type_len, _) = self.record_lens()?;
+        if data.len() < RECORD_HEADER_LEN + type_len.as_usize() {
+            return Err(GcdError::invalid_input(
+                "Descriptor Type record buffer too small",
+            ));
+        }
         //write header
-        RecordHeader::DescriptorType(self.record_type_len())
-            .to_raw::<B>(data)?;
+        RecordHeader::DescriptorType(type_len.get()).to_raw::<B>(data)?;
 
         //write record body
         let mut current = &mut data[RECORD_HEADER_LEN..];
@@ -197,16 +436,521 @@ impl DescriptorRecord {
         &self,
         data: &'a mut [u8],
     ) -> Result<&'a mut [u8]> {
+        let (_, data_len) = self.record_lens()?;
+        if data.len() < RECORD_HEADER_LEN + data_len.as_usize() {
+            return Err(GcdError::invalid_input(
+                "Descriptor Data record buffer too small",
+            ));
+        }
         //write header
-        RecordHeader::DescriptorData(self.record_data_len())
-            .to_raw::<B>(data)?;
+        RecordHeader::DescriptorData(data_len.get()).to_raw::<B>(data)?;
 
         //write record body
         let mut current = &mut data[RECORD_HEADER_LEN..];
         for desc in self.iter() {
-            current = desc.to_raw::<B>(current).unwrap();
+            current = desc.to_raw::<B>(current)?;
         }
 
         Ok(current)
     }
 }
+
+fn parse_types<B: ByteOrder>(
+    mut data: &[u8],
+    location: LenLocation,
+) -> Result<Vec<DescriptorType>> {
+    let mut types = Vec::with_capacity(data.len() / 2);
+    while !data.is_empty() {
+        let (next, descriptor_type) =
+            DescriptorType::from_raw_with::<B>(data, location)?;
+        types.push(descriptor_type);
+        data = next;
+    }
+    Ok(types)
+}
+
+//summed as u32, so a crafted list of big types can't overflow
+fn data_len(types: &[DescriptorType]) -> u32 {
+    types.iter().map(|x| u32::from(x.data_len())).sum()
+}
+
+// The types with the sizes resolved using the descriptor data. If the sizes
+// don't match, the kind 4 are read again with the size in the data.
+pub(crate) fn resolve_types<B: ByteOrder>(
+    types: &[DescriptorType],
+    data: &[u8],
+) -> Vec<DescriptorType> {
+    let resolved = resolve_lens::<B>(types.to_vec(), data);
+    let is_other =
+        |x: &DescriptorType| matches!(x, DescriptorType::Other { .. });
+    if data_len(&resolved) == data.len() as u32 || !types.iter().any(is_other) {
+        return resolved;
+    }
+    //the u16 read as size is the next type
+    let mut raw = vec![0; types.iter().map(|x| usize::from(x.len())).sum()];
+    let mut current = raw.as_mut_slice();
+    for desc in types {
+        current = match desc.to_raw::<B>(current) {
+            Ok(next) => next,
+            Err(_) => return resolved,
+        };
+    }
+    match parse_types::<B>(&raw, LenLocation::Data) {
+        Ok(prefixed) => {
+            let prefixed = resolve_lens::<B>(prefixed, data);
+            if data_len(&prefixed) == data.len() as u32 {
+                prefixed
+            } else {
+                resolved
+            }
+        }
+        Err(_) => resolved,
+    }
+}
+
+fn resolve_lens<B: ByteOrder>(
+    mut types: Vec<DescriptorType>,
+    data: &[u8],
+) -> Vec<DescriptorType> {
+    //the prefixed sizes are read in order, until the first unknown size
+    let mut offset = 0usize;
+    for desc in types.iter_mut() {
+        match desc {
+            DescriptorType::OtherPrefixed { lenght, .. } => {
+                match data.get(offset..offset + 2) {
+                    Some(prefix) => *lenght = B::read_u16(prefix),
+                    None => break,
+                }
+            }
+            DescriptorType::Raw { .. } => break,
+            _ => {}
+        }
+        offset += usize::from(desc.data_len());
+    }
+
+    // Unknown kinds don't declare the data size, if there is only one, it
+    // can be resolved using the data record size.
+    let is_raw = |x: &DescriptorType| matches!(x, DescriptorType::Raw { .. });
+    let known: u32 = types
+        .iter()
+        .filter(|x| !is_raw(x))
+        .map(|x| u32::from(x.data_len()))
+        .sum();
+    let mut raw = types.iter_mut().filter(|x| is_raw(x));
+    if let (Some(DescriptorType::Raw { data_len, .. }), None, true) =
+        (raw.next(), raw.next(), known <= data.len() as u32)
+    {
+        *data_len = (data.len() as u32 - known) as u16;
+    }
+    types
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::composer::compose_all;
+    use crate::parser::parse_all;
+    use crate::record::descriptor::descriptor_data::{
+        DescriptorData, DescriptorDecoded, Field, Unit, FIELDS,
+    };
+    use crate::record::descriptor::descriptor_type::{
+        DescriptorType, LenLocation,
+    };
+    use crate::record::descriptor::{DescriptorRecord, DescriptorTypeRecord};
+    use crate::testing::vectors;
+    use crate::{Record, Version};
+    use byteorder::{BE, LE};
+
+    #[test]
+    fn descriptor_raw_kind() {
+        let descriptor = DescriptorRecord::Simple(vec![
+            DescriptorData::U16 {
+                id: 10,
+                data: 0x0505,
+            },
+            DescriptorData::Raw {
+                kind: 7,
+                id: 1,
+                data: vec![1, 2, 3],
+            },
+            DescriptorData::End,
+        ]);
+        let type_len = descriptor.record_type_len() as usize;
+        let data_len = descriptor.record_data_len() as usize;
+        let mut type_raw = vec![0; type_len + 4];
+        let mut data_raw = vec![0; data_len + 4];
+        descriptor.record_type_to_raw::<LE>(&mut type_raw).unwrap();
+        descriptor.record_data_to_raw::<LE>(&mut data_raw).unwrap();
+
+        let desc_type = DescriptorTypeRecord::new::<_, LE>(
+            &mut &type_raw[4..],
+            type_len as u16,
+        )
+        .unwrap();
+        let parsed = DescriptorRecord::new::<_, LE>(
+            &mut &data_raw[4..],
+            data_len as u16,
+            &desc_type,
+        )
+        .unwrap();
+        assert_eq!(parsed, descriptor);
+    }
+
+    #[test]
+    fn descriptor_len_location() {
+        let vectors = [
+            (vectors::OTHER_LEN_IN_TYPE, LenLocation::Type),
+            (vectors::OTHER_LEN_IN_DATA, LenLocation::Data),
+        ];
+        let mut descriptors = vec![];
+        for (data, location) in vectors {
+            let records = parse_all(data).unwrap();
+            let descriptor = records
+                .iter()
+                .find_map(|x| match x {
+                    Record::Descriptor(x) => Some(x.clone()),
+                    _ => None,
+                })
+                .unwrap();
+            assert_eq!(descriptor.len_location(), Some(location));
+            assert_eq!(descriptor.device_model().as_deref(), Some("fenix"));
+            assert_eq!(descriptor.firmware_len(), Some(8));
+            assert_eq!(compose_all(&records).unwrap(), data);
+            descriptors.push(descriptor);
+        }
+        let DescriptorRecord::Simple(descs) = &descriptors[0];
+        let converted: Vec<_> = descs
+            .iter()
+            .cloned()
+            .map(|x| x.with_len_location(LenLocation::Data))
+            .collect();
+        assert_eq!(
+            converted.iter().find(|x| x.len_location().is_some()),
+            descriptors[1].iter().find(|x| x.len_location().is_some())
+        );
+
+        //a kind 4 at the end, the size can only be in the data
+        let desc_type =
+            DescriptorTypeRecord::new::<_, BE>(&mut &[0x40, 0x05][..], 2)
+                .unwrap();
+        assert_eq!(desc_type.len_location(), Some(LenLocation::Data));
+        let data = [0x00, 0x03, b'a', b'b', b'c'];
+        let parsed =
+            DescriptorRecord::new::<_, BE>(&mut &data[..], 5, &desc_type)
+                .unwrap();
+        let expected = DescriptorData::OtherPrefixed {
+            id: 5,
+            data: b"abc".to_vec(),
+        };
+        assert_eq!(parsed, DescriptorRecord::Simple(vec![expected.clone()]));
+        let mut raw = [0; 5];
+        expected.to_raw::<BE>(&mut raw).unwrap();
+        assert_eq!(raw, data);
+
+        //the prefix don't match the size in the type
+        let desc_type = DescriptorType::OtherPrefixed { id: 5, lenght: 2 };
+        assert!(DescriptorData::from_raw::<BE>(&desc_type, &data).is_err());
+    }
+
+    #[test]
+    fn descriptor_len_overflow() {
+        //two Other of 0x8000 bytes, the sum overflow the u16
+        let type_raw = [0x01, 0x40, 0x00, 0x80, 0x02, 0x40, 0x00, 0x80];
+        let desc_type =
+            DescriptorTypeRecord::new::<_, LE>(&mut &type_raw[..], 8).unwrap();
+        assert_eq!(desc_type.data_len(), u16::MAX);
+        for len in [0, 0x8000, u16::MAX] {
+            let data = vec![0; len as usize];
+            let parsed =
+                DescriptorRecord::new::<_, LE>(&mut &data[..], len, &desc_type);
+            assert!(parsed.is_err());
+        }
+    }
+
+    #[test]
+    fn descriptor_decode_table() {
+        let decoded = vec![
+            DescriptorDecoded::XorKey(0x55),
+            DescriptorDecoded::FirmwareLen(0x1000),
+            DescriptorDecoded::RegionId(2),
+            DescriptorDecoded::BuildTimestamp(1_600_000_000),
+            DescriptorDecoded::DeviceModel("Edge 1030".to_string()),
+            DescriptorDecoded::Sha1([0xaa; 20]),
+            DescriptorDecoded::Sha256([0xbb; 32]),
+            DescriptorDecoded::Unknown {
+                kind: 1,
+                id: 0x123,
+                raw: vec![0x34, 0x12],
+            },
+            DescriptorDecoded::Unknown {
+                kind: 4,
+                id: 0x123,
+                raw: vec![1, 2, 3],
+            },
+        ];
+        for x in decoded.iter() {
+            assert_eq!(&x.clone().encode().decode_any(), x);
+        }
+        assert_eq!(
+            decoded[7].clone().encode(),
+            DescriptorData::U16 {
+                id: 0x123,
+                data: 0x1234
+            }
+        );
+        assert_eq!(decoded[7].clone().encode().decode(), None);
+
+        //a known id with unexpected data is not decoded
+        let sha = DescriptorData::Other {
+            id: Field::Sha1.info().id,
+            data: vec![0; 3],
+        };
+        assert!(matches!(
+            sha.decode_any(),
+            DescriptorDecoded::Unknown { kind: 4, .. }
+        ));
+
+        //the table have no duplicated fields
+        for (i, x) in FIELDS.iter().enumerate() {
+            assert!(FIELDS[i + 1..].iter().all(|y| (x.kind, x.id)
+                != (y.kind, y.id)
+                && x.field != y.field));
+        }
+    }
+
+    #[test]
+    fn descriptor_describe() {
+        let len = DescriptorDecoded::FirmwareLen(8).describe();
+        assert_eq!(len.name, "FirmwareLen");
+        assert_eq!(len.value, "8");
+        assert_eq!(len.unit, Some(Unit::Bytes));
+        assert_eq!(len.to_string(), "FirmwareLen: 8 bytes");
+
+        let described = [
+            (DescriptorDecoded::XorKey(0x55), "XorKey: 0x55"),
+            (DescriptorDecoded::FirmwareId(0x0505), "FirmwareId: 0x0505"),
+            (
+                DescriptorDecoded::FirmwareAddr(0x1000),
+                "FirmwareAddr: 0x00001000",
+            ),
+            (
+                DescriptorDecoded::BuildTimestamp(1_600_000_000),
+                "BuildTimestamp: 1600000000 unix time",
+            ),
+            (DescriptorDecoded::Sha1([0xab; 20]), &"ab".repeat(20)),
+            (
+                DescriptorDecoded::Unknown {
+                    kind: 4,
+                    id: 0x123,
+                    raw: vec![1, 2],
+                },
+                "Unknown(4, 0x123): 0102",
+            ),
+        ];
+        for (x, expected) in described.iter() {
+            assert!(x.describe().to_string().ends_with(expected));
+        }
+        let version = Version::new(1, 0);
+        assert_eq!(
+            DescriptorDecoded::VersionSw(version).describe().value,
+            version.to_string()
+        );
+    }
+
+    #[test]
+    fn descriptor_text() {
+        use super::schema::{DescriptorSchema, SchemaEntry};
+
+        let text = DescriptorDecoded::Text {
+            id: Field::Text.info().id,
+            value: "Europe".to_string(),
+        };
+        assert_eq!(text.clone().encode().decode(), Some(text.clone()));
+        assert_eq!(text.encode().as_str(), Some("Europe"));
+
+        //unknown ids are only text by the heuristic
+        let region = DescriptorData::Other {
+            id: 0x123,
+            data: b"Americas\0\0".to_vec(),
+        };
+        assert_eq!(region.as_str(), Some("Americas"));
+        assert_eq!(region.decode(), None);
+        let mut schema = DescriptorSchema::default();
+        schema.register(SchemaEntry::field(None, 4, 0x123, Field::Text));
+        assert_eq!(
+            schema.decode(None, &region),
+            DescriptorDecoded::Text {
+                id: 0x123,
+                value: "Americas".to_string()
+            }
+        );
+
+        for data in [&b""[..], b"\0", b"\x01\x02", b"abc\xff"].iter() {
+            let blob = DescriptorData::Other {
+                id: 0x123,
+                data: data.to_vec(),
+            };
+            assert_eq!(blob.as_str(), None);
+        }
+        let number = DescriptorData::U16 {
+            id: 0x123,
+            data: 0x4141,
+        };
+        assert_eq!(number.as_str(), None);
+    }
+
+    #[test]
+    fn descriptor_getters() {
+        let descriptor = DescriptorRecord::Simple(vec![
+            DescriptorDecoded::XorKey(0x5a).encode(),
+            DescriptorDecoded::FirmwareId(0x0505).encode(),
+            DescriptorDecoded::VersionSw(Version::new_raw(310)).encode(),
+            DescriptorDecoded::HWID(0x0037).encode(),
+            DescriptorDecoded::FirmwareLen(1000).encode(),
+            //repeated, the last one is used
+            DescriptorDecoded::FirmwareId(0x0506).encode(),
+            DescriptorDecoded::End.encode(),
+        ]);
+        assert_eq!(descriptor.firmware_id(), Some(0x0506));
+        assert_eq!(descriptor.firmware_len(), Some(1000));
+        assert_eq!(descriptor.xor_key(), Some(0x5a));
+        assert_eq!(descriptor.sw_version(), Some(Version::new_raw(310)));
+        assert_eq!(descriptor.remote_version(), None);
+        assert_eq!(descriptor.hw_id(), Some(0x0037));
+
+        let empty = DescriptorRecord::Simple(vec![DescriptorData::End]);
+        assert_eq!(empty.firmware_id(), None);
+        assert_eq!(empty.firmware_len(), None);
+    }
+
+    #[test]
+    fn descriptor_terminated() {
+        let descriptor =
+            DescriptorRecord::Simple(vec![DescriptorDecoded::FirmwareId(
+                0x0505,
+            )
+            .encode()]);
+        assert!(!descriptor.is_terminated());
+        let terminated = descriptor.terminated();
+        assert!(terminated.is_terminated());
+        assert_eq!(terminated.iter().count(), 2);
+        //already terminated, not changed
+        assert_eq!(terminated.terminated(), terminated);
+
+        //the End don't need to be the last one
+        let descriptor = DescriptorRecord::Simple(vec![
+            DescriptorData::End,
+            DescriptorDecoded::FirmwareId(0x0505).encode(),
+        ]);
+        assert!(descriptor.is_terminated());
+        assert!(matches!(
+            descriptor.terminated(),
+            std::borrow::Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn descriptor_mutation() {
+        let mut descriptor = DescriptorRecord::Simple(vec![
+            DescriptorDecoded::FirmwareId(0x0505).encode(),
+            DescriptorDecoded::FirmwareLen(1000).encode(),
+            DescriptorDecoded::End.encode(),
+        ]);
+        let (type_len, data_len) =
+            (descriptor.record_type_len(), descriptor.record_data_len());
+
+        let old = descriptor.set(DescriptorDecoded::FirmwareLen(2000));
+        assert_eq!(old, Some(DescriptorDecoded::FirmwareLen(1000).encode()));
+        assert_eq!(descriptor.firmware_len(), Some(2000));
+        assert_eq!(descriptor.record_type_len(), type_len);
+
+        //new descriptors are added before the End
+        let version = Version::new(3, 80);
+        assert_eq!(descriptor.set(DescriptorDecoded::VersionSw(version)), None);
+        descriptor.push(DescriptorDecoded::DeviceModel("Edge".to_string()));
+        assert_eq!(descriptor.sw_version(), Some(version));
+        assert_eq!(descriptor.iter().last(), Some(&DescriptorData::End));
+        assert_eq!(descriptor.record_type_len(), type_len + 6);
+        assert_eq!(descriptor.record_data_len(), data_len + 2 + 4);
+
+        let model = DescriptorDecoded::DeviceModel(String::new()).encode();
+        let removed = descriptor.remove(model.descriptor_type().value());
+        assert!(matches!(removed, Some(DescriptorData::Other { .. })));
+        let end = DescriptorData::End.descriptor_type().value();
+        assert_eq!(descriptor.remove(end), None);
+        assert_eq!(descriptor.iter().count(), 4);
+        assert_eq!(descriptor.remove(0x0fff), None);
+
+        //the type record is derived from the data
+        let mut type_raw = vec![0; descriptor.record_type_len() as usize + 4];
+        let mut data_raw = vec![0; descriptor.record_data_len() as usize + 4];
+        descriptor.record_type_to_raw::<LE>(&mut type_raw).unwrap();
+        descriptor.record_data_to_raw::<LE>(&mut data_raw).unwrap();
+        let desc_type = DescriptorTypeRecord::new::<_, LE>(
+            &mut &type_raw[4..],
+            descriptor.record_type_len(),
+        )
+        .unwrap();
+        let parsed = DescriptorRecord::new::<_, LE>(
+            &mut &data_raw[4..],
+            descriptor.record_data_len(),
+            &desc_type,
+        )
+        .unwrap();
+        assert_eq!(parsed, descriptor);
+    }
+
+    #[test]
+    fn descriptor_order() {
+        let mut records = parse_all(vectors::MULTI_BLOCK).unwrap();
+        let canonical: Vec<_> = records
+            .iter_mut()
+            .filter_map(|x| match x {
+                Record::Descriptor(x) => Some(x),
+                _ => None,
+            })
+            .map(|descriptor| {
+                let canonical = descriptor.clone();
+                //reverse the order, keeping the End last
+                let DescriptorRecord::Simple(descs) = descriptor;
+                let len = descs.len();
+                descs[..len - 1].reverse();
+                canonical
+            })
+            .collect();
+        //the parser and composer keep the order
+        let file = compose_all(&records).unwrap();
+        assert_eq!(parse_all(&file).unwrap(), records);
+
+        let reordered = records.iter().filter_map(|x| match x {
+            Record::Descriptor(x) => Some(x.clone()),
+            _ => None,
+        });
+        for (mut descriptor, canonical) in reordered.zip(canonical) {
+            assert_ne!(descriptor, canonical);
+            descriptor.canonicalize();
+            assert_eq!(descriptor, canonical);
+            //already canonical, not changed
+            descriptor.canonicalize();
+            assert_eq!(descriptor, canonical);
+        }
+
+        let mut descriptor = DescriptorRecord::Simple(vec![
+            DescriptorDecoded::DeviceModel("Edge".to_string()).encode(),
+            DescriptorDecoded::End.encode(),
+            DescriptorDecoded::FirmwareLen(1000).encode(),
+            DescriptorDecoded::VersionSw(Version::new(3, 80)).encode(),
+            DescriptorDecoded::HWID(0x0800).encode(),
+            DescriptorDecoded::FirmwareId(0x0505).encode(),
+        ]);
+        descriptor.canonicalize();
+        let expected = [
+            DescriptorDecoded::FirmwareId(0x0505).encode(),
+            DescriptorDecoded::HWID(0x0800).encode(),
+            DescriptorDecoded::VersionSw(Version::new(3, 80)).encode(),
+            DescriptorDecoded::FirmwareLen(1000).encode(),
+            DescriptorDecoded::DeviceModel("Edge".to_string()).encode(),
+            DescriptorData::End,
+        ];
+        assert!(descriptor.iter().eq(expected.iter()));
+    }
+}
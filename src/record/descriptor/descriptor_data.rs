@@ -1,32 +1,63 @@
+use crate::error::{GcdError, Result};
 use byteorder::ByteOrder;
 use serde::{Deserialize, Serialize};
-use std::io::{Error, ErrorKind, Result};
+use std::convert::TryInto;
+use std::fmt::{Display, Formatter};
 
 use crate::Version;
 
-use super::descriptor_type::DescriptorType;
+use super::descriptor_type::{DescriptorType, LenLocation};
+use super::fields::{self, *};
+use super::tlv::{self, TlvEntry};
 
 pub const ID: u16 = 7;
 
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum DescriptorData {
-    U8 { id: u16, data: u8 },
-    U16 { id: u16, data: u16 },
-    U32 { id: u16, data: u32 },
-    U64 { id: u16, data: u64 },
-    Other { id: u16, data: Vec<u8> },
+    U8 {
+        id: u16,
+        data: u8,
+    },
+    U16 {
+        id: u16,
+        data: u16,
+    },
+    U32 {
+        id: u16,
+        data: u32,
+    },
+    U64 {
+        id: u16,
+        data: u64,
+    },
+    Other {
+        id: u16,
+        data: Vec<u8>,
+    },
+    /// Same as `Other`, stored with the data size at the start of the data,
+    /// see [`LenLocation::Data`].
+    OtherPrefixed {
+        id: u16,
+        data: Vec<u8>,
+    },
     End, //only 0x5003 is valid, other value have unknown meaning
+    /// Data from a descriptor kind without known meaning.
+    Raw {
+        kind: u8,
+        id: u16,
+        data: Vec<u8>,
+    },
 }
 
 impl DescriptorData {
-    pub fn from_raw<'a, 'b, B: ByteOrder>(
-        descriptor_type: &'a DescriptorType,
+    pub fn from_raw<'b, B: ByteOrder>(
+        descriptor_type: &DescriptorType,
         data: &'b [u8],
     ) -> Result<(&'b [u8], DescriptorData)> {
         let len = descriptor_type.data_len() as usize;
         if data.len() < len {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
+            return Err(GcdError::invalid_input(
                 "Descriptor Data is bigger than the data available",
             ));
         }
@@ -51,7 +82,23 @@ impl DescriptorData {
                 id: *id,
                 data: data[..len].to_vec(),
             },
+            DescriptorType::OtherPrefixed { id, lenght } => {
+                if len < 2 || B::read_u16(data) != *lenght {
+                    return Err(GcdError::invalid_data(
+                        "Descriptor Data prefix don't match the data size",
+                    ));
+                }
+                DescriptorData::OtherPrefixed {
+                    id: *id,
+                    data: data[2..len].to_vec(),
+                }
+            }
             DescriptorType::End => DescriptorData::End,
+            DescriptorType::Raw { kind, id, .. } => DescriptorData::Raw {
+                kind: *kind,
+                id: *id,
+                data: data[..len].to_vec(),
+            },
         };
         Ok((&data[len..], descriptor))
     }
@@ -61,8 +108,7 @@ impl DescriptorData {
     ) -> Result<&'a mut [u8]> {
         let len = self.len() as usize;
         if buf.len() < len {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
+            return Err(GcdError::invalid_input(
                 "Descriptor Data buffer is to small",
             ));
         }
@@ -71,7 +117,14 @@ impl DescriptorData {
             DescriptorData::U16 { data, .. } => B::write_u16(buf, *data),
             DescriptorData::U32 { data, .. } => B::write_u32(buf, *data),
             DescriptorData::U64 { data, .. } => B::write_u64(buf, *data),
-            DescriptorData::Other { data, .. } => buf.copy_from_slice(data),
+            DescriptorData::Other { data, .. }
+            | DescriptorData::Raw { data, .. } => {
+                buf[..len].copy_from_slice(data)
+            }
+            DescriptorData::OtherPrefixed { data, .. } => {
+                B::write_u16(buf, data.len() as u16);
+                buf[2..len].copy_from_slice(data)
+            }
             DescriptorData::End => {}
         }
         Ok(&mut buf[len..])
@@ -86,60 +139,328 @@ impl DescriptorData {
                 id: *id,
                 lenght: data.len() as u16,
             },
+            DescriptorData::OtherPrefixed { id, data } => {
+                DescriptorType::OtherPrefixed {
+                    id: *id,
+                    lenght: data.len() as u16,
+                }
+            }
             DescriptorData::End => DescriptorType::End,
+            DescriptorData::Raw { kind, id, data } => DescriptorType::Raw {
+                kind: *kind,
+                id: *id,
+                data_len: data.len() as u16,
+            },
         }
     }
+    /// Len of the data, only valid if the data is not bigger than u16::MAX.
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> u16 {
         self.descriptor_type().data_len()
     }
-    pub const fn decode(&self) -> Option<DescriptorDecoded> {
+    //len of the data, without the u16 limit
+    pub(crate) fn body_len(&self) -> usize {
+        match self {
+            DescriptorData::Other { data, .. }
+            | DescriptorData::Raw { data, .. } => data.len(),
+            DescriptorData::OtherPrefixed { data, .. } => data.len() + 2,
+            _ => self.len() as usize,
+        }
+    }
+    /// Where the data size is stored, None if not a kind 4 descriptor.
+    pub fn len_location(&self) -> Option<LenLocation> {
         match self {
-            DescriptorData::End => Some(DescriptorDecoded::End),
-            DescriptorData::U8 { id: 10, data } => {
+            DescriptorData::Other { .. } => Some(LenLocation::Type),
+            DescriptorData::OtherPrefixed { .. } => Some(LenLocation::Data),
+            _ => None,
+        }
+    }
+    /// The same descriptor, with the kind 4 data size stored at `location`.
+    /// Other kinds are returned unchanged.
+    pub fn with_len_location(self, location: LenLocation) -> Self {
+        match (self, location) {
+            (
+                DescriptorData::Other { id, data }
+                | DescriptorData::OtherPrefixed { id, data },
+                LenLocation::Type,
+            ) => DescriptorData::Other { id, data },
+            (
+                DescriptorData::Other { id, data }
+                | DescriptorData::OtherPrefixed { id, data },
+                LenLocation::Data,
+            ) => DescriptorData::OtherPrefixed { id, data },
+            (descriptor, _) => descriptor,
+        }
+    }
+    // payload of a kind 4 descriptor, independent of the len location
+    fn other_data(&self) -> Option<(u16, &[u8])> {
+        match self {
+            DescriptorData::Other { id, data }
+            | DescriptorData::OtherPrefixed { id, data } => Some((*id, data)),
+            _ => None,
+        }
+    }
+    /// Data of an `Other` descriptor as TLV entries, None if the data don't
+    /// match the TLV pattern.
+    pub fn nested<B: ByteOrder>(&self) -> Option<Vec<TlvEntry>> {
+        match self.other_data() {
+            Some((_, data)) if !data.is_empty() => tlv::parse::<B>(data),
+            _ => None,
+        }
+    }
+    /// Data of an `Other` descriptor that looks like text: printable ASCII,
+    /// optionally zero terminated/padded. A heuristic for descriptors without
+    /// known meaning, eg: device and region names.
+    pub fn as_str(&self) -> Option<&str> {
+        match self.other_data().and_then(|(_, data)| ascii_text(data)) {
+            Some(text) if !text.is_empty() => Some(text),
+            _ => None,
+        }
+    }
+    /// Decode the known fields, None if the field is unknown, see
+    /// [`DescriptorData::decode_any`].
+    pub fn decode(&self) -> Option<DescriptorDecoded> {
+        match self.decode_any() {
+            DescriptorDecoded::Unknown { .. } => None,
+            decoded => Some(decoded),
+        }
+    }
+    /// Decode the data, fields not in the [`FIELDS`] table, or with
+    /// unexpected values, are returned as [`DescriptorDecoded::Unknown`].
+    pub fn decode_any(&self) -> DescriptorDecoded {
+        let kind = self.descriptor_type().kind();
+        let id = self.descriptor_type().id();
+        let field = FIELDS
+            .iter()
+            .find(|x| x.kind == kind && x.id == id)
+            .map(|x| x.field);
+        self.decode_field(field)
+    }
+    /// Decode the data as the `field`, ignoring the descriptor kind/id. Data
+    /// with unexpected values are returned as [`DescriptorDecoded::Unknown`].
+    pub fn decode_as(&self, field: Field) -> DescriptorDecoded {
+        self.decode_field(Some(field))
+    }
+    pub(super) fn decode_field(
+        &self,
+        field: Option<Field>,
+    ) -> DescriptorDecoded {
+        let decoded = match (field, self) {
+            (_, DescriptorData::OtherPrefixed { id, data }) => {
+                return DescriptorData::Other {
+                    id: *id,
+                    data: data.clone(),
+                }
+                .decode_field(field)
+            }
+            (_, DescriptorData::End) => Some(DescriptorDecoded::End),
+            (Some(Field::XorKey), DescriptorData::U8 { data, .. }) => {
                 Some(DescriptorDecoded::XorKey(*data))
             }
-            DescriptorData::U16 { id: 9, data } => {
+            (Some(Field::HWID), DescriptorData::U16 { data, .. }) => {
                 Some(DescriptorDecoded::HWID(*data))
             }
-            DescriptorData::U16 { id: 10, data } => {
+            (Some(Field::FirmwareId), DescriptorData::U16 { data, .. }) => {
                 Some(DescriptorDecoded::FirmwareId(*data))
             }
-            DescriptorData::U16 { id: 12, data } => {
+            (Some(Field::VersionId12), DescriptorData::U16 { data, .. }) => {
                 Some(DescriptorDecoded::VersionId12(Version::new_raw(*data)))
             }
-            DescriptorData::U16 { id: 13, data } => {
+            (Some(Field::VersionSw), DescriptorData::U16 { data, .. }) => {
                 Some(DescriptorDecoded::VersionSw(Version::new_raw(*data)))
             }
-            DescriptorData::U16 { id: 20, data } => {
+            (Some(Field::RegionId), DescriptorData::U16 { data, .. }) => {
+                Some(DescriptorDecoded::RegionId(*data))
+            }
+            (Some(Field::VersionId20), DescriptorData::U16 { data, .. }) => {
                 Some(DescriptorDecoded::VersionId20(Version::new_raw(*data)))
             }
-            DescriptorData::U16 { id: 21, data } => {
+            (Some(Field::VersionRemote), DescriptorData::U16 { data, .. }) => {
                 Some(DescriptorDecoded::VersionRemote(Version::new_raw(*data)))
             }
-            DescriptorData::U32 { id: 21, data } => {
+            (Some(Field::FirmwareLen), DescriptorData::U32 { data, .. }) => {
                 Some(DescriptorDecoded::FirmwareLen(*data))
             }
-            DescriptorData::U32 { id: 23, data } => {
-                Some(DescriptorDecoded::Firmware2000P1Len(*data))
+            (
+                Some(Field::Firmware2000P1Len),
+                DescriptorData::U32 { data, .. },
+            ) => Some(DescriptorDecoded::Firmware2000P1Len(*data)),
+            (
+                Some(Field::Firmware2000P2Len),
+                DescriptorData::U32 { data, .. },
+            ) => Some(DescriptorDecoded::Firmware2000P2Len(*data)),
+            (
+                Some(Field::Firmware2000P3Len),
+                DescriptorData::U32 { data, .. },
+            ) => Some(DescriptorDecoded::Firmware2000P3Len(*data)),
+            (Some(Field::FirmwareAddr), DescriptorData::U32 { data, .. }) => {
+                Some(DescriptorDecoded::FirmwareAddr(*data))
             }
-            DescriptorData::U32 { id: 24, data } => {
-                Some(DescriptorDecoded::Firmware2000P2Len(*data))
+            (Some(Field::BuildTimestamp), DescriptorData::U32 { data, .. }) => {
+                Some(DescriptorDecoded::BuildTimestamp(*data))
             }
-            DescriptorData::U32 { id: 25, data } => {
-                Some(DescriptorDecoded::Firmware2000P3Len(*data))
+            (Some(Field::DeviceModel), DescriptorData::Other { data, .. }) => {
+                ascii_text(data)
+                    .map(|x| DescriptorDecoded::DeviceModel(x.to_string()))
             }
-            DescriptorData::U32 { id: 26, data } => {
-                Some(DescriptorDecoded::FirmwareAddr(*data))
+            (Some(Field::Text), DescriptorData::Other { id, data }) => {
+                ascii_text(data).map(|x| DescriptorDecoded::Text {
+                    id: *id,
+                    value: x.to_string(),
+                })
             }
-            DescriptorData::U8 { .. } => None,
-            DescriptorData::U16 { .. } => None,
-            DescriptorData::U32 { .. } => None,
-            DescriptorData::U64 { .. } => None,
-            DescriptorData::Other { .. } => None,
+            (Some(Field::Sha1), DescriptorData::Other { data, .. }) => {
+                data.as_slice().try_into().ok().map(DescriptorDecoded::Sha1)
+            }
+            (Some(Field::Sha256), DescriptorData::Other { data, .. }) => data
+                .as_slice()
+                .try_into()
+                .ok()
+                .map(DescriptorDecoded::Sha256),
+            _ => None,
+        };
+        if decoded.is_none() {
+            trace_event!(
+                debug,
+                kind = self.descriptor_type().kind(),
+                id = self.descriptor_type().id(),
+                ?field,
+                "descriptor not decoded",
+            );
+        }
+        decoded.unwrap_or_else(|| DescriptorDecoded::Unknown {
+            kind: self.descriptor_type().kind(),
+            id: self.descriptor_type().id(),
+            raw: self.raw_le(),
+        })
+    }
+    // data bytes, integers are little endian
+    fn raw_le(&self) -> Vec<u8> {
+        match self {
+            DescriptorData::U8 { data, .. } => vec![*data],
+            DescriptorData::U16 { data, .. } => data.to_le_bytes().to_vec(),
+            DescriptorData::U32 { data, .. } => data.to_le_bytes().to_vec(),
+            DescriptorData::U64 { data, .. } => data.to_le_bytes().to_vec(),
+            DescriptorData::Other { data, .. }
+            | DescriptorData::OtherPrefixed { data, .. }
+            | DescriptorData::Raw { data, .. } => data.clone(),
+            DescriptorData::End => vec![],
         }
     }
 }
 
+// printable ASCII, optionally zero terminated
+fn ascii_text(data: &[u8]) -> Option<&str> {
+    let text = match data.iter().position(|x| *x == 0) {
+        Some(end) => &data[..end],
+        None => data,
+    };
+    if text.iter().all(|x| x.is_ascii_graphic() || *x == b' ') {
+        std::str::from_utf8(text).ok()
+    } else {
+        None
+    }
+}
+
+/// The known descriptor fields, see [`FIELDS`].
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum Field {
+    XorKey,
+    HWID,
+    FirmwareId,
+    VersionId12,
+    VersionSw,
+    RegionId,
+    VersionId20,
+    VersionRemote,
+    FirmwareLen,
+    Firmware2000P1Len,
+    Firmware2000P2Len,
+    Firmware2000P3Len,
+    FirmwareAddr,
+    BuildTimestamp,
+    DeviceModel,
+    Sha1,
+    Sha256,
+    /// Any `Other` descriptor holding a string, the id is kept, see
+    /// [`DescriptorDecoded::Text`].
+    Text,
+}
+
+/// A known descriptor field, identified by the descriptor kind and id.
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Copy)]
+pub struct FieldInfo {
+    /// Descriptor kind, 0 U8, 1 U16, 2 U32, 3 U64 and 4 Other.
+    pub kind: u8,
+    pub id: u16,
+    pub field: Field,
+    pub name: &'static str,
+}
+
+const fn field(value: u16, field: Field, name: &'static str) -> FieldInfo {
+    FieldInfo {
+        kind: fields::kind(value),
+        id: fields::id(value),
+        field,
+        name,
+    }
+}
+
+/// Mapping of the descriptor kind/id to the decoded field, the values are
+/// in [`fields`].
+///
+/// The meaning of the RegionId, BuildTimestamp, DeviceModel and Sha fields
+/// is inferred from the values, they were only seen in a few files. Other
+/// ids known to hold strings can be registered as [`Field::Text`] in a
+/// [`DescriptorSchema`](super::schema::DescriptorSchema).
+pub const FIELDS: &[FieldInfo] = &[
+    field(FIELD_XOR_KEY, Field::XorKey, "XorKey"),
+    field(FIELD_HWID, Field::HWID, "HWID"),
+    field(FIELD_FIRMWARE_ID, Field::FirmwareId, "FirmwareId"),
+    field(FIELD_VERSION_ID12, Field::VersionId12, "VersionId12"),
+    field(FIELD_VERSION_SW, Field::VersionSw, "VersionSw"),
+    field(FIELD_REGION_ID, Field::RegionId, "RegionId"),
+    field(FIELD_VERSION_ID20, Field::VersionId20, "VersionId20"),
+    field(FIELD_VERSION_REMOTE, Field::VersionRemote, "VersionRemote"),
+    field(FIELD_FW_LEN, Field::FirmwareLen, "FirmwareLen"),
+    field(
+        FIELD_FW_2000_P1_LEN,
+        Field::Firmware2000P1Len,
+        "Firmware2000P1Len",
+    ),
+    field(
+        FIELD_FW_2000_P2_LEN,
+        Field::Firmware2000P2Len,
+        "Firmware2000P2Len",
+    ),
+    field(
+        FIELD_FW_2000_P3_LEN,
+        Field::Firmware2000P3Len,
+        "Firmware2000P3Len",
+    ),
+    field(FIELD_FIRMWARE_ADDR, Field::FirmwareAddr, "FirmwareAddr"),
+    field(
+        FIELD_BUILD_TIMESTAMP,
+        Field::BuildTimestamp,
+        "BuildTimestamp",
+    ),
+    field(FIELD_DEVICE_MODEL, Field::DeviceModel, "DeviceModel"),
+    field(FIELD_SHA1, Field::Sha1, "Sha1"),
+    field(FIELD_SHA256, Field::Sha256, "Sha256"),
+    field(FIELD_TEXT, Field::Text, "Text"),
+];
+
+impl Field {
+    /// The table entry of the field.
+    pub fn info(self) -> &'static FieldInfo {
+        FIELDS
+            .iter()
+            .find(|x| x.field == self)
+            .expect("All fields are in the table")
+    }
+}
+
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
 pub enum DescriptorDecoded {
     End,
@@ -155,52 +476,246 @@ pub enum DescriptorDecoded {
     Firmware2000P1Len(u32),
     Firmware2000P2Len(u32),
     Firmware2000P3Len(u32),
+    /// Region of the device, eg: Americas or Europe maps.
+    RegionId(u16),
+    /// Unix timestamp of the firmware build.
+    BuildTimestamp(u32),
+    /// Device model name.
+    DeviceModel(String),
+    Sha1([u8; 20]),
+    Sha256([u8; 32]),
+    /// String of an `Other` descriptor, eg: a region name.
+    Text {
+        id: u16,
+        value: String,
+    },
+    /// Field without known meaning, integers are stored little endian.
+    Unknown {
+        kind: u8,
+        id: u16,
+        raw: Vec<u8>,
+    },
 }
 
 impl DescriptorDecoded {
-    pub const fn encode(self) -> DescriptorData {
-        match self {
-            DescriptorDecoded::End => DescriptorData::End,
+    pub fn encode(self) -> DescriptorData {
+        let (field, value) = match self {
+            DescriptorDecoded::End => return DescriptorData::End,
+            DescriptorDecoded::Unknown { kind, id, raw } => {
+                return encode_unknown(kind, id, raw)
+            }
             DescriptorDecoded::XorKey(data) => {
-                DescriptorData::U8 { id: 10, data }
+                (Field::XorKey, data.to_le_bytes().to_vec())
             }
             DescriptorDecoded::HWID(data) => {
-                DescriptorData::U16 { id: 9, data }
+                (Field::HWID, data.to_le_bytes().to_vec())
             }
             DescriptorDecoded::FirmwareId(data) => {
-                DescriptorData::U16 { id: 10, data }
+                (Field::FirmwareId, data.to_le_bytes().to_vec())
+            }
+            DescriptorDecoded::VersionId12(version) => {
+                (Field::VersionId12, version.value().to_le_bytes().to_vec())
+            }
+            DescriptorDecoded::VersionSw(version) => {
+                (Field::VersionSw, version.value().to_le_bytes().to_vec())
+            }
+            DescriptorDecoded::RegionId(data) => {
+                (Field::RegionId, data.to_le_bytes().to_vec())
+            }
+            DescriptorDecoded::VersionId20(version) => {
+                (Field::VersionId20, version.value().to_le_bytes().to_vec())
+            }
+            DescriptorDecoded::VersionRemote(version) => {
+                (Field::VersionRemote, version.value().to_le_bytes().to_vec())
             }
-            DescriptorDecoded::VersionId12(version) => DescriptorData::U16 {
-                id: 12,
-                data: version.value(),
-            },
-            DescriptorDecoded::VersionSw(version) => DescriptorData::U16 {
-                id: 13,
-                data: version.value(),
-            },
-            DescriptorDecoded::VersionId20(version) => DescriptorData::U16 {
-                id: 20,
-                data: version.value(),
-            },
-            DescriptorDecoded::VersionRemote(version) => DescriptorData::U16 {
-                id: 21,
-                data: version.value(),
-            },
             DescriptorDecoded::FirmwareLen(data) => {
-                DescriptorData::U32 { id: 21, data }
+                (Field::FirmwareLen, data.to_le_bytes().to_vec())
             }
             DescriptorDecoded::Firmware2000P1Len(data) => {
-                DescriptorData::U32 { id: 23, data }
+                (Field::Firmware2000P1Len, data.to_le_bytes().to_vec())
             }
             DescriptorDecoded::Firmware2000P2Len(data) => {
-                DescriptorData::U32 { id: 24, data }
+                (Field::Firmware2000P2Len, data.to_le_bytes().to_vec())
             }
             DescriptorDecoded::Firmware2000P3Len(data) => {
-                DescriptorData::U32 { id: 25, data }
+                (Field::Firmware2000P3Len, data.to_le_bytes().to_vec())
             }
             DescriptorDecoded::FirmwareAddr(data) => {
-                DescriptorData::U32 { id: 26, data }
+                (Field::FirmwareAddr, data.to_le_bytes().to_vec())
+            }
+            DescriptorDecoded::BuildTimestamp(data) => {
+                (Field::BuildTimestamp, data.to_le_bytes().to_vec())
+            }
+            DescriptorDecoded::DeviceModel(data) => {
+                (Field::DeviceModel, data.into_bytes())
+            }
+            DescriptorDecoded::Sha1(data) => (Field::Sha1, data.to_vec()),
+            DescriptorDecoded::Sha256(data) => (Field::Sha256, data.to_vec()),
+            DescriptorDecoded::Text { id, value } => {
+                return DescriptorData::Other {
+                    id,
+                    data: value.into_bytes(),
+                }
+            }
+        };
+        let info = field.info();
+        encode_unknown(info.kind, info.id, value)
+    }
+
+    /// The known field, None for the End and Unknown.
+    pub fn field(&self) -> Option<Field> {
+        Some(match self {
+            DescriptorDecoded::End | DescriptorDecoded::Unknown { .. } => {
+                return None
+            }
+            DescriptorDecoded::HWID(_) => Field::HWID,
+            DescriptorDecoded::XorKey(_) => Field::XorKey,
+            DescriptorDecoded::FirmwareId(_) => Field::FirmwareId,
+            DescriptorDecoded::FirmwareLen(_) => Field::FirmwareLen,
+            DescriptorDecoded::FirmwareAddr(_) => Field::FirmwareAddr,
+            DescriptorDecoded::VersionSw(_) => Field::VersionSw,
+            DescriptorDecoded::VersionRemote(_) => Field::VersionRemote,
+            DescriptorDecoded::VersionId12(_) => Field::VersionId12,
+            DescriptorDecoded::VersionId20(_) => Field::VersionId20,
+            DescriptorDecoded::Firmware2000P1Len(_) => {
+                Field::Firmware2000P1Len
+            }
+            DescriptorDecoded::Firmware2000P2Len(_) => {
+                Field::Firmware2000P2Len
+            }
+            DescriptorDecoded::Firmware2000P3Len(_) => {
+                Field::Firmware2000P3Len
+            }
+            DescriptorDecoded::RegionId(_) => Field::RegionId,
+            DescriptorDecoded::BuildTimestamp(_) => Field::BuildTimestamp,
+            DescriptorDecoded::DeviceModel(_) => Field::DeviceModel,
+            DescriptorDecoded::Sha1(_) => Field::Sha1,
+            DescriptorDecoded::Sha256(_) => Field::Sha256,
+            DescriptorDecoded::Text { .. } => Field::Text,
+        })
+    }
+
+    /// Name, formatted value and unit of the field, eg: to be shown by an
+    /// UI, see [`FieldDescription`].
+    ///
+    /// Ids, keys and addresses are in hex, lens in bytes, versions with the
+    /// [`Version`] format, hashes and unknown data as hex strings.
+    pub fn describe(&self) -> FieldDescription {
+        fn hex(data: &[u8]) -> String {
+            data.iter().map(|x| format!("{:02x}", x)).collect()
+        }
+        let (value, unit) = match self {
+            DescriptorDecoded::End => (String::new(), None),
+            DescriptorDecoded::XorKey(x) => (format!("{:#04x}", x), None),
+            DescriptorDecoded::HWID(x)
+            | DescriptorDecoded::FirmwareId(x)
+            | DescriptorDecoded::RegionId(x) => (format!("{:#06x}", x), None),
+            DescriptorDecoded::FirmwareAddr(x) => {
+                (format!("{:#010x}", x), None)
+            }
+            DescriptorDecoded::FirmwareLen(x)
+            | DescriptorDecoded::Firmware2000P1Len(x)
+            | DescriptorDecoded::Firmware2000P2Len(x)
+            | DescriptorDecoded::Firmware2000P3Len(x) => {
+                (x.to_string(), Some(Unit::Bytes))
+            }
+            DescriptorDecoded::BuildTimestamp(x) => {
+                (x.to_string(), Some(Unit::UnixTime))
+            }
+            DescriptorDecoded::VersionSw(x)
+            | DescriptorDecoded::VersionRemote(x)
+            | DescriptorDecoded::VersionId12(x)
+            | DescriptorDecoded::VersionId20(x) => (x.to_string(), None),
+            DescriptorDecoded::DeviceModel(x)
+            | DescriptorDecoded::Text { value: x, .. } => (x.clone(), None),
+            DescriptorDecoded::Sha1(x) => (hex(x), None),
+            DescriptorDecoded::Sha256(x) => (hex(x), None),
+            DescriptorDecoded::Unknown { raw, .. } => (hex(raw), None),
+        };
+        let name = match (self, self.field()) {
+            (DescriptorDecoded::End, _) => "End".to_string(),
+            (DescriptorDecoded::Text { id, .. }, _) => {
+                format!("Text({:#05x})", id)
+            }
+            (DescriptorDecoded::Unknown { kind, id, .. }, _) => {
+                format!("Unknown({}, {:#05x})", kind, id)
+            }
+            (_, field) => {
+                field.map(|x| x.info().name).unwrap_or_default().to_string()
             }
+        };
+        FieldDescription { name, value, unit }
+    }
+}
+
+/// Human readable form of a descriptor, see [`DescriptorDecoded::describe`].
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+pub struct FieldDescription {
+    /// Name of the field, from [`FIELDS`].
+    pub name: String,
+    /// The value formatted to be displayed.
+    pub value: String,
+    pub unit: Option<Unit>,
+}
+
+impl Display for FieldDescription {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name, self.value)?;
+        match self.unit {
+            Some(unit) => write!(f, " {}", unit),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Unit of a descriptor value, see [`FieldDescription`].
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Unit {
+    Bytes,
+    /// Seconds since the Unix epoch.
+    UnixTime,
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unit::Bytes => write!(f, "bytes"),
+            Unit::UnixTime => write!(f, "unix time"),
         }
     }
 }
+
+// integers are little endian, see DescriptorDecoded::Unknown
+fn encode_unknown(kind: u8, id: u16, raw: Vec<u8>) -> DescriptorData {
+    fn int<const N: usize>(raw: &[u8]) -> [u8; N] {
+        let mut buf = [0; N];
+        let len = raw.len().min(N);
+        buf[..len].copy_from_slice(&raw[..len]);
+        buf
+    }
+    match kind {
+        0 => DescriptorData::U8 {
+            id,
+            data: raw.first().copied().unwrap_or(0),
+        },
+        1 => DescriptorData::U16 {
+            id,
+            data: u16::from_le_bytes(int(&raw)),
+        },
+        2 => DescriptorData::U32 {
+            id,
+            data: u32::from_le_bytes(int(&raw)),
+        },
+        3 => DescriptorData::U64 {
+            id,
+            data: u64::from_le_bytes(int(&raw)),
+        },
+        4 => DescriptorData::Other { id, data: raw },
+        5 => DescriptorData::End,
+        kind => DescriptorData::Raw {
+            kind,
+            id,
+            data: raw,
+        },
+    }
+}
@@ -1,42 +1,98 @@
-use std::io::{Error, ErrorKind, Result};
+use crate::error::{GcdError, Result};
+
+use super::fields;
 
 use byteorder::ByteOrder;
 use serde::{Deserialize, Serialize};
 
 pub const ID: u16 = 6;
 
+/// Where the data size of a kind 4 descriptor is stored.
+#[derive(
+    Debug, Default, PartialEq, Hash, Eq, Clone, Copy, Serialize, Deserialize,
+)]
+pub enum LenLocation {
+    /// The u16 after the descriptor type, see [`DescriptorType::Other`].
+    #[default]
+    Type,
+    /// The u16 at the start of the descriptor data, see
+    /// [`DescriptorType::OtherPrefixed`].
+    Data,
+}
+
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
 pub enum DescriptorType {
-    U8 { id: u16 },
-    U16 { id: u16 },
-    U32 { id: u16 },
-    U64 { id: u16 },
-    Other { id: u16, lenght: u16 },
+    U8 {
+        id: u16,
+    },
+    U16 {
+        id: u16,
+    },
+    U32 {
+        id: u16,
+    },
+    U64 {
+        id: u16,
+    },
+    Other {
+        id: u16,
+        lenght: u16,
+    },
+    /// Kind 4 with the data size at the start of the descriptor data,
+    /// `lenght` is the size without the u16 prefix.
+    ///
+    /// The size is resolved when the descriptor data is parsed, see
+    /// [`LenLocation::Data`].
+    OtherPrefixed {
+        id: u16,
+        lenght: u16,
+    },
     End, //only 0x5003 is valid, other value have unknown meaning
+    /// Kind without known meaning (6..=15), the data is kept as raw bytes.
+    ///
+    /// The data size is not encoded on the type, it's resolved when the
+    /// descriptor data is parsed, see [`DescriptorRecord`].
+    ///
+    /// [`DescriptorRecord`]: crate::record::descriptor::DescriptorRecord
+    Raw {
+        kind: u8,
+        id: u16,
+        data_len: u16,
+    },
 }
 
 impl DescriptorType {
     pub fn from_raw<B: ByteOrder>(
         data: &[u8],
+    ) -> Result<(&[u8], DescriptorType)> {
+        Self::from_raw_with::<B>(data, LenLocation::Type)
+    }
+    /// Same as [`DescriptorType::from_raw`], the kind 4 data size is read
+    /// from the `location`.
+    pub fn from_raw_with<B: ByteOrder>(
+        data: &[u8],
+        location: LenLocation,
     ) -> Result<(&[u8], DescriptorType)> {
         if data.len() < 2 {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
+            return Err(GcdError::invalid_input(
                 "Descriptor Type is bigger than the data available",
             ));
         }
         let value = B::read_u16(data);
-        let kind = value >> 12 as u8;
+        let kind = value >> 12;
         let id = value & 0x0fff;
         match kind {
             0 => Ok((&data[2..], DescriptorType::U8 { id })),
             1 => Ok((&data[2..], DescriptorType::U16 { id })),
             2 => Ok((&data[2..], DescriptorType::U32 { id })),
             3 => Ok((&data[2..], DescriptorType::U64 { id })),
+            4 if location == LenLocation::Data => Ok((
+                &data[2..],
+                DescriptorType::OtherPrefixed { id, lenght: 0 },
+            )),
             4 => {
                 if data.len() < 4 {
-                    return Err(Error::new(
-                        ErrorKind::InvalidInput,
+                    return Err(GcdError::invalid_input(
                         "Descriptor Type \"Other\" is missing the lenght",
                     ));
                 }
@@ -44,9 +100,13 @@ impl DescriptorType {
                 Ok((&data[4..], DescriptorType::Other { id, lenght }))
             }
             5 => Ok((&data[2..], DescriptorType::End)),
-            _ => Err(Error::new(
-                ErrorKind::InvalidInput,
-                format!("Descriptor Type has unknown value: {}", value),
+            kind => Ok((
+                &data[2..],
+                DescriptorType::Raw {
+                    kind: kind as u8,
+                    id,
+                    data_len: 0,
+                },
             )),
         }
     }
@@ -56,8 +116,7 @@ impl DescriptorType {
     ) -> Result<&'a mut [u8]> {
         let data_len = self.len() as usize;
         if data.len() < data_len {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
+            return Err(GcdError::invalid_input(
                 "Descriptor Type buffer is to small",
             ));
         }
@@ -67,7 +126,9 @@ impl DescriptorType {
             | DescriptorType::U8 { .. }
             | DescriptorType::U16 { .. }
             | DescriptorType::U32 { .. }
-            | DescriptorType::U64 { .. } => {}
+            | DescriptorType::U64 { .. }
+            | DescriptorType::OtherPrefixed { .. }
+            | DescriptorType::Raw { .. } => {}
             DescriptorType::Other { lenght, .. } => {
                 B::write_u16(&mut data[2..], *lenght)
             }
@@ -80,8 +141,10 @@ impl DescriptorType {
             DescriptorType::U16 { .. } => 1,
             DescriptorType::U32 { .. } => 2,
             DescriptorType::U64 { .. } => 3,
-            DescriptorType::Other { .. } => 4,
+            DescriptorType::Other { .. }
+            | DescriptorType::OtherPrefixed { .. } => 4,
             DescriptorType::End => 5,
+            DescriptorType::Raw { kind, .. } => *kind,
         }
     }
     pub const fn id(&self) -> u16 {
@@ -90,20 +153,25 @@ impl DescriptorType {
             | DescriptorType::U16 { id }
             | DescriptorType::U32 { id }
             | DescriptorType::U64 { id }
-            | DescriptorType::Other { id, .. } => *id,
-            DescriptorType::End => 3,
+            | DescriptorType::Other { id, .. }
+            | DescriptorType::OtherPrefixed { id, .. }
+            | DescriptorType::Raw { id, .. } => *id,
+            DescriptorType::End => fields::id(fields::FIELD_END),
         }
     }
     pub const fn value(&self) -> u16 {
         ((self.kind() as u16) << 12) | self.id()
     }
+    #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u16 {
         match self {
             DescriptorType::U8 { .. }
             | DescriptorType::U16 { .. }
             | DescriptorType::U32 { .. }
             | DescriptorType::U64 { .. }
-            | DescriptorType::End => 2,
+            | DescriptorType::End
+            | DescriptorType::OtherPrefixed { .. }
+            | DescriptorType::Raw { .. } => 2,
             DescriptorType::Other { .. } => 4,
         }
     }
@@ -114,7 +182,19 @@ impl DescriptorType {
             DescriptorType::U32 { .. } => 4,
             DescriptorType::U64 { .. } => 8,
             DescriptorType::Other { lenght, .. } => *lenght,
+            DescriptorType::OtherPrefixed { lenght, .. } => {
+                lenght.saturating_add(2)
+            }
             DescriptorType::End => 0,
+            DescriptorType::Raw { data_len, .. } => *data_len,
+        }
+    }
+    /// Where the data size is stored, None if not a kind 4 descriptor.
+    pub const fn len_location(&self) -> Option<LenLocation> {
+        match self {
+            DescriptorType::Other { .. } => Some(LenLocation::Type),
+            DescriptorType::OtherPrefixed { .. } => Some(LenLocation::Data),
+            _ => None,
         }
     }
 }
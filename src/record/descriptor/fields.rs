@@ -0,0 +1,104 @@
+//! Known descriptor fields.
+//!
+//! Each constant is the descriptor type value, `0xABBB`, with the kind `A`
+//! and the id `BBB`, as stored in the file. Use [`kind`] and [`id`] to split
+//! it, eg: to compare with a [`DescriptorType`].
+//!
+//! ```
+//! use gcd_rs::record::descriptor::descriptor_data::DescriptorData;
+//! use gcd_rs::record::descriptor::fields::{self, FIELD_HWID};
+//!
+//! let data = DescriptorData::U16 { id: fields::id(FIELD_HWID), data: 1 };
+//! assert_eq!(data.descriptor_type().value(), FIELD_HWID);
+//! assert_eq!(fields::name(9, 1), Some("HWID"));
+//! ```
+//!
+//! [`DescriptorType`]: super::descriptor_type::DescriptorType
+
+use super::descriptor_data::FIELDS;
+
+pub const FIELD_XOR_KEY: u16 = 0x000a;
+pub const FIELD_HWID: u16 = 0x1009;
+pub const FIELD_FIRMWARE_ID: u16 = 0x100a;
+pub const FIELD_VERSION_ID12: u16 = 0x100c;
+pub const FIELD_VERSION_SW: u16 = 0x100d;
+pub const FIELD_REGION_ID: u16 = 0x100e;
+pub const FIELD_VERSION_ID20: u16 = 0x1014;
+pub const FIELD_VERSION_REMOTE: u16 = 0x1015;
+pub const FIELD_FW_LEN: u16 = 0x2015;
+pub const FIELD_FW_2000_P1_LEN: u16 = 0x2017;
+pub const FIELD_FW_2000_P2_LEN: u16 = 0x2018;
+pub const FIELD_FW_2000_P3_LEN: u16 = 0x2019;
+pub const FIELD_FIRMWARE_ADDR: u16 = 0x201a;
+pub const FIELD_BUILD_TIMESTAMP: u16 = 0x201b;
+pub const FIELD_DEVICE_MODEL: u16 = 0x4005;
+pub const FIELD_SHA1: u16 = 0x4006;
+pub const FIELD_SHA256: u16 = 0x4007;
+pub const FIELD_TEXT: u16 = 0x4008;
+/// End of the descriptor list, the only End value known.
+pub const FIELD_END: u16 = 0x5003;
+
+/// Descriptor kind of the field, 0 U8, 1 U16, 2 U32, 3 U64 and 4 Other.
+pub const fn kind(field: u16) -> u8 {
+    (field >> 12) as u8
+}
+
+/// Descriptor id of the field, without the kind.
+pub const fn id(field: u16) -> u16 {
+    field & 0x0fff
+}
+
+/// Name of the known field with the descriptor `id` and `kind`.
+pub fn name(id: u16, kind: u8) -> Option<&'static str> {
+    if (kind, id) == (self::kind(FIELD_END), self::id(FIELD_END)) {
+        return Some("End");
+    }
+    FIELDS
+        .iter()
+        .find(|x| x.kind == kind && x.id == id)
+        .map(|x| x.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::descriptor::descriptor_data::{
+        DescriptorDecoded, Field,
+    };
+    use crate::record::descriptor::descriptor_type::DescriptorType;
+    use crate::Version;
+
+    #[test]
+    fn field_constants() {
+        let known = [
+            (FIELD_XOR_KEY, DescriptorDecoded::XorKey(1)),
+            (FIELD_HWID, DescriptorDecoded::HWID(1)),
+            (FIELD_FW_LEN, DescriptorDecoded::FirmwareLen(1)),
+            (
+                FIELD_VERSION_REMOTE,
+                DescriptorDecoded::VersionRemote(Version::new_raw(1)),
+            ),
+            (
+                FIELD_DEVICE_MODEL,
+                DescriptorDecoded::DeviceModel("a".into()),
+            ),
+        ];
+        for (value, decoded) in known {
+            let data = decoded.clone().encode();
+            assert_eq!(data.descriptor_type().value(), value);
+            assert_eq!(data.decode(), Some(decoded));
+        }
+        for info in FIELDS {
+            let value = (u16::from(info.kind) << 12) | info.id;
+            assert_eq!((kind(value), id(value)), (info.kind, info.id));
+        }
+        assert_eq!(DescriptorType::End.value(), FIELD_END);
+
+        assert_eq!(name(id(FIELD_FW_LEN), 2), Some("FirmwareLen"));
+        assert_eq!(name(id(FIELD_FW_LEN), 1), Some("VersionRemote"));
+        assert_eq!(name(3, 5), Some("End"));
+        assert_eq!(name(id(FIELD_SHA1), 0), None);
+        assert_eq!(name(0xfff, 4), None);
+        assert_eq!(Field::Sha256.info().id, id(FIELD_SHA256));
+    }
+}
@@ -0,0 +1,247 @@
+//! Meaning of the descriptors for each device family.
+//!
+//! The [`FIELDS`] table is the same for all the files, but some devices use
+//! the same descriptor id for different values. The schema map the HWID and
+//! the descriptor kind/id to a name and, optionally, a known [`Field`].
+//!
+//! Entries without HWID apply to all devices, entries with HWID take
+//! precedence, and entries registered later take precedence over the
+//! previous ones.
+//!
+//! ```
+//! use gcd_rs::record::descriptor::descriptor_data::{
+//!     DescriptorData, DescriptorDecoded, Field,
+//! };
+//! use gcd_rs::record::descriptor::schema::{DescriptorSchema, SchemaEntry};
+//! use gcd_rs::Version;
+//!
+//! let mut schema = DescriptorSchema::default();
+//! //on this device, the U16 14 is the bootloader version
+//! let entry = SchemaEntry::field(Some(0x1234), 1, 14, Field::VersionSw);
+//! schema.register(entry);
+//!
+//! let data = DescriptorData::U16 { id: 14, data: 310 };
+//! assert_eq!(schema.decode(None, &data), DescriptorDecoded::RegionId(310));
+//! assert_eq!(
+//!     schema.decode(Some(0x1234), &data),
+//!     DescriptorDecoded::VersionSw(Version::new_raw(310))
+//! );
+//! ```
+
+use std::borrow::Cow;
+
+use super::descriptor_data::{
+    DescriptorData, DescriptorDecoded, Field, FIELDS,
+};
+use super::DescriptorRecord;
+
+/// The meaning of a descriptor, see [`DescriptorSchema`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SchemaEntry {
+    /// Device HWID, None for all devices.
+    pub hwid: Option<u16>,
+    /// Descriptor kind, 0 U8, 1 U16, 2 U32, 3 U64 and 4 Other.
+    pub kind: u8,
+    pub id: u16,
+    pub name: Cow<'static, str>,
+    /// How the data is decoded, None is decoded as
+    /// [`DescriptorDecoded::Unknown`].
+    pub field: Option<Field>,
+}
+
+impl SchemaEntry {
+    /// Entry with only a name, the data is not decoded.
+    pub fn new<S>(hwid: Option<u16>, kind: u8, id: u16, name: S) -> Self
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        SchemaEntry {
+            hwid,
+            kind,
+            id,
+            name: name.into(),
+            field: None,
+        }
+    }
+
+    /// Entry decoded as a known field, with the field name.
+    pub fn field(hwid: Option<u16>, kind: u8, id: u16, field: Field) -> Self {
+        SchemaEntry {
+            hwid,
+            kind,
+            id,
+            name: field.info().name.into(),
+            field: Some(field),
+        }
+    }
+
+    fn matches(&self, hwid: Option<u16>, kind: u8, id: u16) -> bool {
+        self.kind == kind
+            && self.id == id
+            && (self.hwid.is_none() || self.hwid == hwid)
+    }
+}
+
+/// Registry of [`SchemaEntry`], the default contains the [`FIELDS`] table.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DescriptorSchema {
+    entries: Vec<SchemaEntry>,
+}
+
+impl Default for DescriptorSchema {
+    fn default() -> Self {
+        let entries = FIELDS
+            .iter()
+            .map(|x| SchemaEntry::field(None, x.kind, x.id, x.field))
+            .collect();
+        DescriptorSchema { entries }
+    }
+}
+
+impl DescriptorSchema {
+    /// Schema without entries, all the data is decoded as Unknown.
+    pub fn empty() -> Self {
+        DescriptorSchema { entries: vec![] }
+    }
+
+    /// Add the entry, overwriting the meaning of previous entries.
+    pub fn register(&mut self, entry: SchemaEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn entries(&self) -> &[SchemaEntry] {
+        &self.entries
+    }
+
+    /// Find the entry for the descriptor, the HWID specific entries first.
+    pub fn lookup(
+        &self,
+        hwid: Option<u16>,
+        kind: u8,
+        id: u16,
+    ) -> Option<&SchemaEntry> {
+        //the first minimum is the last registered
+        self.entries
+            .iter()
+            .rev()
+            .filter(|x| x.matches(hwid, kind, id))
+            .min_by_key(|x| x.hwid.is_none())
+    }
+
+    /// Name of the descriptor, None if not in the schema.
+    pub fn name(
+        &self,
+        hwid: Option<u16>,
+        data: &DescriptorData,
+    ) -> Option<&str> {
+        let desc_type = data.descriptor_type();
+        self.lookup(hwid, desc_type.kind(), desc_type.id())
+            .map(|x| x.name.as_ref())
+    }
+
+    /// Decode the data using the schema of the device.
+    pub fn decode(
+        &self,
+        hwid: Option<u16>,
+        data: &DescriptorData,
+    ) -> DescriptorDecoded {
+        let desc_type = data.descriptor_type();
+        let field = self
+            .lookup(hwid, desc_type.kind(), desc_type.id())
+            .and_then(|x| x.field);
+        data.decode_field(field)
+    }
+
+    /// Decode all the descriptors of the record.
+    pub fn decode_record(
+        &self,
+        hwid: Option<u16>,
+        descriptor: &DescriptorRecord,
+    ) -> Vec<DescriptorDecoded> {
+        descriptor.iter().map(|x| self.decode(hwid, x)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DescriptorSchema, SchemaEntry};
+    use crate::record::descriptor::descriptor_data::{
+        DescriptorData, DescriptorDecoded, Field,
+    };
+    use crate::record::descriptor::DescriptorRecord;
+    use crate::Version;
+
+    #[test]
+    fn schema_registry() {
+        let descriptor = DescriptorRecord::Simple(vec![
+            DescriptorData::U16 { id: 21, data: 310 },
+            DescriptorData::U32 { id: 21, data: 1000 },
+            DescriptorData::U8 { id: 1, data: 7 },
+            DescriptorData::End,
+        ]);
+        let mut schema = DescriptorSchema::default();
+        let default: Vec<_> =
+            descriptor.iter().map(|x| x.decode_any()).collect();
+        assert_eq!(schema.decode_record(None, &descriptor), default);
+
+        //only for the device 0x1234
+        schema.register(SchemaEntry::field(Some(0x1234), 1, 21, Field::HWID));
+        schema.register(SchemaEntry::new(Some(0x1234), 0, 1, "Flags"));
+        //a generic entry don't overwrite the device specific one
+        schema.register(SchemaEntry::new(None, 1, 21, "Generic"));
+        schema.register(SchemaEntry::new(None, 0, 1, "Generic"));
+
+        let decoded = schema.decode_record(Some(0x1234), &descriptor);
+        assert_eq!(decoded[0], DescriptorDecoded::HWID(310));
+        assert_eq!(decoded[1], DescriptorDecoded::FirmwareLen(1000));
+        assert_eq!(
+            decoded[2],
+            DescriptorDecoded::Unknown {
+                kind: 0,
+                id: 1,
+                raw: vec![7],
+            }
+        );
+        assert_eq!(decoded[3], DescriptorDecoded::End);
+        let names: Vec<_> = descriptor
+            .iter()
+            .map(|x| schema.name(Some(0x1234), x))
+            .collect();
+        assert_eq!(
+            names,
+            [Some("HWID"), Some("FirmwareLen"), Some("Flags"), None]
+        );
+
+        //other devices
+        let decoded = schema.decode_record(Some(0x0037), &descriptor);
+        assert_eq!(
+            decoded[0],
+            DescriptorDecoded::Unknown {
+                kind: 1,
+                id: 21,
+                raw: vec![0x36, 0x01],
+            }
+        );
+        assert_eq!(
+            schema.name(None, &DescriptorData::U16 { id: 21, data: 0 }),
+            Some("Generic")
+        );
+        assert_eq!(
+            DescriptorSchema::empty()
+                .decode(None, descriptor.iter().nth(1).unwrap()),
+            DescriptorDecoded::Unknown {
+                kind: 2,
+                id: 21,
+                raw: 1000u32.to_le_bytes().to_vec(),
+            }
+        );
+        assert_eq!(
+            descriptor
+                .iter()
+                .next()
+                .unwrap()
+                .decode_as(Field::VersionSw),
+            DescriptorDecoded::VersionSw(Version::new_raw(310))
+        );
+    }
+}
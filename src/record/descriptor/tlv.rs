@@ -0,0 +1,120 @@
+//! Second level parsing of the variable size descriptors data.
+//!
+//! Some `Other` descriptors data is a sequence of type-length-value entries,
+//! each entry is a u16 tag, followed by a u16 len and the value bytes. The
+//! value can itself be a sequence of entries.
+//!
+//! There is no marker identifying this format, so the data is considered a
+//! TLV sequence only if the entries consume exactly all the bytes.
+
+use byteorder::ByteOrder;
+use serde::{Deserialize, Serialize};
+
+const ENTRY_HEADER_LEN: usize = 4;
+
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+pub enum TlvValue {
+    /// Value that don't match the TLV pattern.
+    Raw(Vec<u8>),
+    /// Value composed of TLV entries.
+    Nested(Vec<TlvEntry>),
+}
+
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+pub struct TlvEntry {
+    pub tag: u16,
+    pub value: TlvValue,
+}
+
+impl TlvValue {
+    /// Len of the value, when encoded.
+    pub fn len(&self) -> usize {
+        match self {
+            TlvValue::Raw(x) => x.len(),
+            TlvValue::Nested(x) => x.iter().map(|x| x.len()).sum(),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl TlvEntry {
+    /// Len of the entry, when encoded, including the tag and len.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        ENTRY_HEADER_LEN + self.value.len()
+    }
+}
+
+/// Parse the data as TLV entries, None if the data don't match the pattern.
+pub fn parse<B: ByteOrder>(mut data: &[u8]) -> Option<Vec<TlvEntry>> {
+    let mut entries = vec![];
+    while !data.is_empty() {
+        if data.len() < ENTRY_HEADER_LEN {
+            return None;
+        }
+        let tag = B::read_u16(data);
+        let len = B::read_u16(&data[2..]) as usize;
+        let value = data.get(ENTRY_HEADER_LEN..ENTRY_HEADER_LEN + len)?;
+        let value = match parse::<B>(value) {
+            Some(nested) if !nested.is_empty() => TlvValue::Nested(nested),
+            _ => TlvValue::Raw(value.to_vec()),
+        };
+        entries.push(TlvEntry { tag, value });
+        data = &data[ENTRY_HEADER_LEN + len..];
+    }
+    Some(entries)
+}
+
+/// Encode the entries, the inverse of [`parse`].
+pub fn to_raw<B: ByteOrder>(entries: &[TlvEntry]) -> Vec<u8> {
+    let mut data = vec![];
+    for entry in entries {
+        let mut header = [0u8; ENTRY_HEADER_LEN];
+        B::write_u16(&mut header, entry.tag);
+        B::write_u16(&mut header[2..], entry.value.len() as u16);
+        data.extend_from_slice(&header);
+        match &entry.value {
+            TlvValue::Raw(x) => data.extend_from_slice(x),
+            TlvValue::Nested(x) => data.extend(to_raw::<B>(x)),
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::record::descriptor::descriptor_data::DescriptorData;
+    use crate::record::descriptor::tlv::{self, TlvEntry, TlvValue};
+    use byteorder::LE;
+
+    #[test]
+    fn tlv_nested() {
+        let entries = vec![
+            TlvEntry {
+                tag: 1,
+                value: TlvValue::Raw(vec![0xff, 0xff]),
+            },
+            TlvEntry {
+                tag: 2,
+                value: TlvValue::Nested(vec![TlvEntry {
+                    tag: 3,
+                    value: TlvValue::Raw(b"text".to_vec()),
+                }]),
+            },
+        ];
+        let data = tlv::to_raw::<LE>(&entries);
+        let desc = DescriptorData::Other { id: 1, data };
+        assert_eq!(desc.nested::<LE>(), Some(entries));
+    }
+
+    #[test]
+    fn tlv_no_match() {
+        let desc = DescriptorData::Other {
+            id: 1,
+            data: vec![1, 0, 5, 0, 0],
+        };
+        assert_eq!(desc.nested::<LE>(), None);
+    }
+}
@@ -1,12 +1,13 @@
+use crate::error::{GcdError, Result};
 use byteorder::ByteOrder;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
-use std::io::{Error, ErrorKind, Result};
 
 use crate::{RecordHeader, RECORD_HEADER_LEN};
 
 pub const ID: u16 = 2;
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[non_exhaustive]
 pub enum FillerRecord {
     Zeros(u16),
@@ -25,13 +26,11 @@ impl Display for FillerRecord {
 impl FillerRecord {
     pub fn new(data: &[u8]) -> Result<Self> {
         if data.iter().find(|&x| *x != 0).is_some() {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
-                "Invalid Filler Record value",
-            ));
+            return Err(GcdError::invalid_input("Invalid Filler Record value"));
         }
         Ok(FillerRecord::Zeros(data.len() as u16))
     }
+    #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u16 {
         match self {
             FillerRecord::Zeros(len) => *len,
@@ -15,23 +15,162 @@
 
 //TODO doc this
 
+use crate::error::{GcdError, Result};
 use byteorder::ByteOrder;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
-use std::io::Result;
+use std::ops::Range;
 
-use crate::RecordHeader;
+use crate::hexdump::HexDump;
+
+pub mod transform;
+use crate::{RecordHeader, RecordId, RecordLen, RECORD_HEADER_LEN};
+use transform::TransformTable;
+
+/// Bytes dumped by the alternate `Display` format, `{:#}`.
+pub const HEXDUMP_LIMIT: usize = 256;
+
+/// Firmware id of the TrueType font file, always XORed with [`FONT_XOR_KEY`].
+pub const FONT_ID: u16 = 0x05A5;
+pub const FONT_XOR_KEY: u8 = 0x76;
+
+/// Firmware id of the main firmware, the "fw_all.bin".
+pub const FW_ALL_ID: u16 = 0x0505;
+
+/// Max len of a single firmware chunk, the record len is a u16.
+pub const MAX_CHUNK_LEN: usize = u16::MAX as usize;
+
+/// Firmware id of the firmware split in parts, see [`MultiPartFirmware`].
+pub const MULTI_PART_ID: u16 = 0x2000;
+
+/// Number of parts of a [`MultiPartFirmware`].
+pub const PARTS: usize = 3;
+
+/// Firmware block split in three parts, with the lens from the
+/// Firmware2000P1Len, Firmware2000P2Len and Firmware2000P3Len descriptors.
+///
+/// The parts are stored in sequence, the block len is the sum of the parts
+/// and each firmware chunk belongs to a single part.
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct MultiPartFirmware {
+    lens: [u32; PARTS],
+}
+
+impl MultiPartFirmware {
+    pub const fn new(lens: [u32; PARTS]) -> Self {
+        MultiPartFirmware { lens }
+    }
+    pub const fn lens(&self) -> [u32; PARTS] {
+        self.lens
+    }
+    /// Sum of the parts lens, None if bigger than a u32.
+    pub fn total_len(&self) -> Option<u32> {
+        self.lens
+            .iter()
+            .try_fold(0u32, |acc, x| acc.checked_add(*x))
+    }
+    /// Range of the part in the block data.
+    pub fn part_range(&self, part_idx: u8) -> Option<Range<u64>> {
+        let part_idx = part_idx as usize;
+        let len = *self.lens.get(part_idx)? as u64;
+        let start: u64 = self.lens[..part_idx].iter().map(|x| *x as u64).sum();
+        Some(start..start + len)
+    }
+    /// Part that contains the block offset, with the bytes left in the part
+    /// after it. None if the offset is after the last part.
+    pub fn part_at(&self, offset: u64) -> Option<(u8, u64)> {
+        (0..PARTS as u8).find_map(|part_idx| {
+            let range = self.part_range(part_idx)?;
+            range
+                .contains(&offset)
+                .then(|| (part_idx, range.end - offset))
+        })
+    }
+}
+
+/// Decode the firmware data, using the XorKey from the descriptor (0 is no
+/// key) and the transformations implicit by the firmware id, from the
+/// default [`TransformTable`].
+///
+/// The default transformations are XORs, that are symmetric, so this also
+/// encode the data.
+pub fn decode(id: u16, xor_key: u8, data: &mut [u8]) {
+    TransformTable::default().decode(id, xor_key, data)
+}
+
+/// Type of the firmware payload, see [`classify`].
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Copy, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum FirmwareKind {
+    /// TrueType or OpenType font.
+    Font,
+    /// Main firmware of the device, the "fw_all.bin".
+    FwAll,
+    /// ELF executable.
+    Elf,
+    Gzip,
+    Zip,
+    Unknown,
+}
+
+impl FirmwareKind {
+    /// Recommended file extension, without the dot.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            FirmwareKind::Font => "ttf",
+            FirmwareKind::FwAll | FirmwareKind::Unknown => "bin",
+            FirmwareKind::Elf => "elf",
+            FirmwareKind::Gzip => "gz",
+            FirmwareKind::Zip => "zip",
+        }
+    }
+}
+
+/// Identify the firmware using the id and the magic bytes of the decoded
+/// data, only the start of the data is needed.
+pub fn classify(id: u16, data: &[u8]) -> FirmwareKind {
+    const MAGICS: &[(&[u8], FirmwareKind)] = &[
+        (b"\x00\x01\x00\x00", FirmwareKind::Font),
+        (b"true", FirmwareKind::Font),
+        (b"OTTO", FirmwareKind::Font),
+        (b"\x7fELF", FirmwareKind::Elf),
+        (b"\x1f\x8b", FirmwareKind::Gzip),
+        (b"PK\x03\x04", FirmwareKind::Zip),
+    ];
+    match id {
+        FONT_ID => return FirmwareKind::Font,
+        FW_ALL_ID => return FirmwareKind::FwAll,
+        _ => {}
+    }
+    MAGICS
+        .iter()
+        .find(|(magic, _)| data.starts_with(magic))
+        .map(|(_, kind)| *kind)
+        .unwrap_or(FirmwareKind::Unknown)
+}
 
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum FirmwareRecord {
     /// Empty firmware chunk. Some files include this if firmware_len = 0.
-    EmptyChunk { id: u16 },
+    EmptyChunk { id: RecordId },
     /// Chunk of firmware data.
-    Chunk { id: u16, data: Vec<u8> },
+    Chunk { id: RecordId, data: Vec<u8> },
+    /// Chunk of a part of a [`MultiPartFirmware`], `part_idx` is 0 based.
+    Part {
+        id: RecordId,
+        part_idx: u8,
+        data: Vec<u8>,
+    },
 }
 
 impl Display for FirmwareRecord {
+    /// The alternate format, `{:#}`, also dumps up to [`HEXDUMP_LIMIT`]
+    /// bytes of the data, see [`FirmwareRecord::hexdump`].
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            return write!(f, "{}", self.hexdump(HEXDUMP_LIMIT));
+        }
         match self {
             FirmwareRecord::EmptyChunk { id } => {
                 write!(f, "FirmwareRecord::EmptyChunk {{ id: {} }}", id)
@@ -42,47 +181,186 @@ impl Display for FirmwareRecord {
                 id,
                 data.len()
             ),
+            FirmwareRecord::Part { id, part_idx, data } => write!(
+                f,
+                "FirmwareRecord::Part {{ id: {}, part_idx: {}, len: {} }}",
+                id,
+                part_idx,
+                data.len()
+            ),
         }
     }
 }
 
 impl FirmwareRecord {
-    pub fn new(data: Vec<u8>, id: u16) -> Self {
-        if data.len() == 0 {
+    pub fn new(data: Vec<u8>, id: RecordId) -> Self {
+        if data.is_empty() {
             FirmwareRecord::EmptyChunk { id }
         } else {
             FirmwareRecord::Chunk { id, data }
         }
     }
+    /// Chunk of the part of a [`MultiPartFirmware`], empty data result in a
+    /// [`FirmwareRecord::EmptyChunk`].
+    pub fn new_part(data: Vec<u8>, id: RecordId, part_idx: u8) -> Self {
+        if data.is_empty() {
+            FirmwareRecord::EmptyChunk { id }
+        } else {
+            FirmwareRecord::Part { id, part_idx, data }
+        }
+    }
+    /// Len of the chunk on the record header, only valid if the data is not
+    /// bigger than [`MAX_CHUNK_LEN`], see [`FirmwareRecord::record_len`].
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> u16 {
         match self {
             FirmwareRecord::EmptyChunk { .. } => 0,
-            FirmwareRecord::Chunk { data, .. } => data.len() as u16,
+            FirmwareRecord::Chunk { data, .. }
+            | FirmwareRecord::Part { data, .. } => data.len() as u16,
         }
     }
-    pub const fn id(&self) -> u16 {
+    /// Len of the chunk, fail if bigger than [`MAX_CHUNK_LEN`].
+    pub fn record_len(&self) -> Result<RecordLen> {
+        RecordLen::new(self.data().len())
+    }
+    pub const fn id(&self) -> RecordId {
         match self {
             FirmwareRecord::EmptyChunk { id }
-            | FirmwareRecord::Chunk { id, .. } => *id,
+            | FirmwareRecord::Chunk { id, .. }
+            | FirmwareRecord::Part { id, .. } => *id,
+        }
+    }
+    pub const fn part_idx(&self) -> Option<u8> {
+        match self {
+            FirmwareRecord::Part { part_idx, .. } => Some(*part_idx),
+            _ => None,
         }
     }
     pub fn data(&self) -> &[u8] {
         match self {
             FirmwareRecord::EmptyChunk { .. } => &[],
-            FirmwareRecord::Chunk { data, .. } => data,
+            FirmwareRecord::Chunk { data, .. }
+            | FirmwareRecord::Part { data, .. } => data,
+        }
+    }
+    /// Format the id, len and a hexdump of the first `limit` bytes, the
+    /// offsets are relative to the start of the chunk.
+    pub fn hexdump(&self, limit: usize) -> FirmwareHexDump<'_> {
+        FirmwareHexDump {
+            record: self,
+            limit,
         }
     }
     pub fn record_to_raw<B: ByteOrder>(&self, data: &mut [u8]) -> Result<()> {
+        let len = self.record_len()?;
+        if data.len() < RECORD_HEADER_LEN + len.as_usize() {
+            return Err(GcdError::invalid_input(
+                "Firmware record buffer too small",
+            ));
+        }
         //write header
         let next = RecordHeader::Unknown {
             id: self.id(),
-            len: self.len(),
+            len: len.get(),
         }
         .to_raw::<B>(data)?;
 
         //write record body
-        next[..self.len() as usize].copy_from_slice(self.data());
+        next[..len.as_usize()].copy_from_slice(self.data());
 
         Ok(())
     }
 }
+
+/// Display the firmware chunk with a hexdump of the data, created by
+/// [`FirmwareRecord::hexdump`].
+#[derive(Debug, Clone, Copy)]
+pub struct FirmwareHexDump<'a> {
+    record: &'a FirmwareRecord,
+    limit: usize,
+}
+
+impl<'a> Display for FirmwareHexDump<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.record)?;
+        write!(
+            f,
+            "{}",
+            HexDump::new(self.record.data(), 0).limit(self.limit)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        classify, FirmwareKind, FirmwareRecord, MultiPartFirmware, FONT_ID,
+    };
+    use crate::RecordId;
+
+    #[test]
+    fn firmware_classify() {
+        assert_eq!(classify(FONT_ID, &[]), FirmwareKind::Font);
+        assert_eq!(classify(0x0505, b"\x7fELF"), FirmwareKind::FwAll);
+        assert_eq!(classify(1, b"\x7fELF\x01\x01"), FirmwareKind::Elf);
+        assert_eq!(classify(1, b"OTTO\x00\x0a"), FirmwareKind::Font);
+        assert_eq!(classify(1, b"PK\x03\x04").extension(), "zip");
+        assert_eq!(classify(1, b"\x1f"), FirmwareKind::Unknown);
+        assert_eq!(classify(1, &[]).extension(), "bin");
+    }
+
+    #[test]
+    fn multi_part() {
+        let parts = MultiPartFirmware::new([10, 0, 5]);
+        assert_eq!(parts.total_len(), Some(15));
+        assert_eq!(parts.part_range(0), Some(0..10));
+        assert_eq!(parts.part_range(1), Some(10..10));
+        assert_eq!(parts.part_range(2), Some(10..15));
+        assert_eq!(parts.part_range(3), None);
+        assert_eq!(parts.part_at(0), Some((0, 10)));
+        assert_eq!(parts.part_at(9), Some((0, 1)));
+        //empty parts are skipped
+        assert_eq!(parts.part_at(10), Some((2, 5)));
+        assert_eq!(parts.part_at(15), None);
+        assert_eq!(MultiPartFirmware::new([u32::MAX, 1, 0]).total_len(), None);
+
+        let part =
+            FirmwareRecord::new_part(vec![1, 2], RecordId::new(0x2000), 2);
+        assert_eq!(part.part_idx(), Some(2));
+        assert_eq!(part.data(), &[1, 2]);
+        assert_eq!(
+            part.to_string(),
+            "FirmwareRecord::Part { id: 0x2000, part_idx: 2, len: 2 }"
+        );
+        assert_eq!(
+            FirmwareRecord::new_part(vec![], RecordId::new(1), 0).part_idx(),
+            None
+        );
+    }
+
+    #[test]
+    fn hexdump() {
+        let record = FirmwareRecord::new(
+            b"0123456789abcdefGARMIN".to_vec(),
+            RecordId::new(1),
+        );
+        assert_eq!(
+            record.hexdump(20).to_string(),
+            "FirmwareRecord::Chunk { id: 0x0001, len: 22 }\n\
+             00000000  30 31 32 33 34 35 36 37 38 39 61 62 63 64 65 66  \
+             |0123456789abcdef|\n\
+             00000010  47 41 52 4d                                      \
+             |GARM|\n\
+             ... 2 bytes omitted\n"
+        );
+        assert_eq!(
+            format!("{:#}", record),
+            record.hexdump(super::HEXDUMP_LIMIT).to_string()
+        );
+        let empty = FirmwareRecord::new(vec![], RecordId::new(2));
+        assert_eq!(
+            format!("{:#}", empty),
+            "FirmwareRecord::EmptyChunk { id: 0x0002 }\n"
+        );
+    }
+}
@@ -0,0 +1,426 @@
+//! Transformations applied to the firmware data, based on the firmware id.
+//!
+//! Some firmware ids have an extra obfuscation, besides the XorKey from the
+//! descriptor, eg: the font file ([`FONT_ID`]) is always XORed with
+//! [`FONT_XOR_KEY`]. The [`TransformTable`] maps the firmware id to the
+//! transformation, the default table only contains the font rule.
+//!
+//! ```
+//! use gcd_rs::record::firmware::transform::{Transform, TransformTable};
+//!
+//! let mut table = TransformTable::default();
+//! table.insert(0x0600, Transform::new(
+//!     |data| data.iter_mut().for_each(|x| *x = x.rotate_left(1)),
+//!     |data| data.iter_mut().for_each(|x| *x = x.rotate_right(1)),
+//! ));
+//! let mut data = [0x81];
+//! table.decode(0x0600, 0, &mut data);
+//! assert_eq!(data, [0x03]);
+//! table.encode(0x0600, 0, &mut data);
+//! assert_eq!(data, [0x81]);
+//! ```
+//!
+//! Some firmware blocks are also compressed, the [`Decompressor`] is applied
+//! to the whole block, after the transformations. The gzip and zlib wrappers
+//! are decompressed with the feature `compression`, other formats can be
+//! added with [`Decompressor::push`].
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use super::{FONT_ID, FONT_XOR_KEY};
+use crate::error::{GcdError, Result};
+
+type TransformFn = Arc<dyn Fn(&mut [u8]) + Send + Sync>;
+type DecompressFn =
+    Arc<dyn Fn(&[u8]) -> Option<std::io::Result<Vec<u8>>> + Send + Sync>;
+
+/// Decode and encode functions of a firmware id.
+///
+/// The functions are called for each chunk, so the transformation can't
+/// depend on the position of the data in the block.
+#[derive(Clone)]
+pub struct Transform {
+    decode: TransformFn,
+    encode: TransformFn,
+}
+
+impl Transform {
+    pub fn new<D, E>(decode: D, encode: E) -> Self
+    where
+        D: Fn(&mut [u8]) + Send + Sync + 'static,
+        E: Fn(&mut [u8]) + Send + Sync + 'static,
+    {
+        Transform {
+            decode: Arc::new(decode),
+            encode: Arc::new(encode),
+        }
+    }
+
+    /// XOR all the bytes with the key, the XOR is symmetric.
+    pub fn xor(key: u8) -> Self {
+        let xor: TransformFn = Arc::new(move |data| xor(data, key));
+        Transform {
+            decode: Arc::clone(&xor),
+            encode: xor,
+        }
+    }
+
+    pub fn decode(&self, data: &mut [u8]) {
+        (self.decode)(data)
+    }
+
+    pub fn encode(&self, data: &mut [u8]) {
+        (self.encode)(data)
+    }
+}
+
+impl Debug for Transform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Transform")
+    }
+}
+
+/// Transformations by firmware id, used by the
+/// [`Parser`](crate::parser::Parser) and
+/// [`Composer`](crate::composer::Composer).
+#[derive(Debug, Clone)]
+pub struct TransformTable {
+    transforms: HashMap<u16, Transform>,
+}
+
+impl Default for TransformTable {
+    /// Table with the font rule.
+    fn default() -> Self {
+        let mut table = Self::empty();
+        table.insert(FONT_ID, Transform::xor(FONT_XOR_KEY));
+        table
+    }
+}
+
+impl TransformTable {
+    /// Table without any transformation, only the XorKey is applied.
+    pub fn empty() -> Self {
+        TransformTable {
+            transforms: HashMap::new(),
+        }
+    }
+
+    /// Set the transformation of the id, returning the previous one.
+    pub fn insert(
+        &mut self,
+        id: u16,
+        transform: Transform,
+    ) -> Option<Transform> {
+        self.transforms.insert(id, transform)
+    }
+
+    /// Disable the transformation of the id, returning it.
+    pub fn remove(&mut self, id: u16) -> Option<Transform> {
+        self.transforms.remove(&id)
+    }
+
+    pub fn get(&self, id: u16) -> Option<&Transform> {
+        self.transforms.get(&id)
+    }
+
+    /// Decode the firmware data, removing the XorKey from the descriptor (0
+    /// is no key) and then applying the transformation of the id.
+    pub fn decode(&self, id: u16, xor_key: u8, data: &mut [u8]) {
+        if xor_key != 0 {
+            xor(data, xor_key);
+        }
+        if let Some(transform) = self.get(id) {
+            transform.decode(data);
+        }
+    }
+
+    /// True if the decode and encode don't change the data of the id.
+    pub fn is_identity(&self, id: u16, xor_key: u8) -> bool {
+        xor_key == 0 && self.get(id).is_none()
+    }
+
+    /// Encode the firmware data, the reverse of [`TransformTable::decode`].
+    pub fn encode(&self, id: u16, xor_key: u8, data: &mut [u8]) {
+        if let Some(transform) = self.get(id) {
+            transform.encode(data);
+        }
+        if xor_key != 0 {
+            xor(data, xor_key);
+        }
+    }
+}
+
+// XOR the data with the key, in blocks of 32 bytes, so the compiler can use
+// SIMD
+fn xor(data: &mut [u8], key: u8) {
+    let mut chunks = data.chunks_exact_mut(32);
+    for chunk in &mut chunks {
+        chunk.iter_mut().for_each(|x| *x ^= key);
+    }
+    chunks.into_remainder().iter_mut().for_each(|x| *x ^= key);
+}
+
+/// Compression wrapper of a firmware block, see [`Compression::detect`].
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Copy, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Deflate with the gzip header, RFC 1952.
+    Gzip,
+    /// Deflate with the zlib header, RFC 1950.
+    Zlib,
+}
+
+impl Compression {
+    /// Compression wrapper of the data, from the header.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x1f, 0x8b, 0x08, ..] => Some(Compression::Gzip),
+            //deflate, window up to 32K, no dictionary and valid FCHECK
+            [cmf, flg, ..]
+                if cmf & 0x8f == 0x08
+                    && flg & 0x20 == 0
+                    && u16::from_be_bytes([*cmf, *flg]) % 31 == 0 =>
+            {
+                Some(Compression::Zlib)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Decompress the firmware blocks.
+///
+/// The custom decompressors are tried in order, then the built-in ones, if
+/// enabled. The first that recognize the data is used.
+#[derive(Clone)]
+pub struct Decompressor {
+    hooks: Vec<DecompressFn>,
+    builtin: bool,
+    max_len: Option<u64>,
+}
+
+impl Default for Decompressor {
+    /// The built-in decompressors, only available with the feature
+    /// `compression`.
+    fn default() -> Self {
+        Decompressor {
+            builtin: cfg!(feature = "compression"),
+            ..Self::empty()
+        }
+    }
+}
+
+impl Debug for Decompressor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Decompressor({} hooks)", self.hooks.len())
+    }
+}
+
+impl Decompressor {
+    /// Decompressor without the built-in decompressors.
+    pub fn empty() -> Self {
+        Decompressor {
+            hooks: vec![],
+            builtin: false,
+            max_len: None,
+        }
+    }
+
+    /// Add a decompressor, `hook` return None if the data is not in its
+    /// format.
+    pub fn push<D>(&mut self, hook: D)
+    where
+        D: Fn(&[u8]) -> Option<std::io::Result<Vec<u8>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.hooks.push(Arc::new(hook));
+    }
+
+    /// Fail with [`GcdError::LimitExceeded`] if the decompressed data is
+    /// bigger than `max`.
+    pub fn set_max_len(&mut self, max: Option<u64>) {
+        self.max_len = max;
+    }
+
+    /// Decompress the block data, None if the data is not compressed.
+    pub fn decompress(&self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        for hook in &self.hooks {
+            if let Some(result) = hook(data) {
+                return self.check_len(result?).map(Some);
+            }
+        }
+        match Compression::detect(data) {
+            Some(compression) if self.builtin => {
+                self.decompress_builtin(compression, data)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Decompress the block data, or return it unchanged if not compressed.
+    pub fn decompress_or_keep(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(self.decompress(&data)?.unwrap_or(data))
+    }
+
+    #[cfg(feature = "compression")]
+    fn decompress_builtin(
+        &self,
+        compression: Compression,
+        data: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        use std::io::Read;
+
+        let reader: Box<dyn Read + '_> = match compression {
+            Compression::Gzip => Box::new(flate2::read::GzDecoder::new(data)),
+            Compression::Zlib => Box::new(flate2::read::ZlibDecoder::new(data)),
+        };
+        //one byte more than the limit, to detect the overflow
+        let limit = self.max_len.map_or(u64::MAX, |x| x.saturating_add(1));
+        let mut output = vec![];
+        reader.take(limit).read_to_end(&mut output)?;
+        self.check_len(output).map(Some)
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn decompress_builtin(
+        &self,
+        _compression: Compression,
+        _data: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    fn check_len(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        match self.max_len {
+            Some(max) if data.len() as u64 > max => {
+                Err(GcdError::LimitExceeded {
+                    limit: "Decompressed firmware",
+                    max,
+                })
+            }
+            _ => Ok(data),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compression, Decompressor, Transform, TransformTable};
+    use crate::error::GcdError;
+    use crate::record::firmware::{FONT_ID, FONT_XOR_KEY};
+
+    #[test]
+    fn transform_table() {
+        let data: Vec<u8> = (0..=255).collect();
+        let mut table = TransformTable::default();
+        //the font is also XORed with the font key
+        for (id, key) in [(FONT_ID, 0x5a ^ FONT_XOR_KEY), (0x0505, 0x5a)] {
+            let expected: Vec<u8> = data.iter().map(|x| x ^ key).collect();
+            let mut decoded = data.clone();
+            table.decode(id, 0x5a, &mut decoded);
+            assert_eq!(decoded, expected);
+            table.encode(id, 0x5a, &mut decoded);
+            assert_eq!(decoded, data);
+        }
+
+        //the font rule can be disabled
+        assert!(table.remove(FONT_ID).is_some());
+        let mut decoded = data.clone();
+        table.decode(FONT_ID, 0, &mut decoded);
+        assert_eq!(decoded, data);
+
+        //the encode is the reverse, applied in the reverse order
+        table.insert(1, Transform::new(|x| x.reverse(), |x| x.reverse()));
+        let mut decoded = data.clone();
+        table.decode(1, 0x01, &mut decoded);
+        assert_eq!(decoded[0], 0xff ^ 0x01);
+        table.encode(1, 0x01, &mut decoded);
+        assert_eq!(decoded, data);
+
+        //all the bytes are XORed, not only the whole words
+        let xor = Transform::xor(0x5a);
+        for len in 0..data.len() {
+            let mut decoded = data[..len].to_vec();
+            xor.decode(&mut decoded);
+            assert!(decoded.iter().zip(&data).all(|(x, y)| x ^ y == 0x5a));
+        }
+    }
+
+    #[test]
+    fn decompressor() {
+        let gzip = [0x1f, 0x8b, 0x08, 0x00];
+        assert_eq!(Compression::detect(&gzip), Some(Compression::Gzip));
+        assert_eq!(Compression::detect(&[0x78, 0x9c]), Some(Compression::Zlib));
+        assert_eq!(Compression::detect(&[0x78, 0x9d]), None);
+        assert_eq!(Compression::detect(b"firmware"), None);
+        assert_eq!(Compression::detect(&[]), None);
+
+        //custom format, a "RLE" header with the byte and the count
+        let mut decompressor = Decompressor::empty();
+        decompressor.push(|data| match data {
+            [b'R', b'L', b'E', byte, count] => {
+                Some(Ok(vec![*byte; *count as usize]))
+            }
+            [b'R', b'L', b'E', ..] => Some(Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid RLE",
+            ))),
+            _ => None,
+        });
+        assert_eq!(
+            decompressor.decompress(b"RLE\x01\x03").unwrap(),
+            Some(vec![1, 1, 1])
+        );
+        assert!(decompressor.decompress(b"RLE\x01").is_err());
+        assert_eq!(decompressor.decompress(b"firmware").unwrap(), None);
+        assert_eq!(
+            decompressor.decompress_or_keep(b"data".to_vec()).unwrap(),
+            b"data"
+        );
+        //the built-in are disabled
+        assert_eq!(decompressor.decompress(&gzip).unwrap(), None);
+
+        decompressor.set_max_len(Some(2));
+        assert!(matches!(
+            decompressor.decompress(b"RLE\x01\x03"),
+            Err(GcdError::LimitExceeded { max: 2, .. })
+        ));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn decompressor_builtin() {
+        use flate2::write::{GzEncoder, ZlibEncoder};
+        use std::io::Write;
+
+        let data: Vec<u8> = (0..0x1000u32).map(|x| (x % 7) as u8).collect();
+        let mut gzip = GzEncoder::new(vec![], flate2::Compression::best());
+        gzip.write_all(&data).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let mut zlib = ZlibEncoder::new(vec![], flate2::Compression::best());
+        zlib.write_all(&data).unwrap();
+        let zlib = zlib.finish().unwrap();
+
+        let mut decompressor = Decompressor::default();
+        for (compressed, compression) in
+            [(&gzip, Compression::Gzip), (&zlib, Compression::Zlib)]
+        {
+            assert_eq!(Compression::detect(compressed), Some(compression));
+            let decompressed = decompressor.decompress(compressed).unwrap();
+            assert_eq!(decompressed.as_ref(), Some(&data));
+        }
+        //truncated stream
+        assert!(decompressor.decompress(&gzip[..gzip.len() / 2]).is_err());
+
+        decompressor.set_max_len(Some(data.len() as u64 - 1));
+        assert!(matches!(
+            decompressor.decompress(&zlib),
+            Err(GcdError::LimitExceeded { .. })
+        ));
+    }
+}
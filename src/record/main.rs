@@ -1,13 +1,16 @@
 //! The first data containing record.
 //!
-//! There are two known variations, the PartNumber (9 bytes) and HwID (2 bytes).
+//! There are two known variations, the PartNumber (9 bytes, or up to 16 with
+//! a suffix) and HwID (2 bytes).
+//! The values found in most files have their own variants, the parser return
+//! them instead of the generic `HWID`/`PartNumber`.
 
+use crate::error::{GcdError, Result};
 use byteorder::ByteOrder;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
-use std::io::{Error, ErrorKind, Result};
 
-use crate::{RecordHeader, RECORD_HEADER_LEN};
+use crate::{PartNumber, RecordHeader, RECORD_HEADER_LEN};
 
 pub const DEFAULT_HWID: u16 = 0x0037;
 //const DEFAULT_PART_NUMBER: u128 = "010-10037-00".parse().data();
@@ -16,11 +19,16 @@ pub const DEFAULT_PART_NUMBER: u128 = 0x41140D4504135CD410;
 pub const ID: u16 = 3;
 /// Only two variations are known, 9 bytes for PartNumber and 2 bytes for HwId.
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum MainRecord {
     /// The only know value is "010-10037-00".
     DefaultPartNumber,
     /// The only know value is 0x0037.
     DefaultHWID,
+    /// HWID of other devices.
+    HWID(u16),
+    /// PartNumber of other devices.
+    PartNumber(PartNumber),
 }
 
 impl Display for MainRecord {
@@ -30,6 +38,12 @@ impl Display for MainRecord {
                 write!(f, "MainRecord::DefaultPartNumber")
             }
             MainRecord::DefaultHWID => write!(f, "MainRecord::DefaultHWID"),
+            MainRecord::HWID(hwid) => {
+                write!(f, "MainRecord::HWID({:#06x})", hwid)
+            }
+            MainRecord::PartNumber(pn) => {
+                write!(f, "MainRecord::PartNumber({})", pn)
+            }
         }
     }
 }
@@ -42,39 +56,71 @@ impl MainRecord {
     {
         Ok(match lenght {
             9 => {
-                let pn = file.read_uint128::<B>(9)?;
-                if pn == DEFAULT_PART_NUMBER {
+                let mut raw = [0u8; 9];
+                file.read_exact(&mut raw)?;
+                if B::read_uint128(&raw, 9) == DEFAULT_PART_NUMBER {
                     MainRecord::DefaultPartNumber
                 } else {
-                    return Err(Error::new(
-                        ErrorKind::InvalidData,
-                        "Invalid/Unknown MainRecord PartNumber",
-                    ));
+                    let (_, pn) =
+                        PartNumber::from_raw::<B>(&raw).map_err(|_| {
+                            GcdError::invalid_data(
+                                "Invalid/Unknown MainRecord PartNumber",
+                            )
+                        })?;
+                    MainRecord::PartNumber(pn)
                 }
             }
-            2 => {
-                let hwid = file.read_u16::<B>()?;
-                if hwid != DEFAULT_HWID {
-                    return Err(Error::new(
-                        ErrorKind::InvalidData,
-                        "Invalid/Unknown MainRecord HWID",
-                    ));
-                }
-                MainRecord::DefaultHWID
+            10..=16 => {
+                let mut raw = vec![0u8; lenght as usize];
+                file.read_exact(&mut raw)?;
+                let (_, pn) = PartNumber::from_raw_len::<B>(&raw, raw.len())
+                    .map_err(|_| {
+                        GcdError::invalid_data(
+                            "Invalid/Unknown MainRecord PartNumber",
+                        )
+                    })?;
+                MainRecord::PartNumber(pn)
             }
+            2 => match file.read_u16::<B>()? {
+                DEFAULT_HWID => MainRecord::DefaultHWID,
+                hwid => MainRecord::HWID(hwid),
+            },
             _ => {
-                return Err(Error::new(
-                    ErrorKind::InvalidData,
+                return Err(GcdError::invalid_data(
                     "Invalid/Unknown Main Record",
                 ))
             }
         })
     }
 
-    pub const fn len(&self) -> u16 {
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u16 {
         match self {
             MainRecord::DefaultPartNumber => 9,
-            MainRecord::DefaultHWID => 2,
+            MainRecord::PartNumber(pn) => pn.raw_len() as u16,
+            MainRecord::DefaultHWID | MainRecord::HWID(_) => 2,
+        }
+    }
+    /// HWID value, if this is a HWID record.
+    pub const fn hwid(&self) -> Option<u16> {
+        match self {
+            MainRecord::DefaultHWID => Some(DEFAULT_HWID),
+            MainRecord::HWID(hwid) => Some(*hwid),
+            MainRecord::DefaultPartNumber | MainRecord::PartNumber(_) => None,
+        }
+    }
+    /// PartNumber value, if this is a PartNumber record.
+    pub fn part_number(&self) -> Option<PartNumber> {
+        match self {
+            MainRecord::DefaultPartNumber => {
+                let mut raw = [0u8; 9];
+                byteorder::LE::write_uint128(&mut raw, DEFAULT_PART_NUMBER, 9);
+                let (_, pn) =
+                    PartNumber::from_raw::<byteorder::LE>(&raw).ok()?;
+                Some(pn)
+            }
+            MainRecord::PartNumber(pn) => Some(pn.clone()),
+            MainRecord::DefaultHWID | MainRecord::HWID(_) => None,
         }
     }
     pub fn record_to_raw<B: ByteOrder>(&self, data: &mut [u8]) -> Result<()> {
@@ -89,6 +135,12 @@ impl MainRecord {
             MainRecord::DefaultHWID => {
                 B::write_u16(&mut data[RECORD_HEADER_LEN..], DEFAULT_HWID)
             }
+            MainRecord::HWID(hwid) => {
+                B::write_u16(&mut data[RECORD_HEADER_LEN..], *hwid)
+            }
+            MainRecord::PartNumber(pn) => {
+                pn.to_raw::<B>(&mut data[RECORD_HEADER_LEN..])?
+            }
         }
 
         Ok(())
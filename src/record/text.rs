@@ -1,14 +1,27 @@
+//! Text records, usually ASCII, but some files use Latin-1/CP1252 and pad
+//! the text with NULs.
+//!
+//! The NUL padding is kept as part of the text, so the record is composed
+//! back identical, see [`TextRecord::trimmed`] and [`TextRecord::padded`].
+
+use crate::error::{GcdError, Result};
 use byteorder::ByteOrder;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::convert::TryFrom;
 use std::fmt::{Display, Formatter};
-use std::io::Result;
+use std::str::FromStr;
 
-use crate::RecordHeader;
+use crate::{RecordHeader, RecordLen, RECORD_HEADER_LEN};
 
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum TextRecord {
     Simple(String),
     Blob(Vec<u8>),
+    /// Text encoded with one byte per char, all chars are in the range
+    /// U+0000..=U+00FF.
+    Latin1(String),
 }
 
 impl Display for TextRecord {
@@ -18,40 +31,337 @@ impl Display for TextRecord {
             TextRecord::Blob(x) => {
                 write!(f, "TextRecord:Blob(len: {})", x.len())
             }
+            TextRecord::Latin1(x) => write!(f, "TextRecord:Latin1({})", x),
         }
     }
 }
 
+/// How the text record data is decoded, see [`TextRecord::from_bytes`].
+#[derive(
+    Debug, Default, PartialEq, Hash, Eq, Clone, Copy, Serialize, Deserialize,
+)]
+pub enum TextEncoding {
+    /// Valid UTF-8 is [`TextRecord::Simple`], otherwise
+    /// [`TextRecord::Blob`].
+    #[default]
+    Utf8,
+    /// Always [`TextRecord::Latin1`].
+    Latin1,
+    /// Valid UTF-8 is [`TextRecord::Simple`], otherwise
+    /// [`TextRecord::Latin1`].
+    Auto,
+    /// Always [`TextRecord::Blob`].
+    Binary,
+}
+
 pub const ID: u16 = 5;
 
+fn latin1(data: &[u8]) -> String {
+    data.iter().map(|x| char::from(*x)).collect()
+}
+
 impl TextRecord {
     pub fn new<F: std::io::Read>(file: &mut F, lenght: u16) -> Result<Self> {
+        Self::new_with(file, lenght, TextEncoding::Utf8)
+    }
+    /// Read the record decoding the text with `encoding`.
+    pub fn new_with<F: std::io::Read>(
+        file: &mut F,
+        lenght: u16,
+        encoding: TextEncoding,
+    ) -> Result<Self> {
         let mut data = vec![0; lenght as usize];
         file.read_exact(&mut data)?;
-        match core::str::from_utf8(&data) {
-            Ok(_) => Ok(TextRecord::Simple(unsafe {
-                //allowed because the check was done on "core::str::from_utf8"
-                String::from_utf8_unchecked(data)
-            })),
-            Err(_) => Ok(TextRecord::Blob(data)),
+        Ok(Self::from_bytes(data, encoding))
+    }
+    /// Decode the text, all the encodings are lossless.
+    pub fn from_bytes(data: Vec<u8>, encoding: TextEncoding) -> Self {
+        match (encoding, String::from_utf8(data)) {
+            (TextEncoding::Utf8, Ok(x)) | (TextEncoding::Auto, Ok(x)) => {
+                TextRecord::Simple(x)
+            }
+            (TextEncoding::Utf8, Err(x)) | (TextEncoding::Binary, Err(x)) => {
+                TextRecord::Blob(x.into_bytes())
+            }
+            (TextEncoding::Binary, Ok(x)) => TextRecord::Blob(x.into_bytes()),
+            (TextEncoding::Latin1, Ok(x)) => {
+                TextRecord::Latin1(latin1(x.as_bytes()))
+            }
+            (TextEncoding::Latin1, Err(x)) | (TextEncoding::Auto, Err(x)) => {
+                TextRecord::Latin1(latin1(x.as_bytes()))
+            }
+        }
+    }
+    /// Text record from the string, with the line endings normalized to
+    /// "\n" and, if `nul`, terminated by a NUL like the Garmin files. Fail if
+    /// the text is bigger than u16::MAX bytes.
+    pub fn from_str_checked(text: &str, nul: bool) -> Result<Self> {
+        let mut text = text.replace("\r\n", "\n").replace('\r', "\n");
+        if nul && !text.ends_with('\0') {
+            text.push('\0');
+        }
+        RecordLen::new(text.len())?;
+        Ok(TextRecord::Simple(text))
+    }
+    /// Copyright text, like the first text record of the Garmin files, the
+    /// "Copyright " prefix is added if missing, see
+    /// [`TextRecord::from_str_checked`].
+    pub fn copyright(text: &str) -> Result<Self> {
+        const PREFIX: &str = "Copyright ";
+        let prefixed = text
+            .get(..PREFIX.len())
+            .is_some_and(|x| x.eq_ignore_ascii_case(PREFIX));
+        match prefixed {
+            true => Self::from_str_checked(text, true),
+            false => {
+                Self::from_str_checked(&format!("{}{}", PREFIX, text), true)
+            }
         }
     }
+    /// Decode the text again, using other encoding.
+    pub fn decode_as(&self, encoding: TextEncoding) -> Result<Self> {
+        Ok(Self::from_bytes(self.encode()?, encoding))
+    }
+    /// Len of the text on the record header, only valid if the text is not
+    /// bigger than u16::MAX, see [`TextRecord::record_len`].
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> u16 {
         match self {
             TextRecord::Simple(data) => data.len() as u16,
             TextRecord::Blob(data) => data.len() as u16,
+            TextRecord::Latin1(data) => data.chars().count() as u16,
+        }
+    }
+    /// Len of the text, fail if bigger than u16::MAX.
+    pub fn record_len(&self) -> Result<RecordLen> {
+        RecordLen::new(self.value().len())
+    }
+    /// The record data, Latin1 chars outside the encoding are replaced by
+    /// '?', see [`TextRecord::encode`].
+    pub fn value(&self) -> Cow<'_, [u8]> {
+        match self {
+            TextRecord::Simple(x) => Cow::Borrowed(x.as_bytes()),
+            TextRecord::Blob(x) => Cow::Borrowed(x),
+            TextRecord::Latin1(x) => Cow::Owned(
+                x.chars()
+                    .map(|x| u8::try_from(u32::from(x)).unwrap_or(b'?'))
+                    .collect(),
+            ),
+        }
+    }
+    /// The record data, fail if a Latin1 char is outside the encoding.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        match self {
+            TextRecord::Latin1(x) => x
+                .chars()
+                .map(|x| u8::try_from(u32::from(x)))
+                .collect::<std::result::Result<_, _>>()
+                .map_err(|_| {
+                    GcdError::invalid_input("Text with non Latin-1 chars")
+                }),
+            _ => Ok(self.value().into_owned()),
+        }
+    }
+    /// The decoded text, None for a [`TextRecord::Blob`].
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TextRecord::Simple(x) | TextRecord::Latin1(x) => Some(x),
+            TextRecord::Blob(_) => None,
+        }
+    }
+    /// Number of NULs at the end of the text.
+    pub fn nul_padding(&self) -> usize {
+        self.value().iter().rev().take_while(|x| **x == 0).count()
+    }
+    /// The text without the NUL padding.
+    pub fn trimmed(&self) -> TextRecord {
+        let trim = |x: &String| x.trim_end_matches('\0').to_string();
+        match self {
+            TextRecord::Simple(x) => TextRecord::Simple(trim(x)),
+            TextRecord::Latin1(x) => TextRecord::Latin1(trim(x)),
+            TextRecord::Blob(x) => {
+                TextRecord::Blob(x[..x.len() - self.nul_padding()].to_vec())
+            }
         }
     }
-    pub fn value(&self) -> &[u8] {
+    /// Add `nul` NULs at the end of the text, eg: to keep the original
+    /// padding after changing a trimmed text.
+    pub fn padded(self, nul: usize) -> TextRecord {
+        let pad = |mut x: String| {
+            x.extend(std::iter::repeat('\0').take(nul));
+            x
+        };
         match self {
-            TextRecord::Simple(x) => x.as_bytes(),
-            TextRecord::Blob(x) => &x,
+            TextRecord::Simple(x) => TextRecord::Simple(pad(x)),
+            TextRecord::Latin1(x) => TextRecord::Latin1(pad(x)),
+            TextRecord::Blob(mut x) => {
+                x.resize(x.len() + nul, 0);
+                TextRecord::Blob(x)
+            }
         }
     }
+    /// Parse the text as a region label, eg: "RGN 14 fw_all".
+    pub fn region(&self) -> Option<RegionLabel> {
+        self.as_str()?.parse().ok()
+    }
     pub fn record_to_raw<B: ByteOrder>(&self, data: &mut [u8]) -> Result<()> {
+        let value = self.encode()?;
+        let len = RecordLen::new(value.len())?;
+        if data.len() < RECORD_HEADER_LEN + len.as_usize() {
+            return Err(GcdError::invalid_input(
+                "Text record buffer too small",
+            ));
+        }
         //write header
-        let next = RecordHeader::Text(self.len()).to_raw::<B>(data)?;
-        next[..self.len() as usize].copy_from_slice(self.value());
+        let next = RecordHeader::Text(len.get()).to_raw::<B>(data)?;
+        next[..len.as_usize()].copy_from_slice(&value);
         Ok(())
     }
 }
+
+/// Label of a firmware region, from text records like "RGN 14 fw_all",
+/// "Region 0x0e: fw_all.bin" or "RGN14".
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Serialize, Deserialize)]
+pub struct RegionLabel {
+    /// Region number
+    pub index: u16,
+    /// Region name, can be empty
+    pub name: String,
+}
+
+impl FromStr for RegionLabel {
+    type Err = GcdError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || GcdError::invalid_data("Invalid region label");
+        let s = s.trim_matches(|x: char| x == '\0' || x.is_whitespace());
+        //case insensitive prefix, "Region" or "RGN"
+        let prefix_len = ["region", "rgn"]
+            .iter()
+            .find(|x| {
+                s.get(..x.len()).is_some_and(|s| s.eq_ignore_ascii_case(x))
+            })
+            .ok_or_else(invalid)?
+            .len();
+        let s = s[prefix_len..].trim_start_matches(|x: char| {
+            x.is_whitespace() || x == '#' || x == '-' || x == '_'
+        });
+        let (radix, s) = match s.get(..2) {
+            Some("0x") | Some("0X") => (16, &s[2..]),
+            _ => (10, s),
+        };
+        let end = s.find(|x: char| !x.is_digit(radix)).unwrap_or(s.len());
+        let index =
+            u16::from_str_radix(&s[..end], radix).map_err(|_| invalid())?;
+        let name = s[end..].trim_start_matches(|x: char| {
+            x.is_whitespace() || x == ':' || x == '-' || x == '='
+        });
+        Ok(RegionLabel {
+            index,
+            name: name.to_string(),
+        })
+    }
+}
+
+impl Display for RegionLabel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "RGN {}", self.index)
+        } else {
+            write!(f, "RGN {} {}", self.index, self.name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TextEncoding, TextRecord};
+    use crate::config::ParserConfig;
+    use crate::parser::Parser;
+    use crate::record::main::MainRecord;
+    use crate::Record;
+
+    #[test]
+    fn text_encoding() {
+        //"Garmin® Ltd." in CP1252, with NUL padding
+        let raw = b"Garmin\xae Ltd.\0\0".to_vec();
+        let utf8 = TextRecord::from_bytes(raw.clone(), TextEncoding::Utf8);
+        assert_eq!(utf8, TextRecord::Blob(raw.clone()));
+        let latin1 = utf8.decode_as(TextEncoding::Auto).unwrap();
+        assert_eq!(latin1, TextRecord::Latin1("Garmin® Ltd.\0\0".to_string()));
+        assert_eq!(latin1.as_str(), Some("Garmin® Ltd.\0\0"));
+        assert_eq!(latin1.len() as usize, raw.len());
+        assert_eq!(&*latin1.value(), &raw[..]);
+        assert_eq!(
+            TextRecord::from_bytes(b"ascii".to_vec(), TextEncoding::Auto),
+            TextRecord::Simple("ascii".to_string())
+        );
+        assert_eq!(
+            TextRecord::from_bytes(b"ascii".to_vec(), TextEncoding::Binary),
+            TextRecord::Blob(b"ascii".to_vec())
+        );
+
+        //NUL padding
+        assert_eq!(latin1.nul_padding(), 2);
+        let trimmed = latin1.trimmed();
+        assert_eq!(trimmed.as_str(), Some("Garmin® Ltd."));
+        assert_eq!(trimmed.padded(2), latin1);
+        let blob = TextRecord::Blob(raw.clone()).trimmed();
+        assert_eq!(blob.nul_padding(), 0);
+        assert_eq!(blob.len() as usize, raw.len() - 2);
+
+        //chars outside Latin-1 can't be encoded
+        let invalid = TextRecord::Latin1("Garmin™".to_string());
+        assert!(invalid.encode().is_err());
+        assert!(invalid
+            .record_to_raw::<byteorder::LE>(&mut [0; 32])
+            .is_err());
+    }
+
+    #[test]
+    fn checked_text() {
+        let text = TextRecord::from_str_checked("a\r\nb\rc\n", true).unwrap();
+        assert_eq!(text, TextRecord::Simple("a\nb\nc\n\0".to_string()));
+        //the NUL is not duplicated
+        let text = TextRecord::from_str_checked("a\0", true).unwrap();
+        assert_eq!(text.nul_padding(), 1);
+        let text = TextRecord::from_str_checked("a", false).unwrap();
+        assert_eq!(text.nul_padding(), 0);
+
+        let big = "a".repeat(0xffff);
+        assert!(TextRecord::from_str_checked(&big, false).is_ok());
+        assert!(TextRecord::from_str_checked(&big, true).is_err());
+        //the CRLF is smaller after normalized
+        let big = "\r\n".repeat(0x8000);
+        assert!(TextRecord::from_str_checked(&big, true).is_ok());
+
+        let expected =
+            TextRecord::Simple("Copyright Garmin Ltd.\0".to_string());
+        assert_eq!(TextRecord::copyright("Garmin Ltd.").unwrap(), expected);
+        let text = TextRecord::copyright("Copyright Garmin Ltd.").unwrap();
+        assert_eq!(text, expected);
+        assert!(TextRecord::copyright(&"a".repeat(0xfff5)).is_err());
+    }
+
+    #[test]
+    fn parse_text_encoding() {
+        let text = TextRecord::Latin1("Garmin® Ltd.".to_string());
+        let file = crate::composer::compose_all(&[
+            Record::Text(text.clone()),
+            Record::MainHeader(MainRecord::DefaultHWID),
+        ])
+        .unwrap();
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        assert!(matches!(
+            parser.read_record().unwrap(),
+            Record::Text(TextRecord::Blob(_))
+        ));
+        let config = ParserConfig {
+            text_encoding: TextEncoding::Auto,
+            ..Default::default()
+        };
+        let mut parser: Parser<&[u8]> =
+            Parser::with_config(&file[..], config).unwrap();
+        assert_eq!(parser.read_record().unwrap(), Record::Text(text));
+    }
+}
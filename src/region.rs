@@ -0,0 +1,99 @@
+//! Garmin region numbers of the firmware blocks.
+//!
+//! The updater tools and the RGN/BIN files identify each payload by a region
+//! number, eg: region 14 is the "fw_all.bin", stored on GCD files with the
+//! firmware id [`FW_ALL_ID`]. [`REGIONS`] map the known regions, unknown
+//! regions use the same value as number and firmware id, like the
+//! [`rgn`](crate::rgn) conversion.
+//!
+//! ```
+//! use gcd_rs::region;
+//!
+//! assert_eq!(region::firmware_id(14), 0x0505);
+//! assert_eq!(region::number(0x0505), 14);
+//! assert_eq!(region::by_number(14).unwrap().name, "fw_all");
+//! //unknown region
+//! assert_eq!(region::firmware_id(0x0506), 0x0506);
+//! ```
+
+use crate::record::descriptor::descriptor_data::DescriptorDecoded;
+use crate::record::descriptor::DescriptorRecord;
+use crate::record::firmware::{FONT_ID, FW_ALL_ID};
+
+/// A known region, see [`REGIONS`].
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Copy)]
+pub struct RegionInfo {
+    /// Region number, used by the updater tools
+    pub number: u16,
+    /// Firmware id of the block on GCD files
+    pub firmware_id: u16,
+    /// Name of the region file, without extension, eg: "fw_all"
+    pub name: &'static str,
+}
+
+/// The known regions.
+pub const REGIONS: &[RegionInfo] = &[
+    RegionInfo {
+        number: 14,
+        firmware_id: FW_ALL_ID,
+        name: "fw_all",
+    },
+    RegionInfo {
+        number: FONT_ID,
+        firmware_id: FONT_ID,
+        name: "font",
+    },
+];
+
+/// The known region with the `number`.
+pub fn by_number(number: u16) -> Option<&'static RegionInfo> {
+    REGIONS.iter().find(|x| x.number == number)
+}
+
+/// The known region stored with the `firmware_id`.
+pub fn by_firmware_id(firmware_id: u16) -> Option<&'static RegionInfo> {
+    REGIONS.iter().find(|x| x.firmware_id == firmware_id)
+}
+
+/// Firmware id of the region, the `number` itself if the region is unknown.
+pub fn firmware_id(number: u16) -> u16 {
+    by_number(number).map_or(number, |x| x.firmware_id)
+}
+
+/// Region number of the firmware id, the `firmware_id` itself if the region
+/// is unknown.
+pub fn number(firmware_id: u16) -> u16 {
+    by_firmware_id(firmware_id).map_or(firmware_id, |x| x.number)
+}
+
+/// Descriptor of a firmware block of the region, with `len` bytes.
+pub fn descriptor(number: u16, len: u32) -> DescriptorRecord {
+    DescriptorRecord::Simple(vec![
+        DescriptorDecoded::FirmwareId(firmware_id(number)).encode(),
+        DescriptorDecoded::FirmwareLen(len).encode(),
+        DescriptorDecoded::End.encode(),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions() {
+        for info in REGIONS {
+            assert_eq!(by_number(info.number), Some(info));
+            assert_eq!(number(firmware_id(info.number)), info.number);
+            assert_eq!(firmware_id(number(info.firmware_id)), info.firmware_id);
+        }
+        assert_eq!(firmware_id(14), FW_ALL_ID);
+        assert_eq!(by_firmware_id(FONT_ID).unwrap().name, "font");
+        assert_eq!(by_number(0x0505), None);
+        assert_eq!(number(0x0506), 0x0506);
+
+        let descriptor = descriptor(14, 0x100);
+        assert_eq!(descriptor.firmware_id(), Some(FW_ALL_ID));
+        assert_eq!(descriptor.firmware_len(), Some(0x100));
+        assert!(descriptor.is_terminated());
+    }
+}
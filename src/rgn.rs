@@ -0,0 +1,429 @@
+//! RGN firmware container.
+//!
+//! Garmin also distribute firmware in RGN files, a simpler container with the
+//! signature "KpGr", the version and a list of records. Each record is a u32
+//! len, a u8 kind and the data, always in LittleEndian:
+//!
+//! 'D' => Data version, a u16.
+//!
+//! 'A' => Application, a u16 version and the builder, build date and build
+//! time, each as a NUL terminated string.
+//!
+//! 'R' => Region, a u16 region id, u32 delay, u32 size and the region data.
+//!
+//! A region is the equivalent of a GCD firmware block, the region id is the
+//! region number, converted to the firmware id with [`region`], see
+//! [`from_gcd`] and [`gcd::from_rgn`].
+//!
+//! [`region`]: crate::region
+//! [`gcd::from_rgn`]: crate::gcd::from_rgn
+
+use byteorder::{ByteOrder, LE};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+
+use crate::error::{GcdError, Result};
+use crate::region;
+use crate::Record;
+
+pub const SIGNATURE: &[u8; 4] = b"KpGr";
+/// The only known file version.
+pub const VERSION: u16 = 100;
+/// Size of the signature and version.
+pub const HEADER_LEN: usize = 6;
+/// Size of the record len and kind.
+pub const RECORD_HEADER_LEN: usize = 5;
+
+const KIND_DATA: u8 = b'D';
+const KIND_APPLICATION: u8 = b'A';
+const KIND_REGION: u8 = b'R';
+//region id, delay and size
+const REGION_HEADER_LEN: usize = 10;
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum RgnRecord {
+    Data {
+        version: u16,
+    },
+    Application {
+        version: u16,
+        builder: String,
+        build_date: String,
+        build_time: String,
+    },
+    Region {
+        id: u16,
+        delay: u32,
+        data: Vec<u8>,
+    },
+    /// Record with unknown kind, the data is kept unparsed.
+    Unknown {
+        kind: u8,
+        data: Vec<u8>,
+    },
+}
+
+impl Display for RgnRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RgnRecord::Data { version } => {
+                write!(f, "RgnRecord::Data {{ version: {} }}", version)
+            }
+            RgnRecord::Application {
+                version, builder, ..
+            } => write!(
+                f,
+                "RgnRecord::Application {{ version: {}, builder: {} }}",
+                version, builder
+            ),
+            RgnRecord::Region { id, delay, data } => write!(
+                f,
+                "RgnRecord::Region {{ id: {:#06x}, delay: {}, len: {} }}",
+                id,
+                delay,
+                data.len()
+            ),
+            RgnRecord::Unknown { kind, data } => write!(
+                f,
+                "RgnRecord::Unknown {{ kind: {:#04x}, len: {} }}",
+                kind,
+                data.len()
+            ),
+        }
+    }
+}
+
+impl RgnRecord {
+    pub fn kind(&self) -> u8 {
+        match self {
+            RgnRecord::Data { .. } => KIND_DATA,
+            RgnRecord::Application { .. } => KIND_APPLICATION,
+            RgnRecord::Region { .. } => KIND_REGION,
+            RgnRecord::Unknown { kind, .. } => *kind,
+        }
+    }
+
+    /// Decode the record data, `kind` is the record kind.
+    pub fn from_raw(kind: u8, data: &[u8]) -> Result<Self> {
+        match kind {
+            KIND_DATA if data.len() == 2 => Ok(RgnRecord::Data {
+                version: LE::read_u16(data),
+            }),
+            KIND_DATA => {
+                Err(GcdError::invalid_data("Invalid RGN Data record len"))
+            }
+            KIND_APPLICATION => {
+                if data.len() < 2 {
+                    return Err(GcdError::invalid_data(
+                        "Invalid RGN Application record len",
+                    ));
+                }
+                let mut strings = data[2..].split(|x| *x == 0);
+                let mut next = || -> Option<String> {
+                    strings.next().map(|x| String::from_utf8_lossy(x).into())
+                };
+                match (next(), next(), next(), next()) {
+                    //the last NUL leave an empty slice
+                    (
+                        Some(builder),
+                        Some(build_date),
+                        Some(build_time),
+                        Some(end),
+                    ) if end.is_empty() => Ok(RgnRecord::Application {
+                        version: LE::read_u16(data),
+                        builder,
+                        build_date,
+                        build_time,
+                    }),
+                    _ => Err(GcdError::invalid_data(
+                        "Invalid RGN Application strings",
+                    )),
+                }
+            }
+            KIND_REGION => {
+                if data.len() < REGION_HEADER_LEN {
+                    return Err(GcdError::invalid_data(
+                        "Invalid RGN Region record len",
+                    ));
+                }
+                let size = LE::read_u32(&data[6..]) as usize;
+                if size != data.len() - REGION_HEADER_LEN {
+                    return Err(GcdError::invalid_data(
+                        "RGN Region size don't match the record len",
+                    ));
+                }
+                Ok(RgnRecord::Region {
+                    id: LE::read_u16(data),
+                    delay: LE::read_u32(&data[2..]),
+                    data: data[REGION_HEADER_LEN..].to_vec(),
+                })
+            }
+            kind => Ok(RgnRecord::Unknown {
+                kind,
+                data: data.to_vec(),
+            }),
+        }
+    }
+
+    /// Encode the record data, without the record header.
+    pub fn to_raw(&self) -> Result<Vec<u8>> {
+        let mut out = vec![];
+        match self {
+            RgnRecord::Data { version } => {
+                out.extend_from_slice(&version.to_le_bytes())
+            }
+            RgnRecord::Application {
+                version,
+                builder,
+                build_date,
+                build_time,
+            } => {
+                out.extend_from_slice(&version.to_le_bytes());
+                for x in [builder, build_date, build_time].iter() {
+                    if x.contains('\0') {
+                        return Err(GcdError::invalid_input(
+                            "RGN Application string with NUL",
+                        ));
+                    }
+                    out.extend_from_slice(x.as_bytes());
+                    out.push(0);
+                }
+            }
+            RgnRecord::Region { id, delay, data } => {
+                if data.len() > u32::MAX as usize - REGION_HEADER_LEN {
+                    return Err(GcdError::invalid_input(
+                        "RGN Region is bigger than u32::MAX",
+                    ));
+                }
+                out.extend_from_slice(&id.to_le_bytes());
+                out.extend_from_slice(&delay.to_le_bytes());
+                out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                out.extend_from_slice(data);
+            }
+            RgnRecord::Unknown { data, .. } => out.extend_from_slice(data),
+        }
+        Ok(out)
+    }
+}
+
+pub struct RgnParser<F> {
+    file: F,
+    end: bool,
+}
+
+impl<F: Read> RgnParser<F> {
+    /// Read and check the signature and version.
+    pub fn new(mut file: F) -> Result<Self> {
+        let mut header = [0; HEADER_LEN];
+        file.read_exact(&mut header)?;
+        if &header[..4] != SIGNATURE {
+            return Err(GcdError::InvalidSignature);
+        }
+        match LE::read_u16(&header[4..]) {
+            VERSION => Ok(RgnParser { file, end: false }),
+            version => Err(GcdError::UnknownVersion(version)),
+        }
+    }
+
+    /// Read the next record, None if the file ended.
+    pub fn read_record(&mut self) -> Result<Option<RgnRecord>> {
+        let mut header = [0; RECORD_HEADER_LEN];
+        //the file ends after the last record, there is no end record
+        if self.file.read(&mut header[..1])? == 0 {
+            return Ok(None);
+        }
+        self.file.read_exact(&mut header[1..])?;
+        let len = LE::read_u32(&header) as usize;
+        let mut data = vec![];
+        (&mut self.file).take(len as u64).read_to_end(&mut data)?;
+        if data.len() != len {
+            return Err(GcdError::invalid_data("RGN record is truncated"));
+        }
+        RgnRecord::from_raw(header[4], &data).map(Some)
+    }
+
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+}
+
+impl<F: Read> Iterator for RgnParser<F> {
+    type Item = Result<RgnRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.end {
+            return None;
+        }
+        let record = self.read_record().transpose();
+        self.end = !matches!(record, Some(Ok(_)));
+        record
+    }
+}
+
+pub struct RgnComposer<F> {
+    file: F,
+}
+
+impl<F: Write> RgnComposer<F> {
+    /// Write the signature and version.
+    pub fn new(mut file: F) -> Result<Self> {
+        file.write_all(SIGNATURE)?;
+        file.write_all(&VERSION.to_le_bytes())?;
+        Ok(RgnComposer { file })
+    }
+
+    pub fn write_record(&mut self, record: &RgnRecord) -> Result<()> {
+        let data = record.to_raw()?;
+        let mut header = [0; RECORD_HEADER_LEN];
+        LE::write_u32(&mut header, data.len() as u32);
+        header[4] = record.kind();
+        self.file.write_all(&header)?;
+        self.file.write_all(&data)?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+}
+
+/// Convert the GCD records into RGN records, each firmware block is converted
+/// into a Region with the region number of the firmware id, see
+/// [`region::number`].
+///
+/// The firmware data is expected to be decoded, like the data returned by the
+/// [`Parser`](crate::parser::Parser).
+pub fn from_gcd<'a, I>(records: I) -> Result<Vec<RgnRecord>>
+where
+    I: IntoIterator<Item = &'a Record>,
+{
+    let mut rgn = vec![RgnRecord::Data { version: VERSION }];
+    for record in records {
+        match record {
+            Record::Descriptor(descriptor) => {
+                let id = descriptor
+                    .firmware_id()
+                    .ok_or(GcdError::MissingDescriptor("Firmware Id"))?;
+                rgn.push(RgnRecord::Region {
+                    id: region::number(id),
+                    delay: 0,
+                    data: vec![],
+                });
+            }
+            Record::FirmwareData(chunk) => match rgn.last_mut() {
+                Some(RgnRecord::Region { id, data, .. }) => {
+                    let expected = region::firmware_id(*id);
+                    if expected != chunk.id().get() {
+                        return Err(GcdError::FirmwareIdMismatch {
+                            expected,
+                            found: chunk.id().get(),
+                        });
+                    }
+                    data.extend_from_slice(chunk.data())
+                }
+                _ => {
+                    return Err(GcdError::invalid_data(
+                        "Firmware chunk without Descriptor",
+                    ))
+                }
+            },
+            _ => {}
+        }
+    }
+    Ok(rgn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_gcd, RgnComposer, RgnParser, RgnRecord};
+    use crate::composer::Composer;
+    use crate::error::Result;
+    use crate::gcd::from_rgn;
+    use crate::gcd_file::tests::{compose, firmware};
+    use crate::parser::Parser;
+    use crate::{GcdFile, Record, Version};
+
+    fn records() -> Vec<RgnRecord> {
+        vec![
+            RgnRecord::Data { version: 100 },
+            RgnRecord::Application {
+                version: 310,
+                builder: "SQA".to_string(),
+                build_date: "Jan 01 2020".to_string(),
+                build_time: "12:00:00".to_string(),
+            },
+            RgnRecord::Region {
+                id: 0x000e,
+                delay: 0,
+                data: firmware(0x000e),
+            },
+            RgnRecord::Unknown {
+                kind: b'X',
+                data: vec![1, 2, 3],
+            },
+        ]
+    }
+
+    #[test]
+    fn rgn_round_trip() {
+        let mut composer = RgnComposer::new(vec![]).unwrap();
+        for record in records().iter() {
+            composer.write_record(record).unwrap();
+        }
+        let file = composer.into_inner();
+        assert_eq!(&file[..6], b"KpGr\x64\x00");
+        //data record
+        assert_eq!(&file[6..13], &[2, 0, 0, 0, b'D', 100, 0]);
+
+        let parser = RgnParser::new(&file[..]).unwrap();
+        let parsed = parser.collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(parsed, records());
+
+        //truncated region
+        let parser = RgnParser::new(&file[..file.len() - 10]).unwrap();
+        assert!(parser.collect::<Result<Vec<_>>>().is_err());
+        assert!(RgnParser::new(&b"KpGr\x65\x00"[..]).is_err());
+    }
+
+    #[test]
+    fn convert() {
+        let file = compose();
+        let parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        let gcd = parser.collect::<Result<Vec<_>>>().unwrap();
+        let rgn = from_gcd(&gcd).unwrap();
+        assert_eq!(rgn.len(), 3);
+        //fw_all is the region 14, the unknown keep the firmware id
+        let regions = [(14, 0x0505), (0x0506, 0x0506)];
+        for (record, (number, id)) in rgn[1..].iter().zip(regions.iter()) {
+            assert_eq!(
+                record,
+                &RgnRecord::Region {
+                    id: *number,
+                    delay: 0,
+                    data: firmware(*id),
+                }
+            );
+        }
+
+        //back to gcd, with the version from the Application
+        let gcd = from_rgn(&records()).unwrap();
+        let mut output = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut output).unwrap();
+        for record in gcd.iter() {
+            composer.write_record(record).unwrap();
+        }
+        drop(composer);
+        let file: GcdFile = GcdFile::from_bytes(output).unwrap();
+        assert_eq!(file.blocks().len(), 1);
+        assert_eq!(file.blocks()[0].id, 0x0505);
+        assert_eq!(file.read_block(0).unwrap(), firmware(0x000e));
+        let descriptor = file.blocks()[0].descriptor;
+        let version = Some(Version::new_raw(310));
+        assert!(matches!(
+            file.record(descriptor).unwrap(),
+            Record::Descriptor(x) if x.sw_version() == version
+        ));
+        assert_eq!(from_gcd(&gcd).unwrap()[1..], records()[2..3]);
+    }
+}
@@ -0,0 +1,169 @@
+//! Check if a file can be parsed and composed back without changes.
+//!
+//! Tools that modify the firmware parse the file, change some records and
+//! compose it again. If the file is not identical after a round trip without
+//! changes, the container would be altered in places the tool never touched.
+//!
+//! ```
+//! use gcd_rs::roundtrip;
+//! use gcd_rs::testing::vectors;
+//!
+//! let report = roundtrip::verify(vectors::MULTI_BLOCK).unwrap();
+//! assert!(report.is_identical());
+//! ```
+
+use byteorder::ByteOrder;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::io::Read;
+
+use crate::composer::Composer;
+use crate::error::Result;
+use crate::parser::Parser;
+use crate::{GcdDefaultEndian, GcdIndex};
+
+/// First difference between the original and the composed file.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct Divergence {
+    /// Offset of the first different byte.
+    pub offset: u64,
+    /// Index of the original record at the offset, None if the offset is
+    /// after the End record.
+    pub record: Option<usize>,
+}
+
+/// Result of the round trip, see [`verify`].
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub original_len: u64,
+    pub composed_len: u64,
+    pub divergence: Option<Divergence>,
+}
+
+impl Report {
+    pub fn is_identical(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.divergence {
+            None => write!(f, "identical, {} bytes", self.original_len),
+            Some(Divergence { offset, record }) => {
+                write!(f, "diverged at offset {:#x}", offset)?;
+                match record {
+                    Some(record) => write!(f, ", record {}", record)?,
+                    None => write!(f, ", after the End record")?,
+                }
+                write!(
+                    f,
+                    ", original {} bytes, composed {} bytes",
+                    self.original_len, self.composed_len
+                )
+            }
+        }
+    }
+}
+
+/// Parse the file, compose it back and compare the result, using the
+/// [`GcdDefaultEndian`].
+pub fn verify<R: Read>(reader: R) -> Result<Report> {
+    verify_endian::<R, GcdDefaultEndian>(reader)
+}
+
+/// Same as [`verify`], with the endian `B`.
+pub fn verify_endian<R, B>(mut reader: R) -> Result<Report>
+where
+    R: Read,
+    B: ByteOrder,
+{
+    let mut original = vec![];
+    reader.read_to_end(&mut original)?;
+
+    let parser: Parser<&[u8], B> = Parser::new(&original[..])?;
+    let mut composed = vec![];
+    let mut composer: Composer<_, B> = Composer::new(&mut composed)?;
+    for record in parser {
+        composer.write_record(&record?)?;
+    }
+    drop(composer);
+
+    let index = GcdIndex::build::<_, B>(&original[..])?;
+    Ok(report(&original, &composed, &index))
+}
+
+fn report(original: &[u8], composed: &[u8], index: &GcdIndex) -> Report {
+    let offset = original
+        .iter()
+        .zip(composed.iter())
+        .position(|(a, b)| a != b)
+        .or_else(|| {
+            //one is a prefix of the other
+            (original.len() != composed.len())
+                .then(|| original.len().min(composed.len()))
+        });
+    let divergence = offset.map(|offset| {
+        let offset = offset as u64;
+        let record = index
+            .entries
+            .iter()
+            .position(|x| offset >= x.offset && offset < x.offset + x.len);
+        Divergence { offset, record }
+    });
+    Report {
+        original_len: original.len() as u64,
+        composed_len: composed.len() as u64,
+        divergence,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{report, verify, Divergence};
+    use crate::testing::vectors;
+    use crate::GcdIndex;
+    use byteorder::LE;
+
+    #[test]
+    fn vectors_round_trip() {
+        for (name, data) in vectors::ALL.iter() {
+            let report = verify(*data).unwrap();
+            assert!(report.is_identical(), "{}: {}", name, report);
+        }
+        let file = crate::gcd_file::tests::compose();
+        assert!(verify(&file[..]).unwrap().is_identical());
+    }
+
+    #[test]
+    fn divergence() {
+        let original = vectors::FILLERS_CHECKSUMS;
+        let index = GcdIndex::build::<_, LE>(original).unwrap();
+
+        //the firmware chunk at 59
+        let mut composed = original.to_vec();
+        composed[62] ^= 1;
+        let divergence = report(original, &composed, &index).divergence;
+        assert_eq!(
+            divergence,
+            Some(Divergence {
+                offset: 62,
+                //text, checksum, filler, main, descriptor and the chunk
+                record: Some(5),
+            })
+        );
+
+        //data after the End
+        let mut original = original.to_vec();
+        original.extend_from_slice(&[0; 4]);
+        let report = verify(&original[..]).unwrap();
+        assert_eq!(
+            report.divergence,
+            Some(Divergence {
+                offset: 95,
+                record: None,
+            })
+        );
+        assert_eq!(report.composed_len, 95);
+    }
+}
@@ -0,0 +1,308 @@
+//! Modify a file in place, over a `Read + Write + Seek` stream.
+//!
+//! The file is indexed once, the records before the change are kept in the
+//! file untouched, only the checksums of it are calculated, so appending a
+//! record don't require the whole file to be parsed and composed again.
+//!
+//! ```
+//! use gcd_rs::record::text::TextRecord;
+//! use gcd_rs::session::Session;
+//! use gcd_rs::testing::vectors;
+//! use gcd_rs::Record;
+//! use std::io::Cursor;
+//!
+//! let file = Cursor::new(vectors::SINGLE_BLOCK.to_vec());
+//! let mut session: Session<_> = Session::open(file).unwrap();
+//! let text = Record::Text(TextRecord::Simple("Appended".to_string()));
+//! session.append(&[text.clone()]).unwrap();
+//! //the new record is before the End
+//! let len = session.len();
+//! assert_eq!(session.get_record(len - 2).unwrap(), text);
+//! ```
+
+use byteorder::ByteOrder;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+
+use crate::composer::Composer;
+use crate::error::{GcdError, Result};
+use crate::gcd_file::{GcdIndex, IndexedRecord};
+use crate::record::checksum::Checksums;
+use crate::record::firmware::transform::TransformTable;
+use crate::{GcdDefaultEndian, Record};
+
+pub struct Session<F, B = GcdDefaultEndian>
+where
+    F: Read + Write + Seek,
+    B: ByteOrder,
+{
+    file: F,
+    // position of the signature in the stream
+    start: u64,
+    index: GcdIndex,
+    transforms: TransformTable,
+    endian: PhantomData<B>,
+}
+
+impl<F, B> Session<F, B>
+where
+    F: Read + Write + Seek,
+    B: ByteOrder,
+{
+    /// Index the file, starting at the current stream position.
+    pub fn open(mut file: F) -> Result<Self> {
+        let start = file.stream_position()?;
+        let index = GcdIndex::build::<_, B>(BufReader::new(&mut file))?;
+        Ok(Session {
+            file,
+            start,
+            index,
+            transforms: TransformTable::default(),
+            endian: PhantomData,
+        })
+    }
+
+    /// Set the transformations used to decode and encode the firmware, by
+    /// default only the font rule, see [`TransformTable`].
+    pub fn set_transforms(&mut self, transforms: TransformTable) {
+        self.transforms = transforms;
+    }
+
+    pub fn index(&self) -> &GcdIndex {
+        &self.index
+    }
+
+    /// Number of records in the file, including the End.
+    pub fn len(&self) -> usize {
+        self.index.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.entries.is_empty()
+    }
+
+    /// Len of the file, until the end of the End record.
+    ///
+    /// After a change that made the file smaller, the stream still contains
+    /// the old data after this len, the caller should truncate it, eg: with
+    /// [`std::fs::File::set_len`].
+    pub fn file_len(&self) -> u64 {
+        self.index
+            .entries
+            .last()
+            .map(|x| x.offset + x.len)
+            .unwrap_or(0)
+    }
+
+    /// Read the record, the firmware chunks are read from the file and
+    /// decoded.
+    pub fn get_record(&mut self, index: usize) -> Result<Record> {
+        let (file, start) = (&mut self.file, self.start);
+        self.index.read_record(index, &self.transforms, |offset, buf| {
+            file.seek(SeekFrom::Start(start + offset))?;
+            file.read_exact(buf)
+        })
+    }
+
+    /// Iterate over all the records, including the End.
+    pub fn records(&mut self) -> Records<'_, F, B> {
+        Records {
+            session: self,
+            next: 0,
+        }
+    }
+
+    /// Discard the record at `index` and all after it, returning a
+    /// [`Composer`] that write after the previous record, with the checksums
+    /// of the file so far.
+    ///
+    /// The composer should write the End record, after that the session
+    /// need to be indexed again with [`Session::reindex`].
+    pub fn truncate(&mut self, index: usize) -> Result<Composer<&mut F, B>> {
+        let offset = self
+            .index
+            .entries
+            .get(index)
+            .ok_or_else(|| {
+                GcdError::invalid_input("Record index out of range")
+            })?
+            .offset;
+        //checksums of the data before the record
+        self.file.seek(SeekFrom::Start(self.start))?;
+        let mut checksums = Checksums::default();
+        let mut prefix = BufReader::new(&mut self.file).take(offset);
+        let mut buf = [0; 0x1000];
+        loop {
+            match prefix.read(&mut buf)? {
+                0 => break,
+                len => checksums.update(&buf[..len]),
+            }
+        }
+        drop(prefix);
+        self.file.seek(SeekFrom::Start(self.start + offset))?;
+
+        //firmware chunks after the last Descriptor are part of its block
+        let block_xor = self
+            .index
+            .blocks
+            .iter()
+            .rev()
+            .find(|x| x.descriptor < index)
+            .map(|x| (x.id, x.xor_key));
+        let mut composer =
+            Composer::resume(&mut self.file, checksums, offset, block_xor);
+        composer.set_transforms(self.transforms.clone());
+        Ok(composer)
+    }
+
+    /// Index the file again, after a change with [`Session::truncate`].
+    pub fn reindex(&mut self) -> Result<()> {
+        self.file.seek(SeekFrom::Start(self.start))?;
+        self.index = GcdIndex::build::<_, B>(BufReader::new(&mut self.file))?;
+        Ok(())
+    }
+
+    /// Replace the record at `index` and all after it with the `records`,
+    /// the last one need to be the End record. Return the new
+    /// [`Session::file_len`].
+    pub fn replace_tail<'a, I>(
+        &mut self,
+        index: usize,
+        records: I,
+    ) -> Result<u64>
+    where
+        I: IntoIterator<Item = &'a Record>,
+    {
+        let mut composer = self.truncate(index)?;
+        let mut last = None;
+        for record in records {
+            composer.write_record(record)?;
+            last = Some(record);
+        }
+        if last != Some(&Record::End) {
+            return Err(GcdError::invalid_input(
+                "The last record need to be the End",
+            ));
+        }
+        drop(composer);
+        self.file.flush()?;
+        self.reindex()?;
+        Ok(self.file_len())
+    }
+
+    /// Add the records before the End record. Return the new
+    /// [`Session::file_len`].
+    pub fn append<'a, I>(&mut self, records: I) -> Result<u64>
+    where
+        I: IntoIterator<Item = &'a Record>,
+    {
+        let end = self
+            .index
+            .entries
+            .iter()
+            .position(|x| x.record == IndexedRecord::Record(Record::End))
+            .ok_or_else(|| GcdError::invalid_data("File without End record"))?;
+        let records = records.into_iter().chain(std::iter::once(&Record::End));
+        self.replace_tail(end, records)
+    }
+
+    /// Return the underlying stream.
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+}
+
+/// Iterator over the records of a [`Session`], see [`Session::records`].
+pub struct Records<'a, F, B>
+where
+    F: Read + Write + Seek,
+    B: ByteOrder,
+{
+    session: &'a mut Session<F, B>,
+    next: usize,
+}
+
+impl<'a, F, B> Iterator for Records<'a, F, B>
+where
+    F: Read + Write + Seek,
+    B: ByteOrder,
+{
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.session.len() {
+            return None;
+        }
+        self.next += 1;
+        Some(self.session.get_record(self.next - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Session;
+    use crate::gcd_file::tests::{compose, firmware};
+    use crate::parser::parse_all;
+    use crate::record::checksum::ChecksumRecord;
+    use crate::record::firmware::FirmwareRecord;
+    use crate::record::text::TextRecord;
+    use crate::{Record, RecordId};
+    use std::io::Cursor;
+
+    #[test]
+    fn session_append() {
+        let original = compose();
+        let mut session: Session<_> =
+            Session::open(Cursor::new(original.clone())).unwrap();
+        let records: Vec<Record> =
+            session.records().collect::<Result<_, _>>().unwrap();
+        assert_eq!(records, parse_all(&original).unwrap());
+
+        let text = Record::Text(TextRecord::Simple("Appended".to_string()));
+        let checksum = Record::Checksum(ChecksumRecord::Simple);
+        let len = session.append(&[text.clone(), checksum.clone()]).unwrap();
+        let file = session.into_inner().into_inner();
+        assert_eq!(len, file.len() as u64);
+        //the records before are untouched
+        let end = original.len() - 4;
+        assert_eq!(&file[..end], &original[..end]);
+        //the parser verify the new checksum
+        let mut expected = records.clone();
+        expected.splice(records.len() - 1.., vec![text, checksum, Record::End]);
+        assert_eq!(parse_all(&file).unwrap(), expected);
+    }
+
+    #[test]
+    fn session_truncate() {
+        let original = compose();
+        let mut session: Session<_> =
+            Session::open(Cursor::new(original.clone())).unwrap();
+        let records: Vec<Record> =
+            session.records().collect::<Result<_, _>>().unwrap();
+        //the last chunk of the second block, replaced by a new data
+        let last_chunk = records
+            .iter()
+            .rposition(|x| matches!(x, Record::FirmwareData(_)))
+            .unwrap();
+        let data = firmware(0x0506);
+        let chunk =
+            FirmwareRecord::new(data[900..].to_vec(), RecordId::new(0x0506));
+        let new = [
+            Record::FirmwareData(chunk),
+            Record::Checksum(ChecksumRecord::Simple),
+            Record::End,
+        ];
+        session.replace_tail(last_chunk, new.iter()).unwrap();
+        let len = session.file_len() as usize;
+        let file = session.into_inner().into_inner();
+        //the chunk is encoded with the block XOR key
+        let parsed = parse_all(&file[..len]).unwrap();
+        assert_eq!(&parsed[..last_chunk], &records[..last_chunk]);
+        assert_eq!(&parsed[last_chunk..], &new[..]);
+
+        let mut session: Session<_> =
+            Session::open(Cursor::new(original)).unwrap();
+        assert!(session.replace_tail(1, &[]).is_err());
+        assert!(session.replace_tail(100, &[Record::End]).is_err());
+    }
+}
@@ -0,0 +1,210 @@
+//! Statistics of a GCD file.
+//!
+//! Count the records and the bytes used by each kind of record, the
+//! firmware bytes of each firmware id and the overhead of the fillers.
+//!
+//! ```
+//! use gcd_rs::stats::RecordKind;
+//! use gcd_rs::testing::vectors;
+//! use gcd_rs::GcdStats;
+//!
+//! let stats = GcdStats::from_reader(vectors::MULTI_BLOCK).unwrap();
+//! assert_eq!(stats.count(RecordKind::FirmwareData), 3);
+//! assert_eq!(stats.firmware[&0x0505], 8);
+//! println!("{}", stats);
+//! ```
+
+use byteorder::ByteOrder;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use crate::config::ParserConfig;
+use crate::error::Result;
+use crate::parser::Parser;
+use crate::{GcdDefaultEndian, Record};
+
+/// Kind of record, used as key of [`GcdStats::records`].
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Copy,
+    Clone,
+    Serialize,
+    Deserialize,
+)]
+pub enum RecordKind {
+    /// Checksum records
+    Checksum,
+    Filler,
+    MainHeader,
+    Text,
+    /// The DescriptorType and DescriptorData records
+    Descriptor,
+    FirmwareData,
+    End,
+    Unknown,
+}
+
+impl RecordKind {
+    pub const fn of(record: &Record) -> Self {
+        match record {
+            Record::Checksum(_) => RecordKind::Checksum,
+            Record::Filler(_) => RecordKind::Filler,
+            Record::MainHeader(_) => RecordKind::MainHeader,
+            Record::Text(_) => RecordKind::Text,
+            Record::Descriptor(_) => RecordKind::Descriptor,
+            Record::FirmwareData(_) => RecordKind::FirmwareData,
+            Record::End => RecordKind::End,
+            Record::Unknown { .. } => RecordKind::Unknown,
+        }
+    }
+}
+
+/// Number of records of a kind and the bytes used by them.
+#[derive(
+    Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize,
+)]
+pub struct RecordStats {
+    pub count: u64,
+    /// Bytes in the file, including the record headers
+    pub bytes: u64,
+}
+
+/// Statistics of a file, see the [module](self) documentation.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GcdStats {
+    /// File size, including the signature
+    pub file_len: u64,
+    /// Records by kind
+    pub records: BTreeMap<RecordKind, RecordStats>,
+    /// Firmware bytes by firmware id, without the record headers
+    pub firmware: BTreeMap<u16, u64>,
+    /// Biggest firmware chunk, without the record header
+    pub largest_chunk: u16,
+}
+
+impl GcdStats {
+    /// Read all the records from the file, using the default endian.
+    pub fn from_reader<F: std::io::Read>(file: F) -> Result<Self> {
+        //only the sizes are relevant, the data is not checked
+        let config = ParserConfig {
+            verify_checksums: false,
+            decode_firmware: false,
+            ..ParserConfig::default()
+        };
+        let mut parser: Parser<F, GcdDefaultEndian> =
+            Parser::with_config(file, config)?;
+        Self::from_parser(&mut parser)
+    }
+
+    /// Read the records until the End record.
+    pub fn from_parser<F, B>(parser: &mut Parser<F, B>) -> Result<Self>
+    where
+        F: std::io::Read,
+        B: ByteOrder,
+    {
+        let mut stats = GcdStats::default();
+        loop {
+            let start = Parser::position(parser);
+            let record = parser.read_record()?;
+            stats.add_record(&record, Parser::position(parser) - start);
+            if let Record::End = record {
+                stats.file_len = Parser::position(parser);
+                return Ok(stats);
+            }
+        }
+    }
+
+    fn add_record(&mut self, record: &Record, bytes: u64) {
+        let entry = self.records.entry(RecordKind::of(record)).or_default();
+        entry.count += 1;
+        entry.bytes += bytes;
+        if let Record::FirmwareData(firmware) = record {
+            *self.firmware.entry(firmware.id().get()).or_default() +=
+                u64::from(firmware.len());
+            self.largest_chunk = self.largest_chunk.max(firmware.len());
+        }
+    }
+
+    /// Number of records of the kind.
+    pub fn count(&self, kind: RecordKind) -> u64 {
+        self.records.get(&kind).map_or(0, |x| x.count)
+    }
+
+    /// Bytes used by the records of the kind, including the headers.
+    pub fn bytes(&self, kind: RecordKind) -> u64 {
+        self.records.get(&kind).map_or(0, |x| x.bytes)
+    }
+
+    /// Bytes used by the filler records, including the headers.
+    pub fn filler_overhead(&self) -> u64 {
+        self.bytes(RecordKind::Filler)
+    }
+
+    /// Number of checksum records.
+    pub fn checksum_count(&self) -> u64 {
+        self.count(RecordKind::Checksum)
+    }
+}
+
+impl Display for GcdStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "File: {} bytes", self.file_len)?;
+        for (kind, stats) in &self.records {
+            writeln!(
+                f,
+                "{:?}: {} records, {} bytes",
+                kind, stats.count, stats.bytes
+            )?;
+        }
+        for (id, bytes) in &self.firmware {
+            writeln!(f, "Firmware {:#06x}: {} bytes", id, bytes)?;
+        }
+        write!(f, "Largest chunk: {} bytes", self.largest_chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GcdStats, RecordKind, RecordStats};
+    use crate::testing::vectors;
+
+    #[test]
+    fn stats() {
+        let stats = GcdStats::from_reader(vectors::FILLERS_CHECKSUMS).unwrap();
+        assert_eq!(stats.file_len, vectors::FILLERS_CHECKSUMS.len() as u64);
+        let total: u64 = stats.records.values().map(|x| x.bytes).sum();
+        //all the bytes, except the signature
+        assert_eq!(total + 8, stats.file_len);
+        assert_eq!(stats.count(RecordKind::Text), 1);
+        assert_eq!(stats.filler_overhead(), 8 + 6);
+        assert_eq!(stats.checksum_count(), 3);
+        assert_eq!(
+            stats.records[&RecordKind::Descriptor],
+            RecordStats {
+                count: 1,
+                bytes: 12 + 12,
+            }
+        );
+        assert_eq!(
+            stats.firmware.into_iter().collect::<Vec<_>>(),
+            [(0x0505, 8)]
+        );
+        assert_eq!(stats.largest_chunk, 4);
+
+        let stats = GcdStats::from_reader(vectors::MULTI_BLOCK).unwrap();
+        assert_eq!(stats.count(RecordKind::FirmwareData), 3);
+        assert_eq!(stats.bytes(RecordKind::FirmwareData), 3 * 8);
+        assert_eq!(stats.bytes(RecordKind::Filler), 0);
+        assert_eq!(stats.firmware[&0x0506], 4);
+        assert_eq!(stats.to_string().lines().count(), 9);
+
+        assert!(GcdStats::from_reader(&vectors::MULTI_BLOCK[..30]).is_err());
+    }
+}
@@ -0,0 +1,343 @@
+//! Storage backends, used to read and write GCD files without assuming
+//! `std::fs`.
+//!
+//! [`GcdSource`] is a random access, read only, storage. [`GcdSink`] is an
+//! append only storage. Both are implemented for `File`, byte buffers and
+//! can be implemented for any user defined backend, eg: a cloud blob.
+//!
+//! [`SourceReader`] and [`SinkWriter`] adapt the backends to `Read + Seek`
+//! and `Write`, so they can be used by the
+//! [`Parser`](crate::parser::Parser) and
+//! [`Composer`](crate::composer::Composer).
+
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+
+/// Random access storage, the data can be read from multiple threads.
+pub trait GcdSource {
+    /// Total size of the data.
+    fn len(&self) -> Result<u64>;
+    fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+    /// Read data from the offset, returning the number of bytes read, 0 if
+    /// the offset is at or after the end of the data.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize>;
+
+    /// Read exactly `buf.len()` bytes from the offset.
+    fn read_exact_at(&self, mut offset: u64, mut buf: &mut [u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.read_at(offset, buf) {
+                Ok(0) => {
+                    return Err(Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "Read outside the source",
+                    ))
+                }
+                Ok(read) => {
+                    offset += read as u64;
+                    buf = &mut buf[read..];
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Append only storage.
+pub trait GcdSink {
+    /// Total size of the data already written.
+    fn len(&self) -> Result<u64>;
+    fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+    /// Write all the data at the end of the storage.
+    fn append(&mut self, data: &[u8]) -> Result<()>;
+    /// Make sure all the data was written to the storage.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl GcdSource for [u8] {
+    fn len(&self) -> Result<u64> {
+        Ok(<[u8]>::len(self) as u64)
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        if offset >= GcdSource::len(self)? {
+            return Ok(0);
+        }
+        let data = &self[offset as usize..];
+        let len = buf.len().min(data.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+}
+
+impl GcdSource for Vec<u8> {
+    fn len(&self) -> Result<u64> {
+        GcdSource::len(self.as_slice())
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        self.as_slice().read_at(offset, buf)
+    }
+}
+
+impl GcdSource for File {
+    fn len(&self) -> Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+    #[cfg(unix)]
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        std::os::unix::fs::FileExt::read_at(self, buf, offset)
+    }
+    #[cfg(windows)]
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        std::os::windows::fs::FileExt::seek_read(self, buf, offset)
+    }
+    #[cfg(not(any(unix, windows)))]
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let mut file = self;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read(buf)
+    }
+}
+
+/// Any seekable stream can be a source, the lock serializes the access.
+impl<F: Read + Seek> GcdSource for Mutex<F> {
+    fn len(&self) -> Result<u64> {
+        let mut file = self.lock().unwrap_or_else(|poison| poison.into_inner());
+        file.seek(SeekFrom::End(0))
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        //the position is always set before reading, so a poisoned lock can
+        //be safely reused
+        let mut file = self.lock().unwrap_or_else(|poison| poison.into_inner());
+        file.seek(SeekFrom::Start(offset))?;
+        file.read(buf)
+    }
+}
+
+impl<S: GcdSource + ?Sized> GcdSource for &S {
+    fn len(&self) -> Result<u64> {
+        (**self).len()
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        (**self).read_at(offset, buf)
+    }
+}
+
+impl<S: GcdSource + ?Sized> GcdSource for Box<S> {
+    fn len(&self) -> Result<u64> {
+        (**self).len()
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        (**self).read_at(offset, buf)
+    }
+}
+
+impl<S: GcdSource + ?Sized> GcdSource for Arc<S> {
+    fn len(&self) -> Result<u64> {
+        (**self).len()
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        (**self).read_at(offset, buf)
+    }
+}
+
+impl GcdSink for Vec<u8> {
+    fn len(&self) -> Result<u64> {
+        Ok(Vec::len(self) as u64)
+    }
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+impl GcdSink for File {
+    fn len(&self) -> Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        self.seek(SeekFrom::End(0))?;
+        self.write_all(data)
+    }
+    fn flush(&mut self) -> Result<()> {
+        Write::flush(self)
+    }
+}
+
+impl<S: GcdSink + ?Sized> GcdSink for &mut S {
+    fn len(&self) -> Result<u64> {
+        (**self).len()
+    }
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        (**self).append(data)
+    }
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+impl<S: GcdSink + ?Sized> GcdSink for Box<S> {
+    fn len(&self) -> Result<u64> {
+        (**self).len()
+    }
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        (**self).append(data)
+    }
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+/// `Read + Seek` over a [`GcdSource`].
+#[derive(Debug, Clone)]
+pub struct SourceReader<S> {
+    source: S,
+    pos: u64,
+}
+
+impl<S: GcdSource> SourceReader<S> {
+    pub fn new(source: S) -> Self {
+        SourceReader { source, pos: 0 }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S: GcdSource> Read for SourceReader<S> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let read = self.source.read_at(self.pos, buf)?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl<S: GcdSource> Seek for SourceReader<S> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let new = match pos {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => {
+                let len = self.source.len()?;
+                if x < 0 {
+                    len.checked_sub(x.unsigned_abs())
+                } else {
+                    len.checked_add(x as u64)
+                }
+            }
+            SeekFrom::Current(x) => {
+                if x < 0 {
+                    self.pos.checked_sub(x.unsigned_abs())
+                } else {
+                    self.pos.checked_add(x as u64)
+                }
+            }
+        };
+        self.pos = new.ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, "Invalid seek position")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// `Write` over a [`GcdSink`].
+#[derive(Debug, Clone)]
+pub struct SinkWriter<S> {
+    sink: S,
+}
+
+impl<S: GcdSink> SinkWriter<S> {
+    pub fn new(sink: S) -> Self {
+        SinkWriter { sink }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+impl<S: GcdSink> Write for SinkWriter<S> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.sink.append(buf)?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> Result<()> {
+        self.sink.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GcdSink, GcdSource, SinkWriter, SourceReader};
+    use crate::composer::Composer;
+    use crate::parser::Parser;
+    use crate::record::main::MainRecord;
+    use crate::record::text::TextRecord;
+    use crate::Record;
+    use std::io::Result;
+
+    //user defined backend, split in fixed size blocks
+    #[derive(Default)]
+    struct Blocks(Vec<Vec<u8>>);
+
+    impl GcdSource for Blocks {
+        fn len(&self) -> Result<u64> {
+            Ok(self.0.iter().map(|x| x.len() as u64).sum())
+        }
+        fn read_at(&self, mut offset: u64, buf: &mut [u8]) -> Result<usize> {
+            for block in self.0.iter() {
+                if offset < block.len() as u64 {
+                    return block.as_slice().read_at(offset, buf);
+                }
+                offset -= block.len() as u64;
+            }
+            Ok(0)
+        }
+    }
+
+    impl GcdSink for Blocks {
+        fn len(&self) -> Result<u64> {
+            GcdSource::len(self)
+        }
+        fn append(&mut self, data: &[u8]) -> Result<()> {
+            for chunk in data.chunks(3) {
+                self.0.push(chunk.to_vec());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn custom_backend() {
+        let records = [
+            Record::Text(TextRecord::Simple("text".to_string())),
+            Record::MainHeader(MainRecord::DefaultHWID),
+        ];
+        let mut composer: Composer<_> =
+            Composer::new(SinkWriter::new(Blocks::default())).unwrap();
+        for record in records.iter() {
+            composer.write_record(record).unwrap();
+        }
+        let blocks = composer.into_inner().into_inner();
+        let mut data = vec![0; GcdSource::len(&blocks).unwrap() as usize];
+        blocks.read_exact_at(0, &mut data).unwrap();
+        assert!(blocks.read_exact_at(1, &mut data.clone()).is_err());
+
+        let mut parser: Parser<_> =
+            Parser::new(SourceReader::new(&blocks)).unwrap();
+        for record in records.iter() {
+            assert_eq!(&parser.read_record().unwrap(), record);
+        }
+        let mut parser: Parser<_> = Parser::new(&data[..]).unwrap();
+        for record in records.iter() {
+            assert_eq!(&parser.read_record().unwrap(), record);
+        }
+    }
+}
@@ -0,0 +1,5 @@
+//! Helpers for testing code that uses this crate.
+
+pub mod fuzz;
+pub mod testgen;
+pub mod vectors;
@@ -0,0 +1,135 @@
+//! Entry point for fuzzers.
+//!
+//! No input can panic the crate readers, all the problems are returned as
+//! errors. [`parse`] feeds the data to the [`Parser`], in all the modes, and
+//! the other readers, ignoring the results, so it can be called directly
+//! from a fuzz target, eg: with cargo-fuzz:
+//!
+//! ```ignore
+//! fuzz_target!(|data: &[u8]| gcd_rs::testing::fuzz::parse(data));
+//! ```
+
+use byteorder::LE;
+
+use crate::config::ParserConfig;
+use crate::parser::{Parser, RecordVisitor};
+use crate::{carve, codec, hexdump, rgn, roundtrip, GcdIndex, Record};
+
+struct Visitor;
+
+impl RecordVisitor for Visitor {}
+
+/// Parse the data with all the readers, the results are discarded.
+pub fn parse(data: &[u8]) {
+    let lenient = ParserConfig {
+        strict: false,
+        allow_unknown_len: true,
+        report_checksums: true,
+        max_main_headers: u8::MAX,
+        ..ParserConfig::default()
+    };
+    let recover = ParserConfig {
+        recover: true,
+        ..lenient.clone()
+    };
+    for config in [ParserConfig::default(), lenient, recover] {
+        if let Ok(parser) = Parser::<_, LE>::with_config(data, config.clone()) {
+            //each record consume at least one byte, the iteration ends
+            parser.take(data.len()).for_each(drop);
+        }
+        if let Ok(mut parser) = Parser::<_, LE>::with_config(data, config) {
+            //stream the firmware, without the chunk records
+            for _ in 0..data.len() {
+                match parser.read_record() {
+                    Ok(Record::Descriptor(_)) => {
+                        let _ = parser.read_firmware_into(std::io::sink());
+                    }
+                    Ok(Record::End) => {
+                        let _ = parser.finish();
+                        break;
+                    }
+                    Err(_) => break,
+                    Ok(_) => {}
+                }
+            }
+        }
+    }
+    if let Ok(mut parser) = Parser::<_, LE>::new(data) {
+        let _ = parser.visit(&mut Visitor);
+    }
+    if let Ok(mut parser) = Parser::<_, LE>::new(data) {
+        for _ in 0..data.len() {
+            match parser.read_cow_record() {
+                Ok(record) if !record.is_end() => {}
+                _ => break,
+            }
+        }
+    }
+    let _ = GcdIndex::build::<_, LE>(data);
+    let _ = roundtrip::verify_endian::<_, LE>(data);
+    let _ = hexdump::annotate::<LE, _>(data, Some(16), &mut std::io::sink());
+    let _ = carve::scan(data);
+    if let Ok((rest, _)) = codec::decode_signature::<LE>(data) {
+        codec::frames::<LE>(rest).for_each(drop);
+    }
+    if let Ok(parser) = rgn::RgnParser::new(data) {
+        parser.take(data.len()).for_each(drop);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::testing::vectors;
+
+    // xorshift, so the mutations are the same on each run
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+        fn below(&mut self, max: usize) -> usize {
+            (self.next() % max as u64) as usize
+        }
+    }
+
+    #[test]
+    fn no_panic() {
+        const VALUES: [u8; 8] =
+            [0x00, 0x01, 0x02, 0x7f, 0x80, 0xfe, 0xff, 0x50];
+        for (_, data) in vectors::ALL {
+            for len in 0..=data.len() {
+                parse(&data[..len]);
+            }
+            //each byte replaced by interesting values
+            for pos in 0..data.len() {
+                for value in VALUES {
+                    let mut data = data.to_vec();
+                    data[pos] = value;
+                    parse(&data);
+                }
+            }
+        }
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..2000 {
+            let (_, data) = vectors::ALL[rng.below(vectors::ALL.len())];
+            let mut data = data.to_vec();
+            for _ in 0..=rng.below(8) {
+                let pos = 8 + rng.below(data.len() - 8);
+                match rng.below(4) {
+                    0 => data[pos] = rng.next() as u8,
+                    1 => data[pos] = VALUES[rng.below(VALUES.len())],
+                    2 => {
+                        data.remove(pos);
+                    }
+                    _ => data.insert(pos, rng.next() as u8),
+                }
+            }
+            parse(&data);
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! Generator of synthetic GCD files, in LittleEndian.
+//!
+//! The bytes are built by hand, without the [`Composer`], so the files can be
+//! used as a reference for the crate and for downstream crates, without
+//! redistributing Garmin binaries. The output only depend on the arguments,
+//! eg: [`FileGen`] with the firmware of [`vectors::SINGLE_BLOCK`] result in
+//! the same bytes.
+//!
+//! ```
+//! use gcd_rs::parser::parse_all;
+//! use gcd_rs::testing::testgen::{self, FileGen};
+//!
+//! let file = FileGen::new()
+//!     .text("test")
+//!     .block(0x0505, testgen::firmware(0x1000, 1))
+//!     .chunk_size(0x800)
+//!     .build();
+//! //text, main header, descriptor, 2 chunks, checksum and end
+//! assert_eq!(parse_all(&file).unwrap().len(), 7);
+//! ```
+//!
+//! [`Composer`]: crate::composer::Composer
+//! [`vectors::SINGLE_BLOCK`]: crate::testing::vectors::SINGLE_BLOCK
+
+/// Signature and version 100.
+pub fn signature() -> Vec<u8> {
+    b"GARMIN\x64\x00".to_vec()
+}
+
+/// Record with the `id` and the `body`, the len is the body len.
+///
+/// # Panics
+///
+/// If the body is bigger than u16::MAX.
+pub fn record(id: u16, body: &[u8]) -> Vec<u8> {
+    assert!(body.len() <= u16::MAX as usize, "Record body too big");
+    let mut record = header(id, body.len() as u16);
+    record.extend_from_slice(body);
+    record
+}
+
+/// Record header with the `id` and `len`.
+pub fn header(id: u16, len: u16) -> Vec<u8> {
+    let mut header = id.to_le_bytes().to_vec();
+    header.extend_from_slice(&len.to_le_bytes());
+    header
+}
+
+/// Checksum record that result in a sum of 0 of the `file` plus the record.
+pub fn checksum(file: &[u8]) -> Vec<u8> {
+    let mut record = header(1, 1);
+    let sum = file
+        .iter()
+        .chain(record.iter())
+        .fold(0u8, |acc, x| acc.wrapping_add(*x));
+    record.push(sum.wrapping_neg());
+    record
+}
+
+/// Filler record with `len` zeros.
+pub fn filler(len: u16) -> Vec<u8> {
+    record(2, &vec![0; len.into()])
+}
+
+/// MainHeader record with the `hwid`.
+pub fn main_header(hwid: u16) -> Vec<u8> {
+    record(3, &hwid.to_le_bytes())
+}
+
+/// Text record, without a null terminator.
+pub fn text(text: &str) -> Vec<u8> {
+    record(5, text.as_bytes())
+}
+
+/// The DescriptorType and DescriptorData records of a firmware block, with
+/// the FirmwareId, VersionSw (1.00) and FirmwareLen.
+pub fn descriptor(id: u16, len: u32) -> Vec<u8> {
+    //FirmwareId, VersionSw, FirmwareLen and End
+    let types = [0x100au16, 0x100d, 0x2015, 0x5003];
+    let types: Vec<u8> = types.iter().flat_map(|x| x.to_le_bytes()).collect();
+    let mut data = id.to_le_bytes().to_vec();
+    data.extend_from_slice(&100u16.to_le_bytes());
+    data.extend_from_slice(&len.to_le_bytes());
+    let mut descriptor = record(6, &types);
+    descriptor.extend(record(7, &data));
+    descriptor
+}
+
+/// Firmware chunk record of the firmware `id`, the data is not encoded.
+pub fn firmware_chunk(id: u16, data: &[u8]) -> Vec<u8> {
+    record(id, data)
+}
+
+/// End record.
+pub fn end() -> Vec<u8> {
+    header(0xffff, 0)
+}
+
+/// Firmware data with `len` bytes, the same for the same `seed`.
+pub fn firmware(len: usize, seed: u32) -> Vec<u8> {
+    //xorshift, the first value is never 0
+    let mut state = seed.wrapping_mul(0x9e37_79b9) | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+/// Generator of a whole file, the texts, a MainHeader, the firmware blocks,
+/// a checksum and End.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FileGen {
+    hwid: u16,
+    texts: Vec<String>,
+    blocks: Vec<(u16, Vec<u8>)>,
+    chunk_size: u16,
+    block_checksums: bool,
+}
+
+impl Default for FileGen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileGen {
+    /// File with the HWID 0x0037, without firmware blocks, with the firmware
+    /// split in chunks of 0xff00 bytes.
+    pub fn new() -> Self {
+        FileGen {
+            hwid: 0x0037,
+            texts: vec![],
+            blocks: vec![],
+            chunk_size: 0xff00,
+            block_checksums: false,
+        }
+    }
+
+    pub fn hwid(mut self, hwid: u16) -> Self {
+        self.hwid = hwid;
+        self
+    }
+
+    /// Text record before the MainHeader, can be called multiple times.
+    pub fn text(mut self, text: &str) -> Self {
+        self.texts.push(text.to_string());
+        self
+    }
+
+    /// Firmware block with the firmware `id` and `data`, in the call order.
+    /// An empty `data` result in a block with only the Descriptor.
+    pub fn block(mut self, id: u16, data: Vec<u8>) -> Self {
+        self.blocks.push((id, data));
+        self
+    }
+
+    /// Split the firmware in chunks of up to `size` bytes.
+    ///
+    /// # Panics
+    ///
+    /// If the size is 0.
+    pub fn chunk_size(mut self, size: u16) -> Self {
+        assert!(size != 0, "Chunk size can't be zero");
+        self.chunk_size = size;
+        self
+    }
+
+    /// Write a checksum after each firmware block, not only before the End.
+    pub fn block_checksums(mut self, enable: bool) -> Self {
+        self.block_checksums = enable;
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut file = signature();
+        for value in &self.texts {
+            file.extend(text(value));
+        }
+        file.extend(main_header(self.hwid));
+        for (id, data) in &self.blocks {
+            file.extend(descriptor(*id, data.len() as u32));
+            for chunk in data.chunks(self.chunk_size.into()) {
+                file.extend(firmware_chunk(*id, chunk));
+            }
+            if self.block_checksums {
+                let sum = checksum(&file);
+                file.extend(sum);
+            }
+        }
+        if !self.block_checksums || self.blocks.is_empty() {
+            let sum = checksum(&file);
+            file.extend(sum);
+        }
+        file.extend(end());
+        file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{firmware, FileGen};
+    use crate::block::FirmwareBlockBuilder;
+    use crate::composer::compose_all;
+    use crate::lint::Linter;
+    use crate::parser::parse_all;
+    use crate::record::checksum::ChecksumRecord;
+    use crate::record::main::MainRecord;
+    use crate::record::text::TextRecord;
+    use crate::testing::vectors;
+    use crate::{Record, Version};
+
+    #[test]
+    fn same_as_vectors() {
+        let gen = FileGen::new().block(0x0505, b"firmware".to_vec());
+        assert_eq!(gen.build(), vectors::SINGLE_BLOCK);
+        let gen = FileGen::new()
+            .block(0x0505, b"firmware".to_vec())
+            .block(0x0506, b"font".to_vec())
+            .chunk_size(4);
+        assert_eq!(gen.build(), vectors::MULTI_BLOCK);
+    }
+
+    #[test]
+    fn same_as_composer() {
+        let blocks = [(0x0505, firmware(0x2800, 1)), (0x0601, vec![])];
+        let gen = blocks
+            .iter()
+            .fold(FileGen::new().text("test").hwid(0x0036), |gen, x| {
+                gen.block(x.0, x.1.clone())
+            })
+            .chunk_size(0x1000)
+            .block_checksums(true);
+        let file = gen.build();
+
+        let mut records = vec![
+            Record::Text(TextRecord::Simple("test".to_string())),
+            Record::MainHeader(MainRecord::HWID(0x0036)),
+        ];
+        for (id, data) in blocks.iter() {
+            let block = FirmwareBlockBuilder::new(*id, data.clone())
+                .version(Version::new(1, 0))
+                .max_chunk_size(0x1000)
+                .build()
+                .unwrap();
+            records.extend(block);
+        }
+        records.push(Record::End);
+        assert_eq!(compose_all(&records).unwrap(), file);
+
+        //valid for the parser and the linter, the checksum is at the end
+        let parsed = parse_all(&file).unwrap();
+        let mut linter = Linter::default();
+        parsed.iter().for_each(|x| linter.check(x));
+        assert_eq!(linter.finish(), vec![]);
+        assert_eq!(
+            parsed[parsed.len() - 2],
+            Record::Checksum(ChecksumRecord::Simple)
+        );
+    }
+
+    #[test]
+    fn deterministic_firmware() {
+        assert_eq!(firmware(100, 7), firmware(100, 7));
+        assert_ne!(firmware(100, 7), firmware(100, 8));
+        assert_eq!(firmware(10, 0).len(), 10);
+    }
+}
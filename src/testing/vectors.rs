@@ -0,0 +1,210 @@
+//! Tiny hand-constructed valid GCD files, in LittleEndian.
+//!
+//! Known-good inputs for tests and fuzz harnesses, without real firmware.
+//! Each firmware block have the descriptors FirmwareId, VersionSw (1.00) and
+//! FirmwareLen, all checksums are valid.
+
+/// Main header, a single firmware block 0x0505 with the data "firmware", a
+/// checksum and End.
+#[rustfmt::skip]
+pub const SINGLE_BLOCK: &[u8] = &[
+    //signature, version 100
+    0x47, 0x41, 0x52, 0x4d, 0x49, 0x4e, 0x64, 0x00,
+    //main header, HWID 0x0037
+    0x03, 0x00, 0x02, 0x00, 0x37, 0x00,
+    //descriptor types
+    0x06, 0x00, 0x08, 0x00, 0x0a, 0x10, 0x0d, 0x10, 0x15, 0x20, 0x03, 0x50,
+    //descriptor data
+    0x07, 0x00, 0x08, 0x00, 0x05, 0x05, 0x64, 0x00, 0x08, 0x00, 0x00, 0x00,
+    //firmware chunk
+    0x05, 0x05, 0x08, 0x00, 0x66, 0x69, 0x72, 0x6d, 0x77, 0x61, 0x72, 0x65,
+    //checksum
+    0x01, 0x00, 0x01, 0x00, 0xdf,
+    //end
+    0xff, 0xff, 0x00, 0x00,
+];
+
+/// Main header, the firmware block 0x0505 with the data "firmware" split in
+/// two chunks, the firmware block 0x0506 with the data "font", a checksum
+/// and End.
+#[rustfmt::skip]
+pub const MULTI_BLOCK: &[u8] = &[
+    //signature, version 100
+    0x47, 0x41, 0x52, 0x4d, 0x49, 0x4e, 0x64, 0x00,
+    //main header, HWID 0x0037
+    0x03, 0x00, 0x02, 0x00, 0x37, 0x00,
+    //descriptor types
+    0x06, 0x00, 0x08, 0x00, 0x0a, 0x10, 0x0d, 0x10, 0x15, 0x20, 0x03, 0x50,
+    //descriptor data, firmware 0x0505
+    0x07, 0x00, 0x08, 0x00, 0x05, 0x05, 0x64, 0x00, 0x08, 0x00, 0x00, 0x00,
+    //firmware chunks
+    0x05, 0x05, 0x04, 0x00, 0x66, 0x69, 0x72, 0x6d,
+    0x05, 0x05, 0x04, 0x00, 0x77, 0x61, 0x72, 0x65,
+    //descriptor types
+    0x06, 0x00, 0x08, 0x00, 0x0a, 0x10, 0x0d, 0x10, 0x15, 0x20, 0x03, 0x50,
+    //descriptor data, firmware 0x0506
+    0x07, 0x00, 0x08, 0x00, 0x06, 0x05, 0x64, 0x00, 0x04, 0x00, 0x00, 0x00,
+    //firmware chunk
+    0x06, 0x05, 0x04, 0x00, 0x66, 0x6f, 0x6e, 0x74,
+    //checksum
+    0x01, 0x00, 0x01, 0x00, 0xc0,
+    //end
+    0xff, 0xff, 0x00, 0x00,
+];
+
+/// A text "test", then the same block as [`SINGLE_BLOCK`], with the
+/// firmware split in two chunks, and checksums and fillers in between.
+#[rustfmt::skip]
+pub const FILLERS_CHECKSUMS: &[u8] = &[
+    //signature, version 100
+    0x47, 0x41, 0x52, 0x4d, 0x49, 0x4e, 0x64, 0x00,
+    //text
+    0x05, 0x00, 0x04, 0x00, 0x74, 0x65, 0x73, 0x74,
+    //checksum
+    0x01, 0x00, 0x01, 0x00, 0x13,
+    //filler, 4 bytes
+    0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00,
+    //main header, HWID 0x0037
+    0x03, 0x00, 0x02, 0x00, 0x37, 0x00,
+    //descriptor types
+    0x06, 0x00, 0x08, 0x00, 0x0a, 0x10, 0x0d, 0x10, 0x15, 0x20, 0x03, 0x50,
+    //descriptor data
+    0x07, 0x00, 0x08, 0x00, 0x05, 0x05, 0x64, 0x00, 0x08, 0x00, 0x00, 0x00,
+    //firmware chunk
+    0x05, 0x05, 0x04, 0x00, 0x66, 0x69, 0x72, 0x6d,
+    //checksum
+    0x01, 0x00, 0x01, 0x00, 0xae,
+    //filler, 2 bytes
+    0x02, 0x00, 0x02, 0x00, 0x00, 0x00,
+    //firmware chunk
+    0x05, 0x05, 0x04, 0x00, 0x77, 0x61, 0x72, 0x65,
+    //checksum
+    0x01, 0x00, 0x01, 0x00, 0x3d,
+    //end
+    0xff, 0xff, 0x00, 0x00,
+];
+
+/// Same as [`SINGLE_BLOCK`], with the DeviceModel "fenix", a kind 4
+/// descriptor with the data size after the descriptor type.
+#[rustfmt::skip]
+pub const OTHER_LEN_IN_TYPE: &[u8] = &[
+    //signature, version 100
+    0x47, 0x41, 0x52, 0x4d, 0x49, 0x4e, 0x64, 0x00,
+    //main header, HWID 0x0037
+    0x03, 0x00, 0x02, 0x00, 0x37, 0x00,
+    //descriptor types, DeviceModel with the size 5
+    0x06, 0x00, 0x0c, 0x00, 0x0a, 0x10, 0x0d, 0x10, 0x15, 0x20,
+    0x05, 0x40, 0x05, 0x00, 0x03, 0x50,
+    //descriptor data
+    0x07, 0x00, 0x0d, 0x00, 0x05, 0x05, 0x64, 0x00, 0x08, 0x00, 0x00, 0x00,
+    0x66, 0x65, 0x6e, 0x69, 0x78,
+    //firmware chunk
+    0x05, 0x05, 0x08, 0x00, 0x66, 0x69, 0x72, 0x6d, 0x77, 0x61, 0x72, 0x65,
+    //checksum
+    0x01, 0x00, 0x01, 0x00, 0x72,
+    //end
+    0xff, 0xff, 0x00, 0x00,
+];
+
+/// Same as [`OTHER_LEN_IN_TYPE`], with the DeviceModel before the
+/// FirmwareLen and the data size at the start of the descriptor data.
+#[rustfmt::skip]
+pub const OTHER_LEN_IN_DATA: &[u8] = &[
+    //signature, version 100
+    0x47, 0x41, 0x52, 0x4d, 0x49, 0x4e, 0x64, 0x00,
+    //main header, HWID 0x0037
+    0x03, 0x00, 0x02, 0x00, 0x37, 0x00,
+    //descriptor types, DeviceModel without the size
+    0x06, 0x00, 0x0a, 0x00, 0x0a, 0x10, 0x0d, 0x10, 0x05, 0x40,
+    0x15, 0x20, 0x03, 0x50,
+    //descriptor data, DeviceModel with the size 5
+    0x07, 0x00, 0x0f, 0x00, 0x05, 0x05, 0x64, 0x00, 0x05, 0x00,
+    0x66, 0x65, 0x6e, 0x69, 0x78, 0x08, 0x00, 0x00, 0x00,
+    //firmware chunk
+    0x05, 0x05, 0x08, 0x00, 0x66, 0x69, 0x72, 0x6d, 0x77, 0x61, 0x72, 0x65,
+    //checksum
+    0x01, 0x00, 0x01, 0x00, 0x72,
+    //end
+    0xff, 0xff, 0x00, 0x00,
+];
+
+/// Main header, a firmware block 0x0601 with only the Descriptor, the
+/// firmware block 0x0505 with the data "firmware", the firmware block 0x0506
+/// with a single empty chunk, a checksum and End. Both empty blocks declare
+/// FirmwareLen 0, like the descriptor-only blocks of some updates.
+#[rustfmt::skip]
+pub const EMPTY_BLOCKS: &[u8] = &[
+    //signature, version 100
+    0x47, 0x41, 0x52, 0x4d, 0x49, 0x4e, 0x64, 0x00,
+    //main header, HWID 0x0037
+    0x03, 0x00, 0x02, 0x00, 0x37, 0x00,
+    //descriptor types
+    0x06, 0x00, 0x08, 0x00, 0x0a, 0x10, 0x0d, 0x10, 0x15, 0x20, 0x03, 0x50,
+    //descriptor data, firmware 0x0601, no data
+    0x07, 0x00, 0x08, 0x00, 0x01, 0x06, 0x64, 0x00, 0x00, 0x00, 0x00, 0x00,
+    //descriptor types
+    0x06, 0x00, 0x08, 0x00, 0x0a, 0x10, 0x0d, 0x10, 0x15, 0x20, 0x03, 0x50,
+    //descriptor data, firmware 0x0505
+    0x07, 0x00, 0x08, 0x00, 0x05, 0x05, 0x64, 0x00, 0x08, 0x00, 0x00, 0x00,
+    //firmware chunk
+    0x05, 0x05, 0x08, 0x00, 0x66, 0x69, 0x72, 0x6d, 0x77, 0x61, 0x72, 0x65,
+    //descriptor types
+    0x06, 0x00, 0x08, 0x00, 0x0a, 0x10, 0x0d, 0x10, 0x15, 0x20, 0x03, 0x50,
+    //descriptor data, firmware 0x0506, no data
+    0x07, 0x00, 0x08, 0x00, 0x06, 0x05, 0x64, 0x00, 0x00, 0x00, 0x00, 0x00,
+    //empty firmware chunk
+    0x06, 0x05, 0x00, 0x00,
+    //checksum
+    0x01, 0x00, 0x01, 0x00, 0x42,
+    //end
+    0xff, 0xff, 0x00, 0x00,
+];
+
+/// All the vectors, with their names.
+pub const ALL: &[(&str, &[u8])] = &[
+    ("single_block", SINGLE_BLOCK),
+    ("multi_block", MULTI_BLOCK),
+    ("fillers_checksums", FILLERS_CHECKSUMS),
+    ("other_len_in_type", OTHER_LEN_IN_TYPE),
+    ("other_len_in_data", OTHER_LEN_IN_DATA),
+    ("empty_blocks", EMPTY_BLOCKS),
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::lint::Linter;
+    use crate::parser::Parser;
+    use crate::{GcdFile, Record};
+
+    #[test]
+    fn vectors_are_valid() {
+        for (name, data) in super::ALL.iter() {
+            let mut parser: Parser<&[u8]> = Parser::new(*data).unwrap();
+            let mut linter = Linter::default();
+            loop {
+                let record = parser.read_record().unwrap();
+                linter.check(&record);
+                if record == Record::End {
+                    break;
+                }
+            }
+            assert_eq!(linter.finish(), vec![], "{}", name);
+        }
+        let blocks = |x: &[u8]| {
+            let file: GcdFile = GcdFile::from_bytes(x.to_vec()).unwrap();
+            (0..file.blocks().len())
+                .map(|i| file.read_block(i).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(blocks(super::SINGLE_BLOCK), [b"firmware".to_vec()]);
+        assert_eq!(
+            blocks(super::MULTI_BLOCK),
+            [b"firmware".to_vec(), b"font".to_vec()]
+        );
+        assert_eq!(blocks(super::FILLERS_CHECKSUMS), [b"firmware".to_vec()]);
+        assert_eq!(
+            blocks(super::EMPTY_BLOCKS),
+            [vec![], b"firmware".to_vec(), vec![]]
+        );
+    }
+}
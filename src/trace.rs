@@ -0,0 +1,12 @@
+//! Events for the `tracing` crate, with the feature `tracing`.
+//!
+//! Without the feature the macros expand to nothing, so the crate don't
+//! depend on `tracing`.
+
+// emit a tracing event, eg: `trace_event!(debug, id, "message")`
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+    };
+}
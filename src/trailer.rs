@@ -0,0 +1,171 @@
+//! Data appended after the End record.
+//!
+//! Newer files have a RSA signature appended after the End record, the
+//! signature covers all the file data before it. The layout of the blob is
+//! not documented, a trailer is identified as a signature only by the size,
+//! the size of a RSA key from 1024 to 4096 bits.
+//!
+//! The signature is verified by a [`SignatureVerifier`], that implements the
+//! RSA verification with the public key, eg: using a crypto crate.
+//!
+//! ```
+//! use gcd_rs::parser::Parser;
+//! use gcd_rs::testing::vectors;
+//! use gcd_rs::trailer::Trailer;
+//!
+//! let mut file = vectors::SINGLE_BLOCK.to_vec();
+//! file.extend_from_slice(&[0xaa; 256]);
+//! let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+//! let trailer = Trailer::from_parser(&mut parser).unwrap().unwrap();
+//! assert_eq!(trailer.offset, vectors::SINGLE_BLOCK.len() as u64);
+//! assert_eq!(trailer.signature(), Some(&[0xaa; 256][..]));
+//!
+//! //a fake verifier, that only check the signed data
+//! let verifier = |data: &[u8], _signature: &[u8]| data.starts_with(b"GARMIN");
+//! assert!(trailer.verify(&file, &verifier).unwrap());
+//! ```
+
+use byteorder::ByteOrder;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use crate::error::{GcdError, Result};
+use crate::parser::Parser;
+use crate::Record;
+
+/// Sizes of the RSA signatures, in bytes.
+pub const SIGNATURE_LENS: [usize; 4] = [128, 256, 384, 512];
+
+/// Verify the signature of a file, see [`Trailer::verify`].
+pub trait SignatureVerifier {
+    /// True if the `signature` of the `data` is valid for the public key.
+    fn verify(&self, data: &[u8], signature: &[u8]) -> bool;
+}
+
+impl<F> SignatureVerifier for F
+where
+    F: Fn(&[u8], &[u8]) -> bool,
+{
+    fn verify(&self, data: &[u8], signature: &[u8]) -> bool {
+        self(data, signature)
+    }
+}
+
+/// Data after the End record, kept verbatim.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Trailer {
+    /// Offset in the file, right after the End record
+    pub offset: u64,
+    /// The data, as stored in the file
+    pub data: Vec<u8>,
+}
+
+impl Trailer {
+    pub fn new(offset: u64, data: Vec<u8>) -> Self {
+        Trailer { offset, data }
+    }
+
+    /// Read the records until the End, then the trailer, None if the End is
+    /// the last record, see [`Parser::finish`].
+    pub fn from_parser<F, B>(parser: &mut Parser<F, B>) -> Result<Option<Self>>
+    where
+        F: std::io::Read,
+        B: ByteOrder,
+    {
+        while parser.read_record()? != Record::End {}
+        let offset = Parser::position(parser);
+        let data = parser.finish()?;
+        Ok((!data.is_empty()).then(|| Trailer::new(offset, data)))
+    }
+
+    /// True if the data looks like a RSA signature.
+    pub fn is_signature(&self) -> bool {
+        SIGNATURE_LENS.contains(&self.data.len())
+    }
+
+    /// The RSA signature, if the data looks like one.
+    pub fn signature(&self) -> Option<&[u8]> {
+        self.is_signature().then_some(self.data.as_slice())
+    }
+
+    /// The data covered by the signature, all the `file` before the trailer.
+    pub fn signed_data<'a>(&self, file: &'a [u8]) -> Result<&'a [u8]> {
+        usize::try_from(self.offset)
+            .ok()
+            .and_then(|offset| file.get(..offset))
+            .ok_or_else(|| {
+                GcdError::invalid_input("Trailer offset after the file end")
+            })
+    }
+
+    /// Verify the signature of the `file`, the whole file, including the
+    /// trailer. Fail if the trailer is not a signature.
+    pub fn verify<V>(&self, file: &[u8], verifier: &V) -> Result<bool>
+    where
+        V: SignatureVerifier + ?Sized,
+    {
+        let signature = self.signature().ok_or_else(|| {
+            GcdError::invalid_data("Trailer is not a signature")
+        })?;
+        let data = self.signed_data(file)?;
+        Ok(verifier.verify(data, signature))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SignatureVerifier, Trailer};
+    use crate::composer::{compose_all, Composer};
+    use crate::parser::{parse_all, Parser};
+    use crate::testing::vectors;
+
+    // xor of all the signed bytes, repeated
+    struct XorVerifier;
+
+    impl SignatureVerifier for XorVerifier {
+        fn verify(&self, data: &[u8], signature: &[u8]) -> bool {
+            let xor = data.iter().fold(0, |acc, x| acc ^ x);
+            signature.iter().all(|x| *x == xor)
+        }
+    }
+
+    #[test]
+    fn trailer() {
+        let data = vectors::MULTI_BLOCK;
+        let xor = data.iter().fold(0, |acc, x| acc ^ x);
+        let mut file = data.to_vec();
+        file.extend_from_slice(&[xor; 128]);
+
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        let trailer = Trailer::from_parser(&mut parser).unwrap().unwrap();
+        assert_eq!(trailer.offset, data.len() as u64);
+        assert!(trailer.is_signature());
+        assert_eq!(trailer.signed_data(&file).unwrap(), data);
+        assert!(trailer.verify(&file, &XorVerifier).unwrap());
+        let mut tampered = file.clone();
+        tampered[20] ^= 1;
+        assert!(!trailer.verify(&tampered, &XorVerifier).unwrap());
+        assert!(trailer.verify(&file[..10], &XorVerifier).is_err());
+
+        //attached verbatim by the composer
+        let mut out = vec![];
+        let mut composer: Composer<_> = Composer::new(&mut out).unwrap();
+        assert!(composer.write_trailer(&trailer).is_err());
+        for record in parse_all(data).unwrap() {
+            composer.write_record(&record).unwrap();
+        }
+        composer.write_trailer(&trailer).unwrap();
+        drop(composer);
+        assert_eq!(out, file);
+
+        //not a signature
+        let trailer = Trailer::new(0, vec![0; 3]);
+        assert_eq!(trailer.signature(), None);
+        assert!(trailer.verify(&file, &XorVerifier).is_err());
+
+        //no trailer
+        let file = compose_all(&parse_all(data).unwrap()).unwrap();
+        let mut parser: Parser<&[u8]> = Parser::new(&file[..]).unwrap();
+        assert_eq!(Trailer::from_parser(&mut parser).unwrap(), None);
+    }
+}
@@ -5,7 +5,7 @@
 //!
 //! The minor value is know to be on the range 0..100
 //!
-//! The major value is know to be on the range 0..2 in u8 format, or 0..65334 in
+//! The major value is know to be on the range 0..3 in u8 format, or 0..656 in
 //! u16 format.
 //!
 //! The version is represented in decimal, the two least significant values
@@ -14,9 +14,32 @@
 //!
 //! The value 0xffff seems to be reserved. Possibly representing an Null for
 //! the version value, if forced to print, it will simply print "0.0".
+//!
+//! The Beta and RC firmwares are identified by the text of the release, eg:
+//! "3.80 Beta", the stage is not stored in the raw value, so
+//! [`Version::Beta`] and [`Version::Rc`] are only created from text, and
+//! their raw value is the same of the release. Versions are ordered by major,
+//! minor and then stage, a Beta is older than the RC and the release with the
+//! same major and minor.
+//!
+//! ```
+//! use gcd_rs::Version;
+//!
+//! let device = Version::new_raw(380);
+//! let file: Version = "3.80 RC".parse().unwrap();
+//! assert_eq!(file.to_string(), "3.80 RC");
+//! assert!(file < device);
+//! assert!(file > "3.80 Beta".parse().unwrap());
+//! ```
+//!
+//! Text records can also include versions with a third, patch, component,
+//! eg: "2.60.1", represented by [`VersionExt`].
 
+use crate::error::{GcdError, Result};
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::fmt;
+use std::str::FromStr;
 
 /// Can be created from/to a u8 or u16 values.
 #[derive(Debug, PartialEq, Hash, Eq, Copy, Clone, Serialize, Deserialize)]
@@ -26,6 +49,10 @@ pub enum Version {
     None,
     /// Simple version format {major}.{minor}, eg: major = 3, minor = 80: v3.80
     Simple { major: u16, minor: u8 },
+    /// Beta firmware {major}.{minor}, eg: "3.80 Beta"
+    Beta { major: u16, minor: u8 },
+    /// Release candidate {major}.{minor}, eg: "3.80 RC"
+    Rc { major: u16, minor: u8 },
     // reserved for future version formats
 }
 
@@ -44,10 +71,31 @@ impl Version {
         Version::Simple { major, minor }
     }
 
+    /// The raw value, the Beta and RC have the value of the release. The
+    /// versions bigger than v655.34 don't fit the u16 and wrap around.
     pub const fn value(&self) -> u16 {
         match self {
             Version::None => 0xffff,
-            Version::Simple { major, minor } => (*major * 100) + *minor as u16,
+            Version::Simple { major, minor }
+            | Version::Beta { major, minor }
+            | Version::Rc { major, minor } => {
+                major.wrapping_mul(100).wrapping_add(*minor as u16)
+            }
+        }
+    }
+
+    /// Beta or RC firmware.
+    pub const fn is_prerelease(&self) -> bool {
+        matches!(self, Version::Beta { .. } | Version::Rc { .. })
+    }
+
+    // major, minor and the release stage, Beta < RC < release
+    const fn key(&self) -> Option<(u16, u8, u8)> {
+        match *self {
+            Version::None => None,
+            Version::Beta { major, minor } => Some((major, minor, 0)),
+            Version::Rc { major, minor } => Some((major, minor, 1)),
+            Version::Simple { major, minor } => Some((major, minor, 2)),
         }
     }
 }
@@ -59,7 +107,54 @@ impl fmt::Display for Version {
             Version::Simple { major, minor } => {
                 write!(f, "{}.{}", major, minor)
             }
+            Version::Beta { major, minor } => {
+                write!(f, "{}.{} Beta", major, minor)
+            }
+            Version::Rc { major, minor } => write!(f, "{}.{} RC", major, minor),
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = GcdError;
+
+    /// Parse "major.minor", optionally prefixed by "v" and followed by
+    /// "Beta" or "RC", eg: "3.80", "v3.80 Beta" or "3.80-rc".
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || GcdError::invalid_data("Unable to parse Version");
+        let (s, stage) = match s.rsplit_once([' ', '-']) {
+            Some((s, x)) if x.eq_ignore_ascii_case("beta") => (s, Some(0)),
+            Some((s, x)) if x.eq_ignore_ascii_case("rc") => (s, Some(1)),
+            _ => (s, None),
+        };
+        //the patch is not part of the Version
+        if s.split('.').count() != 2 {
+            return Err(invalid());
+        }
+        let VersionExt { major, minor, .. } = s.parse()?;
+        //only the values that fit the u16, 0xffff is reserved
+        if u32::from(major) * 100 + u32::from(minor) >= 0xffff {
+            return Err(invalid());
         }
+        Ok(match stage {
+            Some(0) => Version::Beta { major, minor },
+            Some(_) => Version::Rc { major, minor },
+            None => Version::Simple { major, minor },
+        })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    /// Ordered by major, minor and stage, Version::None is older than any
+    /// version.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
     }
 }
 
@@ -80,3 +175,191 @@ impl From<Version> for u16 {
         x.value()
     }
 }
+
+/// Version in the {major}.{minor}.{patch} format, usually found in text.
+///
+/// Can be compared with [`Version`], that is equivalent to a patch 0.
+#[derive(
+    Debug,
+    PartialEq,
+    Hash,
+    Eq,
+    PartialOrd,
+    Ord,
+    Copy,
+    Clone,
+    Serialize,
+    Deserialize,
+)]
+pub struct VersionExt {
+    pub major: u16,
+    pub minor: u8,
+    pub patch: u16,
+}
+
+impl VersionExt {
+    pub const fn new(major: u16, minor: u8, patch: u16) -> Self {
+        VersionExt {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// The [`Version`] equivalent, the patch value is discarded.
+    pub const fn version(&self) -> Version {
+        Version::new(self.major, self.minor)
+    }
+
+    /// Find the first version in the text, eg: "Software Version 2.60.1".
+    pub fn find(text: &str) -> Option<Self> {
+        text.split(|x: char| x.is_whitespace() || x == ',' || x == '(')
+            .find_map(|x| x.trim_end_matches(')').parse().ok())
+    }
+}
+
+impl FromStr for VersionExt {
+    type Err = GcdError;
+
+    /// Parse "major.minor" or "major.minor.patch", optionally prefixed by "v".
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || GcdError::invalid_data("Unable to parse Version");
+        let s = s.strip_prefix(|x| x == 'v' || x == 'V').unwrap_or(s);
+        let mut values = s.split('.').map(|x| {
+            if !x.is_empty() && x.bytes().all(|x| x.is_ascii_digit()) {
+                x.parse::<u16>().map_err(|_| invalid())
+            } else {
+                Err(invalid())
+            }
+        });
+        let major = values.next().ok_or_else(invalid)??;
+        let minor = values.next().ok_or_else(invalid)??;
+        let patch = values.next().transpose()?.unwrap_or(0);
+        if values.next().is_some() || minor >= 100 {
+            return Err(invalid());
+        }
+        Ok(VersionExt::new(major, minor as u8, patch))
+    }
+}
+
+impl fmt::Display for VersionExt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl PartialEq<Version> for VersionExt {
+    fn eq(&self, other: &Version) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd<Version> for VersionExt {
+    /// Version::None can't be compared, a Beta or RC is older than the
+    /// release with the same major and minor.
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        let (major, minor, stage) = other.key()?;
+        let release = if stage == 2 {
+            Ordering::Equal
+        } else {
+            Ordering::Greater
+        };
+        Some(
+            (self.major, self.minor, self.patch)
+                .cmp(&(major, minor, 0))
+                .then(release),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Version, VersionExt};
+
+    #[test]
+    fn version_parse() {
+        assert_eq!("3.80".parse::<Version>().unwrap(), Version::new(3, 80));
+        assert_eq!("v3.08".parse::<Version>().unwrap().value(), 308);
+        let beta: Version = "3.80 Beta".parse().unwrap();
+        assert_eq!(
+            beta,
+            Version::Beta {
+                major: 3,
+                minor: 80
+            }
+        );
+        //the raw value don't store the stage
+        assert_eq!(beta.value(), 380);
+        assert_eq!(Version::new_raw(beta.value()), Version::new(3, 80));
+        let rc: Version = "3.80-rc".parse().unwrap();
+        assert_eq!(rc.to_string(), "3.80 RC");
+        assert_eq!(rc.to_string().parse::<Version>().unwrap(), rc);
+        assert!(rc.is_prerelease() && !Version::new(3, 80).is_prerelease());
+
+        assert!("3.80.1".parse::<Version>().is_err());
+        assert!("3".parse::<Version>().is_err());
+        assert!("3.80 alpha".parse::<Version>().is_err());
+        //out of the u16 range, 0xffff is reserved
+        assert!("655.34".parse::<Version>().is_ok());
+        assert!("655.35".parse::<Version>().is_err());
+        assert!("655.35 RC".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn version_raw() {
+        for value in [0, 380, 0x8000, 0xfffe].iter() {
+            assert_eq!(Version::new_raw(*value).value(), *value);
+        }
+        assert_eq!(Version::new_raw(0xffff), Version::None);
+        assert_eq!(Version::None.value(), 0xffff);
+        assert_eq!(Version::None.to_string(), "0.0");
+        //too big for the u16, wrap around without panic
+        assert_eq!(Version::new(656, 0).value(), 64);
+        const VERSION: Version = Version::new(3, 80);
+        assert_eq!(VERSION.value(), 380);
+    }
+
+    #[test]
+    fn version_order() {
+        let mut versions: Vec<Version> =
+            ["3.80", "3.80 RC", "2.90", "3.80 Beta"]
+                .iter()
+                .map(|x| x.parse().unwrap())
+                .collect();
+        versions.push(Version::None);
+        versions.sort();
+        let sorted: Vec<_> = versions.iter().map(|x| x.to_string()).collect();
+        assert_eq!(sorted, ["0.0", "2.90", "3.80 Beta", "3.80 RC", "3.80"]);
+        assert!(Version::new(3, 80) > Version::new(2, 95));
+
+        let beta = Version::Beta {
+            major: 3,
+            minor: 80,
+        };
+        assert!(VersionExt::new(3, 80, 0) > beta);
+        assert!(VersionExt::new(3, 79, 9) < beta);
+        assert!(VersionExt::new(3, 80, 0) == Version::new(3, 80));
+    }
+
+    #[test]
+    fn version_ext_parse() {
+        let version: VersionExt = "2.60.1".parse().unwrap();
+        assert_eq!(version, VersionExt::new(2, 60, 1));
+        assert_eq!("v3.80".parse::<VersionExt>().unwrap(), Version::new(3, 80));
+        assert!("2.100".parse::<VersionExt>().is_err());
+        assert!("2.60.1.0".parse::<VersionExt>().is_err());
+        assert!("2.+6".parse::<VersionExt>().is_err());
+    }
+
+    #[test]
+    fn version_ext_compare() {
+        let version = VersionExt::new(2, 60, 1);
+        assert!(version > Version::new(2, 60));
+        assert!(version < Version::new(2, 61));
+        assert_eq!(version.partial_cmp(&Version::None), None);
+        assert_eq!(
+            VersionExt::find("Software Version 2.60.1 (beta)"),
+            Some(version)
+        );
+    }
+}